@@ -0,0 +1,200 @@
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{bail, Result};
+
+/// The CPU architecture an executable was built for, read from its PE, ELF or
+/// Mach-O header, named like `std::env::consts::ARCH`.
+pub fn binary_arch(path: &Path) -> Result<&'static str> {
+    let mut header = Vec::new();
+    File::open(path)?.take(4096).read_to_end(&mut header)?;
+
+    let u16_at = |at: usize| {
+        header
+            .get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |at: usize| {
+        header
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    let machine = match header.get(..4) {
+        Some([b'M', b'Z', ..]) => {
+            let pe = u32_at(0x3C).unwrap_or_default() as usize;
+            if header.get(pe..pe + 4) != Some(b"PE\0\0") {
+                bail!("{} has no PE header", path.display());
+            }
+            match u16_at(pe + 4) {
+                Some(0x8664) => "x86_64",
+                Some(0x014C) => "x86",
+                Some(0xAA64) => "aarch64",
+                _ => "unknown",
+            }
+        }
+        Some([0x7F, b'E', b'L', b'F']) => match u16_at(0x12) {
+            Some(0x3E) => "x86_64",
+            Some(0x03) => "x86",
+            Some(0xB7) => "aarch64",
+            _ => "unknown",
+        },
+        Some([0xCF, 0xFA, 0xED, 0xFE]) => match u32_at(4) {
+            Some(0x0100_0007) => "x86_64",
+            Some(0x0100_000C) => "aarch64",
+            _ => "unknown",
+        },
+        Some([0xCA, 0xFE, 0xBA, 0xBE]) => "universal",
+        _ => bail!(
+            "{} isn't an executable this tool recognizes",
+            path.display()
+        ),
+    };
+
+    Ok(machine)
+}
+
+/// Whether a binary of `arch` runs natively, or under the OS's emulation, on this host.
+pub fn runs_on_host(arch: &str) -> bool {
+    let host = std::env::consts::ARCH;
+    arch == host
+        || arch == "universal"
+        || (host == "x86_64" && arch == "x86" && cfg!(windows))
+        // windows and macos on arm emulate x64 binaries
+        || (host == "aarch64" && arch == "x86_64" && (cfg!(windows) || cfg!(target_os = "macos")))
+}
+
+/// The platform of this machine's OS whose drivers are built for `--arch`, one of
+/// `x86`, `x64` and `arm64` or their `std::env::consts::ARCH` names.
+pub fn platform_for(arch: &str) -> Result<&'static str> {
+    let requested = arch.trim();
+    let arch = match requested.to_ascii_lowercase().as_str() {
+        "x86" | "i686" | "i386" => "x86",
+        "x64" | "x86_64" | "amd64" => "x86_64",
+        "arm64" | "aarch64" => "aarch64",
+        other => bail!("unknown --arch {}, expected x86, x64 or arm64", other),
+    };
+    let platform = if cfg!(windows) {
+        match arch {
+            "x86" => Some("win32"),
+            "x86_64" => Some("win64"),
+            _ => Some("arm64"),
+        }
+    } else if cfg!(target_os = "macos") {
+        match arch {
+            "x86_64" => Some("mac64"),
+            "aarch64" => Some("mac64_m1"),
+            _ => None,
+        }
+    } else {
+        match arch {
+            "x86_64" => Some("linux64"),
+            "aarch64" => Some("linux_arm64"),
+            _ => None,
+        }
+    };
+    match platform {
+        Some(platform) => Ok(platform),
+        None => bail!(
+            "no {} driver is published for {}",
+            requested,
+            std::env::consts::OS
+        ),
+    }
+}
+
+/// The architecture drivers for `platform` are built for, named like
+/// [`binary_arch`] names them.
+pub fn of_platform(platform: &str) -> &'static str {
+    match platform {
+        "win32" => "x86",
+        "arm64" | "mac64_m1" | "linux_arm64" => "aarch64",
+        _ => "x86_64",
+    }
+}
+
+/// The platform whose drivers run natively on this machine, which on windows on
+/// arm isn't the one this build targets when it runs under emulation, or the other
+/// way around.
+pub fn native_platform() -> &'static str {
+    // wine reports the machine it emulates inconsistently, but runs what this build does
+    if crate::wine::detected() {
+        return match std::env::consts::ARCH {
+            "x86" => "win32",
+            "aarch64" => "arm64",
+            _ => "win64",
+        };
+    }
+    match native_machine() {
+        Some("aarch64") => "arm64",
+        Some("x86_64") => "win64",
+        Some("x86") => "win32",
+        _ => crate::PLATFORM,
+    }
+}
+
+#[cfg(windows)]
+fn native_machine() -> Option<&'static str> {
+    use windows_sys::{
+        core::PCSTR,
+        Win32::{
+            Foundation::{BOOL, HANDLE},
+            System::{
+                LibraryLoader::{GetModuleHandleW, GetProcAddress},
+                SystemInformation::{
+                    GetNativeSystemInfo, IMAGE_FILE_MACHINE, IMAGE_FILE_MACHINE_AMD64,
+                    IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_I386,
+                    PROCESSOR_ARCHITECTURE_AMD64, PROCESSOR_ARCHITECTURE_ARM64,
+                    PROCESSOR_ARCHITECTURE_INTEL, SYSTEM_INFO,
+                },
+                Threading::GetCurrentProcess,
+            },
+        },
+    };
+
+    type IsWow64Process2 =
+        unsafe extern "system" fn(HANDLE, *mut IMAGE_FILE_MACHINE, *mut IMAGE_FILE_MACHINE) -> BOOL;
+
+    // looked up rather than linked, as windows before 10 1709 lacks it
+    let kernel32: Vec<u16> = "kernel32.dll".encode_utf16().chain(Some(0)).collect();
+    // SAFETY: both names are nul terminated
+    let proc = unsafe {
+        GetProcAddress(
+            GetModuleHandleW(kernel32.as_ptr()),
+            c"IsWow64Process2".as_ptr() as PCSTR,
+        )
+    };
+    if let Some(proc) = proc {
+        // SAFETY: IsWow64Process2 has this signature
+        let is_wow64_process2: IsWow64Process2 = unsafe { std::mem::transmute(proc) };
+        let (mut process, mut native) = (0, 0);
+        // SAFETY: both out pointers are valid for writes
+        if unsafe { is_wow64_process2(GetCurrentProcess(), &mut process, &mut native) } != 0 {
+            return match native {
+                IMAGE_FILE_MACHINE_ARM64 => Some("aarch64"),
+                IMAGE_FILE_MACHINE_AMD64 => Some("x86_64"),
+                IMAGE_FILE_MACHINE_I386 => Some("x86"),
+                _ => None,
+            };
+        }
+    }
+
+    // an x64 process emulated on arm gets told amd64 here, hence the above first
+    // SAFETY: SYSTEM_INFO is plain data, filled in by the call
+    let info = unsafe {
+        let mut info: SYSTEM_INFO = std::mem::zeroed();
+        GetNativeSystemInfo(&mut info);
+        info
+    };
+    // SAFETY: every variant of the union starts with these fields
+    match unsafe { info.Anonymous.Anonymous.wProcessorArchitecture } {
+        PROCESSOR_ARCHITECTURE_ARM64 => Some("aarch64"),
+        PROCESSOR_ARCHITECTURE_AMD64 => Some("x86_64"),
+        PROCESSOR_ARCHITECTURE_INTEL => Some("x86"),
+        _ => None,
+    }
+}
+
+#[cfg(not(windows))]
+fn native_machine() -> Option<&'static str> {
+    None
+}
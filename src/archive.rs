@@ -0,0 +1,150 @@
+//! The formats drivers are published in, and finding the driver inside each.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::{anyhow, bail, Result};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::{explain, tar};
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    TarGz,
+    /// The driver itself, published without an archive around it.
+    Binary,
+}
+
+impl Format {
+    /// The format of the download from `url` starting with `head`. The content
+    /// decides where it is recognisable, as mirrors don't always keep the extension.
+    pub fn detect(url: &str, head: &[u8]) -> Self {
+        if head.starts_with(b"PK\x03\x04") {
+            return Format::Zip;
+        }
+        if head.starts_with(&[0x1f, 0x8b]) {
+            return Format::TarGz;
+        }
+
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Format::TarGz
+        } else if path.ends_with(".zip") {
+            Format::Zip
+        } else {
+            Format::Binary
+        }
+    }
+}
+
+/// The selected file of an archive, ready to be read.
+pub struct Entry<'a> {
+    pub reader: Box<dyn Read + 'a>,
+    /// The size the entry claims to decompress to.
+    pub size: u64,
+    /// The size it takes up in the archive, for the compression ratio limit.
+    pub compressed: u64,
+    /// Unix permission bits recorded in the archive, if any.
+    pub mode: Option<u32>,
+    /// Whether the entry is a symbolic link, whose contents are its target.
+    pub symlink: bool,
+}
+
+pub enum Archive {
+    Zip(ZipArchive<Box<dyn ReadSeek>>),
+    TarGz(GzDecoder<Box<dyn ReadSeek>>, u64),
+    Binary(Box<dyn ReadSeek>, u64),
+}
+
+impl Archive {
+    /// Opens the `len` byte download from `url`, sniffing its format.
+    pub fn open(url: &str, mut download: Box<dyn ReadSeek>, len: u64) -> Result<Self> {
+        let mut head = [0u8; 4];
+        let read = download.read(&mut head)?;
+        download.seek(SeekFrom::Start(0))?;
+        let format = Format::detect(url, &head[..read]);
+        explain::step(format!("download from {} is {:?}", url, format));
+
+        Ok(match format {
+            Format::Zip => Archive::Zip(ZipArchive::new(download)?),
+            Format::TarGz => Archive::TarGz(GzDecoder::new(download), len),
+            Format::Binary => Archive::Binary(download, len),
+        })
+    }
+
+    /// The paths of every entry, directories ending in `/`, for picking other files
+    /// to extract by name.
+    pub fn names(&self) -> Result<Vec<String>> {
+        match self {
+            Archive::Zip(zip) => {
+                let mut names: Vec<_> = zip.file_names().map(String::from).collect();
+                // directories sort before what they hold
+                names.sort();
+                Ok(names)
+            }
+            // tarballs can only be read through once, and hold nothing but the driver
+            _ => bail!("only zip archives have other files to extract"),
+        }
+    }
+
+    /// The entry named `filename`; a bare binary is its own only entry, whatever it
+    /// is called.
+    pub fn entry(&mut self, filename: &str) -> Result<Entry<'_>> {
+        match self {
+            Archive::Zip(zip) => {
+                let file = zip.by_name(filename)?;
+                Ok(Entry {
+                    size: file.size(),
+                    compressed: file.compressed_size(),
+                    mode: file.unix_mode(),
+                    symlink: file
+                        .unix_mode()
+                        .is_some_and(|mode| mode & 0o170000 == 0o120000),
+                    reader: Box::new(file),
+                })
+            }
+            Archive::TarGz(tar, len) => {
+                let header = tar::seek_entry(tar, filename)?
+                    .ok_or_else(|| anyhow!("{} isn't in the archive", filename))?;
+                Ok(Entry {
+                    size: header.size,
+                    // entries aren't compressed on their own, so the ratio is the archive's
+                    compressed: *len,
+                    mode: Some(header.mode),
+                    // only regular files are ever found
+                    symlink: false,
+                    reader: Box::new(tar.take(header.size)),
+                })
+            }
+            Archive::Binary(file, len) => Ok(Entry {
+                size: *len,
+                compressed: *len,
+                mode: None,
+                symlink: false,
+                reader: Box::new(file),
+            }),
+        }
+    }
+}
+
+/// Whether the archive path `name` matches `pattern`, where `?` is any one
+/// character, `*` any run within one directory and `**` any run across them.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[u8], name: &[u8]) -> bool {
+        match pattern {
+            [] => name.is_empty(),
+            [b'*', b'*', rest @ ..] => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            [b'*', rest @ ..] => (0..=name.len())
+                .take_while(|&i| i == 0 || name[i - 1] != b'/')
+                .any(|i| matches(rest, &name[i..])),
+            [b'?', rest @ ..] => matches!(name, [c, ..] if *c != b'/') && matches(rest, &name[1..]),
+            [c, rest @ ..] => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), name.as_bytes())
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde_json::json;
+
+use crate::download::Hashes;
+
+const BUILD_TYPE: &str = "https://github.com/chippers/msedgedriver-tool/install@v1";
+
+/// What went into an install, recorded as SLSA provenance for the extracted driver.
+#[derive(Debug)]
+pub struct Provenance<'a> {
+    pub driver_name: &'a str,
+    pub source_url: &'a str,
+    pub webview2_version: &'a str,
+    pub platform: &'a str,
+    pub hashes: &'a Hashes,
+    pub started_on: &'a str,
+    pub finished_on: &'a str,
+}
+
+/// Writes an in-toto statement with a SLSA v1 provenance predicate to `path`.
+pub fn write_statement(path: &Path, builder_id: &str, provenance: &Provenance) -> Result<()> {
+    let statement = json!({
+        "_type": "https://in-toto.io/Statement/v1",
+        "subject": [{
+            "name": provenance.driver_name,
+            "digest": { "sha256": provenance.hashes.driver },
+        }],
+        "predicateType": "https://slsa.dev/provenance/v1",
+        "predicate": {
+            "buildDefinition": {
+                "buildType": BUILD_TYPE,
+                "externalParameters": {
+                    "webview2Version": provenance.webview2_version,
+                    "platform": provenance.platform,
+                },
+                "resolvedDependencies": [{
+                    "uri": provenance.source_url,
+                    "digest": { "sha256": provenance.hashes.archive },
+                }],
+            },
+            "runDetails": {
+                "builder": {
+                    "id": builder_id,
+                    "version": { env!("CARGO_PKG_NAME"): env!("CARGO_PKG_VERSION") },
+                },
+                "metadata": {
+                    "startedOn": provenance.started_on,
+                    "finishedOn": provenance.finished_on,
+                },
+            },
+        },
+    });
+
+    std::fs::write(path, serde_json::to_string_pretty(&statement)? + "\n")?;
+    Ok(())
+}
+
+/// Identifies this tool as the builder when `--builder-id` isn't given.
+pub fn default_builder_id() -> String {
+    format!(
+        "https://github.com/chippers/msedgedriver-tool@{}",
+        env!("CARGO_PKG_VERSION")
+    )
+}
@@ -0,0 +1,83 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{flag_value, policy, timestamp};
+
+/// One change made to a machine, appended as a JSON line to the audit log.
+#[derive(Debug, Serialize)]
+pub struct Event<'a> {
+    /// `install`, `update` or `remove`.
+    pub action: &'a str,
+    pub path: &'a Path,
+    pub version: Option<&'a str>,
+    pub previous_version: Option<&'a str>,
+    pub platform: Option<&'a str>,
+    pub archive_sha256: Option<&'a str>,
+    pub driver_sha256: Option<&'a str>,
+    pub source: Option<&'a str>,
+}
+
+impl<'a> Event<'a> {
+    /// An event with only its action and path known.
+    pub fn new(action: &'a str, path: &'a Path) -> Self {
+        Self {
+            action,
+            path,
+            version: None,
+            previous_version: None,
+            platform: None,
+            archive_sha256: None,
+            driver_sha256: None,
+            source: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    timestamp: String,
+    user: Option<String>,
+    host: Option<String>,
+    tool_version: &'static str,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// The audit log in use: the policy mandated one, else `--audit-log`, else
+/// `MSEDGEDRIVER_TOOL_AUDIT_LOG`. Logging is off when none are set.
+pub fn log_path(args: &[String]) -> Option<PathBuf> {
+    policy::get()
+        .audit_log
+        .clone()
+        .or_else(|| flag_value(args, "--audit-log").map(PathBuf::from))
+        .or_else(|| std::env::var_os("MSEDGEDRIVER_TOOL_AUDIT_LOG").map(PathBuf::from))
+}
+
+/// Appends `event` to the audit log at `log`.
+pub fn record(log: &Path, event: &Event) -> Result<()> {
+    let line = Line {
+        timestamp: timestamp::now(),
+        user: env_any(&["USERNAME", "USER"]),
+        host: env_any(&["COMPUTERNAME", "HOSTNAME"]),
+        tool_version: env!("CARGO_PKG_VERSION"),
+        event,
+    };
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .with_context(|| format!("unable to open audit log {}", log.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&line)?)?;
+    Ok(())
+}
+
+fn env_any(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| std::env::var(name).ok())
+}
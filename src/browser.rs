@@ -0,0 +1,839 @@
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Error, Result};
+use serde_json::json;
+
+use crate::{arch, channel::Channel, explain, flag_value, has_flag, output, registry, trace};
+
+/// Whose version the driver is matched to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    /// The WebView2 runtime embedded by apps.
+    WebView2,
+    /// The full Edge browser of one channel, or the first installed of stable, beta,
+    /// dev and canary.
+    Edge(Option<Channel>),
+}
+
+impl FromStr for Browser {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().split_once(':') {
+            None if s.trim().eq_ignore_ascii_case("webview2") => Ok(Browser::WebView2),
+            None if s.trim().eq_ignore_ascii_case("edge") => Ok(Browser::Edge(None)),
+            Some(("edge", channel)) => Ok(Browser::Edge(Some(channel.parse()?))),
+            _ => bail!(
+                "unknown browser {}, expected webview2, edge or edge:<channel>",
+                s
+            ),
+        }
+    }
+}
+
+/// Webview2 on windows; linux and macos have no webview2, only the edge browser.
+impl Default for Browser {
+    fn default() -> Self {
+        if cfg!(windows) {
+            Browser::WebView2
+        } else {
+            Browser::Edge(None)
+        }
+    }
+}
+
+impl Browser {
+    /// Reads `--browser`, defaulting to webview2 on windows and edge elsewhere.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        flag_value(args, "--browser")
+            .map(|b| b.parse())
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+}
+
+const ALL_CHANNELS: [Channel; 4] = [
+    Channel::Stable,
+    Channel::Beta,
+    Channel::Dev,
+    Channel::Canary,
+];
+
+/// The version of the installed Edge `channel`, or of the first installed channel
+/// of stable, beta, dev and canary, along with which it was.
+pub fn edge_version(channel: Option<Channel>) -> Result<Option<(String, Channel)>> {
+    let _span = trace::span("detect edge");
+    let channels = match channel {
+        Some(channel) => vec![channel],
+        None => ALL_CHANNELS.to_vec(),
+    };
+    for channel in channels {
+        if let Some(version) = channel_version(channel)? {
+            return Ok(Some((version, channel)));
+        }
+    }
+
+    explain::step("no installed edge channel found");
+    Ok(None)
+}
+
+fn channel_version(channel: Channel) -> Result<Option<String>> {
+    for key in registry_keys(channel) {
+        if let Some(version) = registry::read_string(&key, "pv")?.filter(|v| !v.is_empty()) {
+            explain::step(format!(
+                "edge {} registry key {} answered with {}",
+                channel.as_str(),
+                key,
+                version
+            ));
+            return Ok(Some(version));
+        }
+    }
+
+    // linux packages know their version without the browser being started
+    if cfg!(target_os = "linux") {
+        if let Some((version, source)) = package_version(channel) {
+            explain::step(format!(
+                "edge {} package answered {} with {}",
+                channel.as_str(),
+                source,
+                version
+            ));
+            return Ok(Some(version));
+        }
+    }
+
+    // windows installs sit beside a directory named for their version
+    if cfg!(windows) {
+        let dirs: Vec<PathBuf> = executables(channel)
+            .iter()
+            .filter_map(|exe| exe.parent().map(Path::to_path_buf))
+            .collect();
+        if let Some((version, dir)) = scan_installations(&dirs) {
+            explain::step(format!(
+                "edge {} found in {}",
+                channel.as_str(),
+                dir.display()
+            ));
+            return Ok(Some(version));
+        }
+    }
+
+    for exe in executables(channel) {
+        if !exe.is_file() {
+            continue;
+        }
+        if let Some(version) = file_version(&exe)? {
+            explain::step(format!(
+                "edge {} at {} reports {}",
+                channel.as_str(),
+                exe.display(),
+                version
+            ));
+            return Ok(Some(version));
+        }
+    }
+
+    explain::step(format!("edge {} not installed", channel.as_str()));
+    Ok(None)
+}
+
+/// The version of the `channel` package dpkg or rpm installed, and which answered.
+fn package_version(channel: Channel) -> Option<(String, &'static str)> {
+    let package = linux_package(channel)?;
+    if crate::sandbox::active() {
+        return None;
+    }
+    let queries = [
+        ("dpkg", "dpkg-query", ["-W", "-f", "${Version}", package]),
+        ("rpm", "rpm", ["-q", "--qf", "%{VERSION}", package]),
+    ];
+    queries.into_iter().find_map(|(source, program, args)| {
+        let output = Command::new(program).args(args).output().ok()?;
+        // e.g. `120.0.2210.91-1`, the debian revision after the dash
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = stdout.trim().split('-').next().unwrap_or_default();
+        (output.status.success() && crate::check_version(version).is_ok())
+            .then(|| (version.to_string(), source))
+    })
+}
+
+fn linux_package(channel: Channel) -> Option<&'static str> {
+    match channel {
+        Channel::Stable => Some("microsoft-edge-stable"),
+        Channel::Beta => Some("microsoft-edge-beta"),
+        Channel::Dev => Some("microsoft-edge-dev"),
+        Channel::Canary => None,
+    }
+}
+
+/// The highest version among the version-named directories in `dirs`, such as
+/// `EdgeWebView\Application\120.0.2210.91`, and the directory it was found in.
+pub fn scan_installations(dirs: &[PathBuf]) -> Option<(String, PathBuf)> {
+    let mut best: Option<(String, PathBuf)> = None;
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if crate::check_version(&name).is_err() || !entry.path().is_dir() {
+                continue;
+            }
+            let newer = best
+                .as_ref()
+                .is_none_or(|(version, _)| crate::version_key(&name) > crate::version_key(version));
+            if newer {
+                best = Some((name, entry.path()));
+            }
+        }
+    }
+
+    if let Some((version, dir)) = &best {
+        explain::step(format!("found {} installed in {}", version, dir.display()));
+    }
+    best
+}
+
+/// The fixed version webview2 runtime named by `--webview2-dir`, or found beside the
+/// app executable named by `--webview2-app`, with its version.
+///
+/// Apps that bring their own runtime register nothing with EdgeUpdate, so only the
+/// runtime's files can tell which version it is.
+pub fn fixed_runtime(args: &[String]) -> Result<Option<(String, PathBuf)>> {
+    let dir = match (
+        flag_value(args, "--webview2-dir"),
+        flag_value(args, "--webview2-app"),
+    ) {
+        (Some(_), Some(_)) => {
+            bail!("--webview2-dir and --webview2-app both locate the runtime, pass only one")
+        }
+        (Some(dir), None) => PathBuf::from(dir),
+        (None, Some(app)) => {
+            let app = PathBuf::from(app);
+            let app_dir = app.parent().filter(|d| !d.as_os_str().is_empty());
+            find_fixed_runtime(app_dir.unwrap_or(Path::new("."))).ok_or_else(|| {
+                anyhow!(
+                    "no fixed version webview2 runtime found beside {}",
+                    app.display()
+                )
+            })?
+        }
+        (None, None) => return Ok(None),
+    };
+    fixed_runtime_in(dir).map(Some)
+}
+
+/// The version of the fixed version webview2 runtime in `dir`.
+pub fn fixed_runtime_in(dir: PathBuf) -> Result<(String, PathBuf)> {
+    let exe = dir.join(WEBVIEW2_EXECUTABLE);
+    if !exe.is_file() {
+        bail!(
+            "{} has no {}, so it isn't a webview2 runtime",
+            dir.display(),
+            WEBVIEW2_EXECUTABLE
+        );
+    }
+    // e.g. Microsoft.WebView2.FixedVersionRuntime.120.0.2210.91.x64
+    let from_name = dir
+        .file_name()
+        .and_then(|name| version_in(&name.to_string_lossy()));
+    let version = match from_name {
+        Some(version) => version,
+        None => file_version(&exe)?.ok_or_else(|| {
+            anyhow!(
+                "unable to read the version of the webview2 runtime in {}",
+                dir.display()
+            )
+        })?,
+    };
+    explain::step(format!(
+        "fixed version webview2 runtime {} in {}",
+        version,
+        dir.display()
+    ));
+    Ok((version, dir))
+}
+
+const WEBVIEW2_EXECUTABLE: &str = "msedgewebview2.exe";
+
+/// A directory holding the runtime in `app_dir` or one of its subdirectories.
+fn find_fixed_runtime(app_dir: &Path) -> Option<PathBuf> {
+    if app_dir.join(WEBVIEW2_EXECUTABLE).is_file() {
+        return Some(app_dir.to_path_buf());
+    }
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(app_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|dir| dir.join(WEBVIEW2_EXECUTABLE).is_file())
+        .collect();
+    // with several runtimes around, prefer the newest
+    dirs.sort_by_key(|dir| {
+        dir.file_name()
+            .and_then(|name| version_in(&name.to_string_lossy()))
+            .map(|version| crate::version_key(&version))
+    });
+    dirs.pop()
+}
+
+/// The first `MAJOR.MINOR.BUILD.PATCH` version within `name`.
+fn version_in(name: &str) -> Option<String> {
+    let parts: Vec<&str> = name.split('.').collect();
+    parts.windows(4).find_map(|window| {
+        let candidate = window.join(".");
+        crate::check_version(&candidate)
+            .is_ok()
+            .then_some(candidate)
+    })
+}
+
+/// The EdgeUpdate client id each channel registers its version under.
+fn client_id(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Stable => "{56EB18F8-B008-4CBD-B6D2-8C97FE7E9062}",
+        Channel::Beta => "{2CD8A007-E189-409D-A2C8-9AF4EF3C72AA}",
+        Channel::Dev => "{0D50BFEC-CD6A-4F9A-964C-C7416E3ACB10}",
+        Channel::Canary => "{65C35B14-6C1D-4122-AC46-7148CC9D6497}",
+    }
+}
+
+fn registry_keys(channel: Channel) -> Vec<String> {
+    let id = client_id(channel);
+    vec![
+        format!(
+            "HKLM\\SOFTWARE\\WOW6432Node\\Microsoft\\EdgeUpdate\\Clients\\{}",
+            id
+        ),
+        format!("HKLM\\SOFTWARE\\Microsoft\\EdgeUpdate\\Clients\\{}", id),
+        // canary only ever installs per user
+        format!("HKCU\\SOFTWARE\\Microsoft\\EdgeUpdate\\Clients\\{}", id),
+    ]
+}
+
+/// Where each channel's browser executable is installed.
+pub fn executables(channel: Channel) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let folder = match channel {
+            Channel::Stable => "Edge",
+            Channel::Beta => "Edge Beta",
+            Channel::Dev => "Edge Dev",
+            Channel::Canary => "Edge SxS",
+        };
+        ["ProgramFiles(x86)", "ProgramFiles", "LOCALAPPDATA"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(|root| {
+                PathBuf::from(root)
+                    .join("Microsoft")
+                    .join(folder)
+                    .join("Application")
+                    .join("msedge.exe")
+            })
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        let name = match channel {
+            Channel::Stable => "Microsoft Edge",
+            Channel::Beta => "Microsoft Edge Beta",
+            Channel::Dev => "Microsoft Edge Dev",
+            Channel::Canary => "Microsoft Edge Canary",
+        };
+        // dragged into the user's own Applications without admin rights
+        std::iter::once(PathBuf::from("/"))
+            .chain(crate::paths::home_dir())
+            .map(|root| {
+                root.join("Applications")
+                    .join(format!("{name}.app/Contents/MacOS/{name}"))
+            })
+            .collect()
+    } else {
+        let (Some(name), dir) = (
+            linux_package(channel),
+            match channel {
+                Channel::Stable => "msedge",
+                Channel::Beta => "msedge-beta",
+                _ => "msedge-dev",
+            },
+        ) else {
+            return Vec::new();
+        };
+        vec![
+            PathBuf::from("/usr/bin").join(name),
+            PathBuf::from("/opt/microsoft").join(dir).join("msedge"),
+        ]
+    }
+}
+
+/// The product version of the browser at `exe`.
+pub fn file_version(exe: &Path) -> Result<Option<String>> {
+    if cfg!(windows) {
+        // running msedge.exe would open a window, so read the version resource instead,
+        // natively under wine, which seldom has powershell
+        if crate::wine::detected() {
+            return version_resource(exe);
+        }
+        return powershell(&format!(
+            "(Get-Item -LiteralPath {} -ErrorAction Stop).VersionInfo.ProductVersion",
+            ps_quote(&exe.to_string_lossy())
+        ));
+    }
+
+    if cfg!(target_os = "macos") {
+        if let Some(version) = bundle_version(exe) {
+            return Ok(Some(version));
+        }
+    }
+
+    // e.g. `Microsoft Edge 120.0.2210.91 unknown`
+    crate::sandbox::check("running the browser for its version")?;
+    let output = Command::new(exe).arg("--version").output()?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .find(|word| word.contains('.') && word.bytes().all(|b| b == b'.' || b.is_ascii_digit()))
+        .map(str::to_string))
+}
+
+/// The product version in the version resource of the windows executable `exe`.
+#[cfg(windows)]
+fn version_resource(exe: &Path) -> Result<Option<String>> {
+    use std::{ffi::c_void, os::windows::ffi::OsStrExt, ptr};
+
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+    };
+
+    let path: Vec<u16> = exe.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `path` is nul terminated
+    let size = unsafe { GetFileVersionInfoSizeW(path.as_ptr(), ptr::null_mut()) };
+    if size == 0 {
+        return Ok(None);
+    }
+    let mut data = vec![0u8; size as usize];
+    // SAFETY: `data` is `size` bytes long
+    if unsafe { GetFileVersionInfoW(path.as_ptr(), 0, size, data.as_mut_ptr().cast()) } == 0 {
+        return Ok(None);
+    }
+    let root: Vec<u16> = "\\".encode_utf16().chain(Some(0)).collect();
+    let (mut info, mut len): (*mut c_void, u32) = (ptr::null_mut(), 0);
+    // SAFETY: `data` holds the version info just read, `info` points into it after
+    if unsafe { VerQueryValueW(data.as_ptr().cast(), root.as_ptr(), &mut info, &mut len) } == 0
+        || (len as usize) < std::mem::size_of::<VS_FIXEDFILEINFO>()
+    {
+        return Ok(None);
+    }
+    // SAFETY: the root block is a VS_FIXEDFILEINFO, of at least the checked length
+    let info = unsafe { &*(info as *const VS_FIXEDFILEINFO) };
+    Ok(Some(format!(
+        "{}.{}.{}.{}",
+        info.dwProductVersionMS >> 16,
+        info.dwProductVersionMS & 0xFFFF,
+        info.dwProductVersionLS >> 16,
+        info.dwProductVersionLS & 0xFFFF
+    )))
+}
+
+#[cfg(not(windows))]
+fn version_resource(_exe: &Path) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// `CFBundleShortVersionString` from the `Info.plist` of the app bundle holding
+/// `exe`, which unlike `--version` needs nothing started.
+fn bundle_version(exe: &Path) -> Option<String> {
+    let bundle = exe
+        .ancestors()
+        .find(|dir| dir.extension().is_some_and(|ext| ext == "app"))?;
+    // binary plists are left to `--version`
+    let plist = std::fs::read_to_string(bundle.join("Contents").join("Info.plist")).ok()?;
+    let (_, after) = plist.split_once("<key>CFBundleShortVersionString</key>")?;
+    let (_, value) = after.split_once("<string>")?;
+    let (version, _) = value.split_once("</string>")?;
+    Some(version.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn powershell(command: &str) -> Result<Option<String>> {
+    crate::sandbox::check("running powershell")?;
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command", command])
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(stdout).filter(|s| output.status.success() && !s.is_empty()))
+}
+
+pub fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// One installed copy of webview2 or an edge channel, as `detect` lists them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Installation {
+    /// `webview2` or `edge`.
+    pub browser: &'static str,
+    /// Unset for webview2, which has a single evergreen channel.
+    pub channel: Option<Channel>,
+    pub version: String,
+    /// `machine` or `user` wide.
+    pub scope: &'static str,
+    /// The architecture of its executable, when that could be found.
+    pub arch: Option<&'static str>,
+    /// Its executable, when that could be found.
+    pub path: Option<PathBuf>,
+    /// Whether it is a fixed version webview2 runtime an app brings along, rather
+    /// than the evergreen one EdgeUpdate keeps current.
+    pub fixed: bool,
+    /// The registry key, directory or executable the version came from.
+    pub source: String,
+}
+
+impl Installation {
+    /// One line saying what and where it is, as `detect` prints it.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}{} {} ({}-wide, {}) from {}",
+            self.browser,
+            match (self.channel, self.fixed) {
+                (Some(channel), _) => format!("{} ", channel.as_str()),
+                (None, true) => "fixed ".into(),
+                (None, false) => String::new(),
+            },
+            self.version,
+            self.arch.unwrap_or("unknown arch"),
+            self.scope,
+            self.bitness(),
+            self.source
+        )
+    }
+
+    /// `32-bit`, `64-bit` or `bitness unknown`, from its executable's architecture.
+    pub fn bitness(&self) -> &'static str {
+        self.arch.map_or("bitness unknown", bitness)
+    }
+
+    /// The fixed version runtime in `dir`, of `version`.
+    pub fn fixed_runtime(version: String, dir: &Path) -> Self {
+        let exe = dir.join(WEBVIEW2_EXECUTABLE);
+        Installation {
+            browser: "webview2",
+            channel: None,
+            version,
+            scope: exe_scope(dir),
+            arch: arch::binary_arch(&exe).ok(),
+            path: Some(exe),
+            fixed: true,
+            source: dir.display().to_string(),
+        }
+    }
+}
+
+/// Every installation `detect` lists for `args`: the fixed version runtime given
+/// with `--webview2-dir` or `--webview2-app` first, then [`installations`] of
+/// `--browser`.
+pub fn all(args: &[String]) -> Result<Vec<Installation>> {
+    let browser = flag_value(args, "--browser")
+        .map(|b| b.parse::<Browser>())
+        .transpose()?;
+    let mut found = Vec::new();
+    if matches!(browser, None | Some(Browser::WebView2)) {
+        if let Some((version, dir)) = fixed_runtime(args)? {
+            found.push(Installation::fixed_runtime(version, &dir));
+        }
+    }
+    found.extend(installations(browser)?);
+    Ok(found)
+}
+
+/// The first of `found` that `browser` would match, which is the one install picks
+/// from a list in the order [`installations`] returns.
+pub fn preferred(found: &[Installation], browser: Browser) -> Option<&Installation> {
+    found.iter().find(|i| match browser {
+        Browser::WebView2 => i.browser == "webview2",
+        Browser::Edge(None) => i.browser == "edge",
+        Browser::Edge(Some(channel)) => i.browser == "edge" && i.channel == Some(channel),
+    })
+}
+
+/// Lists installed copies of `browser`, or of webview2 and every edge channel,
+/// reading only the registry and the filesystem.
+///
+/// They come in order of precedence, the first being what detection matches: the
+/// webview2 runtime from its machine-wide registry keys, the WOW6432Node view
+/// before the native one, then from the per-user key and then its installation
+/// directories; after it edge stable, beta, dev and canary, each machine-wide
+/// before per user and from the registry before its executables.
+pub fn installations(browser: Option<Browser>) -> Result<Vec<Installation>> {
+    let mut found = Vec::new();
+    if matches!(browser, None | Some(Browser::WebView2)) {
+        for key in [
+            crate::WEBVIEW2_KEY_64BIT_MACHINE,
+            crate::WEBVIEW2_KEY_32BIT_MACHINE,
+            crate::WEBVIEW2_KEY_USER,
+        ] {
+            let Some(version) = registry::read_string(key, "pv")?.filter(|v| !v.trim().is_empty())
+            else {
+                continue;
+            };
+            let version = version.trim().to_string();
+            let scope = key_scope(key);
+            let exe = webview2_executables(scope, &version)
+                .into_iter()
+                .find(|exe| exe.is_file());
+            add(
+                &mut found,
+                "webview2",
+                None,
+                version,
+                scope,
+                exe,
+                key.into(),
+            );
+        }
+        // also when the registry knew one, a runtime it lost track of still counts
+        if let Some((version, dir)) = scan_installations(&crate::webview2_dirs()) {
+            let scope = exe_scope(&dir);
+            let exe = Some(dir.join(WEBVIEW2_EXECUTABLE)).filter(|exe| exe.is_file());
+            let source = dir.display().to_string();
+            add(&mut found, "webview2", None, version, scope, exe, source);
+        }
+    }
+
+    let channels = match browser {
+        None | Some(Browser::Edge(None)) => ALL_CHANNELS.to_vec(),
+        Some(Browser::Edge(Some(channel))) => vec![channel],
+        Some(Browser::WebView2) => Vec::new(),
+    };
+    for channel in channels {
+        let before = found.len();
+        for key in registry_keys(channel) {
+            if let Some(version) = registry::read_string(&key, "pv")?.filter(|v| !v.is_empty()) {
+                let scope = key_scope(&key);
+                let exe = executables(channel)
+                    .into_iter()
+                    .find(|exe| exe.is_file() && exe_scope(exe) == scope);
+                add(&mut found, "edge", Some(channel), version, scope, exe, key);
+            }
+        }
+        if found.len() > before {
+            continue;
+        }
+        for exe in executables(channel) {
+            if exe.is_file() {
+                if let Some(version) = file_version(&exe)? {
+                    let source = exe.display().to_string();
+                    let scope = exe_scope(&exe);
+                    add(
+                        &mut found,
+                        "edge",
+                        Some(channel),
+                        version,
+                        scope,
+                        Some(exe),
+                        source,
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Adds an installation unless the same one was already found, as both 64 and
+/// 32-bit views of the machine-wide registry can list it.
+fn add(
+    found: &mut Vec<Installation>,
+    browser: &'static str,
+    channel: Option<Channel>,
+    version: String,
+    scope: &'static str,
+    exe: Option<PathBuf>,
+    source: String,
+) {
+    let version = version.trim().to_string();
+    if found.iter().any(|i| {
+        i.browser == browser && i.channel == channel && i.scope == scope && i.version == version
+    }) {
+        return;
+    }
+    explain::step(format!("{} {} found at {}", browser, version, source));
+    found.push(Installation {
+        browser,
+        channel,
+        version,
+        scope,
+        arch: exe.as_deref().and_then(|exe| arch::binary_arch(exe).ok()),
+        path: exe,
+        fixed: false,
+        source,
+    });
+}
+
+fn key_scope(key: &str) -> &'static str {
+    if key.starts_with("HKCU") {
+        "user"
+    } else {
+        "machine"
+    }
+}
+
+/// Per-user installs live under the user's profile, everything else is machine-wide.
+fn exe_scope(exe: &Path) -> &'static str {
+    let home = std::env::var_os(if cfg!(windows) {
+        "LOCALAPPDATA"
+    } else {
+        "HOME"
+    });
+    match home {
+        Some(home) if exe.starts_with(&home) => "user",
+        _ => "machine",
+    }
+}
+
+fn webview2_executables(scope: &str, version: &str) -> Vec<PathBuf> {
+    let roots: &[&str] = match scope {
+        "user" => &["LOCALAPPDATA"],
+        _ => &["ProgramFiles(x86)", "ProgramFiles"],
+    };
+    roots
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|root| {
+            PathBuf::from(root)
+                .join("Microsoft")
+                .join("EdgeWebView")
+                .join("Application")
+                .join(version)
+                .join(WEBVIEW2_EXECUTABLE)
+        })
+        .collect()
+}
+
+/// Prints every installed webview2 and edge, or only the `--browser` given, without
+/// downloading anything.
+pub fn detect(args: &[String]) -> Result<()> {
+    let found = all(args)?;
+    if found.is_empty() {
+        bail!("no installed webview2 or edge found");
+    }
+
+    for installation in &found {
+        let channel = installation.channel.map(Channel::as_str);
+        output::line(&installation.describe());
+        output::push(
+            "installations",
+            json!({
+                "browser": installation.browser,
+                "channel": channel,
+                "version": installation.version,
+                "scope": installation.scope,
+                "arch": installation.arch,
+                "bitness": installation.arch.map(bitness),
+                "path": installation.path,
+                "fixed": installation.fixed,
+                "source": installation.source,
+            }),
+        );
+    }
+
+    // what install would match, for build steps reading single values
+    if let Some(webview2) = preferred(&found, Browser::WebView2) {
+        output::parameter("webview2.version", &webview2.version);
+    }
+    if let Some(edge) = preferred(&found, Browser::Edge(None)) {
+        output::parameter("edge.version", &edge.version);
+        output::parameter("edge.channel", edge.channel.map_or("", Channel::as_str));
+    }
+    Ok(())
+}
+
+/// The installation `--interactive`, `--install-index` or `--channel` picks, among
+/// those `detect` lists for `--browser`, or `None` when none of them is given and
+/// detection goes on as usual.
+///
+/// `--install-index` counts from 1 in `detect`'s order, `--channel` is an edge
+/// channel or `webview2` and takes the first match, and `--interactive` asks on the
+/// terminal unless only one installation is left to choose from.
+pub fn choose(args: &[String]) -> Result<Option<Installation>> {
+    let interactive = has_flag(args, "--interactive");
+    let index = flag_value(args, "--install-index");
+    let channel = flag_value(args, "--channel");
+    if !interactive && index.is_none() && channel.is_none() {
+        return Ok(None);
+    }
+    if interactive && index.is_some() {
+        bail!("--install-index already picks an installation, so it can't be combined with --interactive");
+    }
+
+    let mut found = all(args)?;
+    if let Some(channel) = &channel {
+        let wanted = match channel.trim().to_ascii_lowercase().as_str() {
+            "webview2" => None,
+            other => Some(other.parse::<Channel>()?),
+        };
+        found.retain(|i| match wanted {
+            Some(wanted) => i.browser == "edge" && i.channel == Some(wanted),
+            None => i.browser == "webview2",
+        });
+    }
+    if found.is_empty() {
+        return Err(crate::Error::NotInstalled {
+            what: match channel.as_deref().map(str::trim) {
+                Some(channel) if channel.eq_ignore_ascii_case("webview2") => "webview2".into(),
+                Some(channel) => format!("edge {}", channel),
+                None => "an installed webview2 or edge".into(),
+            },
+        }
+        .into());
+    }
+
+    let pick = match index {
+        Some(index) => match index.trim().parse::<usize>() {
+            Ok(n) if (1..=found.len()).contains(&n) => n - 1,
+            _ => bail!(
+                "--install-index {} isn't one of the {} installations found, see `detect`",
+                index,
+                found.len()
+            ),
+        },
+        None if interactive && found.len() > 1 => prompt(&found)?,
+        None => 0,
+    };
+    let chosen = found.swap_remove(pick);
+    explain::step(format!("matching {}", chosen.describe()));
+    Ok(Some(chosen))
+}
+
+/// Lists `found` on the terminal and reads which of them to match.
+fn prompt(found: &[Installation]) -> Result<usize> {
+    if !(io::stdin().is_terminal() && io::stderr().is_terminal()) {
+        bail!("--interactive needs a terminal, pick with --install-index or --channel instead");
+    }
+    for (n, installation) in found.iter().enumerate() {
+        eprintln!("  {}) {}", n + 1, installation.describe());
+    }
+    let mut line = String::new();
+    loop {
+        eprint!("match which installation [1-{}]? ", found.len());
+        io::stderr().flush()?;
+        line.clear();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            bail!("no installation chosen");
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=found.len()).contains(&n) => return Ok(n - 1),
+            _ => eprintln!("expected a number from 1 to {}", found.len()),
+        }
+    }
+}
+
+fn bitness(arch: &str) -> &'static str {
+    match arch {
+        "x86" => "32-bit",
+        "unknown" => "bitness unknown",
+        _ => "64-bit",
+    }
+}
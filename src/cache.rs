@@ -0,0 +1,537 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    checksums,
+    download::sha256_file,
+    explain, flag_value, has_flag, http, lock, output, parse_size, paths,
+    receipt::{self, Receipt},
+    retry, version_key,
+};
+
+/// The parts of the cache that hold downloads, as opposed to settings or logs.
+const CONTENTS: &[&str] = &["archives", "drivers", "downloads", "http", "mirrors.json"];
+
+/// Where the `filename` driver of `version` for `platform` is kept in the shared
+/// cache.
+pub fn driver_path(version: &str, platform: &str, filename: &str) -> Result<PathBuf> {
+    let dir = paths::cache_dir().ok_or_else(|| anyhow!("unable to locate a cache directory"))?;
+    Ok(dir
+        .join("drivers")
+        .join(version.trim())
+        .join(platform)
+        .join(filename))
+}
+
+/// A cached `filename` driver of `version` for `platform` that is unchanged since
+/// it was cached, and came from an archive matching `pin` or the checksum source if
+/// either is set.
+pub fn lookup(
+    version: &str,
+    platform: &str,
+    filename: &str,
+    pin: Option<&str>,
+) -> Option<(PathBuf, Receipt)> {
+    let path = driver_path(version, platform, filename).ok()?;
+    // wait out another process storing this entry rather than judge it half written
+    let _lock = if http::dry_run() {
+        None
+    } else {
+        Some(lock(&path).ok()?)
+    };
+    let receipt = Receipt::read(&path).ok().flatten()?;
+    if !path.is_file() {
+        return None;
+    }
+
+    let expected = match pin {
+        Some(pin) => Some(pin.trim().to_string()),
+        None => checksums::expected(&receipt.source).ok()?,
+    };
+    if expected.is_some_and(|e| !e.eq_ignore_ascii_case(&receipt.archive_sha256)) {
+        explain::step(format!(
+            "cached {} came from a different archive than expected, not using it",
+            path.display()
+        ));
+        return None;
+    }
+    if !sha256_file(&path).is_ok_and(|sha| sha == receipt.driver_sha256) {
+        explain::step(format!(
+            "cached {} was modified since it was cached, discarding it",
+            path.display()
+        ));
+        if !http::dry_run() {
+            let _ = fs::remove_file(&path);
+        }
+        return None;
+    }
+
+    explain::step(format!(
+        "cache has {} {} at {}",
+        version,
+        platform,
+        path.display()
+    ));
+    Some((path, receipt))
+}
+
+/// Like [`lookup`], but for the driver chosen for browser `version`, as its
+/// receipt records, so a driver whose versions don't follow the browser's is
+/// found without asking where it is published.
+pub fn lookup_for_browser(
+    version: &str,
+    platform: &str,
+    filename: &str,
+    pin: Option<&str>,
+) -> Option<(PathBuf, Receipt)> {
+    let dir = paths::cache_dir()?;
+    let mut found: Vec<Receipt> = read_dirs(&dir.join("drivers"))
+        .ok()?
+        .into_iter()
+        .filter_map(|driver| Receipt::read(&driver.join(platform).join(filename)).ok()?)
+        .filter(|receipt| receipt.platform == platform && receipt.matches_browser(version))
+        .collect();
+    // the newest driver cached for it, should a later release have been cached too
+    found.sort_by_key(|receipt| version_key(&receipt.version));
+    found
+        .into_iter()
+        .rev()
+        .find_map(|receipt| lookup(&receipt.version, platform, filename, pin))
+}
+
+/// Keeps a copy of the installed `driver`, described by `receipt`, as `filename`
+/// for later runs.
+pub fn store(driver: &Path, filename: &str, receipt: &Receipt) -> Result<()> {
+    let path = driver_path(&receipt.version, &receipt.platform, filename)?;
+    let _lock = lock(&path)?;
+    // another agent sharing the cache may have published the same driver first
+    let stored = Receipt::read(&path).ok().flatten().is_some_and(|stored| {
+        stored.driver_sha256 == receipt.driver_sha256
+            && sha256_file(&path).is_ok_and(|sha| sha == receipt.driver_sha256)
+    });
+    if stored {
+        explain::step(format!("{} is already cached", path.display()));
+        return Ok(());
+    }
+
+    // copied aside under a name no other writer uses and renamed, so a reader never
+    // sees part of a driver, nor a receipt describing another
+    let temp = path.with_file_name(format!(".{}.{}.tmp", filename, lock::holder()));
+    let result = (|| {
+        fs::copy(driver, &temp)?;
+        receipt.write(&temp)?;
+        let _ = fs::remove_file(receipt::path_for(&path));
+        retry::when_locked(&path.display().to_string(), || fs::rename(&temp, &path))?;
+        fs::rename(receipt::path_for(&temp), receipt::path_for(&path))?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        let _ = fs::remove_file(receipt::path_for(&temp));
+    }
+    result
+}
+
+/// Holds the right to download the `filename` driver of `version` for `platform`
+/// into a shared cache, so only one agent of a fleet fetches it while the others
+/// wait to use its copy; `None` when the cache isn't shared.
+pub fn claim(version: &str, platform: &str, filename: &str) -> Result<Option<lock::Lock>> {
+    if !paths::shared_cache() {
+        return Ok(None);
+    }
+    let path = driver_path(version, platform, filename)?;
+    lock::acquire_shared(&path.with_file_name(format!("{}.download", filename))).map(Some)
+}
+
+/// Locks the cache entry at `path`, with a lease on a shared cache, whose clients
+/// don't all honour advisory locks taken on other machines.
+fn lock(path: &Path) -> Result<lock::Lock> {
+    if paths::shared_cache() {
+        lock::acquire_shared(path)
+    } else {
+        lock::acquire(path)
+    }
+}
+
+/// Removes every cached driver and download, from `--clear-cache`.
+pub fn clear() -> Result<()> {
+    let Some(dir) = paths::cache_dir() else {
+        return Ok(());
+    };
+    for name in CONTENTS {
+        let path = dir.join(name);
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else if path.is_file() {
+            fs::remove_file(&path)?;
+        }
+    }
+    output::progress(&format!("cleared cache {}", dir.display()));
+    Ok(())
+}
+
+/// What `clean` and `cache prune` keep: drivers cached within `--max-age` (or
+/// `--older-than`) days, the `--keep-latest` newest versions of each driver and
+/// platform whatever their age, and then as many of the rest as fit in
+/// `--max-size`, newest first. Without any of them nothing is kept.
+pub struct Retention {
+    max_age: Option<Duration>,
+    keep_latest: Option<usize>,
+    max_size: Option<u64>,
+}
+
+impl Retention {
+    fn from_args(args: &[String]) -> Result<Self> {
+        let max_age = ["--max-age", "--older-than"]
+            .iter()
+            .find_map(|flag| flag_value(args, flag).map(|days| (flag, days)))
+            .map(
+                |(flag, days)| match days.trim_end_matches('d').parse::<u64>() {
+                    Ok(days) => Ok(Duration::from_secs(days * 24 * 60 * 60)),
+                    Err(_) => bail!("invalid {} {}, expected a number of days", flag, days),
+                },
+            )
+            .transpose()?;
+        let keep_latest = flag_value(args, "--keep-latest")
+            .map(|k| {
+                k.parse::<usize>()
+                    .map_err(|_| anyhow!("invalid --keep-latest {}, expected a count", k))
+            })
+            .transpose()?;
+        let max_size = flag_value(args, "--max-size")
+            .map(|size| {
+                parse_size(&size).map_err(|e| anyhow!("invalid --max-size {}: {}", size, e))
+            })
+            .transpose()?;
+        Ok(Retention {
+            max_age,
+            keep_latest,
+            max_size,
+        })
+    }
+
+    /// The policy to prune by after each install, when `--auto-prune` asks for one.
+    pub fn auto(args: &[String]) -> Result<Option<Self>> {
+        if !has_flag(args, "--auto-prune") {
+            return Ok(None);
+        }
+        let retention = Retention::from_args(args)?;
+        if !retention.is_set() {
+            bail!("--auto-prune needs --keep-latest, --max-age or --max-size to prune by");
+        }
+        Ok(Some(retention))
+    }
+
+    fn is_set(&self) -> bool {
+        self.max_age.is_some() || self.keep_latest.is_some() || self.max_size.is_some()
+    }
+
+    /// Whether a file last written at `modified` is too old to keep.
+    fn expired(&self, modified: SystemTime) -> bool {
+        self.max_age
+            .is_none_or(|age| modified.elapsed().is_ok_and(|elapsed| elapsed > age))
+    }
+
+    /// Prunes the cache after a successful install; a prune that fails is only
+    /// worth a warning, the driver is in place.
+    pub fn prune_after_install(&self) {
+        if http::dry_run() {
+            return;
+        }
+        if let Err(e) = self.prune() {
+            output::note_warning(
+                "prune-failed",
+                &format!("unable to prune the cache: {:#}", e),
+            );
+        }
+    }
+
+    fn prune(&self) -> Result<()> {
+        let Some(dir) = paths::cache_dir() else {
+            return Ok(());
+        };
+        let mut pruned = Pruned::default();
+        // what neither age nor count removed, for --max-size to choose from
+        let mut candidates = Vec::new();
+        let by_age_or_count = self.max_age.is_some() || self.keep_latest.is_some();
+
+        for mut drivers in cached_drivers(&dir)?.into_values() {
+            drivers.sort_by_key(|(version, _)| std::cmp::Reverse(version_key(version)));
+            let newest = self.keep_latest.unwrap_or(0);
+            for (version, path) in drivers.into_iter().skip(newest) {
+                let modified = fs::metadata(receipt::path_for(&path))?.modified()?;
+                if by_age_or_count && self.expired(modified) {
+                    pruned.driver(&version, &path)?;
+                } else {
+                    candidates.push((modified, path, Some(version)));
+                }
+            }
+        }
+
+        // downloads have no version, so only their age decides
+        for name in ["archives", "downloads"] {
+            let downloads = dir.join(name);
+            if !downloads.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&downloads)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if !metadata.is_file() {
+                    continue;
+                }
+                if self.max_age.is_some() && self.expired(metadata.modified()?) {
+                    pruned.file(&entry.path())?;
+                } else {
+                    candidates.push((metadata.modified()?, entry.path(), None));
+                }
+            }
+        }
+
+        if let Some(max_size) = self.max_size {
+            let mut total: u64 = CONTENTS.iter().map(|name| size_of(&dir.join(name))).sum();
+            candidates.sort_by_key(|(modified, _, _)| *modified);
+            for (_, path, version) in candidates {
+                if total <= max_size {
+                    break;
+                }
+                let before = pruned.freed;
+                match version {
+                    Some(version) => pruned.driver(&version, &path)?,
+                    None => pruned.file(&path)?,
+                }
+                total = total.saturating_sub(pruned.freed - before);
+            }
+            if total > max_size {
+                explain::step(format!(
+                    "the cache is still {} bytes, over --max-size {}, in drivers --keep-latest keeps",
+                    total, max_size
+                ));
+            }
+        }
+
+        remove_empty_dirs(&dir.join("drivers"))?;
+        output::progress(&format!(
+            "removed {} cached drivers, freeing {} bytes",
+            pruned.removed, pruned.freed
+        ));
+        output::parameter("cache.removed", &pruned.removed.to_string());
+        output::parameter("cache.freed_bytes", &pruned.freed.to_string());
+        Ok(())
+    }
+}
+
+/// What a prune removed so far.
+#[derive(Default)]
+struct Pruned {
+    removed: usize,
+    freed: u64,
+}
+
+impl Pruned {
+    /// Removes the cached `version` driver at `path` and its receipt, holding its
+    /// lock throughout. The `.lock` file itself stays, since a process waiting on
+    /// it would otherwise go on to lock a file nobody else sees.
+    fn driver(&mut self, version: &str, path: &Path) -> Result<()> {
+        let receipt = receipt::path_for(path);
+        let _entry = lock(path)?;
+        self.freed += [path, receipt.as_path()]
+            .iter()
+            .map(|file| fs::metadata(file).map_or(0, |m| m.len()))
+            .sum::<u64>();
+        explain::step(format!("removing cached {} {}", version, path.display()));
+        for file in [path, receipt.as_path()] {
+            match fs::remove_file(file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        self.removed += 1;
+        Ok(())
+    }
+
+    fn file(&mut self, path: &Path) -> Result<()> {
+        self.freed += fs::metadata(path).map_or(0, |m| m.len());
+        explain::step(format!("removing {}", path.display()));
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+/// The `clean` command, and `cache clear`: empties the cache or, with
+/// `--max-age`, `--keep-latest` or `--max-size`, removes only the drivers and
+/// downloads they don't keep.
+pub fn clean(args: &[String]) -> Result<()> {
+    let retention = Retention::from_args(args)?;
+    if !retention.is_set() {
+        return clear();
+    }
+    retention.prune()
+}
+
+/// Each cached version of a driver and where it is, by platform and filename.
+type Groups = BTreeMap<(String, String), Vec<(String, PathBuf)>>;
+
+/// The cached drivers, at `drivers/<version>/<platform>/<filename>`, grouped by
+/// platform and filename so each driver keeps its own versions.
+fn cached_drivers(dir: &Path) -> Result<Groups> {
+    let mut groups = Groups::new();
+    for version in read_dirs(&dir.join("drivers"))? {
+        for platform in read_dirs(&version)? {
+            // every cached driver has a receipt beside it
+            for entry in fs::read_dir(&platform)? {
+                let name = file_name(&entry?.path());
+                let Some(driver) = name.strip_suffix(".receipt.json") else {
+                    continue;
+                };
+                let key = (file_name(&platform), driver.to_string());
+                groups
+                    .entry(key)
+                    .or_default()
+                    .push((file_name(&version), platform.join(driver)));
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// How many bytes the files at or under `path` take.
+fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| size_of(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+fn read_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Removes the directories under `dir` left with nothing in them.
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for child in read_dirs(dir)? {
+        remove_empty_dirs(&child)?;
+        if fs::read_dir(&child)?.next().is_none() {
+            fs::remove_dir(&child)?;
+        }
+    }
+    Ok(())
+}
+
+/// The `cache` command: `cache path` prints where the cache is, `cache clear` empties
+/// it, or prunes it like `clean`, and `cache prune` only ever prunes it.
+pub fn command(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("path") => {
+            let dir =
+                paths::cache_dir().ok_or_else(|| anyhow!("unable to locate a cache directory"))?;
+            output::line(&dir.display().to_string());
+            output::parameter("cache.path", &dir.to_string_lossy());
+            Ok(())
+        }
+        Some("clear") => clean(args),
+        Some("prune") => {
+            let retention = Retention::from_args(args)?;
+            if !retention.is_set() {
+                bail!(
+                    "cache prune needs --keep-latest, --max-age or --max-size, or use cache clear"
+                );
+            }
+            retention.prune()
+        }
+        Some(other) => bail!(
+            "unknown cache command {}, expected path, clear or prune",
+            other
+        ),
+        None => bail!("cache needs a command, path, clear or prune"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn retention(flags: &[&str]) -> Result<Retention> {
+        Retention::from_args(&args(flags))
+    }
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn reads_retention_flags() {
+        let r = retention(&["--max-age", "30d", "--keep-latest=2", "--max-size", "1g"]).unwrap();
+        assert_eq!(r.max_age, Some(30 * DAY));
+        assert_eq!(r.keep_latest, Some(2));
+        assert_eq!(r.max_size, Some(1 << 30));
+        assert_eq!(
+            retention(&["--older-than", "7"]).unwrap().max_age,
+            Some(7 * DAY)
+        );
+        assert!(!retention(&[]).unwrap().is_set());
+
+        for flags in [
+            &["--max-age", "a week"][..],
+            &["--keep-latest", "-1"],
+            &["--max-size", "lots"],
+        ] {
+            assert!(retention(flags).is_err(), "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn auto_prunes_only_with_something_to_prune_by() {
+        assert!(Retention::auto(&args(&["--keep-latest", "1"]))
+            .unwrap()
+            .is_none());
+        assert!(Retention::auto(&args(&["--auto-prune"])).is_err());
+        assert!(
+            Retention::auto(&args(&["--auto-prune", "--keep-latest", "1"]))
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn expires_by_age_or_else_everything() {
+        let month = retention(&["--max-age", "30"]).unwrap();
+        assert!(!month.expired(SystemTime::now() - DAY));
+        assert!(month.expired(SystemTime::now() - 31 * DAY));
+        // without --max-age only count and size decide, so age never protects a file
+        let latest = retention(&["--keep-latest", "1"]).unwrap();
+        assert!(latest.expired(SystemTime::now()));
+    }
+}
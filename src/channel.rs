@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use crate::{base_url, explain, http_cache, manifest_url};
+
+/// A release channel of Microsoft Edge, each publishing its own driver builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    Beta,
+    Dev,
+    Canary,
+}
+
+impl Channel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Channel::Stable => "stable",
+            Channel::Beta => "beta",
+            Channel::Dev => "dev",
+            Channel::Canary => "canary",
+        }
+    }
+
+    /// The blob holding the newest driver version published for this channel.
+    fn marker(self) -> &'static str {
+        match self {
+            Channel::Stable => "LATEST_STABLE",
+            Channel::Beta => "LATEST_BETA",
+            Channel::Dev => "LATEST_DEV",
+            Channel::Canary => "LATEST_CANARY",
+        }
+    }
+
+    /// The newest driver version published for this channel.
+    pub fn latest_version(self) -> Result<String> {
+        let url = format!("{}/{}", manifest_url(), self.marker());
+        explain::step(format!("querying {} marker at {}", self.as_str(), url));
+
+        let version = decode_marker(&http_cache::get(&url)?)?;
+        explain::step(format!("{} marker answered {}", self.as_str(), version));
+        Ok(version)
+    }
+}
+
+impl FromStr for Channel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "stable" => Ok(Channel::Stable),
+            "beta" => Ok(Channel::Beta),
+            "dev" => Ok(Channel::Dev),
+            "canary" => Ok(Channel::Canary),
+            _ => bail!(
+                "unknown channel {}, expected one of stable, beta, dev, canary",
+                s
+            ),
+        }
+    }
+}
+
+/// Serves the per-major `LATEST_RELEASE_*` markers, which the listing host lacks.
+const RELEASE_MARKER_URL: &str = "https://msedgedriver.microsoft.com";
+
+/// The newest driver version Microsoft published for `major` on `platform`, from
+/// its `LATEST_RELEASE_<MAJOR>_<OS>` marker.
+pub fn latest_release(major: &str, platform: &str) -> Result<String> {
+    let os = match platform {
+        "win64" | "win32" | "arm64" => "WINDOWS",
+        "mac64" | "mac64_m1" => "MACOS",
+        "linux64" | "linux_arm64" => "LINUX",
+        other => bail!("no release marker for platform {}", other),
+    };
+    let base = base_url().unwrap_or(RELEASE_MARKER_URL);
+    let url = format!("{}/LATEST_RELEASE_{}_{}", base, major, os);
+    explain::step(format!("querying release marker at {}", url));
+
+    let version = decode_marker(&http_cache::get(&url)?)?;
+    explain::step(format!("release marker answered {}", version));
+    Ok(version)
+}
+
+/// Parses a comma separated channel list such as `stable,beta,dev`.
+pub fn parse_list(list: &str) -> Result<Vec<Channel>> {
+    list.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Marker files are UTF-16 with a byte order mark, though mirrors may re-encode them.
+pub fn decode_marker(bytes: &[u8]) -> Result<String> {
+    let text = match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    };
+
+    let version = text.trim().to_string();
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Err(anyhow!("unexpected version marker contents {:?}", version));
+    }
+    Ok(version)
+}
+
+fn utf16(bytes: &[u8], decode: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| decode([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
@@ -0,0 +1,348 @@
+use std::{path::PathBuf, process::exit, time::Instant};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    arch,
+    browser::{edge_version, Browser},
+    compat::SkewPolicy,
+    download::sha256_file,
+    driver_location, driver_version, fetch_manifest, find_blob, flag_value, host_can_run,
+    host_platform,
+    junit::{self, Outcome, TestCase},
+    major, output,
+    receipt::Receipt,
+    signature, webview2_detection, webview2_version,
+};
+
+/// Collects the outcome of each step, printing them as they complete.
+#[derive(Default)]
+struct Suite {
+    cases: Vec<TestCase>,
+}
+
+impl Suite {
+    fn run<T>(&mut self, name: &str, f: impl FnOnce() -> Result<(T, String)>) -> Option<T> {
+        let start = Instant::now();
+        let (value, outcome) = match f() {
+            Ok((value, detail)) => {
+                output::line(&format!("ok    {}: {}", name, detail));
+                (Some(value), Outcome::Passed)
+            }
+            Err(e) => {
+                output::line(&format!("FAIL  {}: {}", name, e));
+                output::problem(&format!("{}: {}", name, e));
+                (None, Outcome::Failed(e.to_string()))
+            }
+        };
+        self.cases.push(TestCase {
+            name: name.into(),
+            outcome,
+            duration: start.elapsed(),
+        });
+        value
+    }
+
+    fn skip(&mut self, name: &str, reason: &str) {
+        output::line(&format!("skip  {}: {}", name, reason));
+        self.cases.push(TestCase {
+            name: name.into(),
+            outcome: Outcome::Skipped(reason.into()),
+            duration: Default::default(),
+        });
+    }
+
+    /// Writes the requested report and exits nonzero if any step failed.
+    fn finish(self, suite: &str, args: &[String]) -> Result<()> {
+        if let Some(path) = flag_value(args, "--report-junit") {
+            junit::write_report(&PathBuf::from(path), suite, &self.cases)?;
+        }
+
+        if self
+            .cases
+            .iter()
+            .any(|c| matches!(c.outcome, Outcome::Failed(_)))
+        {
+            exit(1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the installed driver, or `--driver <path>`, matches the installed
+/// webview2, or edge with `--browser edge`, failing if it doesn't.
+pub fn check(args: &[String]) -> Result<()> {
+    let skew = SkewPolicy::from_args(args)?;
+    // install's `--driver` picks a kind of driver, here it may name the file
+    let driver_path = match flag_value(args, "--driver") {
+        Some(path)
+            if !matches!(
+                path.as_str(),
+                "edge"
+                    | "msedgedriver"
+                    | "chrome"
+                    | "chromedriver"
+                    | "gecko"
+                    | "geckodriver"
+                    | "iedriver"
+            ) =>
+        {
+            PathBuf::from(path)
+        }
+        _ => driver_location(args)?,
+    };
+    let browser = Browser::from_args(args)?;
+    let name = match browser {
+        Browser::WebView2 => "webview2",
+        Browser::Edge(_) => "edge",
+    };
+    let mut suite = Suite::default();
+
+    let detected = suite.run(&format!("{} detected", name), || match browser {
+        Browser::WebView2 => {
+            let (version, key) =
+                webview2_detection()?.ok_or_else(|| anyhow!("unable to find webview2_version"))?;
+            let version = version.trim().to_string();
+            Ok(((version.clone(), key), version))
+        }
+        Browser::Edge(channel) => {
+            let (version, channel) = edge_version(channel)?
+                .ok_or_else(|| anyhow!("unable to find an installed edge browser"))?;
+            let detail = format!("{} ({})", version, channel.as_str());
+            Ok(((version, format!("edge {}", channel.as_str())), detail))
+        }
+    });
+    let driver = suite.run("driver runs", || {
+        let version = driver_version(&driver_path)?;
+        Ok((version.clone(), version))
+    });
+
+    let step = format!("driver matches {}", name);
+    match (detected, driver) {
+        (Some((browser_version, source)), Some(driver)) => {
+            suite.run(&step, || {
+                if let Some(mismatch) = skew.mismatch(&driver, &browser_version) {
+                    let expected = major(&browser_version)
+                        .parse()
+                        .map(|browser_major| skew.compatible(browser_major))
+                        .unwrap_or_else(|_| format!("{}.x", major(&browser_version)));
+                    bail!(
+                        "{}\n{}",
+                        mismatch,
+                        comparison(&[
+                            (
+                                "-",
+                                "installed driver",
+                                &driver,
+                                &driver_path.display().to_string()
+                            ),
+                            (
+                                "+",
+                                "expected driver",
+                                &expected,
+                                &format!("to match {}", name)
+                            ),
+                            (" ", name, &browser_version, &source),
+                        ])
+                    );
+                }
+                Ok(((), format!("major version {}", major(&driver))))
+            });
+        }
+        _ => suite.skip(&step, "missing a version to compare"),
+    }
+
+    suite.finish("check", args)
+}
+
+/// Checks that everything needed for an install is reachable, without installing.
+pub fn selftest(args: &[String]) -> Result<()> {
+    let mut suite = Suite::default();
+
+    let webview2 = suite.run("webview2 detected", detect);
+    let manifest = suite.run("manifest reachable", || {
+        let manifest = fetch_manifest()?;
+        let detail = format!("{} bytes", manifest.len());
+        Ok((manifest, detail))
+    });
+
+    match (webview2, manifest) {
+        (Some(webview2), Some(manifest)) => {
+            suite.run("driver published", || {
+                match find_blob(&manifest, &webview2, host_platform())? {
+                    Some(blob) => Ok(((), blob.url)),
+                    None => bail!("no driver published for {}", webview2),
+                }
+            });
+        }
+        _ => suite.skip("driver published", "missing webview2 version or manifest"),
+    }
+
+    suite.finish("selftest", args)
+}
+
+/// Checks that an installed driver is still the file it was installed as, and still
+/// fits the installed webview2, reporting tampering, replacement or drift.
+pub fn verify(args: &[String]) -> Result<()> {
+    let driver = match flag_value(args, "--driver") {
+        Some(driver) => PathBuf::from(driver),
+        None => driver_location(args)?,
+    };
+    let skew = SkewPolicy::from_args(args)?;
+    let mut suite = Suite::default();
+
+    let receipt = suite.run("install receipt present", || {
+        match Receipt::read(&driver)? {
+            Some(receipt) => {
+                let detail = format!("{} installed {}", receipt.version, receipt.installed_at);
+                Ok((receipt, detail))
+            }
+            None => bail!(
+                "no install receipt for {}, it wasn't installed by this tool",
+                driver.display()
+            ),
+        }
+    });
+
+    let Some(receipt) = receipt else {
+        suite.skip("driver unmodified", "no receipt to compare against");
+        suite.skip(
+            "driver version matches receipt",
+            "no receipt to compare against",
+        );
+        suite.skip("driver matches webview2", "no receipt to compare against");
+        return suite.finish("verify", args);
+    };
+
+    suite.run("driver unmodified", || {
+        let actual = sha256_file(&driver)?;
+        if !actual.eq_ignore_ascii_case(&receipt.driver_sha256) {
+            bail!(
+                "sha256 is {} but {} was installed, the file was modified or replaced",
+                actual,
+                receipt.driver_sha256
+            );
+        }
+        Ok(((), actual))
+    });
+
+    if host_can_run(&receipt.platform) {
+        suite.run("driver version matches receipt", || {
+            let version = driver_version(&driver)?;
+            if version != receipt.version {
+                bail!(
+                    "driver reports {} but {} was installed",
+                    version,
+                    receipt.version
+                );
+            }
+            Ok(((), version))
+        });
+    } else {
+        suite.skip(
+            "driver version matches receipt",
+            &format!("{} drivers can't run on this host", receipt.platform),
+        );
+    }
+
+    match suite.run("webview2 detected", detect) {
+        Some(webview2) => {
+            suite.run("driver matches webview2", || {
+                if let Some(mismatch) = skew.mismatch(&receipt.version, &webview2) {
+                    bail!("webview2 has drifted since install: {}", mismatch);
+                }
+                Ok(((), format!("major version {}", major(&receipt.version))))
+            });
+        }
+        None => suite.skip("driver matches webview2", "missing a version to compare"),
+    }
+
+    suite.finish("verify", args)
+}
+
+/// Inspects any driver binary, wherever it came from, and whether it suits the
+/// installed webview2.
+pub fn verify_path(args: &[String]) -> Result<()> {
+    let path = match args.first().filter(|arg| !arg.starts_with("--")) {
+        Some(path) => PathBuf::from(path),
+        None => bail!(
+            "verify-path needs the driver to inspect, e.g. verify-path C:\\tools\\msedgedriver.exe"
+        ),
+    };
+    if !path.is_file() {
+        bail!("no file at {}", path.display());
+    }
+    let skew = SkewPolicy::from_args(args)?;
+    let mut suite = Suite::default();
+
+    suite.run("sha256", || Ok(((), sha256_file(&path)?)));
+    let arch = suite.run("architecture", || {
+        let arch = arch::binary_arch(&path)?;
+        if !arch::runs_on_host(arch) {
+            bail!(
+                "{} binary can't run on this {} host",
+                arch,
+                std::env::consts::ARCH
+            );
+        }
+        Ok((arch, arch.to_string()))
+    });
+    if cfg!(windows) {
+        suite.run("signature", || {
+            let signer = signature::verify(&path)?;
+            Ok(((), format!("signed by {}", signer)))
+        });
+    } else {
+        suite.skip("signature", "Authenticode is only checked on Windows");
+    }
+
+    let driver = match arch {
+        Some(_) => suite.run("driver runs", || {
+            let version = driver_version(&path)?;
+            Ok((version.clone(), version))
+        }),
+        None => {
+            suite.skip("driver runs", "not runnable on this host");
+            None
+        }
+    };
+    let webview2 = suite.run("webview2 detected", detect);
+
+    match (driver, webview2) {
+        (Some(driver), Some(webview2)) => {
+            suite.run("driver matches webview2", || {
+                if let Some(mismatch) = skew.mismatch(&driver, &webview2) {
+                    bail!(mismatch);
+                }
+                Ok(((), format!("major version {}", major(&driver))))
+            });
+        }
+        _ => suite.skip("driver matches webview2", "missing a version to compare"),
+    }
+
+    suite.finish("verify-path", args)
+}
+
+/// Lines up `(marker, what, version, where)` rows like a diff, so a CI log shows
+/// at a glance which side is wrong.
+fn comparison(rows: &[(&str, &str, &str, &str)]) -> String {
+    let what_width = rows.iter().map(|r| r.1.len()).max().unwrap_or_default();
+    let version_width = rows.iter().map(|r| r.2.len()).max().unwrap_or_default();
+    rows.iter()
+        .map(|(marker, what, version, location)| {
+            format!(
+                "      {} {:<what_width$}  {:<version_width$}  {}",
+                marker, what, version, location
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn detect() -> Result<(String, String)> {
+    let version = webview2_version()?
+        .map(|v| v.trim().to_string())
+        .ok_or_else(|| anyhow!("unable to find webview2_version"))?;
+    Ok((version.clone(), version))
+}
@@ -0,0 +1,87 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{explain, http, policy};
+
+static SOURCE: OnceLock<Option<String>> = OnceLock::new();
+static TABLE: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Sets where expected archive hashes come from: `--checksums`, else
+/// `MSEDGEDRIVER_TOOL_CHECKSUMS`, else the policy's `checksums`.
+pub fn configure(flag: Option<String>) {
+    let source = flag
+        .or_else(|| std::env::var("MSEDGEDRIVER_TOOL_CHECKSUMS").ok())
+        .or_else(|| policy::get().checksums.clone())
+        .filter(|s| !s.trim().is_empty());
+    let _ = SOURCE.set(source);
+}
+
+/// Whether downloads are verified against a checksum source.
+pub fn configured() -> bool {
+    SOURCE.get().is_some_and(Option::is_some)
+}
+
+/// The hash the checksum source lists for the archive at `url`, failing if a source
+/// is configured but has no entry for it.
+///
+/// Entries are `sha256sum` lines naming an archive by a trailing part of its URL,
+/// e.g. `<hex>  120.0.2210.91/edgedriver_win64.zip`.
+pub fn expected(url: &str) -> Result<Option<String>> {
+    let Some(source) = SOURCE.get().and_then(Option::as_deref) else {
+        return Ok(None);
+    };
+
+    let table = match TABLE.get() {
+        Some(table) => table,
+        None => {
+            let table = load(source)?;
+            TABLE.get_or_init(|| table)
+        }
+    };
+
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match table
+        .iter()
+        .filter(|(name, _)| path == name.as_str() || path.ends_with(&format!("/{}", name)))
+        .max_by_key(|(name, _)| name.len())
+    {
+        Some((name, hash)) => {
+            explain::step(format!("checksum source lists {} as {}", name, hash));
+            Ok(Some(hash.clone()))
+        }
+        None => bail!("checksum source {} has no entry for {}", source, url),
+    }
+}
+
+fn load(source: &str) -> Result<HashMap<String, String>> {
+    explain::step(format!("loading checksums from {}", source));
+    let contents = if source.starts_with("https://") || source.starts_with("http://") {
+        policy::get().check_url(source)?;
+        http::call(http::get(source))
+            .with_context(|| format!("unable to fetch checksums from {}", source))?
+            .into_string()?
+    } else {
+        std::fs::read_to_string(source)
+            .with_context(|| format!("unable to read checksums from {}", source))?
+    };
+
+    let mut table = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once(char::is_whitespace) {
+            Some((hash, name))
+                if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                let name = name.trim().trim_start_matches('*');
+                table.insert(name.to_string(), hash.to_ascii_lowercase());
+            }
+            _ => bail!("invalid line {} in checksums {}", number + 1, source),
+        }
+    }
+
+    Ok(table)
+}
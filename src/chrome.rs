@@ -0,0 +1,210 @@
+//! chromedriver, matched to an installed Google Chrome and fetched from the Chrome
+//! for Testing listing.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::{browser, driver::Driver, explain, http, registry, version_key, Error};
+
+const LISTING_URL: &str =
+    "https://googlechromelabs.github.io/chrome-for-testing/known-good-versions-with-downloads.json";
+
+/// Where Chrome records the version of the browser last run.
+const REGISTRY_KEYS: &[&str] = &[
+    "HKCU\\SOFTWARE\\Google\\Chrome\\BLBeacon",
+    "HKLM\\SOFTWARE\\Google\\Chrome\\BLBeacon",
+    "HKLM\\SOFTWARE\\WOW6432Node\\Google\\Chrome\\BLBeacon",
+];
+
+#[derive(Deserialize)]
+struct Listing {
+    versions: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    version: String,
+    #[serde(default)]
+    downloads: Downloads,
+}
+
+#[derive(Deserialize, Default)]
+struct Downloads {
+    // only published from chrome 115 on
+    #[serde(default)]
+    chromedriver: Vec<Download>,
+}
+
+#[derive(Deserialize)]
+struct Download {
+    platform: String,
+    url: String,
+}
+
+/// chromedriver, following Google Chrome.
+pub struct Chrome;
+
+impl Driver for Chrome {
+    fn name(&self) -> &'static str {
+        "chromedriver"
+    }
+
+    fn browser(&self) -> &'static str {
+        "chrome"
+    }
+
+    fn filename(&self, platform: &str) -> String {
+        if platform.starts_with("win") {
+            "chromedriver.exe".into()
+        } else {
+            "chromedriver".into()
+        }
+    }
+
+    fn archive_entry(&self, platform: &str) -> String {
+        // e.g. chromedriver-win64/chromedriver.exe
+        format!(
+            "chromedriver-{}/{}",
+            listing_platform(platform).unwrap_or(platform),
+            self.filename(platform)
+        )
+    }
+
+    fn supports(&self, platform: &str) -> bool {
+        listing_platform(platform).is_some()
+    }
+
+    fn browser_version(&self) -> Result<String> {
+        Ok(installed_version()?.ok_or_else(|| Error::NotInstalled {
+            what: "an installed google chrome".into(),
+        })?)
+    }
+
+    fn resolve(&self, version: &str, platform: &str) -> Result<(String, String)> {
+        let wanted = listing_platform(platform).ok_or_else(|| Error::UnsupportedPlatform {
+            platform: platform.into(),
+        })?;
+        explain::step(format!(
+            "querying chrome for testing listing at {}",
+            LISTING_URL
+        ));
+        let listing: Listing = http::call(http::get(LISTING_URL))?.into_json()?;
+        let published: Vec<(String, String)> = listing
+            .versions
+            .into_iter()
+            .filter_map(|release| {
+                let download = release
+                    .downloads
+                    .chromedriver
+                    .into_iter()
+                    .find(|d| d.platform == wanted)?;
+                Some((release.version, download.url))
+            })
+            .collect();
+
+        // the same build is the closest match, then the newest of the major version
+        let key = version_key(version);
+        [3, 1]
+            .iter()
+            .find_map(|&parts| {
+                published
+                    .iter()
+                    .filter(|(v, _)| {
+                        let candidate = version_key(v);
+                        candidate.len() >= parts
+                            && key.len() >= parts
+                            && candidate[..parts] == key[..parts]
+                    })
+                    .max_by_key(|(v, _)| version_key(v))
+            })
+            .cloned()
+            .ok_or_else(|| {
+                Error::VersionMissing {
+                    version: version.trim().into(),
+                    platform: platform.into(),
+                }
+                .into()
+            })
+    }
+}
+
+/// The Chrome for Testing name of `platform`, unless chromedriver isn't built for it.
+fn listing_platform(platform: &str) -> Option<&'static str> {
+    match platform {
+        "win64" => Some("win64"),
+        "win32" => Some("win32"),
+        "mac64" => Some("mac-x64"),
+        "mac64_m1" => Some("mac-arm64"),
+        "linux64" => Some("linux64"),
+        _ => None,
+    }
+}
+
+/// The version of the installed Google Chrome, from the registry or its files.
+fn installed_version() -> Result<Option<String>> {
+    for key in REGISTRY_KEYS {
+        match registry::read_string(key, "version") {
+            Ok(Some(version)) if !version.trim().is_empty() => {
+                explain::step(format!(
+                    "chrome registry key {} answered with {}",
+                    key, version
+                ));
+                return Ok(Some(version.trim().into()));
+            }
+            Ok(_) => {}
+            Err(e) => explain::step(format!("chrome registry key {} unreadable: {:#}", key, e)),
+        }
+    }
+
+    let executables = executables();
+    // windows installs sit beside a directory named for their version
+    if cfg!(windows) {
+        let dirs: Vec<PathBuf> = executables
+            .iter()
+            .filter_map(|exe| exe.parent().map(PathBuf::from))
+            .collect();
+        if let Some((version, _)) = browser::scan_installations(&dirs) {
+            return Ok(Some(version));
+        }
+    }
+    for exe in executables.iter().filter(|exe| exe.is_file()) {
+        if let Some(version) = browser::file_version(exe)? {
+            explain::step(format!("chrome at {} reports {}", exe.display(), version));
+            return Ok(Some(version));
+        }
+    }
+
+    explain::step("no installed google chrome found");
+    Ok(None)
+}
+
+fn executables() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        ["ProgramFiles", "ProgramFiles(x86)", "LOCALAPPDATA"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(|root| {
+                PathBuf::from(root)
+                    .join("Google")
+                    .join("Chrome")
+                    .join("Application")
+                    .join("chrome.exe")
+            })
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        vec![PathBuf::from(
+            "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+        )]
+    } else {
+        [
+            "/usr/bin/google-chrome-stable",
+            "/usr/bin/google-chrome",
+            "/opt/google/chrome/chrome",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+}
@@ -0,0 +1,157 @@
+//! `--connections`: a large archive fetched as ranges over several connections at
+//! once, which high latency links fill far better than a single stream.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{explain, http, output};
+
+/// The smallest range worth a connection of its own.
+pub const MIN_CHUNK: u64 = 1 << 20;
+
+/// The most connections `--connections` may ask for.
+pub const MAX_CONNECTIONS: usize = 16;
+
+/// How many connections a body of `total` bytes is split over, at most `connections`.
+pub fn split(total: u64, connections: usize) -> usize {
+    (total / MIN_CHUNK).min(connections as u64).max(1) as usize
+}
+
+/// Downloads the `total` bytes at `url` to `path` over `connections` ranges in
+/// parallel, each only if the body still has `etag`, and returns the SHA-256 of
+/// the whole once every range has arrived in full.
+pub fn fetch(
+    url: &str,
+    total: u64,
+    etag: Option<&str>,
+    path: &Path,
+    connections: usize,
+) -> Result<String> {
+    File::create(path)
+        .and_then(|file| file.set_len(total))
+        .with_context(|| format!("unable to create {}", path.display()))?;
+    let size = total.div_ceil(connections as u64);
+    let ranges: Vec<Range<u64>> = (0..connections as u64)
+        .map(|n| n * size..((n + 1) * size).min(total))
+        .filter(|range| !range.is_empty())
+        .collect();
+    explain::step(format!(
+        "downloading {} in {} ranges of up to {} bytes",
+        url,
+        ranges.len(),
+        size
+    ));
+
+    let written = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+    let result = thread::scope(|scope| {
+        let workers: Vec<_> = ranges
+            .iter()
+            .map(|range| {
+                let (written, stop) = (&written, &stop);
+                scope.spawn(move || {
+                    let result = fetch_range(url, range.clone(), etag, path, written, stop);
+                    if result.is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    result
+                })
+            })
+            .collect();
+        // progress and cancellation belong to this thread
+        while !workers.iter().all(|worker| worker.is_finished()) {
+            if http::check_cancelled().is_err() {
+                stop.store(true, Ordering::Relaxed);
+            }
+            output::transfer("downloading", written.load(Ordering::Relaxed), Some(total));
+            thread::sleep(Duration::from_millis(100));
+        }
+        output::transfer_done();
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| bail!("a download thread panicked"))
+            })
+            .collect::<Result<Vec<()>>>()
+    });
+    http::check_cancelled()?;
+    result?;
+
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let hashed = std::io::copy(&mut file, &mut hasher)?;
+    if hashed != total {
+        bail!(
+            "reassembled {} bytes of {}, expected {}",
+            hashed,
+            url,
+            total
+        );
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Writes `range` of the body at `url` to the same place in `path`.
+fn fetch_range(
+    url: &str,
+    range: Range<u64>,
+    etag: Option<&str>,
+    path: &Path,
+    written: &AtomicU64,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let mut request =
+        http::get(url).set("Range", &format!("bytes={}-{}", range.start, range.end - 1));
+    if let Some(etag) = etag {
+        request = request.set("If-Range", etag);
+    }
+    let response = http::call(request)?;
+    if response.status() != 206 {
+        bail!(
+            "{} answered a range request with {}, so it changed or doesn't serve ranges",
+            url,
+            response.status()
+        );
+    }
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut reader = response.into_reader().take(range.end - range.start);
+    let mut chunk = [0; 64 << 10];
+    let mut received = 0;
+    loop {
+        // another range failed or the download was cancelled, which is reported instead
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&chunk[..read])?;
+        received += read as u64;
+        written.fetch_add(read as u64, Ordering::Relaxed);
+    }
+    if received != range.end - range.start {
+        bail!(
+            "range {}-{} of {} ended after {} bytes",
+            range.start,
+            range.end - 1,
+            url,
+            received
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,1147 @@
+//! The command table behind dispatch, `--help` and `--version`.
+
+/// A subcommand, the one line `--help` says about it and the flags it takes
+/// besides the global ones.
+pub struct Command {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub flags: &'static [&'static [Flag]],
+}
+
+impl Command {
+    /// Every flag the command takes besides the global ones.
+    pub fn flags(&self) -> impl Iterator<Item = &'static Flag> {
+        self.flags.iter().flat_map(|group| group.iter())
+    }
+}
+
+/// Every subcommand, in the order `--help` lists them; the first is the default.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "install",
+        summary: "download the driver matching the installed browser (the default)",
+        flags: &[INSTALL, LOCATION, SCOPE, PLATFORM, DETECTION, FALLBACK, LOCKING, EXTRACTION, VERIFICATION, RETENTION, NETWORK],
+    },
+    Command {
+        name: "detect",
+        summary: "list installed webview2 and edge versions, without network access",
+        flags: &[DETECTION],
+    },
+    Command {
+        name: "run",
+        summary: "install the driver and launch it, forwarding arguments after --",
+        flags: &[RUN, INSTALL, LOCATION, SCOPE, PLATFORM, DETECTION, FALLBACK, LOCKING, EXTRACTION, VERIFICATION, RETENTION, NETWORK],
+    },
+    Command {
+        name: "info",
+        summary: "show what is known about a driver version, published or cached",
+        flags: &[PLATFORM, DETECTION, LOCKING, NETWORK],
+    },
+    Command {
+        name: "list-remote",
+        summary: "list published driver versions, filtered by --major and --latest N",
+        flags: &[LIST_REMOTE, PLATFORM, NETWORK],
+    },
+    Command {
+        name: "cache",
+        summary: "`cache path` prints the cache directory, `cache clear` empties it, `cache prune` trims it",
+        flags: &[RETENTION, SCOPE],
+    },
+    Command {
+        name: "clean",
+        summary: "prune the cache by --max-age days, --keep-latest versions or --max-size",
+        flags: &[RETENTION, SCOPE],
+    },
+    Command {
+        name: "cache-key",
+        summary: "print a key that changes with the detected webview2 version",
+        flags: &[],
+    },
+    Command {
+        name: "env",
+        summary: "print shell statements exporting the driver's path and version",
+        flags: &[ENV, LOCATION, SCOPE, PLATFORM, SKEW],
+    },
+    Command {
+        name: "check",
+        summary: "report whether a driver matches the installed webview2 or edge",
+        flags: &[&[DRIVER_PATH, REPORT_JUNIT], LOCATION, SCOPE, PLATFORM, DETECTION, SKEW],
+    },
+    Command {
+        name: "selftest",
+        summary: "check that everything an install needs is reachable",
+        flags: &[&[REPORT_JUNIT], NETWORK],
+    },
+    Command {
+        name: "verify",
+        summary: "report tampering, replacement or drift of the installed driver",
+        flags: &[&[DRIVER_PATH, REPORT_JUNIT], LOCATION, SCOPE, PLATFORM, SKEW],
+    },
+    Command {
+        name: "verify-path",
+        summary: "inspect any driver binary and whether it suits webview2",
+        flags: &[&[REPORT_JUNIT], SKEW],
+    },
+    Command {
+        name: "deploy",
+        summary: "copy the local driver to each --host",
+        flags: &[DEPLOY, SKEW],
+    },
+    Command {
+        name: "sync",
+        summary: "make the output directory match the lab manifest",
+        flags: &[SYNC, EXTRACTION, VERIFICATION, NETWORK],
+    },
+    Command {
+        name: "watch",
+        summary: "reinstall the driver whenever the browser's major version changes",
+        flags: &[WATCH, INSTALL, LOCATION, SCOPE, PLATFORM, DETECTION, FALLBACK, LOCKING, EXTRACTION, VERIFICATION, RETENTION, NETWORK],
+    },
+    Command {
+        name: "register-task",
+        summary: "run `watch --once` every --interval from the windows task scheduler",
+        flags: &[REGISTER_TASK, INSTALL, LOCATION, SCOPE, PLATFORM, DETECTION, FALLBACK, LOCKING, EXTRACTION, VERIFICATION, RETENTION, NETWORK],
+    },
+    Command {
+        name: "prefetch",
+        summary: "cache the newest driver of every requested channel",
+        flags: &[PREFETCH, EXTRACTION, NETWORK],
+    },
+    Command {
+        name: "compat",
+        summary: "report whether webview2 can host apps built against an sdk",
+        flags: &[COMPAT],
+    },
+    Command {
+        name: "doctor",
+        summary: "check this machine can install drivers, with hints for what it can't",
+        flags: &[&[REPORT_JUNIT], LOCATION, SCOPE, PLATFORM, DETECTION, SKEW, NETWORK],
+    },
+    Command {
+        name: "diagnose",
+        summary: "collect everything useful for a bug report into a zip",
+        flags: &[DIAGNOSE],
+    },
+    Command {
+        name: "uninstall",
+        summary: "remove everything the tool installed",
+        flags: &[UNINSTALL, SCOPE, RETENTION],
+    },
+    Command {
+        name: "self-update",
+        summary: "replace this executable with the latest release",
+        flags: &[SELF_UPDATE, NETWORK],
+    },
+    Command {
+        name: "status",
+        summary: "list which --project installed which driver version where",
+        flags: &[],
+    },
+    Command {
+        name: "resolve",
+        summary: "print only the resolved driver's download url, --with-sha256 adds its hash",
+        flags: &[RESOLVE, PLATFORM, DETECTION, FALLBACK, LOCKING, NETWORK],
+    },
+    Command {
+        name: "generate-script",
+        summary: "print a powershell or bash script fetching the resolved driver by sha256",
+        flags: &[PLATFORM, DETECTION, FALLBACK, LOCKING, NETWORK],
+    },
+    Command {
+        name: "completions",
+        summary: "print a completion script for bash, zsh, fish or powershell",
+        flags: &[],
+    },
+    Command {
+        name: "manpage",
+        summary: "print the man page",
+        flags: &[],
+    },
+];
+
+/// A flag as `--help` lists it.
+pub struct Flag {
+    /// Each spelling, e.g. `-q` and `--quiet`.
+    pub names: &'static [&'static str],
+    /// What follows the flag, e.g. `<dir>`, or `[<mode>]` for a value it may go
+    /// without.
+    pub value: Option<&'static str>,
+    pub summary: &'static str,
+}
+
+impl Flag {
+    /// Whether the flag always takes the word after it as its value.
+    pub fn takes_value(&self) -> bool {
+        self.value.is_some_and(|value| !value.starts_with('['))
+    }
+}
+
+/// The flags every command takes.
+const GLOBAL: &[Flag] = &[
+    Flag {
+        names: &["-h", "--help"],
+        value: None,
+        summary: "print this help, or a command's with `<command> --help`",
+    },
+    Flag {
+        names: &["-V", "--version"],
+        value: Some("[<v>]"),
+        summary: "print the tool's version, or with <v> pin the driver",
+    },
+    Flag {
+        names: &["-q", "--quiet"],
+        value: None,
+        summary: "print only results",
+    },
+    Flag {
+        names: &["-v", "--verbose", "-vv"],
+        value: None,
+        summary: "explain decisions and log requests, -vv traces them too",
+    },
+    Flag {
+        names: &["--explain"],
+        value: None,
+        summary: "explain decisions without logging requests",
+    },
+    Flag {
+        names: &["--json"],
+        value: None,
+        summary: "print results as one JSON object on stdout",
+    },
+    Flag {
+        names: &["--teamcity"],
+        value: None,
+        summary: "report with TeamCity service messages",
+    },
+    Flag {
+        names: &["--ci"],
+        value: None,
+        summary: "never prompt, print JSON, keep to msedgedriver.lock and fail on warnings unless --allow-warnings",
+    },
+    Flag {
+        names: &["--allow-warnings"],
+        value: None,
+        summary: "with --ci, report warnings without failing on them",
+    },
+    Flag {
+        names: &["--deny-warnings"],
+        value: None,
+        summary: "fail once the command is done if anything warned",
+    },
+    Flag {
+        names: &["--portable"],
+        value: None,
+        summary: "keep all state beside the executable",
+    },
+    Flag {
+        names: &["--offline"],
+        value: None,
+        summary: "use only the cache and installed drivers, exit 3 if short",
+    },
+    Flag {
+        names: &["--no-detect"],
+        value: None,
+        summary: "with --version, read no registry and start no process, for sandboxed builds",
+    },
+    Flag {
+        names: &["--version-from"],
+        value: Some("<file>"),
+        summary: "install the version pinned in <file>: package.json's edgedriver field, Cargo.toml's edgedriver metadata or a .edge-version file",
+    },
+    Flag {
+        names: &["--version-sources"],
+        value: Some("<list>"),
+        summary: "detect from registry, dirs, msedge, latest-stable in order",
+    },
+    Flag {
+        names: &["--registry-powershell"],
+        value: None,
+        summary: "read the registry through PowerShell rather than directly",
+    },
+    Flag {
+        names: &["--connect-timeout"],
+        value: Some("<t>"),
+        summary: "give up connecting to a server after <t>, e.g. 30s",
+    },
+    Flag {
+        names: &["--read-timeout"],
+        value: Some("<t>"),
+        summary: "give up on a server that sends nothing for <t>",
+    },
+    Flag {
+        names: &["--max-retry-after"],
+        value: Some("<t>"),
+        summary: "wait up to <t>, 60s unless given, when a rate limited server's Retry-After asks",
+    },
+    Flag {
+        names: &["--ca-bundle"],
+        value: Some("<pem>"),
+        summary: "trust the certificates in <pem> besides the system's",
+    },
+    Flag {
+        names: &["--max-duration"],
+        value: Some("<t>"),
+        summary: "give up on the whole run after <t>, e.g. 10m, and exit 9",
+    },
+    Flag {
+        names: &["--cache-dir"],
+        value: Some("<dir>"),
+        summary: "keep downloads and cached drivers in <dir>",
+    },
+    Flag {
+        names: &["--project"],
+        value: Some("<name>"),
+        summary: "keep <name>'s cache, pool and user or machine install apart from other projects on the machine",
+    },
+    Flag {
+        names: &["--auto-prune"],
+        value: None,
+        summary: "after installing, prune the cache as `cache prune` would, by the --keep-latest, --max-age and --max-size given",
+    },
+    Flag {
+        names: &["--shared-cache"],
+        value: None,
+        summary: "the cache is on a file server other agents use too, which a UNC --cache-dir implies",
+    },
+    Flag {
+        names: &["--config"],
+        value: Some("<file>"),
+        summary: "read settings from <file> instead of msedgedriver-tool.toml",
+    },
+    Flag {
+        names: &["--no-config"],
+        value: None,
+        summary: "ignore msedgedriver-tool.toml files",
+    },
+    Flag {
+        names: &["--otlp-endpoint"],
+        value: Some("<url>"),
+        summary: "export trace spans to the OTLP/HTTP collector at <url>",
+    },
+    Flag {
+        names: &["--report-endpoint"],
+        value: Some("<url>"),
+        summary: "post a JSON report of the run to <url>",
+    },
+    Flag {
+        names: &["--metrics-textfile"],
+        value: Some("<file>"),
+        summary: "write the run's metrics to <file> for a Prometheus textfile collector",
+    },
+];
+
+/// How every command that downloads reaches the driver host.
+const NETWORK: &[Flag] = &[
+    Flag {
+        names: &["--base-url"],
+        value: Some("<url>"),
+        summary: "download from <url>, an internal copy of Microsoft's driver host",
+    },
+    Flag {
+        names: &["--mirror"],
+        value: Some("<url>"),
+        summary: "download archives from the mirror at <url> first, repeatable",
+    },
+    Flag {
+        names: &["--probe-mirrors"],
+        value: None,
+        summary: "download from whichever mirror answers first",
+    },
+    Flag {
+        names: &["--checksums"],
+        value: Some("<source>"),
+        summary: "check archives against the sha256sum lines in <source>, a file or url",
+    },
+    Flag {
+        names: &["--version-map"],
+        value: Some("<file>"),
+        summary: "map browser versions to drivers as <file> says, after version-map.toml",
+    },
+    Flag {
+        names: &["--proxy"],
+        value: Some("<url>"),
+        summary: "send requests through <url> instead of HTTPS_PROXY or HTTP_PROXY",
+    },
+    Flag {
+        names: &["--resolve"],
+        value: Some("<host:port:address>"),
+        summary: "connect to <address> for <host:port>, repeatable",
+    },
+    Flag {
+        names: &["--retries"],
+        value: Some("<n>"),
+        summary: "retry a failed request up to <n> times",
+    },
+    Flag {
+        names: &["--retry-on"],
+        value: Some("<list>"),
+        summary: "the failures worth retrying, 429,5xx,timeout,reset unless given",
+    },
+    Flag {
+        names: &["--connections"],
+        value: Some("<n>"),
+        summary: "fetch a large archive over <n> connections at once",
+    },
+    Flag {
+        names: &["--keep-archive"],
+        value: None,
+        summary: "keep every downloaded archive, for --reextract",
+    },
+    Flag {
+        names: &["--discard-archive"],
+        value: None,
+        summary: "keep no downloaded archive, dropping any kept before",
+    },
+];
+
+const PLATFORM: &[Flag] = &[
+    Flag {
+        names: &["--platform"],
+        value: Some("<platform>"),
+        summary: "the driver's platform, e.g. win64, linux64 or mac64, rather than this machine's",
+    },
+    Flag {
+        names: &["--arch"],
+        value: Some("<arch>"),
+        summary: "the driver's architecture, x86, x64 or arm64, on this machine's OS",
+    },
+];
+
+/// Where the driver is, or goes.
+const LOCATION: &[Flag] = &[
+    Flag {
+        names: &["--output"],
+        value: Some("<path>"),
+        summary: "the driver's file, or the directory it is in",
+    },
+    Flag {
+        names: &["--filename"],
+        value: Some("<name>"),
+        summary: "the driver's file name, rather than the platform's usual one",
+    },
+];
+
+const SCOPE: &[Flag] = &[
+    Flag {
+        names: &["--scope"],
+        value: Some("<user|machine>"),
+        summary: "install for the user or the whole machine, in a directory on PATH",
+    },
+    Flag {
+        names: &["--install-to-path"],
+        value: None,
+        summary: "short for --scope user",
+    },
+];
+
+/// Which installed browser the driver is matched to.
+const DETECTION: &[Flag] = &[
+    Flag {
+        names: &["--browser"],
+        value: Some("<webview2|edge>"),
+        summary: "match webview2 or edge, webview2 unless given on windows and edge elsewhere",
+    },
+    Flag {
+        names: &["--channel"],
+        value: Some("<channel>"),
+        summary: "match the installed edge of <channel>, or webview2",
+    },
+    Flag {
+        names: &["--install-index"],
+        value: Some("<n>"),
+        summary: "match the <n>th installation `detect` lists",
+    },
+    Flag {
+        names: &["--interactive"],
+        value: None,
+        summary: "ask which installation to match",
+    },
+    Flag {
+        names: &["--webview2-dir"],
+        value: Some("<dir>"),
+        summary: "match the fixed version webview2 runtime in <dir>",
+    },
+    Flag {
+        names: &["--webview2-app"],
+        value: Some("<exe>"),
+        summary: "match the fixed version webview2 runtime beside the app <exe>",
+    },
+];
+
+const SKEW: &[Flag] = &[
+    Flag {
+        names: &["--allow-major-skew"],
+        value: Some("<n>"),
+        summary: "accept a driver up to <n> major versions from the browser's",
+    },
+    Flag {
+        names: &["--strict-match"],
+        value: None,
+        summary: "fail instead of warn when the driver doesn't match the browser",
+    },
+];
+
+const LOCKING: &[Flag] = &[
+    Flag {
+        names: &["--lockfile"],
+        value: Some("<file>"),
+        summary: "record each resolved driver and its sha256 in <file>",
+    },
+    Flag {
+        names: &["--frozen"],
+        value: None,
+        summary: "install exactly what the lockfile, msedgedriver.lock unless --lockfile, names",
+    },
+];
+
+const FALLBACK: &[Flag] = &[
+    Flag {
+        names: &["--fallback"],
+        value: Some("<policy>"),
+        summary:
+            "without a driver for the browser's build, take exact, same-major, same-build or latest",
+    },
+    Flag {
+        names: &["--match"],
+        value: Some("<exact|major|minor>"),
+        summary: "how much of the browser's version the driver must match",
+    },
+];
+
+const MIN_DRIVER_VERSION: Flag = Flag {
+    names: &["--min-driver-version"],
+    value: Some("<v>"),
+    summary: "refuse any driver older than <v>",
+};
+
+const PARALLEL: Flag = Flag {
+    names: &["--parallel"],
+    value: Some("<n>"),
+    summary: "download at most <n> archives at once",
+};
+
+/// What an archive may unpack to.
+const EXTRACTION: &[Flag] = &[
+    Flag {
+        names: &["--max-decompressed-size"],
+        value: Some("<size>"),
+        summary: "refuse an archive entry larger than <size> unpacked",
+    },
+    Flag {
+        names: &["--max-total-decompressed-size"],
+        value: Some("<size>"),
+        summary: "refuse an archive larger than <size> unpacked in all",
+    },
+    Flag {
+        names: &["--max-compression-ratio"],
+        value: Some("<n>"),
+        summary: "refuse an archive entry compressed more than <n> times",
+    },
+];
+
+const AUDIT_LOG: Flag = Flag {
+    names: &["--audit-log"],
+    value: Some("<file>"),
+    summary: "append each change to the installed drivers to <file>",
+};
+
+/// What is checked and recorded of a driver before it is used.
+const VERIFICATION: &[Flag] = &[
+    Flag {
+        names: &["--verify-signature"],
+        value: Some("[<require|warn|off>]"),
+        summary: "check the driver's signature, failing unless it is valid with require",
+    },
+    Flag {
+        names: &["--require-signature"],
+        value: None,
+        summary: "short for --verify-signature require",
+    },
+    Flag {
+        names: &["--keep-quarantine"],
+        value: None,
+        summary: "leave macOS's quarantine on the driver",
+    },
+    Flag {
+        names: &["--zone-identifier"],
+        value: Some("<keep|strip|set>"),
+        summary: "what happens to the driver's windows download zone",
+    },
+    AUDIT_LOG,
+];
+
+/// What the cache keeps when pruned.
+const RETENTION: &[Flag] = &[
+    Flag {
+        names: &["--max-age"],
+        value: Some("<days>"),
+        summary: "keep drivers cached within <days>",
+    },
+    Flag {
+        names: &["--older-than"],
+        value: Some("<days>"),
+        summary: "the same as --max-age",
+    },
+    Flag {
+        names: &["--keep-latest"],
+        value: Some("<n>"),
+        summary: "keep the <n> newest versions of each driver",
+    },
+    Flag {
+        names: &["--max-size"],
+        value: Some("<size>"),
+        summary: "keep the newest drivers that fit in <size>",
+    },
+];
+
+const INSTALL: &[Flag] = &[
+    Flag {
+        names: &["--driver"],
+        value: Some("<driver>"),
+        summary: "install msedgedriver, chromedriver, geckodriver or iedriver, repeatable",
+    },
+    Flag {
+        names: &["--driver-pool"],
+        value: Some("<dir>"),
+        summary: "keep every installed version side by side in <dir>",
+    },
+    Flag {
+        names: &["--stdout"],
+        value: None,
+        summary: "write the driver to stdout instead of a file",
+    },
+    Flag {
+        names: &["--force"],
+        value: None,
+        summary: "install even if the driver is already up to date",
+    },
+    Flag {
+        names: &["--allow-downgrade"],
+        value: None,
+        summary: "replace the driver with an older one",
+    },
+    Flag {
+        names: &["--no-cache"],
+        value: None,
+        summary: "download again even if the driver is cached",
+    },
+    Flag {
+        names: &["--clear-cache"],
+        value: None,
+        summary: "empty the cache before installing",
+    },
+    Flag {
+        names: &["--dry-run"],
+        value: None,
+        summary: "say what would be installed, sending only HEAD requests",
+    },
+    Flag {
+        names: &["--reextract"],
+        value: None,
+        summary: "rebuild the installed driver from its kept archive, without the network",
+    },
+    MIN_DRIVER_VERSION,
+    Flag {
+        names: &["--sha256"],
+        value: Some("<hex>"),
+        summary: "the sha256 the archive must have",
+    },
+    Flag {
+        names: &["--include"],
+        value: Some("<glob>"),
+        summary: "also extract the archive's files matching <glob>, repeatable",
+    },
+    Flag {
+        names: &["--extract-all"],
+        value: None,
+        summary: "extract the whole archive",
+    },
+    Flag {
+        names: &["--stop-running"],
+        value: None,
+        summary: "stop running copies of the driver so it can be replaced",
+    },
+    Flag {
+        names: &["--elevate"],
+        value: None,
+        summary: "ask for administrator rights when the destination needs them",
+    },
+    Flag {
+        names: &["--no-elevate"],
+        value: None,
+        summary: "never ask for administrator rights, even for --scope machine",
+    },
+    Flag {
+        names: &["--install-runtime"],
+        value: None,
+        summary: "install the evergreen webview2 runtime first if it is missing",
+    },
+    Flag {
+        names: &["--emit"],
+        value: Some("<kind> [<file>]"),
+        summary:
+            "describe the driver for later tools: github-env, dotenv, cmake, json or rust-embed",
+    },
+    Flag {
+        names: &["--smoke-test"],
+        value: None,
+        summary: "open a session with the driver before keeping it",
+    },
+    Flag {
+        names: &["--smoke-app"],
+        value: Some("<exe>"),
+        summary: "smoke test against the webview2 app <exe>",
+    },
+    Flag {
+        names: &["--headless"],
+        value: None,
+        summary: "smoke test against headless edge",
+    },
+    Flag {
+        names: &["--attest"],
+        value: Some("<file>"),
+        summary: "write a provenance statement of the install to <file>",
+    },
+    Flag {
+        names: &["--builder-id"],
+        value: Some("<id>"),
+        summary: "the builder --attest names, this tool unless given",
+    },
+    Flag {
+        names: &["--ie-arch"],
+        value: Some("<x64|win32>"),
+        summary: "the iedriver architecture, x64 unless given",
+    },
+    Flag {
+        names: &["--ie-version"],
+        value: Some("<v>"),
+        summary: "the iedriver version, the latest unless given",
+    },
+    PARALLEL,
+];
+
+const RUN: &[Flag] = &[
+    Flag {
+        names: &["--port"],
+        value: Some("<port>"),
+        summary: "run the driver on <port>, a free one unless given",
+    },
+    Flag {
+        names: &["--any-port-if-busy"],
+        value: None,
+        summary: "take a free port when --port is in use",
+    },
+    Flag {
+        names: &["--allowed-ips"],
+        value: Some("<list>"),
+        summary: "passed on to the driver",
+    },
+    Flag {
+        names: &["--allowed-origins"],
+        value: Some("<list>"),
+        summary: "passed on to the driver",
+    },
+    Flag {
+        names: &["--max-restarts"],
+        value: Some("<n>"),
+        summary: "start a crashed driver again up to <n> times",
+    },
+    Flag {
+        names: &["--log-file"],
+        value: Some("<file>"),
+        summary: "keep the driver's output in <file>",
+    },
+    Flag {
+        names: &["--log-max-size"],
+        value: Some("<size>"),
+        summary: "rotate --log-file once it grows past <size>",
+    },
+    Flag {
+        names: &["--pid-file"],
+        value: Some("<file>"),
+        summary: "keep the running driver's pid in <file>",
+    },
+];
+
+const INTERVAL: Flag = Flag {
+    names: &["--interval"],
+    value: Some("<t>"),
+    summary: "check every <t>, e.g. 1h",
+};
+
+const WATCH: &[Flag] = &[
+    INTERVAL,
+    Flag {
+        names: &["--once"],
+        value: None,
+        summary: "check only once",
+    },
+];
+
+const REGISTER_TASK: &[Flag] = &[
+    INTERVAL,
+    Flag {
+        names: &["--task-name"],
+        value: Some("<name>"),
+        summary: "the scheduled task's name",
+    },
+    Flag {
+        names: &["--remove"],
+        value: None,
+        summary: "remove the scheduled task again",
+    },
+];
+
+const DEPLOY: &[Flag] = &[
+    Flag {
+        names: &["--host"],
+        value: Some("<host>"),
+        summary: "deploy to <host>, repeatable",
+    },
+    Flag {
+        names: &["--ssh"],
+        value: None,
+        summary: "reach hosts over ssh",
+    },
+    Flag {
+        names: &["--winrm"],
+        value: None,
+        summary: "reach hosts over WinRM, the default",
+    },
+    Flag {
+        names: &["--remote-dir"],
+        value: Some("<dir>"),
+        summary: "the directory on each host the driver goes to",
+    },
+    Flag {
+        names: &["--detect"],
+        value: None,
+        summary: "check the driver against each host's webview2 first",
+    },
+    Flag {
+        names: &["--stop-running"],
+        value: None,
+        summary: "stop the driver on each host so it can be replaced",
+    },
+];
+
+const SYNC: &[Flag] = &[
+    Flag {
+        names: &["--manifest"],
+        value: Some("<file>"),
+        summary: "the lab manifest to sync to",
+    },
+    Flag {
+        names: &["--no-prune"],
+        value: None,
+        summary: "keep drivers the manifest no longer names",
+    },
+    MIN_DRIVER_VERSION,
+    PARALLEL,
+];
+
+const PREFETCH: &[Flag] = &[
+    Flag {
+        names: &["--channels"],
+        value: Some("<list>"),
+        summary: "the edge channels to cache, e.g. stable,beta, stable unless given",
+    },
+    Flag {
+        names: &["--all-platforms"],
+        value: None,
+        summary: "cache the drivers of every platform, not only this machine's",
+    },
+    MIN_DRIVER_VERSION,
+    PARALLEL,
+];
+
+const COMPAT: &[Flag] = &[
+    Flag {
+        names: &["--sdk-version"],
+        value: Some("<v>"),
+        summary: "the webview2 sdk the app is built against",
+    },
+    Flag {
+        names: &["--runtime-version"],
+        value: Some("<v>"),
+        summary: "the webview2 runtime to check, the installed one unless given",
+    },
+];
+
+const DRIVER_PATH: Flag = Flag {
+    names: &["--driver"],
+    value: Some("<path>"),
+    summary: "check the driver at <path> instead of the installed one",
+};
+
+const REPORT_JUNIT: Flag = Flag {
+    names: &["--report-junit"],
+    value: Some("<file>"),
+    summary: "write the checks as a JUnit report to <file>",
+};
+
+const LIST_REMOTE: &[Flag] = &[
+    Flag {
+        names: &["--major"],
+        value: Some("<n>"),
+        summary: "list only versions of major <n>",
+    },
+    Flag {
+        names: &["--latest"],
+        value: Some("<n>"),
+        summary: "list only the <n> newest versions",
+    },
+];
+
+const ENV: &[Flag] = &[Flag {
+    names: &["--shell"],
+    value: Some("<shell>"),
+    summary:
+        "print statements for bash, pwsh, cmd or fish, this machine's usual shell unless given",
+}];
+
+const DIAGNOSE: &[Flag] = &[
+    Flag {
+        names: &["--output"],
+        value: Some("<zip>"),
+        summary: "write the bug report to <zip>",
+    },
+    AUDIT_LOG,
+];
+
+const UNINSTALL: &[Flag] = &[
+    Flag {
+        names: &["--purge-cache"],
+        value: None,
+        summary: "empty the cache too, or prune it with --older-than or --keep-latest",
+    },
+    AUDIT_LOG,
+];
+
+const SELF_UPDATE: &[Flag] = &[
+    Flag {
+        names: &["--check"],
+        value: None,
+        summary: "only report whether a newer release exists",
+    },
+    Flag {
+        names: &["--force"],
+        value: None,
+        summary: "replace the executable even if it is the latest release",
+    },
+];
+
+const RESOLVE: &[Flag] = &[Flag {
+    names: &["--with-sha256"],
+    value: None,
+    summary: "print the archive's sha256 after its url",
+}];
+const NOTES: &str = "\
+any flag can also be set as MSEDGEDRIVER_TOOL_<FLAG>, e.g. MSEDGEDRIVER_TOOL_BASE_URL,
+or as `base_url = \"...\"` in msedgedriver-tool.toml in the current directory or the
+user's config directory; the command line wins, then the environment, then files.
+
+exit codes:
+  0 success, 1 other failure, 2 usage, 3 unavailable offline, 4 no browser found,
+  5 unsupported platform, 6 network error, 7 version not published, 8 extraction failed,
+  9 --max-duration exceeded";
+
+/// Where `--help` starts a flag's summary, and how wide its lines may get.
+const SUMMARY_COLUMN: usize = 26;
+const WIDTH: usize = 86;
+
+/// The global flags, as `--help`, completions and the man page describe them.
+pub fn flags() -> &'static [Flag] {
+    GLOBAL
+}
+
+/// `flags` laid out as `--help` lists them, a summary too long for one line
+/// carrying on indented.
+fn table<'a>(flags: impl IntoIterator<Item = &'a Flag>) -> String {
+    let mut lines = Vec::new();
+    for flag in flags {
+        let indent = if flag.names[0].starts_with("--") {
+            "      "
+        } else {
+            "  "
+        };
+        let mut line = format!("{}{}", indent, flag.names.join(", "));
+        if let Some(value) = flag.value {
+            line.push(' ');
+            line.push_str(value);
+        }
+        line = if line.len() + 2 > SUMMARY_COLUMN {
+            line + "  "
+        } else {
+            format!("{:width$}", line, width = SUMMARY_COLUMN)
+        };
+        let mut started = false;
+        for word in flag.summary.split(' ') {
+            if started && line.len() + 1 + word.len() > WIDTH {
+                lines.push(line);
+                line = " ".repeat(SUMMARY_COLUMN);
+                started = false;
+            }
+            if started {
+                line.push(' ');
+            }
+            line.push_str(word);
+            started = true;
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// The first flag in `args` the command, or install without one, doesn't take,
+/// skipping each flag's value and anything after `--` meant for a child.
+pub fn unknown_flag<'a>(command: Option<&str>, args: &'a [String]) -> Option<&'a str> {
+    let command = find(command.unwrap_or("install"))?;
+    let mut iter = args.iter().take_while(|arg| *arg != "--");
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            continue;
+        }
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (arg.as_str(), false),
+        };
+        match GLOBAL
+            .iter()
+            .chain(command.flags())
+            .find(|flag| flag.names.contains(&name))
+        {
+            Some(flag) if inline && flag.value.is_none() => return Some(arg),
+            Some(flag) if !inline && flag.takes_value() => {
+                iter.next();
+            }
+            Some(_) => {}
+            None => return Some(arg),
+        }
+    }
+    None
+}
+
+fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// The paragraphs `--help` prints after the flags, on configuration and exit codes.
+pub fn notes() -> impl Iterator<Item = &'static str> {
+    NOTES.split("\n\n")
+}
+
+/// Whether `name` is a known subcommand.
+pub fn is_command(name: &str) -> bool {
+    find(name).is_some()
+}
+
+/// The `--help` text, for one `command` or the tool as a whole.
+pub fn help(command: Option<&str>) -> String {
+    let name = env!("CARGO_PKG_NAME");
+    let global = format!("global flags:\n{}\n\n{}", table(GLOBAL), NOTES);
+    match command.and_then(find) {
+        Some(command) => {
+            let mut help = format!(
+                "{} {}: {}\n\nusage: {} {} [flags]\n\n",
+                name, command.name, command.summary, name, command.name
+            );
+            if command.flags().next().is_some() {
+                help.push_str(&format!("flags:\n{}\n\n", table(command.flags())));
+            }
+            help + &global
+        }
+        None => {
+            let width = COMMANDS.iter().map(|c| c.name.len()).max().unwrap_or(0);
+            let commands: Vec<String> = COMMANDS
+                .iter()
+                .map(|c| format!("  {:width$}  {}", c.name, c.summary, width = width))
+                .collect();
+            format!(
+                "{} {}\n\nusage: {} [command] [flags]\n\ncommands:\n{}\n\n{}",
+                name,
+                env!("CARGO_PKG_VERSION"),
+                name,
+                commands.join("\n"),
+                global
+            )
+        }
+    }
+}
+
+/// Whether `args` ask for the tool's own version: `-V`, or `--version` with no
+/// driver version after it.
+pub fn wants_version(args: &[String]) -> bool {
+    let mut iter = args.iter().take_while(|arg| *arg != "--").peekable();
+    while let Some(arg) = iter.next() {
+        if arg == "-V" || (arg == "--version" && iter.peek().is_none_or(|v| v.starts_with('-'))) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether `args` ask for help, ignoring anything after `--` meant for a child.
+pub fn wants_help(args: &[String]) -> bool {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "-h" || arg == "--help")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unknown(command: &str, args: &[&str]) -> Option<String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        unknown_flag(Some(command), &args).map(str::to_string)
+    }
+
+    #[test]
+    fn rejects_flags_the_command_does_not_take() {
+        assert_eq!(
+            unknown("install", &["--sha265", "abc"]),
+            Some("--sha265".into())
+        );
+        assert_eq!(
+            unknown("detect", &["--platform", "win64"]),
+            Some("--platform".into())
+        );
+        assert_eq!(
+            unknown("install", &["--force=yes"]),
+            Some("--force=yes".into())
+        );
+        assert_eq!(
+            unknown("cache", &["path", "--keep-latest", "2", "-q"]),
+            None
+        );
+    }
+
+    #[test]
+    fn skips_values_and_what_follows_a_double_dash() {
+        assert_eq!(unknown("install", &["--output", "-", "--sha256=abc"]), None);
+        assert_eq!(
+            unknown("install", &["--verify-signature", "warn", "--force"]),
+            None
+        );
+        assert_eq!(
+            unknown("run", &["--port", "9515", "--", "--verbose=3"]),
+            None
+        );
+    }
+
+    #[test]
+    fn no_command_takes_a_flag_twice() {
+        for command in COMMANDS {
+            let mut names: Vec<&str> = GLOBAL
+                .iter()
+                .chain(command.flags())
+                .flat_map(|f| f.names.iter().copied())
+                .collect();
+            let len = names.len();
+            names.sort_unstable();
+            names.dedup();
+            assert_eq!(names.len(), len, "{} repeats a flag", command.name);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::{flag_value, has_flag, major, output};
+
+/// How far a driver's major version may drift from the browser's before it's reported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkewPolicy {
+    /// Majors the driver may be ahead of or behind the browser, from `--allow-major-skew`.
+    pub allowed: u32,
+    /// Fail instead of warn on a mismatch, from `--strict-match`.
+    pub strict: bool,
+}
+
+impl SkewPolicy {
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let allowed = match flag_value(args, "--allow-major-skew") {
+            Some(n) => n
+                .parse()
+                .map_err(|_| anyhow!("invalid --allow-major-skew {:?}", n))?,
+            None => 0,
+        };
+
+        Ok(Self {
+            allowed,
+            strict: has_flag(args, "--strict-match"),
+        })
+    }
+
+    /// Describes the mismatch between `driver` and `browser` if it is outside the policy.
+    pub fn mismatch(&self, driver: &str, browser: &str) -> Option<String> {
+        let (driver_major, browser_major) =
+            match (major(driver).parse::<u32>(), major(browser).parse::<u32>()) {
+                (Ok(d), Ok(b)) => (d, b),
+                _ => {
+                    return (major(driver) != major(browser)).then(|| {
+                        format!(
+                            "driver {} does not match browser {}",
+                            driver.trim(),
+                            browser.trim()
+                        )
+                    })
+                }
+            };
+
+        if driver_major.abs_diff(browser_major) <= self.allowed {
+            return None;
+        }
+
+        let range = self.compatible(browser_major);
+        Some(format!(
+            "driver {} (major {}) does not match browser {} (major {}), compatible drivers are {}",
+            driver.trim(),
+            driver_major,
+            browser.trim(),
+            browser_major,
+            range
+        ))
+    }
+
+    /// The driver majors allowed for a browser of major `browser_major`, e.g. `120.x`.
+    pub fn compatible(&self, browser_major: u32) -> String {
+        if self.allowed == 0 {
+            format!("{}.x", browser_major)
+        } else {
+            format!(
+                "{}.x through {}.x",
+                browser_major.saturating_sub(self.allowed),
+                browser_major + self.allowed
+            )
+        }
+    }
+
+    /// Warns about a mismatch outside the policy, or fails on one when strict.
+    pub fn enforce(&self, driver: &str, browser: &str) -> Result<()> {
+        match self.mismatch(driver, browser) {
+            Some(message) if self.strict => bail!(message),
+            Some(message) => output::warn("compat-mismatch", &message),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reports whether the installed WebView2 Runtime can host apps built against an SDK.
+///
+/// Release SDKs `1.0.BUILD.x` need a runtime whose build number, the third component
+/// of its version, is at least `BUILD`.
+pub fn sdk_compat(args: &[String]) -> Result<()> {
+    let sdk = flag_value(args, "--sdk-version")
+        .ok_or_else(|| anyhow!("compat needs --sdk-version, e.g. --sdk-version 1.0.2210.55"))?;
+    let required =
+        build_number(&sdk).ok_or_else(|| anyhow!("invalid WebView2 SDK version {:?}", sdk))?;
+
+    let runtime = match flag_value(args, "--runtime-version") {
+        Some(version) => version,
+        None => crate::detected_version()?.trim().to_string(),
+    };
+    let actual = build_number(&runtime)
+        .ok_or_else(|| anyhow!("invalid WebView2 Runtime version {:?}", runtime))?;
+
+    if actual >= required {
+        crate::output::progress(&format!(
+            "compatible: runtime {} (build {}) meets the minimum build {} required by SDK {}",
+            runtime, actual, required, sdk
+        ));
+        Ok(())
+    } else {
+        bail!(
+            "incompatible: runtime {} (build {}) is older than the minimum build {} required by SDK {}",
+            runtime,
+            actual,
+            required,
+            sdk
+        )
+    }
+}
+
+fn build_number(version: &str) -> Option<u32> {
+    version.trim().split('.').nth(2)?.parse().ok()
+}
@@ -0,0 +1,385 @@
+//! `completions` and `manpage`: shell completion scripts and a man page, generated
+//! from the same command and flag tables as `--help` so packages can install them.
+
+use anyhow::{bail, Result};
+
+use crate::{
+    cli::{self, Command, Flag, COMMANDS},
+    shell::{fish_quote, posix_quote, pwsh_quote},
+};
+
+const NAME: &str = env!("CARGO_PKG_NAME");
+
+/// The shells `completions` writes scripts for.
+const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+/// Prints the completion script for the shell named first in `args`.
+pub fn completions(args: &[String]) -> Result<()> {
+    let script = match args.first().map(String::as_str) {
+        Some("bash") => bash(),
+        Some("zsh") => zsh(),
+        Some("fish") => fish(),
+        Some("powershell" | "pwsh") => powershell(),
+        Some(other) => bail!(
+            "unknown shell {}, expected one of {}",
+            other,
+            SHELLS.join(", ")
+        ),
+        None => bail!("completions needs a shell, one of {}", SHELLS.join(", ")),
+    };
+    print!("{}", script);
+    Ok(())
+}
+
+/// Every spelling of every flag in `flags`, e.g. for a word list.
+fn flag_names<'a>(flags: impl IntoIterator<Item = &'a Flag>) -> Vec<&'static str> {
+    flags
+        .into_iter()
+        .flat_map(|f| f.names.iter().copied())
+        .collect()
+}
+
+/// Every flag `command` takes, the global ones first.
+fn all_flags(command: &Command) -> impl Iterator<Item = &'static Flag> {
+    cli::flags().iter().chain(command.flags())
+}
+
+fn command_names() -> Vec<&'static str> {
+    COMMANDS.iter().map(|c| c.name).collect()
+}
+
+fn bash() -> String {
+    let mut takes_value: Vec<&str> = flag_names(
+        COMMANDS
+            .iter()
+            .flat_map(all_flags)
+            .filter(|f| f.takes_value()),
+    );
+    takes_value.sort_unstable();
+    takes_value.dedup();
+    let cases: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| {
+            format!(
+                "        {})\n            flags={}\n            ;;",
+                command.name,
+                posix_quote(&flag_names(all_flags(command)).join(" "))
+            )
+        })
+        .collect();
+    let function = format!("_{}", NAME.replace('-', "_"));
+    format!(
+        r#"{function}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        {takes_value})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+        completions)
+            COMPREPLY=($(compgen -W {shells} -- "$cur"))
+            return
+            ;;
+    esac
+    if [[ $COMP_CWORD -eq 1 && "$cur" != -* ]]; then
+        COMPREPLY=($(compgen -W {commands} -- "$cur"))
+        return
+    fi
+    local command=install flags
+    if [[ "${{COMP_WORDS[1]}}" != -* ]]; then
+        command="${{COMP_WORDS[1]}}"
+    fi
+    case "$command" in
+{cases}
+    esac
+    COMPREPLY=($(compgen -W "$flags" -- "$cur"))
+}}
+complete -o default -F {function} {name}
+"#,
+        function = function,
+        takes_value = takes_value.join("|"),
+        shells = posix_quote(&SHELLS.join(" ")),
+        commands = posix_quote(&command_names().join(" ")),
+        cases = cases.join("\n"),
+        name = NAME,
+    )
+}
+
+/// `s` safe inside a zsh `_arguments` or `_describe` description.
+fn zsh_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh() -> String {
+    let commands: Vec<String> = COMMANDS
+        .iter()
+        .map(|c| {
+            format!(
+                "    {}",
+                posix_quote(&format!("{}:{}", c.name, zsh_escape(c.summary)))
+            )
+        })
+        .collect();
+    let cases: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| {
+            let flags: Vec<String> = all_flags(command)
+                .flat_map(|flag| {
+                    flag.names.iter().map(|name| {
+                        let value = if flag.takes_value() {
+                            ":value:_files"
+                        } else {
+                            ""
+                        };
+                        format!(
+                            "            {}",
+                            posix_quote(&format!(
+                                "{}[{}]{}",
+                                name,
+                                zsh_escape(flag.summary),
+                                value
+                            ))
+                        )
+                    })
+                })
+                .collect();
+            format!(
+                "    {})\n        flags=(\n{}\n        )\n        ;;",
+                command.name,
+                flags.join("\n")
+            )
+        })
+        .collect();
+    format!(
+        r#"#compdef {name}
+
+local -a commands flags
+commands=(
+{commands}
+)
+local command=install
+if (( CURRENT > 2 )) && [[ $words[2] != -* ]]; then
+    command=$words[2]
+fi
+case $command in
+{cases}
+esac
+if (( CURRENT == 2 )) && [[ $words[CURRENT] != -* ]]; then
+    _describe command commands
+elif [[ $words[2] == completions ]] && (( CURRENT == 3 )); then
+    _values shell {shells}
+else
+    _arguments -s $flags '*:file:_files'
+fi
+"#,
+        name = NAME,
+        commands = commands.join("\n"),
+        cases = cases.join("\n"),
+        shells = SHELLS.join(" "),
+    )
+}
+
+fn fish() -> String {
+    let mut lines = vec![format!("complete -c {} -f", NAME)];
+    for command in COMMANDS {
+        lines.push(format!(
+            "complete -c {} -n __fish_use_subcommand -a {} -d {}",
+            NAME,
+            command.name,
+            fish_quote(command.summary)
+        ));
+    }
+    lines.push(format!(
+        "complete -c {} -n '__fish_seen_subcommand_from completions' -a {}",
+        NAME,
+        fish_quote(&SHELLS.join(" "))
+    ));
+    // each flag once, offered after the commands taking it, or before any for install's
+    let mut flags: Vec<(&Flag, Vec<&str>)> = cli::flags().iter().map(|f| (f, vec![])).collect();
+    for command in COMMANDS {
+        for flag in command.flags() {
+            match flags
+                .iter_mut()
+                .find(|(f, _)| f.names == flag.names && f.summary == flag.summary)
+            {
+                Some((_, commands)) => commands.push(command.name),
+                None => flags.push((flag, vec![command.name])),
+            }
+        }
+    }
+    for (flag, commands) in flags {
+        let mut line = format!("complete -c {}", NAME);
+        if !commands.is_empty() {
+            let mut condition = format!("__fish_seen_subcommand_from {}", commands.join(" "));
+            if commands.contains(&COMMANDS[0].name) {
+                condition = format!("__fish_use_subcommand; or {}", condition);
+            }
+            line.push_str(&format!(" -n {}", fish_quote(&condition)));
+        }
+        for name in flag.names {
+            let option = match name.strip_prefix("--") {
+                Some(long) => format!(" -l {}", long),
+                None if name.len() == 2 => format!(" -s {}", &name[1..]),
+                None => format!(" -o {}", &name[1..]),
+            };
+            line.push_str(&option);
+        }
+        if flag.takes_value() {
+            line.push_str(" -r -F");
+        }
+        line.push_str(&format!(" -d {}", fish_quote(flag.summary)));
+        lines.push(line);
+    }
+    lines.join("\n") + "\n"
+}
+
+fn powershell() -> String {
+    let entries = |items: Vec<(&str, &str)>, indent: &str| -> String {
+        items
+            .iter()
+            .map(|(name, summary)| {
+                format!("{}@({}, {})", indent, pwsh_quote(name), pwsh_quote(summary))
+            })
+            .collect::<Vec<_>>()
+            .join(",\n")
+    };
+    let commands = entries(
+        COMMANDS.iter().map(|c| (c.name, c.summary)).collect(),
+        "        ",
+    );
+    let flags: Vec<String> = COMMANDS
+        .iter()
+        .map(|command| {
+            let flags = all_flags(command)
+                .flat_map(|f| f.names.iter().map(|name| (*name, f.summary)))
+                .collect();
+            format!(
+                "        {} = @(\n{}\n        )",
+                pwsh_quote(command.name),
+                entries(flags, "            ")
+            )
+        })
+        .collect();
+    let shells = entries(
+        SHELLS.iter().map(|shell| (*shell, *shell)).collect(),
+        "        ",
+    );
+    format!(
+        r#"Register-ArgumentCompleter -Native -CommandName '{name}', '{name}.exe' -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $commands = @(
+{commands}
+    )
+    $flags = @{{
+{flags}
+    }}
+    $shells = @(
+{shells}
+    )
+    $words = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object {{ $_.ToString() }})
+    $done = if ($wordToComplete) {{ $words.Count - 1 }} else {{ $words.Count }}
+    $candidates = if ($done -eq 0 -and $wordToComplete -notlike '-*') {{
+        $commands
+    }} elseif ($done -eq 1 -and $words[0] -eq 'completions') {{
+        $shells
+    }} elseif ($words.Count -gt 0 -and $words[0] -notlike '-*') {{
+        $flags[$words[0]]
+    }} else {{
+        $flags['{default}']
+    }}
+    $candidates | Where-Object {{ $_[0] -like "$wordToComplete*" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterValue', $_[1])
+    }}
+}}
+"#,
+        name = NAME,
+        commands = commands,
+        flags = flags.join("\n"),
+        shells = shells,
+        default = COMMANDS[0].name,
+    )
+}
+
+/// `s` as roff text, which must not start a line with a control character.
+fn roff(s: &str) -> String {
+    let escaped = s.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with(['.', '\'']) {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// `flag` as a man page paragraph.
+fn flag_entry(flag: &Flag) -> [String; 3] {
+    let names: Vec<String> = flag
+        .names
+        .iter()
+        .map(|name| format!("\\fB{}\\fR", roff(name)))
+        .collect();
+    let value = flag
+        .value
+        .map(|v| format!(" \\fI{}\\fR", roff(v)))
+        .unwrap_or_default();
+    [
+        ".TP".into(),
+        format!("{}{}", names.join(", "), value),
+        roff(flag.summary),
+    ]
+}
+
+/// Prints the man page, in roff for `man` to render.
+pub fn manpage(_args: &[String]) -> Result<()> {
+    let mut page = vec![
+        format!(
+            ".TH {} 1 \"\" \"{} {}\" \"User Commands\"",
+            NAME.to_uppercase(),
+            NAME,
+            env!("CARGO_PKG_VERSION")
+        ),
+        ".SH NAME".into(),
+        format!(
+            "{} \\- download the msedgedriver matching the installed webview2 or edge",
+            roff(NAME)
+        ),
+        ".SH SYNOPSIS".into(),
+        format!(".B {}", roff(NAME)),
+        "[\\fIcommand\\fR] [\\fIflags\\fR]".into(),
+        ".SH COMMANDS".into(),
+    ];
+    for command in COMMANDS {
+        page.push(".TP".into());
+        page.push(format!(".B {}", roff(command.name)));
+        page.push(roff(command.summary));
+    }
+
+    page.push(".SH GLOBAL FLAGS".into());
+    page.extend(cli::flags().iter().flat_map(flag_entry));
+    page.push(".SH COMMAND FLAGS".into());
+    for command in COMMANDS.iter().filter(|c| c.flags().next().is_some()) {
+        page.push(format!(".SS {}", roff(command.name)));
+        page.extend(command.flags().flat_map(flag_entry));
+    }
+
+    for note in cli::notes() {
+        match note.split_once(":\n") {
+            Some((heading, body)) if !heading.contains('\n') => {
+                page.push(format!(".SH {}", roff(&heading.to_uppercase())));
+                for line in body.lines() {
+                    page.push(roff(line.trim()));
+                    page.push(".br".into());
+                }
+            }
+            _ => {
+                page.push(".SH CONFIGURATION".into());
+                page.push(roff(&note.lines().collect::<Vec<_>>().join(" ")));
+            }
+        }
+    }
+    println!("{}", page.join("\n"));
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! Defaults for any flag from `MSEDGEDRIVER_TOOL_*` environment variables and
+//! `msedgedriver-tool.toml` files, so teams can commit shared settings.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{flag_value, has_flag, paths};
+
+const CONFIG_FILENAME: &str = "msedgedriver-tool.toml";
+
+const ENV_PREFIX: &str = "MSEDGEDRIVER_TOOL_";
+
+/// Variables with the prefix that already mean something of their own rather
+/// than naming a flag.
+const RESERVED_VARS: &[&str] = &["METRICS", "MIRRORS", "OFFLINE", "UPDATE_CHECK"];
+
+/// Adds a flag for every setting not already given on the command line, from the
+/// environment first, then `--config` or the nearest config files: the current
+/// directory's, then the user's.
+///
+/// `base_url = "..."` or `MSEDGEDRIVER_TOOL_BASE_URL=...` both become `--base-url`,
+/// `true` adds a bare flag, `false` nothing and an array repeats the flag. Returns
+/// where each setting came from, for `--explain`.
+pub fn apply(args: &mut Vec<String>) -> Result<Vec<String>> {
+    let mut settings = Vec::new();
+    let mut notes = Vec::new();
+
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter_map(|(name, value)| Some((name.strip_prefix(ENV_PREFIX)?.to_string(), value)))
+        .filter(|(name, _)| !RESERVED_VARS.contains(&name.as_str()))
+        .collect();
+    vars.sort();
+    for (name, value) in vars {
+        let flag = format!("--{}", name.to_ascii_lowercase().replace('_', "-"));
+        let values = match value.as_str() {
+            "true" => vec![None],
+            "false" | "" => Vec::new(),
+            _ => vec![Some(value.clone())],
+        };
+        settings.push((flag, values, format!("{}{}", ENV_PREFIX, name)));
+    }
+
+    if !has_flag(args, "--no-config") {
+        for path in config_files(args)? {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("unable to read config {}", path.display()))?;
+            let table: toml::value::Table = toml::from_str(&contents)
+                .with_context(|| format!("invalid config {}", path.display()))?;
+            for (key, value) in table {
+                let flag = format!("--{}", key.replace('_', "-"));
+                let values = values(&key, value)
+                    .with_context(|| format!("invalid config {}", path.display()))?;
+                settings.push((flag, values, path.display().to_string()));
+            }
+        }
+    }
+
+    // anything meant for a launched driver stays after `--`
+    let mut insert_at = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let mut given: Vec<String> = Vec::new();
+    for (flag, values, source) in settings {
+        if given.contains(&flag) || is_given(&args[..insert_at], &flag) {
+            continue;
+        }
+        notes.push(format!("{} from {}", flag, source));
+        for value in values {
+            args.insert(insert_at, flag.clone());
+            insert_at += 1;
+            if let Some(value) = value {
+                args.insert(insert_at, value);
+                insert_at += 1;
+            }
+        }
+        given.push(flag);
+    }
+
+    Ok(notes)
+}
+
+/// `--config`, else the config files that exist, the closest first.
+fn config_files(args: &[String]) -> Result<Vec<PathBuf>> {
+    if let Some(path) = flag_value(args, "--config") {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            bail!("config {} doesn't exist", path.display());
+        }
+        return Ok(vec![path]);
+    }
+
+    let mut files = vec![Path::new(CONFIG_FILENAME).to_path_buf()];
+    files.extend(paths::config_dir().map(|dir| dir.join(CONFIG_FILENAME)));
+    files.retain(|path| path.is_file());
+    Ok(files)
+}
+
+fn values(key: &str, value: toml::Value) -> Result<Vec<Option<String>>> {
+    Ok(match value {
+        toml::Value::Boolean(true) => vec![None],
+        toml::Value::Boolean(false) => Vec::new(),
+        toml::Value::String(s) => vec![Some(s)],
+        toml::Value::Integer(n) => vec![Some(n.to_string())],
+        toml::Value::Float(n) => vec![Some(n.to_string())],
+        toml::Value::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                toml::Value::String(s) => Ok(Some(s)),
+                toml::Value::Integer(n) => Ok(Some(n.to_string())),
+                _ => bail!("{} must list strings or numbers", key),
+            })
+            .collect::<Result<_>>()?,
+        _ => bail!("{} must be a string, number, boolean or array", key),
+    })
+}
+
+fn is_given(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| {
+        arg == flag
+            || arg
+                .strip_prefix(flag)
+                .is_some_and(|rest| rest.starts_with('='))
+    })
+}
@@ -0,0 +1,296 @@
+use std::{
+    path::Path,
+    process::{exit, Command},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    compat::SkewPolicy, driver_version, flag_value, flag_values, has_flag, output, trace,
+    DRIVER_FILENAME,
+};
+
+const DEFAULT_REMOTE_DIR: &str = "C:\\msedgedriver";
+
+// same keys as `webview2_version`, first match wins
+const REMOTE_DETECT_SCRIPT: &str = "foreach ($key in @(\
+    'HKLM:\\SOFTWARE\\WOW6432Node\\Microsoft\\EdgeUpdate\\Clients\\{F3017226-FE2A-4295-8BDF-00C3A9A7E4C5}', \
+    'HKLM:\\SOFTWARE\\Microsoft\\EdgeUpdate\\Clients\\{F3017226-FE2A-4295-8BDF-00C3A9A7E4C5}', \
+    'HKCU:\\SOFTWARE\\Microsoft\\EdgeUpdate\\Clients\\{F3017226-FE2A-4295-8BDF-00C3A9A7E4C5}')) \
+    { $pv = (Get-ItemProperty -Path $key -ErrorAction SilentlyContinue).pv; if ($pv) { $pv; exit 0 } }; exit 1";
+
+const STOP_SCRIPT: &str =
+    "Stop-Process -Name msedgedriver -Force -ErrorAction SilentlyContinue; exit 0";
+
+#[derive(Debug, Clone, Copy)]
+enum Transport {
+    Ssh,
+    WinRm,
+}
+
+/// How every host in one deploy is reached and prepared.
+#[derive(Debug)]
+struct DeployOptions {
+    transport: Transport,
+    remote_dir: String,
+    detect: bool,
+    stop: bool,
+    skew: SkewPolicy,
+}
+
+/// Copies the local driver to each `--host`, reporting success per host.
+pub fn deploy(args: &[String]) -> Result<()> {
+    let hosts = flag_values(args, "--host");
+    if hosts.is_empty() {
+        bail!("deploy needs at least one --host");
+    }
+
+    let transport = match (has_flag(args, "--ssh"), has_flag(args, "--winrm")) {
+        (true, true) => bail!("--ssh and --winrm are mutually exclusive"),
+        (true, false) => Transport::Ssh,
+        _ => Transport::WinRm,
+    };
+    let options = DeployOptions {
+        transport,
+        remote_dir: flag_value(args, "--remote-dir").unwrap_or_else(|| DEFAULT_REMOTE_DIR.into()),
+        detect: has_flag(args, "--detect"),
+        stop: has_flag(args, "--stop-running"),
+        skew: SkewPolicy::from_args(args)?,
+    };
+
+    let local = std::env::current_dir()?.join(DRIVER_FILENAME);
+    if !local.is_file() {
+        bail!(
+            "no driver found at {}, run install before deploying",
+            local.display()
+        );
+    }
+    let local_version = driver_version(&local)?;
+
+    let mut failed = 0;
+    for host in &hosts {
+        output::progress(&format!("deploying to {} over {:?}", host, transport));
+        let mut span = trace::span("deploy host");
+        span.attr("host", host);
+        match span.record(deploy_host(host, &local, &local_version, &options)) {
+            Ok(()) => output::progress(&format!("{}: ok", host)),
+            Err(e) => {
+                failed += 1;
+                output::line(&format!("{}: FAILED: {}", host, e));
+                output::problem(&format!("deploy to {} failed: {}", host, e));
+            }
+        }
+    }
+
+    output::progress(&format!(
+        "deployed to {} of {} hosts",
+        hosts.len() - failed,
+        hosts.len()
+    ));
+    if failed > 0 {
+        exit(1);
+    }
+
+    Ok(())
+}
+
+fn deploy_host(
+    host: &str,
+    local: &Path,
+    local_version: &str,
+    options: &DeployOptions,
+) -> Result<()> {
+    let transport = options.transport;
+    let remote_dir = options.remote_dir.as_str();
+    if options.detect {
+        let remote_version = remote_powershell(host, transport, REMOTE_DETECT_SCRIPT)
+            .map_err(|e| anyhow::anyhow!("unable to detect remote webview2 version: {}", e))?;
+        let remote_version = remote_version.trim();
+        options
+            .skew
+            .enforce(local_version, remote_version)
+            .map_err(|e| anyhow::anyhow!("remote webview2 mismatch: {}", e))?;
+        output::progress(&format!("{}: webview2 version {}", host, remote_version));
+    }
+
+    if options.stop {
+        remote_powershell(host, transport, STOP_SCRIPT)?;
+    }
+
+    let remote_dir_quoted = ps_quote(remote_dir);
+    remote_powershell(
+        host,
+        transport,
+        &format!(
+            "New-Item -ItemType Directory -Force -Path {} | Out-Null",
+            remote_dir_quoted
+        ),
+    )?;
+
+    match transport {
+        Transport::Ssh => {
+            let destination = format!(
+                "{}:{}/{}",
+                host,
+                remote_dir.replace('\\', "/"),
+                DRIVER_FILENAME
+            );
+            run(Command::new("scp").arg(local).arg(destination))?;
+        }
+        Transport::WinRm => {
+            let script = format!(
+                "$s = New-PSSession -ComputerName {}; try {{ Copy-Item -Path {} -Destination {} -ToSession $s -Force }} finally {{ Remove-PSSession $s }}",
+                ps_quote(host),
+                ps_quote(&local.to_string_lossy()),
+                remote_dir_quoted
+            );
+            run(Command::new("powershell")
+                .args(["-NoProfile", "-Command"])
+                .arg(script))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `script` on `host`, returning its stdout.
+fn remote_powershell(host: &str, transport: Transport, script: &str) -> Result<String> {
+    run(&mut remote_command(host, transport, script))
+}
+
+/// The command running `script` on `host`. Over ssh the remote user's shell, cmd.exe
+/// by default on windows, parses the command line again, so the script is only
+/// ever sent encoded where that shell can't see its pipes and semicolons.
+fn remote_command(host: &str, transport: Transport, script: &str) -> Command {
+    let mut command;
+    match transport {
+        Transport::Ssh => {
+            command = Command::new("ssh");
+            command
+                .arg(host)
+                .args([
+                    "powershell",
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-EncodedCommand",
+                ])
+                .arg(encode_command(script));
+        }
+        Transport::WinRm => {
+            command = Command::new("powershell");
+            command.args(["-NoProfile", "-Command"]).arg(format!(
+                "Invoke-Command -ComputerName {} -ScriptBlock {{ {} }}",
+                ps_quote(host),
+                script
+            ));
+        }
+    }
+    command
+}
+
+/// `script` as `-EncodedCommand` takes it: base64 of its UTF-16LE code units.
+fn encode_command(script: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let bytes: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn run(command: &mut Command) -> Result<String> {
+    let output = command.output()?;
+    if !output.status.success() {
+        bail!(
+            "{:?} exited with {}: {}",
+            command.get_program(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(command: &Command) -> Vec<String> {
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn encodes_scripts_as_base64_utf16le() {
+        assert_eq!(encode_command(""), "");
+        assert_eq!(encode_command("a"), "YQA=");
+        assert_eq!(encode_command("ab"), "YQBiAA==");
+        assert_eq!(encode_command("abc"), "YQBiAGMA");
+        // as [Convert]::ToBase64String([Text.Encoding]::Unicode.GetBytes('dir | Out-Null'))
+        assert_eq!(
+            encode_command("dir | Out-Null"),
+            "ZABpAHIAIAB8ACAATwB1AHQALQBOAHUAbABsAA=="
+        );
+    }
+
+    #[test]
+    fn sends_ssh_scripts_encoded_so_the_remote_shell_leaves_them_alone() {
+        let script = "New-Item -ItemType Directory -Force -Path 'C:\\msedgedriver' | Out-Null";
+        let command = remote_command("build-01", Transport::Ssh, script);
+        assert_eq!(
+            argv(&command),
+            [
+                "ssh",
+                "build-01",
+                "powershell",
+                "-NoProfile",
+                "-NonInteractive",
+                "-EncodedCommand",
+                &encode_command(script),
+            ]
+        );
+        let encoded = argv(&remote_command(
+            "build-01",
+            Transport::Ssh,
+            REMOTE_DETECT_SCRIPT,
+        ));
+        assert!(encoded
+            .iter()
+            .all(|arg| !arg.contains(['|', ';', '$', '{', ' '])));
+    }
+
+    #[test]
+    fn runs_winrm_scripts_through_invoke_command() {
+        let command = remote_command("build-01", Transport::WinRm, STOP_SCRIPT);
+        assert_eq!(
+            argv(&command),
+            [
+                "powershell",
+                "-NoProfile",
+                "-Command",
+                &format!(
+                    "Invoke-Command -ComputerName 'build-01' -ScriptBlock {{ {} }}",
+                    STOP_SCRIPT
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! `doctor`: what a new machine is missing for installs to work, each finding with
+//! what to do about it, so setting one up needn't start with a support request.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+use anyhow::{bail, Result};
+use serde_json::json;
+
+use crate::{
+    browser::edge_version,
+    compat::SkewPolicy,
+    driver_location, driver_version, fetch_manifest, flag_value, host_can_run, http,
+    junit::{self, Outcome, TestCase},
+    manifest_url, output, paths, protected,
+    receipt::Receipt,
+    registry, target_platform, webview2_detection, wine, WEBVIEW2_KEY_64BIT_MACHINE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+    Skip,
+}
+
+/// What one check found, and for anything short of a pass what to do about it.
+struct Finding {
+    status: Status,
+    detail: String,
+    hint: Option<String>,
+}
+
+fn pass(detail: impl Into<String>) -> Finding {
+    Finding {
+        status: Status::Pass,
+        detail: detail.into(),
+        hint: None,
+    }
+}
+
+fn warn(detail: impl Into<String>, hint: impl Into<String>) -> Finding {
+    Finding {
+        status: Status::Warn,
+        detail: detail.into(),
+        hint: Some(hint.into()),
+    }
+}
+
+fn fail(detail: impl Into<String>, hint: impl Into<String>) -> Finding {
+    Finding {
+        status: Status::Fail,
+        detail: detail.into(),
+        hint: Some(hint.into()),
+    }
+}
+
+fn skip(reason: impl Into<String>) -> Finding {
+    Finding {
+        status: Status::Skip,
+        detail: reason.into(),
+        hint: None,
+    }
+}
+
+/// Prints each finding as it is made, like the other check commands.
+#[derive(Default)]
+struct Report {
+    cases: Vec<TestCase>,
+    failed: usize,
+    warned: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, f: impl FnOnce() -> Finding) {
+        let start = Instant::now();
+        let finding = f();
+        let (label, outcome) = match finding.status {
+            Status::Pass => ("ok  ", Outcome::Passed),
+            Status::Warn => ("warn", Outcome::Passed),
+            Status::Fail => ("FAIL", Outcome::Failed(finding.detail.clone())),
+            Status::Skip => ("skip", Outcome::Skipped(finding.detail.clone())),
+        };
+        output::line(&format!("{}  {}: {}", label, name, finding.detail));
+        if let Some(hint) = &finding.hint {
+            output::line(&format!("      hint: {}", hint));
+        }
+        match finding.status {
+            Status::Fail => {
+                self.failed += 1;
+                output::problem(&format!("{}: {}", name, finding.detail));
+            }
+            Status::Warn => self.warned += 1,
+            Status::Pass | Status::Skip => {}
+        }
+        output::push(
+            "checks",
+            json!({
+                "name": name,
+                "status": match finding.status {
+                    Status::Pass => "pass",
+                    Status::Warn => "warn",
+                    Status::Fail => "fail",
+                    Status::Skip => "skip",
+                },
+                "detail": finding.detail,
+                "hint": finding.hint,
+            }),
+        );
+        self.cases.push(TestCase {
+            name: name.into(),
+            outcome,
+            duration: start.elapsed(),
+        });
+    }
+}
+
+/// The `doctor` command: checks the registry, PowerShell, the connection to the
+/// driver host, the cache and output directories, the installed driver and the
+/// browsers it is matched to, failing if anything would stop an install.
+pub fn doctor(args: &[String]) -> Result<()> {
+    let mut report = Report::default();
+    let windows = cfg!(windows) || wine::detected();
+
+    report.check("registry", || {
+        if !windows {
+            return skip("there is no registry off windows");
+        }
+        match registry::read_string(WEBVIEW2_KEY_64BIT_MACHINE, "pv") {
+            Ok(_) => pass("EdgeUpdate's keys are readable"),
+            Err(e) => fail(
+                format!("{:#}", e),
+                "a policy may be blocking registry reads; try --registry-powershell, or \
+                 --version-sources dirs to find webview2 by its installation directories",
+            ),
+        }
+    });
+    report.check("powershell", || {
+        if !cfg!(windows) {
+            return skip("only used on windows");
+        }
+        powershell()
+    });
+    report.check("driver host", connectivity);
+    report.check("cache directory", || match paths::cache_dir() {
+        Some(dir) => writable(&dir).unwrap_or_else(|e| {
+            fail(
+                format!("unable to write {}: {}", dir.display(), e),
+                format!(
+                    "fix the permissions of {}, or pass --cache-dir <dir> somewhere writable",
+                    dir.display()
+                ),
+            )
+        }),
+        None => fail(
+            "unable to locate a cache directory",
+            "pass --cache-dir <dir>, or set LOCALAPPDATA, XDG_CACHE_HOME or HOME",
+        ),
+    });
+
+    let driver = driver_location(args);
+    report.check("output directory", || {
+        let driver = match &driver {
+            Ok(driver) => driver,
+            Err(e) => return fail(format!("{:#}", e), "check --output, --scope and --filename"),
+        };
+        let dir = driver
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        writable(dir).unwrap_or_else(|e| {
+            fail(
+                protected::write_error(driver, e).to_string(),
+                "pass --output <dir> somewhere writable",
+            )
+        })
+    });
+
+    let mut driver_found = None;
+    report.check("installed driver", || {
+        let Ok(driver) = &driver else {
+            return skip("no output location");
+        };
+        if !driver.is_file() {
+            return warn(
+                format!("none at {}", driver.display()),
+                "run install to put one there",
+            );
+        }
+        let receipt = Receipt::read(driver).ok().flatten();
+        let runs = target_platform(args).is_ok_and(host_can_run);
+        match (runs, receipt) {
+            (true, _) => match driver_version(driver) {
+                Ok(version) => {
+                    let detail = format!("{} at {}", version, driver.display());
+                    driver_found = Some(version);
+                    pass(detail)
+                }
+                Err(e) => fail(
+                    format!("{:#}", e),
+                    "the file may be damaged, or blocked by antivirus; run install --force \
+                     to replace it",
+                ),
+            },
+            (false, Some(receipt)) => pass(format!(
+                "{} at {}, as its receipt says",
+                receipt.version,
+                driver.display()
+            )),
+            (false, None) => skip(format!(
+                "{} can't run here and has no receipt",
+                driver.display()
+            )),
+        }
+    });
+
+    let mut webview2 = None;
+    report.check("webview2", || {
+        if !windows {
+            return skip("webview2 is only for windows");
+        }
+        match webview2_detection() {
+            Ok(Some((version, location))) => {
+                let detail = format!("{} at {}", version.trim(), location);
+                webview2 = Some(version.trim().to_string());
+                pass(detail)
+            }
+            Ok(None) => warn(
+                "not installed",
+                "install the evergreen WebView2 runtime, or pass --version to pick a driver",
+            ),
+            Err(e) => fail(
+                format!("{:#}", e),
+                "pass --version, or try --version-sources to detect it another way",
+            ),
+        }
+    });
+    let mut edge = None;
+    report.check("edge", || match edge_version(None) {
+        Ok(Some((version, channel))) => {
+            let detail = format!("{} ({})", version, channel.as_str());
+            edge = Some(version);
+            pass(detail)
+        }
+        Ok(None) => warn(
+            "not installed",
+            "only needed with --browser edge, install Microsoft Edge if you use it",
+        ),
+        Err(e) => warn(format!("{:#}", e), "pass --version with --browser edge"),
+    });
+
+    let skew = SkewPolicy::from_args(args)?;
+    let browser = match flag_value(args, "--browser").as_deref() {
+        Some("edge") => edge.map(|version| ("edge", version)),
+        _ => webview2.map(|version| ("webview2", version)),
+    };
+    report.check("driver matches", || match (&driver_found, &browser) {
+        (Some(driver), Some((name, version))) => match skew.mismatch(driver, version) {
+            Some(mismatch) => warn(mismatch, "run install to fetch the matching driver"),
+            None => pass(format!("{} suits {} {}", driver, name, version)),
+        },
+        _ => skip("missing a version to compare"),
+    });
+
+    if let Some(path) = flag_value(args, "--report-junit") {
+        junit::write_report(&PathBuf::from(path), "doctor", &report.cases)?;
+    }
+    if report.failed > 0 {
+        bail!(
+            "{} of {} checks failed, see the hints above",
+            report.failed,
+            report.cases.len()
+        );
+    }
+    output::line(&match report.warned {
+        0 => "everything an install needs is in place".to_string(),
+        n => format!("installs should work, with {} warning(s) above", n),
+    });
+    Ok(())
+}
+
+fn powershell() -> Finding {
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "$PSVersionTable.PSVersion.ToString()",
+        ])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => pass(format!(
+            "version {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        )),
+        Ok(output) => warn(
+            format!(
+                "exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            "an execution policy or constrained language mode may be restricting it; \
+             only --registry-powershell and updating PATH for --scope need it",
+        ),
+        Err(e) => warn(
+            format!("unable to start powershell: {}", e),
+            "only --registry-powershell and updating PATH for --scope need it; \
+             put powershell.exe on PATH to use them",
+        ),
+    }
+}
+
+/// Fetches the driver listing, telling a proxy, TLS interception and a firewall
+/// apart by how the request failed.
+fn connectivity() -> Finding {
+    if http::offline() {
+        return skip("--offline given");
+    }
+    let url = manifest_url();
+    let via = match http::proxy_for(url) {
+        Some(proxy) => format!(" through proxy {}", proxy),
+        None => String::new(),
+    };
+    let e = match fetch_manifest() {
+        Ok(manifest) => return pass(format!("{} answered{}, {} bytes", url, via, manifest.len())),
+        Err(e) => e,
+    };
+    let message = format!("{:#}", e);
+    let lower = message.to_ascii_lowercase();
+    let hint = if lower.contains("certificate") || lower.contains("tls") {
+        "a proxy may be intercepting TLS; pass its root certificate with --ca-bundle <pem>"
+    } else if lower.contains("407") || lower.contains("proxy") {
+        "the proxy refused the request; check the credentials in --proxy or HTTPS_PROXY"
+    } else if lower.contains("dns") || lower.contains("connect") || lower.contains("timed out") {
+        if via.is_empty() {
+            "the host may be firewalled; set --proxy or HTTPS_PROXY, or use a --base-url mirror"
+        } else {
+            "the proxy may be unreachable; check --proxy, HTTPS_PROXY and NO_PROXY"
+        }
+    } else {
+        "pass --base-url to use a mirror, or --offline to work from the cache"
+    };
+    // the error names the url already
+    fail(format!("{}{}", message, via), hint)
+}
+
+/// Whether a file can be created in `dir`, or `dir` in the nearest directory
+/// above it that exists, leaving nothing behind.
+fn writable(dir: &Path) -> std::io::Result<Finding> {
+    let existing = dir
+        .ancestors()
+        .find(|dir| dir.as_os_str().is_empty() || dir.is_dir())
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .unwrap_or(Path::new("."));
+    let probe = existing.join(format!(".msedgedriver-tool-doctor-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)?;
+    Ok(pass(if existing == dir {
+        format!("{} is writable", dir.display())
+    } else {
+        format!("{} can be created in {}", dir.display(), existing.display())
+    }))
+}
@@ -0,0 +1,714 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    archive::{self, Archive, Entry, ReadSeek},
+    checksums,
+    events::{self, Event},
+    filesystem::{self, Fs},
+    flag_value, flag_values, has_flag, metrics, output, panic, parse_size, policy, protected,
+    resume, retry, trace, Error,
+};
+
+/// Guards against archives that decompress to far more than any driver ever has.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    /// Largest number of bytes a single extracted file may decompress to.
+    pub max_size: u64,
+    /// Largest allowed ratio of decompressed to compressed size.
+    pub max_ratio: u64,
+    /// Largest number of bytes everything extracted from one archive may add up to.
+    pub max_total: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 256 << 20,
+            max_ratio: 100,
+            max_total: 1 << 30,
+        }
+    }
+}
+
+impl ExtractLimits {
+    /// Reads `--max-decompressed-size`, `--max-total-decompressed-size` and
+    /// `--max-compression-ratio`, keeping defaults otherwise.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut limits = Self::default();
+        if let Some(size) = flag_value(args, "--max-decompressed-size") {
+            limits.max_size = parse_size(&size)?;
+        }
+        if let Some(size) = flag_value(args, "--max-total-decompressed-size") {
+            limits.max_total = parse_size(&size)?;
+        }
+        if let Some(ratio) = flag_value(args, "--max-compression-ratio") {
+            limits.max_ratio = ratio
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid compression ratio {:?}", ratio))?;
+        }
+
+        Ok(limits)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DownloadOptions {
+    /// The archive must hash to this or nothing is extracted.
+    pub sha256: Option<String>,
+    pub limits: ExtractLimits,
+    /// Globs over the archive's paths, such as `Driver_Notes/*`, of other files to
+    /// extract beside the driver.
+    pub include: Vec<String>,
+    /// The archive's size if already known, shown when the server doesn't send it.
+    pub size_hint: Option<u64>,
+}
+
+/// Reads every `--include <glob>`, or `--extract-all` for the whole archive.
+pub fn include_patterns(args: &[String]) -> Result<Vec<String>> {
+    let include = if has_flag(args, "--extract-all") {
+        vec!["**".to_string()]
+    } else {
+        flag_values(args, "--include")
+    };
+    if !include.is_empty() && has_flag(args, "--stdout") {
+        bail!("--include and --extract-all write files beside the driver, so they can't be combined with --stdout");
+    }
+    Ok(include)
+}
+
+/// Reads `--sha256`, the digest the downloaded archive must have, failing early on
+/// anything that can't be one rather than reporting a confusing mismatch later.
+pub fn sha256_pin(args: &[String]) -> Result<Option<String>> {
+    let Some(pin) = flag_value(args, "--sha256") else {
+        return Ok(None);
+    };
+    let pin = pin.trim();
+    if pin.len() != 64 || !pin.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!(
+            "invalid --sha256 {:?}, expected 64 hex digits such as sha256sum prints",
+            pin
+        );
+    }
+    Ok(Some(pin.to_ascii_lowercase()))
+}
+
+/// Hex encoded SHA-256 digests of a downloaded archive and the driver extracted from it.
+#[derive(Debug)]
+pub struct Hashes {
+    pub archive: String,
+    pub driver: String,
+}
+
+/// Downloads the archive at `url` and extracts its `filename` entry to `dest`.
+pub fn download_driver(
+    url: &str,
+    filename: &str,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> Result<Hashes> {
+    let fs = filesystem::installed();
+    let target = Target::File { dest, fs: &*fs };
+    let result = fetch_and_extract(url, filename, target, options);
+    metrics::record_download(result.is_ok());
+    result
+}
+
+/// Like [`download_driver`], but writes the driver to stdout and nothing to disk.
+pub fn download_driver_to_stdout(
+    url: &str,
+    filename: &str,
+    options: &DownloadOptions,
+) -> Result<Hashes> {
+    let result = fetch_and_extract(url, filename, Target::Stdout, options);
+    metrics::record_download(result.is_ok());
+    result
+}
+
+/// Extracts `filename` to `dest` from the archive kept in the cache when `url` was
+/// last downloaded, without touching the network, for `--reextract`.
+pub fn extract_kept(
+    url: &str,
+    filename: &str,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> Result<Hashes> {
+    let kept = resume::open_kept(url)?;
+    let fs = filesystem::installed();
+    output::progress("re-extracting from the cached archive");
+    let (len, sha256) = (kept.len, kept.sha256.clone());
+    extract(
+        url,
+        Box::new(kept),
+        len,
+        sha256,
+        filename,
+        Target::File { dest, fs: &*fs },
+        options,
+    )
+}
+
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    File { dest: &'a Path, fs: &'a dyn Fs },
+    Stdout,
+}
+
+fn fetch_and_extract(
+    url: &str,
+    filename: &str,
+    target: Target,
+    options: &DownloadOptions,
+) -> Result<Hashes> {
+    policy::get().check_url(url)?;
+
+    panic::set_phase("downloading driver archive");
+    output::progress("downloading found zip file");
+    let mut span = trace::span("download archive");
+    span.attr("url", url);
+    // archives are streamed to disk, except when nothing may be written there
+    let (archive, len, archive_sha256): (Box<dyn ReadSeek>, u64, String) =
+        span.record(match target {
+            Target::File { .. } => resume::fetch(url, options.size_hint).map(|downloaded| {
+                let (len, sha256) = (downloaded.len, downloaded.sha256.clone());
+                (Box::new(downloaded) as Box<dyn ReadSeek>, len, sha256)
+            }),
+            Target::Stdout => resume::fetch_in_memory(url, options.size_hint).map(|buf| {
+                let (len, sha256) = (buf.len() as u64, format!("{:x}", Sha256::digest(&buf)));
+                (Box::new(Cursor::new(buf)) as Box<dyn ReadSeek>, len, sha256)
+            }),
+        })?;
+    span.attr("bytes", len);
+    drop(span);
+    extract(url, archive, len, archive_sha256, filename, target, options)
+}
+
+/// Checks the archive fetched from `url` against its pin or checksum source, then
+/// extracts `filename` from it to `target`, with `options.include` beside it.
+fn extract(
+    url: &str,
+    archive: Box<dyn ReadSeek>,
+    len: u64,
+    archive_sha256: String,
+    filename: &str,
+    target: Target,
+    options: &DownloadOptions,
+) -> Result<Hashes> {
+    output::progress(&format!("archive sha256: {}", archive_sha256));
+    let expected = match &options.sha256 {
+        Some(pin) => Some(pin.clone()),
+        None => checksums::expected(url)?,
+    };
+    if let Some(expected) = &expected {
+        if !archive_sha256.eq_ignore_ascii_case(expected.trim()) {
+            bail!(
+                "sha256 mismatch for {}: expected {}, got {}",
+                url,
+                expected.trim(),
+                archive_sha256
+            );
+        }
+        output::progress("archive sha256 matches pin");
+    }
+
+    panic::set_phase("extracting driver");
+    let _span = trace::span("extract driver");
+    output::progress(&format!("extracting {} from downloaded archive", filename));
+    events::emit(Event::ExtractionStarted { filename });
+    let mut archive = Archive::open(url, archive, len)?;
+    let Entry {
+        reader: driver,
+        size,
+        compressed,
+        mode,
+        symlink,
+    } = archive
+        .entry(filename)
+        .map_err(|source| Error::Extraction { source })?;
+    if symlink {
+        return Err(Error::Extraction {
+            source: anyhow!("{} is a symbolic link rather than the driver", filename),
+        }
+        .into());
+    }
+    check_limits(filename, size, compressed, options.limits)
+        .map_err(|source| Error::Extraction { source })?;
+
+    // the declared size can lie, so cap what is actually decompressed as well
+    let max_size = options.limits.max_size.min(options.limits.max_total);
+    let mut driver = driver.take(max_size + 1);
+    // a file target is written beside `dest` and renamed over it only once complete,
+    // so a failed extraction or a racing invocation never leaves it truncated
+    let temp = match target {
+        Target::File { dest, fs } => Some(TempFile::beside(dest, fs)),
+        Target::Stdout => None,
+    };
+    let sink: Box<dyn Write> = match &temp {
+        Some(temp) => retry::when_locked(&temp.path.display().to_string(), || {
+            temp.fs.create(&temp.path)
+        })
+        .map_err(|e| protected::write_error(&temp.path, e))?,
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = HashingWriter::new(sink);
+    let written = std::io::copy(&mut driver, &mut writer)?;
+    writer.flush()?;
+    if written > max_size {
+        return Err(Error::Extraction {
+            source: anyhow!(
+                "{} decompresses to more than the {} byte limit",
+                filename,
+                max_size
+            ),
+        }
+        .into());
+    }
+
+    let driver_sha256 = writer.hex_digest();
+    if let (Some(temp), Target::File { dest, fs }) = (&temp, target) {
+        fs.set_mode(&temp.path, executable_mode(mode))?;
+        // the existing driver may still be running, which windows reports as a lock
+        retry::when_locked(&dest.display().to_string(), || fs.rename(&temp.path, dest))
+            .map_err(|e| protected::write_error(dest, e))?;
+    }
+    output::progress(&format!("{} sha256: {}", filename, driver_sha256));
+
+    if let (false, Target::File { dest, fs }) = (options.include.is_empty(), target) {
+        drop(driver);
+        let dir = dest.parent().unwrap_or(Path::new("."));
+        extract_included(&mut archive, filename, dir, written, options, fs)
+            .map_err(|source| Error::Extraction { source })?;
+    }
+    if let Target::File { dest, fs } = target {
+        protected::check_intact(dest, fs, written, &driver_sha256)
+            .map_err(|source| Error::Extraction { source })?;
+    }
+
+    Ok(Hashes {
+        archive: archive_sha256,
+        driver: driver_sha256,
+    })
+}
+
+/// Extracts the entries besides the driver that `options.include` matches into
+/// `dir`, keeping their paths within the archive. `extracted` bytes have already
+/// been taken from it for the driver.
+fn extract_included(
+    archive: &mut Archive,
+    driver: &str,
+    dir: &Path,
+    mut extracted: u64,
+    options: &DownloadOptions,
+    fs: &dyn Fs,
+) -> Result<()> {
+    let names = archive.names()?;
+    for pattern in &options.include {
+        if !names.iter().any(|name| archive::glob_match(pattern, name)) {
+            output::warn(
+                "include-unmatched",
+                &format!("--include {} matches nothing in the archive", pattern),
+            )?;
+        }
+    }
+    let limits = options.limits;
+    let root = fs.canonicalize(dir)?;
+    for name in names {
+        let wanted = options
+            .include
+            .iter()
+            .any(|pattern| archive::glob_match(pattern, &name));
+        if name == driver || !wanted {
+            continue;
+        }
+        let relative = relative_path(&name)?;
+        let path = dir.join(&relative);
+        if name.ends_with('/') {
+            fs.create_dir_all(&path)?;
+            inside(&root, &path, &name, fs)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent)?;
+            inside(&root, parent, &name, fs)?;
+        }
+        if fs.is_symlink(&path) {
+            bail!(
+                "{} would be written through the symbolic link {}",
+                name,
+                path.display()
+            );
+        }
+
+        let entry = archive.entry(&name)?;
+        check_limits(&name, entry.size, entry.compressed, limits)?;
+        if entry.symlink {
+            let mut target = String::new();
+            entry.reader.take(4096).read_to_string(&mut target)?;
+            let resolved = relative.parent().unwrap_or(Path::new("")).join(&target);
+            if Path::new(&target).has_root() || lexically_escapes(&resolved) {
+                bail!(
+                    "{} is a symbolic link to {} outside the driver's directory",
+                    name,
+                    target
+                );
+            }
+            output::warn(
+                "symlink-skipped",
+                &format!("skipping {}, a symbolic link to {}", name, target),
+            )?;
+            continue;
+        }
+
+        // what is left of the total, as well as the per-file limit
+        let allowed = limits
+            .max_size
+            .min(limits.max_total.saturating_sub(extracted));
+        let mut file = fs
+            .create(&path)
+            .map_err(|e| protected::write_error(&path, e))?;
+        let written = std::io::copy(&mut entry.reader.take(allowed + 1), &mut file)?;
+        file.flush()?;
+        if written > allowed {
+            drop(file);
+            let _ = fs.remove_file(&path);
+            if allowed < limits.max_size {
+                bail!(
+                    "the archive decompresses to more than the {} byte limit in total",
+                    limits.max_total
+                );
+            }
+            bail!(
+                "{} decompresses to more than the {} byte limit",
+                name,
+                limits.max_size
+            );
+        }
+        extracted += written;
+        output::progress(&format!("extracted {}", path.display()));
+    }
+    Ok(())
+}
+
+/// The archive path `name` as a path relative to where it is extracted, refusing
+/// absolute paths, `..` and anything windows would read as a drive or stream.
+fn relative_path(name: &str) -> Result<PathBuf> {
+    // archives made on windows may separate with backslashes
+    let name_slashed = name.replace('\\', "/");
+    let path = Path::new(&name_slashed);
+    let normal = path.components().all(|component| match component {
+        Component::Normal(part) => !part.to_string_lossy().contains(':'),
+        _ => false,
+    });
+    if !normal || name_slashed.starts_with('/') {
+        bail!("{} would be extracted outside the driver's directory", name);
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Whether the relative `path` leaves the directory it starts in, such as `a/../..`.
+fn lexically_escapes(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return true,
+        }
+    }
+    false
+}
+
+/// Fails unless `path` really is within `root`, which a symbolic link already
+/// on disk could otherwise redirect.
+fn inside(root: &Path, path: &Path, name: &str, fs: &dyn Fs) -> Result<()> {
+    if !fs.canonicalize(path)?.starts_with(root) {
+        bail!("{} would be extracted outside the driver's directory", name);
+    }
+    Ok(())
+}
+
+/// Makes an extracted driver executable, keeping the archive's permissions when
+/// they already allow that. Drivers cached by older versions, which ignored the
+/// archive's permissions, are fixed up with `None` when copied out of the cache.
+pub fn set_permissions(path: &Path, mode: Option<u32>) -> Result<()> {
+    filesystem::installed().set_mode(path, executable_mode(mode))?;
+    Ok(())
+}
+
+/// The archive's permission bits `mode` if they let the owner execute, else `0o755`.
+fn executable_mode(mode: Option<u32>) -> u32 {
+    match mode.map(|mode| mode & 0o777) {
+        Some(mode) if mode & 0o100 != 0 => mode,
+        _ => 0o755,
+    }
+}
+
+/// A file being written next to its final path, removed unless renamed into place.
+struct TempFile<'a> {
+    path: PathBuf,
+    fs: &'a dyn Fs,
+}
+
+impl<'a> TempFile<'a> {
+    fn beside(dest: &Path, fs: &'a dyn Fs) -> Self {
+        let name = dest.file_name().unwrap_or_default().to_string_lossy();
+        // the pid keeps concurrent invocations out of each other's way
+        let path = dest.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        Self { path, fs }
+    }
+}
+
+impl Drop for TempFile<'_> {
+    fn drop(&mut self) {
+        let _ = self.fs.remove_file(&self.path);
+    }
+}
+
+/// Hex encoded SHA-256 digest of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn check_limits(name: &str, size: u64, compressed: u64, limits: ExtractLimits) -> Result<()> {
+    if size > limits.max_size {
+        bail!(
+            "{} would decompress to {} bytes, more than the {} byte limit",
+            name,
+            size,
+            limits.max_size
+        );
+    }
+    if size > compressed.max(1).saturating_mul(limits.max_ratio) {
+        bail!(
+            "{} has a compression ratio above the limit of {}",
+            name,
+            limits.max_ratio
+        );
+    }
+
+    Ok(())
+}
+
+/// Passes writes through to `inner` while hashing everything written.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn hex_digest(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+    use crate::filesystem::MemoryFs;
+
+    const URL: &str = "https://msedgedriver.azureedge.net/120.0.2210.91/edgedriver_linux64.zip";
+    const DEST: &str = "/drivers/msedgedriver";
+
+    /// A zip of `entries`, each a path, its contents and its unix mode.
+    fn zip(entries: &[(&str, &[u8], u32)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents, mode) in entries {
+            zip.start_file(*name, FileOptions::default().unix_permissions(*mode))
+                .unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn extract_to(fs: &MemoryFs, archive: Vec<u8>, options: &DownloadOptions) -> Result<Hashes> {
+        let (len, sha256) = (
+            archive.len() as u64,
+            format!("{:x}", Sha256::digest(&archive)),
+        );
+        extract(
+            URL,
+            Box::new(Cursor::new(archive)),
+            len,
+            sha256,
+            "msedgedriver",
+            Target::File {
+                dest: Path::new(DEST),
+                fs,
+            },
+            options,
+        )
+    }
+
+    #[test]
+    fn extracts_the_driver_executable() {
+        let fs = MemoryFs::default();
+        let archive = zip(&[
+            ("msedgedriver", b"driver", 0o644),
+            ("Driver_Notes/credits.html", b"notes", 0o644),
+        ]);
+        let archive_sha256 = format!("{:x}", Sha256::digest(&archive));
+
+        let hashes = extract_to(&fs, archive, &DownloadOptions::default()).unwrap();
+
+        assert_eq!(hashes.archive, archive_sha256);
+        assert_eq!(hashes.driver, format!("{:x}", Sha256::digest(b"driver")));
+        // nothing but the driver, and no temp file left behind
+        assert_eq!(fs.paths(), vec![PathBuf::from(DEST)]);
+        assert_eq!(fs.read(Path::new(DEST)).unwrap(), b"driver");
+        assert_eq!(fs.mode(Path::new(DEST)), Some(0o755));
+    }
+
+    #[test]
+    fn keeps_an_executable_mode_from_the_archive() {
+        let fs = MemoryFs::default();
+        let archive = zip(&[("msedgedriver", b"driver", 0o750)]);
+        extract_to(&fs, archive, &DownloadOptions::default()).unwrap();
+        assert_eq!(fs.mode(Path::new(DEST)), Some(0o750));
+    }
+
+    #[test]
+    fn refuses_an_archive_not_matching_its_pin() {
+        let fs = MemoryFs::default();
+        let options = DownloadOptions {
+            sha256: Some("0".repeat(64)),
+            ..Default::default()
+        };
+        let e = extract_to(&fs, zip(&[("msedgedriver", b"driver", 0o755)]), &options).unwrap_err();
+        assert!(e.to_string().contains("sha256 mismatch"), "{:#}", e);
+        assert!(fs.paths().is_empty());
+    }
+
+    #[test]
+    fn refuses_a_driver_above_the_size_limit() {
+        let fs = MemoryFs::default();
+        let options = DownloadOptions {
+            limits: ExtractLimits {
+                max_size: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let e = extract_to(&fs, zip(&[("msedgedriver", b"driver", 0o755)]), &options).unwrap_err();
+        assert_eq!(crate::error::exit_code(&e), 8, "{:#}", e);
+        assert!(fs.paths().is_empty());
+    }
+
+    #[test]
+    fn refuses_a_driver_that_is_a_symbolic_link() {
+        let fs = MemoryFs::default();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_symlink("msedgedriver", "/usr/bin/sh", FileOptions::default())
+            .unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+
+        let e = extract_to(&fs, archive, &DownloadOptions::default()).unwrap_err();
+        assert_eq!(crate::error::exit_code(&e), 8, "{:#}", e);
+        assert!(fs.paths().is_empty());
+    }
+
+    #[test]
+    fn extracts_included_files_beside_the_driver() {
+        let fs = MemoryFs::default();
+        let archive = zip(&[
+            ("msedgedriver", b"driver", 0o755),
+            ("Driver_Notes/credits.html", b"notes", 0o644),
+            ("Driver_Notes/LICENSE", b"license", 0o644),
+        ]);
+        let options = DownloadOptions {
+            include: vec!["Driver_Notes/credits*".into()],
+            ..Default::default()
+        };
+
+        extract_to(&fs, archive, &options).unwrap();
+
+        let notes = Path::new("/drivers/Driver_Notes/credits.html");
+        assert_eq!(fs.read(notes).unwrap(), b"notes");
+        assert_eq!(fs.paths(), vec![notes.to_path_buf(), PathBuf::from(DEST)]);
+    }
+
+    #[test]
+    fn refuses_included_paths_leaving_the_directory() {
+        let fs = MemoryFs::default();
+        let archive = zip(&[
+            ("msedgedriver", b"driver", 0o755),
+            ("../../etc/profile", b"evil", 0o644),
+        ]);
+        let options = DownloadOptions {
+            include: vec!["**".into()],
+            ..Default::default()
+        };
+        let e = extract_to(&fs, archive, &options).unwrap_err();
+        assert!(
+            format!("{:#}", e).contains("outside the driver's directory"),
+            "{:#}",
+            e
+        );
+    }
+
+    #[test]
+    fn refuses_writing_through_a_link_already_on_disk() {
+        let fs = MemoryFs::default();
+        fs.symlink(Path::new("/drivers/Driver_Notes"));
+        let archive = zip(&[
+            ("msedgedriver", b"driver", 0o755),
+            ("Driver_Notes/credits.html", b"notes", 0o644),
+        ]);
+        let options = DownloadOptions {
+            include: vec!["Driver_Notes/*".into()],
+            ..Default::default()
+        };
+        let e = extract_to(&fs, archive, &options).unwrap_err();
+        assert!(
+            format!("{:#}", e).contains("outside the driver's directory"),
+            "{:#}",
+            e
+        );
+        assert!(fs
+            .read(Path::new("/drivers/Driver_Notes/credits.html"))
+            .is_none());
+    }
+
+    #[test]
+    fn reports_a_driver_quarantined_right_after_extraction() {
+        let fs = MemoryFs::default();
+        fs.quarantine(Path::new(DEST));
+        let archive = zip(&[("msedgedriver", b"driver", 0o755)]);
+        let e = extract_to(&fs, archive, &DownloadOptions::default()).unwrap_err();
+        assert_eq!(crate::error::exit_code(&e), 8);
+        let message = format!("{:#}", e);
+        assert!(message.contains("disappeared"), "{}", message);
+        assert!(message.contains("antivirus"), "{}", message);
+    }
+}
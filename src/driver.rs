@@ -0,0 +1,516 @@
+//! What differs between the drivers the tool can install, so fetching, caching
+//! and checking them is written once.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    audit, cache, check_version, checksums, detected_version,
+    download::{self, download_driver, DownloadOptions, ExtractLimits, Hashes},
+    driver_filename, driver_location_named, explain, fetch_manifest, find_blob, flag_value,
+    flag_values, has_flag, host_platform, http, lock,
+    lockfile::{Locked, Lockfile},
+    metrics, mirror, output, parallel, policy,
+    receipt::Receipt,
+    retry,
+    scope::Scope,
+    target_platform, without_flag, Error,
+};
+
+/// A webdriver and the browser it has to match.
+pub trait Driver {
+    /// The driver's name in messages and output parameters, e.g. `chromedriver`.
+    fn name(&self) -> &'static str;
+
+    /// The browser the driver's version follows, e.g. `chrome`.
+    fn browser(&self) -> &'static str;
+
+    /// The driver executable's file name for `platform`.
+    fn filename(&self, platform: &str) -> String;
+
+    /// The driver's path inside the archive for `platform`.
+    fn archive_entry(&self, platform: &str) -> String {
+        self.filename(platform)
+    }
+
+    /// Whether the driver is built for `platform`.
+    fn supports(&self, _platform: &str) -> bool {
+        true
+    }
+
+    /// Rejects a browser `version` that can't be what `--version` means.
+    fn check_version(&self, version: &str) -> Result<()> {
+        check_version(version)
+    }
+
+    /// The version of the installed browser.
+    fn browser_version(&self) -> Result<String>;
+
+    /// The driver for browser `version` on `platform`: its exact version and the
+    /// url of its archive.
+    fn resolve(&self, version: &str, platform: &str) -> Result<(String, String)>;
+}
+
+/// msedgedriver, following webview2.
+pub struct Edge;
+
+impl Driver for Edge {
+    fn name(&self) -> &'static str {
+        "msedgedriver"
+    }
+
+    fn browser(&self) -> &'static str {
+        "webview2"
+    }
+
+    fn filename(&self, platform: &str) -> String {
+        driver_filename(platform).into()
+    }
+
+    fn browser_version(&self) -> Result<String> {
+        Ok(detected_version()?.trim().to_string())
+    }
+
+    fn resolve(&self, version: &str, platform: &str) -> Result<(String, String)> {
+        let blob = find_blob(&fetch_manifest()?, version, platform)?.ok_or_else(|| {
+            Error::VersionMissing {
+                version: version.trim().into(),
+                platform: platform.into(),
+            }
+        })?;
+        Ok((version.trim().into(), mirror::select(&blob.url, &blob.name)))
+    }
+}
+
+/// Installs `driver` to match its browser, or `--version`, reusing the cache and
+/// leaving a driver alone that its receipt says is already the one wanted.
+///
+/// msedgedriver has its own install with pools, journaling and signature checks;
+/// this serves every other driver.
+pub fn install(driver: &dyn Driver, args: &[String]) -> Result<Option<PathBuf>> {
+    let name = driver.name();
+    let options = DownloadOptions {
+        sha256: download::sha256_pin(args)?,
+        limits: ExtractLimits::from_args(args)?,
+        include: download::include_patterns(args)?,
+        size_hint: None,
+    };
+    if policy::get().require_sha256 && options.sha256.is_none() && !checksums::configured() {
+        bail!("policy requires the archive to be pinned with --sha256 or a checksum source");
+    }
+    for flag in [
+        "--driver-pool",
+        "--stdout",
+        "--browser",
+        "--install-runtime",
+    ] {
+        if flag_value(args, flag).is_some() || has_flag(args, flag) {
+            bail!("{} only applies to msedgedriver, not {}", flag, name);
+        }
+    }
+
+    let platform = target_platform(args)?;
+    if !driver.supports(platform) {
+        bail!("{} isn't built for {}", name, platform);
+    }
+    let mut lockfile = Lockfile::from_args(args)?;
+    let (options, frozen_version) = match &lockfile {
+        Some(lockfile) => (
+            lockfile.pin(name, platform, options)?,
+            lockfile.frozen_version(name, platform)?,
+        ),
+        None => (options, None),
+    };
+    let wanted = match flag_value(args, "--version") {
+        Some(pinned) => {
+            driver.check_version(pinned.trim())?;
+            explain::step(format!(
+                "--version {} given, skipping {} detection",
+                pinned,
+                driver.browser()
+            ));
+            pinned.trim().to_string()
+        }
+        None if !crate::same_os(platform) => bail!(
+            "--platform {} can't match a browser on this machine, pass --version as well",
+            platform
+        ),
+        None => {
+            let detected = driver.browser_version()?;
+            output::progress(&format!("{} version: {}", driver.browser(), detected));
+            output::parameter(&format!("{}.version", driver.browser()), &detected);
+            detected
+        }
+    };
+
+    let scope = Scope::from_args(args)?;
+    let dest = driver_location_named(args, &driver.filename(platform))?;
+    if let Some(dir) = dest.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+        if scope == Some(Scope::Machine) {
+            crate::secure_machine_dirs(dir)?;
+        }
+    }
+    let _lock = lock::acquire(&dest)?;
+
+    let path = std::path::absolute(&dest)?;
+    let force = has_flag(args, "--force");
+    // a driver already installed for this browser needs no network at all
+    if let Some(receipt) = (!force)
+        .then(|| {
+            installed(&dest, platform, frozen_version.as_deref(), |r| {
+                r.matches_browser(&wanted)
+            })
+        })
+        .flatten()
+    {
+        return up_to_date(name, &dest, &path, receipt, lockfile.as_mut()).map(Some);
+    }
+
+    let filename = driver.filename(platform);
+    let use_cache = !has_flag(args, "--no-cache");
+    // the cache keeps only drivers, so other files mean extracting the archive again
+    let lookup = use_cache && options.include.is_empty();
+    let pin = options.sha256.as_deref();
+    // browsers usually have a driver of their exact version, which needs no listing,
+    // and the others one whose receipt says which browser it was chosen for
+    let mut cached = lookup
+        .then(|| {
+            cache::lookup(&wanted, platform, &filename, pin)
+                .or_else(|| cache::lookup_for_browser(&wanted, platform, &filename, pin))
+        })
+        .flatten();
+    let (version, url) = match &cached {
+        Some((_, receipt)) => (receipt.version.clone(), receipt.source.clone()),
+        None if http::offline() => {
+            return Err(Error::Offline {
+                what: format!("{} {} for {}", name, wanted, platform),
+            }
+            .into())
+        }
+        None => {
+            output::progress(&format!("searching for {} {}", name, wanted));
+            let (version, url) = driver.resolve(&wanted, platform)?;
+            if version != wanted {
+                output::progress(&format!(
+                    "{} {} matches {} {}",
+                    name,
+                    version,
+                    driver.browser(),
+                    wanted
+                ));
+                cached = lookup
+                    .then(|| cache::lookup(&version, platform, &filename, pin))
+                    .flatten();
+            }
+            (version, url)
+        }
+    };
+    // on a shared cache one agent downloads while the others wait to use its copy
+    let _claim = match cached {
+        None if lookup && !http::dry_run() && !http::offline() => {
+            let claim = cache::claim(&version, platform, &filename)?;
+            if claim.is_some() {
+                cached = cache::lookup(&version, platform, &filename, pin);
+            }
+            claim
+        }
+        _ => None,
+    };
+    if let Some(lockfile) = &lockfile {
+        lockfile.check(name, platform, &version)?;
+    }
+
+    if let Some(receipt) = (!force)
+        .then(|| {
+            installed(&dest, platform, frozen_version.as_deref(), |r| {
+                r.version == version
+            })
+        })
+        .flatten()
+    {
+        return up_to_date(name, &dest, &path, receipt, lockfile.as_mut()).map(Some);
+    }
+
+    if !has_flag(args, "--allow-downgrade") {
+        let present = Receipt::read(&dest)
+            .ok()
+            .flatten()
+            .filter(|receipt| receipt.platform == platform);
+        crate::refuse_downgrade(present.map(|r| r.version).as_deref(), &dest, &version)?;
+    }
+
+    let hashes = match &cached {
+        Some((cached_path, receipt)) => {
+            output::progress(&format!("using cached driver {}", cached_path.display()));
+            metrics::record_cache_hit();
+            // copied aside and renamed, so a reader never sees part of a driver
+            let temp = dest.with_extension("tmp");
+            fs::copy(cached_path, &temp)?;
+            download::set_permissions(&temp, None)?;
+            retry::when_locked(&dest.display().to_string(), || fs::rename(&temp, &dest))?;
+            Hashes {
+                archive: receipt.archive_sha256.clone(),
+                driver: receipt.driver_sha256.clone(),
+            }
+        }
+        None => download_driver(&url, &driver.archive_entry(platform), &dest, &options)?,
+    };
+    let mut receipt = Receipt::new(&version, platform, &url, &hashes).for_browser(&wanted);
+    if let Some((_, cached)) = &cached {
+        receipt = receipt.copied_from(cached);
+    }
+    receipt.write(&dest)?;
+    if use_cache && cached.is_none() {
+        if let Err(e) = cache::store(&dest, &filename, &receipt) {
+            output::note_warning(
+                "cache-write-failed",
+                &format!("unable to cache the driver: {:#}", e),
+            );
+        }
+    }
+
+    if let Some(lockfile) = &mut lockfile {
+        lockfile.record(Locked::new(name, platform, &version, &url, &hashes))?;
+    }
+    if let Some(scope) = scope {
+        scope.add_to_path(path.parent().unwrap_or(&path))?;
+    }
+    report(name, &path, &version, platform, &hashes.driver);
+    output::parameter(&format!("{}.archive_sha256", name), &hashes.archive);
+    output::parameter(&format!("{}.source", name), &url);
+    output::flag(&format!("{}.cached", name), cached.is_some());
+    output::flag(&format!("{}.up_to_date", name), false);
+
+    if let Some(log) = audit::log_path(args) {
+        audit::record(
+            &log,
+            &audit::Event {
+                version: Some(&version),
+                platform: Some(platform),
+                archive_sha256: Some(&hashes.archive),
+                driver_sha256: Some(&hashes.driver),
+                source: Some(&url),
+                ..audit::Event::new("install", &path)
+            },
+        )?;
+    }
+
+    Ok(Some(path))
+}
+
+/// The receipt of the driver at `dest` when it is unchanged since it was installed
+/// for `platform`, is the lockfile's `frozen` version if there is one, and is
+/// the one `wanted`.
+fn installed(
+    dest: &Path,
+    platform: &str,
+    frozen: Option<&str>,
+    wanted: impl Fn(&Receipt) -> bool,
+) -> Option<Receipt> {
+    let receipt = Receipt::read(dest).ok().flatten()?;
+    let unchanged = download::sha256_file(dest).is_ok_and(|sha| sha == receipt.driver_sha256);
+    let locked = frozen.is_none_or(|v| v == receipt.version);
+    (unchanged && locked && receipt.platform == platform && wanted(&receipt)).then_some(receipt)
+}
+
+/// Leaves the `name` driver at `dest`, described by `receipt`, as it is.
+fn up_to_date(
+    name: &str,
+    dest: &Path,
+    path: &Path,
+    receipt: Receipt,
+    lockfile: Option<&mut Lockfile>,
+) -> Result<PathBuf> {
+    output::progress(&format!(
+        "{} is already {} {}; pass --force to reinstall",
+        dest.display(),
+        name,
+        receipt.version
+    ));
+    if let Some(lockfile) = lockfile {
+        lockfile.check(name, &receipt.platform, &receipt.version)?;
+        lockfile.record(Locked::from_receipt(name, &receipt))?;
+    }
+    report(
+        name,
+        path,
+        &receipt.version,
+        &receipt.platform,
+        &receipt.driver_sha256,
+    );
+    output::flag(&format!("{}.up_to_date", name), true);
+    Ok(path.to_path_buf())
+}
+
+fn report(name: &str, path: &std::path::Path, version: &str, platform: &str, sha256: &str) {
+    metrics::set_installed(version, path);
+    output::parameter(&format!("{}.path", name), &path.to_string_lossy());
+    output::parameter(&format!("{}.version", name), version);
+    output::parameter(&format!("{}.platform", name), platform);
+    output::parameter(&format!("{}.sha256", name), sha256);
+    if output::quiet() {
+        println!("{}", path.display());
+    }
+}
+
+/// Installs every `--driver` for every `--platform` at once, sharing connections,
+/// each to `<output>/<driver>/<platform>/` under `--output` or the current directory.
+pub fn install_many(args: &[String]) -> Result<()> {
+    for flag in [
+        "--filename",
+        "--scope",
+        "--install-to-path",
+        "--driver-pool",
+        "--stdout",
+        "--smoke-test",
+        "--emit",
+    ] {
+        if has_flag(args, flag) || flag_value(args, flag).is_some() {
+            bail!(
+                "{} can't be combined with several drivers or platforms",
+                flag
+            );
+        }
+    }
+    let base = match flag_value(args, "--output") {
+        Some(output) => PathBuf::from(output),
+        None => std::env::current_dir()?,
+    };
+
+    let mut drivers = flag_values(args, "--driver");
+    if drivers.is_empty() {
+        drivers.push("msedgedriver".into());
+    }
+    let mut platforms = flag_values(args, "--platform");
+    if platforms.is_empty() {
+        platforms.push(host_platform().into());
+    }
+    let mut jobs = Vec::new();
+    for driver in &drivers {
+        let name = match driver.as_str() {
+            "edge" | "msedgedriver" => "msedgedriver",
+            "chrome" | "chromedriver" => "chromedriver",
+            "gecko" | "geckodriver" => "geckodriver",
+            "iedriver" => "iedriver",
+            other => bail!(
+                "unknown driver {}, expected edge, chrome, gecko or iedriver",
+                other
+            ),
+        };
+        for platform in &platforms {
+            if !jobs.contains(&(name, platform.as_str())) {
+                jobs.push((name, platform.as_str()));
+            }
+        }
+    }
+
+    let common = ["--driver", "--platform", "--output"]
+        .iter()
+        .fold(args.to_vec(), |args, flag| without_flag(&args, flag));
+    let results = parallel::map(&jobs, parallel::limit(args)?, |&(name, platform)| {
+        let dir = base.join(name).join(platform);
+        fs::create_dir_all(&dir)?;
+        let mut job = common.clone();
+        job.extend(["--driver", name, "--platform", platform].map(String::from));
+        job.extend(["--output".into(), dir.display().to_string()]);
+        crate::install(&job)
+    });
+
+    let mut failed = 0;
+    for (&(name, platform), result) in jobs.iter().zip(results) {
+        match result {
+            Ok(path) => output::push(
+                "installs",
+                serde_json::json!({
+                    "driver": name,
+                    "platform": platform,
+                    "path": path,
+                }),
+            ),
+            Err(e) => {
+                failed += 1;
+                output::line(&format!("{} {}: FAILED: {:#}", name, platform, e));
+                output::problem(&format!("install of {} {} failed: {:#}", name, platform, e));
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} drivers failed to install", failed, jobs.len());
+    }
+    output::progress(&format!(
+        "installed {} drivers under {}",
+        jobs.len(),
+        base.display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+    use crate::{gecko::Gecko, MemoryHttp};
+
+    const RELEASES_URL: &str =
+        "https://api.github.com/repos/mozilla/geckodriver/releases?per_page=50";
+    const ARCHIVE_URL: &str = "https://github.com/mozilla/geckodriver/releases/download/v0.36.0/geckodriver-v0.36.0-win64.zip";
+
+    fn archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("geckodriver.exe", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"geckodriver").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn args(output: &Path) -> Vec<String> {
+        ["--version", "128.0", "--platform", "win64", "--output"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain([format!("{}/", output.display())])
+            .collect()
+    }
+
+    #[test]
+    fn finds_a_driver_chosen_for_the_browser_without_asking_again() {
+        let http = MemoryHttp::new();
+        http.serve(
+            RELEASES_URL,
+            format!(
+                r#"[{{"tag_name": "v0.36.0", "assets": [{{"name": "geckodriver-v0.36.0-win64.zip", "browser_download_url": "{}"}}]}}]"#,
+                ARCHIVE_URL
+            ),
+        );
+        http.serve(ARCHIVE_URL, archive());
+        let _serving = http::serving(&http);
+        let dir = std::env::temp_dir().join(format!(
+            "msedgedriver-tool-gecko-test-{}",
+            std::process::id()
+        ));
+
+        let first = install(&Gecko, &args(&dir.join("first"))).unwrap().unwrap();
+        assert_eq!(fs::read(&first).unwrap(), b"geckodriver");
+        let receipt = Receipt::read(&first).unwrap().unwrap();
+        assert_eq!(receipt.version, "0.36.0");
+        assert_eq!(receipt.browser_version.as_deref(), Some("128.0"));
+        let requests = http.requested().len();
+
+        // up to date where it was installed, and copied from the cache elsewhere
+        install(&Gecko, &args(&dir.join("first"))).unwrap();
+        let second = install(&Gecko, &args(&dir.join("second")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fs::read(&second).unwrap(), b"geckodriver");
+        assert_eq!(http.requested().len(), requests);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    path::Path,
+    process::{exit, Command},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{output, scope::Scope};
+
+/// Makes sure `dir` can be written to before anything is downloaded into it.
+///
+/// Creates `dir` if needed. When it needs administrator rights, `elevate` reruns the
+/// whole command through UAC and exits with its status; otherwise this fails with
+/// what to do instead.
+pub fn ensure_writable(dir: &Path, elevate: bool) -> Result<()> {
+    let probe = dir.join(".msedgedriver-tool-write-test");
+    let result = fs::create_dir_all(dir)
+        .and_then(|()| OpenOptions::new().write(true).create_new(true).open(&probe));
+    match result {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            if elevate && cfg!(windows) {
+                output::progress(&format!(
+                    "{} needs administrator rights, rerunning elevated",
+                    dir.display()
+                ));
+                exit(relaunch_elevated()?);
+            }
+
+            let alternative = Scope::User
+                .dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|_| "your home directory".into());
+            bail!(
+                "{} isn't writable without administrator rights ({}); rerun with --elevate \
+                 to install through UAC, or use --scope user to install into {}",
+                dir.display(),
+                e,
+                alternative
+            );
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reruns this process through UAC with the same arguments, told not to elevate again.
+fn relaunch_elevated() -> Result<i32> {
+    let exe = std::env::current_exe()?;
+    let dir = std::env::current_dir()?;
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| arg != "--elevate")
+        .chain(["--no-elevate".to_string()])
+        .map(|arg| quote_arg(&arg))
+        .collect();
+
+    let mut script = format!(
+        "$p = Start-Process -FilePath {} -WorkingDirectory {} -Verb RunAs -Wait -PassThru",
+        ps_quote(&exe.to_string_lossy()),
+        ps_quote(&dir.to_string_lossy())
+    );
+    script.push_str(&format!(" -ArgumentList {}", ps_quote(&args.join(" "))));
+    script.push_str("; exit $p.ExitCode");
+
+    crate::sandbox::check("running powershell to elevate")?;
+    let status = Command::new("powershell")
+        .args(["-NoProfile", "-Command"])
+        .arg(script)
+        .status()?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Quotes one argument the way the Windows C runtime splits a command line.
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.into();
+    }
+
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
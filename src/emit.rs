@@ -0,0 +1,212 @@
+//! `--emit`: files describing the installed driver for tools that run after us,
+//! so pipelines needn't parse what the tool prints.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::json;
+
+use crate::{output, receipt::Receipt};
+
+/// One file to write after an install.
+#[derive(Debug)]
+pub enum Emit {
+    /// `NAME=value` lines appended to the file GitHub Actions names in `$GITHUB_ENV`.
+    GithubEnv,
+    Dotenv(PathBuf),
+    /// `set(NAME "value")` lines for a CMake `include()`.
+    Cmake(PathBuf),
+    Json(PathBuf),
+    /// A Rust module of constants embedding the driver, copied beside it as a
+    /// `.bin` file for `include_bytes!`.
+    RustEmbed(PathBuf),
+}
+
+/// Reads every `--emit <kind> [file]`, or `--emit <kind>=<file>`, checked before
+/// anything is installed.
+pub fn requested(args: &[String]) -> Result<Vec<Emit>> {
+    let mut emits = Vec::new();
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        let spec = match arg.strip_prefix("--emit") {
+            Some("") => iter.next().cloned().ok_or_else(|| {
+                anyhow!("--emit needs a kind, github-env, dotenv, cmake, json or rust-embed")
+            })?,
+            Some(rest) if rest.starts_with('=') => rest[1..].to_string(),
+            _ => continue,
+        };
+        let (kind, mut file) = match spec.split_once('=') {
+            Some((kind, file)) => (kind.to_string(), Some(PathBuf::from(file))),
+            None => (spec, None),
+        };
+        if file.is_none() && kind != "github-env" {
+            file = iter
+                .next_if(|next| !next.starts_with('-'))
+                .map(PathBuf::from);
+        }
+        emits.push(match kind.as_str() {
+            "github-env" if file.is_some() => {
+                bail!("--emit github-env writes to $GITHUB_ENV, it takes no file")
+            }
+            "github-env" => Emit::GithubEnv,
+            "dotenv" => Emit::Dotenv(file.unwrap_or_else(|| ".env".into())),
+            "cmake" => Emit::Cmake(file.unwrap_or_else(|| "msedgedriver.cmake".into())),
+            "json" => Emit::Json(file.unwrap_or_else(|| "msedgedriver.json".into())),
+            "rust-embed" => {
+                let file = file.unwrap_or_else(|| "msedgedriver.rs".into());
+                if file.extension().is_some_and(|ext| ext == "bin") {
+                    bail!(
+                        "--emit rust-embed writes the driver beside {} as a .bin file, name the module something else",
+                        file.display()
+                    );
+                }
+                Emit::RustEmbed(file)
+            }
+            other => bail!(
+                "unknown --emit {}, expected github-env, dotenv, cmake, json or rust-embed",
+                other
+            ),
+        });
+    }
+    Ok(emits)
+}
+
+/// Writes each of `emits` for the driver installed at `driver`, with what its
+/// receipt says about it.
+pub fn write(emits: &[Emit], driver: &Path) -> Result<()> {
+    if emits.is_empty() {
+        return Ok(());
+    }
+    let receipt = Receipt::read(driver)?
+        .ok_or_else(|| anyhow!("{} has no install receipt to emit", driver.display()))?;
+    // e.g. MSEDGEDRIVER_PATH, CHROMEDRIVER_PATH
+    let prefix = driver
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_uppercase())
+        .unwrap_or_default();
+    let path = driver.to_string_lossy();
+    let variables = [
+        (format!("{}_PATH", prefix), path.as_ref()),
+        (format!("{}_VERSION", prefix), receipt.version.as_str()),
+        (format!("{}_PLATFORM", prefix), receipt.platform.as_str()),
+        (format!("{}_SHA256", prefix), receipt.driver_sha256.as_str()),
+    ];
+
+    for emit in emits {
+        let (file, contents, append) = match emit {
+            Emit::GithubEnv => {
+                let file = std::env::var_os("GITHUB_ENV").ok_or_else(|| {
+                    anyhow!("--emit github-env needs $GITHUB_ENV, which GitHub Actions sets")
+                })?;
+                let lines: String = variables
+                    .iter()
+                    .map(|(name, value)| format!("{}={}\n", name, value))
+                    .collect();
+                (PathBuf::from(file), lines, true)
+            }
+            Emit::Dotenv(file) => {
+                let lines: String = variables
+                    .iter()
+                    .map(|(name, value)| format!("{}={}\n", name, dotenv_quote(value)))
+                    .collect();
+                (file.clone(), lines, false)
+            }
+            Emit::Cmake(file) => {
+                let lines: String = variables
+                    .iter()
+                    .map(|(name, value)| format!("set({} \"{}\")\n", name, cmake_escape(value)))
+                    .collect();
+                (file.clone(), lines, false)
+            }
+            Emit::Json(file) => {
+                let object = json!({
+                    "path": path,
+                    "version": receipt.version,
+                    "platform": receipt.platform,
+                    "sha256": receipt.driver_sha256,
+                    "archive_sha256": receipt.archive_sha256,
+                    "source": receipt.source,
+                });
+                (
+                    file.clone(),
+                    serde_json::to_string_pretty(&object)? + "\n",
+                    false,
+                )
+            }
+            Emit::RustEmbed(file) => {
+                let bytes = file.with_extension("bin");
+                fs::copy(driver, &bytes)
+                    .with_context(|| format!("unable to write {}", bytes.display()))?;
+                output::progress(&format!("wrote {}", bytes.display()));
+                (file.clone(), rust_module(driver, &bytes, &receipt), false)
+            }
+        };
+
+        if append {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&file)
+                .and_then(|mut f| f.write_all(contents.as_bytes()))
+        } else {
+            fs::write(&file, contents)
+        }
+        .with_context(|| format!("unable to write {}", file.display()))?;
+        output::progress(&format!("wrote {}", file.display()));
+    }
+    Ok(())
+}
+
+/// The module `--emit rust-embed` writes, including the driver copied to `bytes`
+/// by a path relative to the module, as `include_bytes!` resolves it.
+fn rust_module(driver: &Path, bytes: &Path, receipt: &Receipt) -> String {
+    let name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    format!(
+        "// generated by {} {}, do not edit\n\
+         \n\
+         /// The embedded driver's version, e.g. to compare with the browser's.\n\
+         pub const VERSION: &str = {:?};\n\
+         /// The platform the embedded driver runs on, e.g. `win64`.\n\
+         pub const PLATFORM: &str = {:?};\n\
+         /// The name to write [`BYTES`] out as.\n\
+         pub const FILENAME: &str = {:?};\n\
+         /// Hex SHA-256 of [`BYTES`].\n\
+         pub const SHA256: &str = {:?};\n\
+         /// The driver executable.\n\
+         pub static BYTES: &[u8] = include_bytes!({:?});\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        receipt.version,
+        receipt.platform,
+        name(driver),
+        receipt.driver_sha256,
+        name(bytes),
+    )
+}
+
+fn dotenv_quote(value: &str) -> String {
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/\\:._-".contains(c))
+    {
+        value.into()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+fn cmake_escape(value: &str) -> String {
+    // cmake paths use forward slashes, and backslashes would be escapes
+    value
+        .replace('\\', "/")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+}
@@ -0,0 +1,227 @@
+use std::{fs, path::PathBuf, time::Instant};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    cache, check_platform, check_version, download,
+    driver::{Driver, Edge},
+    driver_filename, host_platform, lock, mapping, metrics, output,
+    receipt::Receipt,
+    report, webview2_version, Chain, DownloadOptions, Error,
+};
+
+/// What [`ensure_driver`] should provide.
+#[derive(Debug, Default, Clone)]
+pub struct EnsureOptions {
+    /// The exact driver version, e.g. `120.0.2210.91`; the installed webview2's
+    /// when unset.
+    pub version: Option<String>,
+    /// The platform, e.g. `win64`; [`host_platform`](crate::host_platform) when unset.
+    pub platform: Option<String>,
+    /// The directory the driver goes in; `$OUT_DIR/msedgedriver` when unset, as it
+    /// is for a build script.
+    pub dir: Option<PathBuf>,
+    pub download: DownloadOptions,
+    /// Where the version comes from when `version` is unset; the registry, then
+    /// the runtime's directories, when unset.
+    pub version_sources: Option<Chain>,
+}
+
+/// Makes sure a driver is in place and returns its path, for `build.rs` or test
+/// setup.
+///
+/// Nothing is written outside the driver's directory and the tool's cache, nothing
+/// is printed to stdout, where cargo looks for build script directives, and a driver
+/// already matching is left alone. Concurrent callers, such as parallel cargo
+/// invocations, wait for each other rather than race.
+///
+/// ```no_run
+/// // build.rs
+/// let driver = msedgedriver_tool::ensure_driver(Default::default()).unwrap();
+/// println!("cargo:rustc-env=MSEDGEDRIVER={}", driver.display());
+/// ```
+pub fn ensure_driver(options: EnsureOptions) -> Result<PathBuf, Error> {
+    let started = Instant::now();
+    let result = ensure(options);
+    report::emit(
+        "ensure_driver",
+        started.elapsed(),
+        result.as_ref().err().map(Error::exit_code),
+    );
+    result
+}
+
+fn ensure(options: EnsureOptions) -> Result<PathBuf, Error> {
+    output::use_stderr();
+    output::set_verbosity(output::Verbosity::Quiet);
+
+    let platform = options.platform.as_deref().unwrap_or(host_platform());
+    check_platform(platform)?;
+    let version = match options.version {
+        Some(version) => version.trim().to_string(),
+        None => match &options.version_sources {
+            Some(chain) => chain.detect()?.map(|found| found.version),
+            None => webview2_version()?,
+        }
+        .map(|version| mapping::driver_for(&version))
+        .ok_or_else(|| Error::NotInstalled {
+            what: "webview2_version".into(),
+        })?,
+    };
+    check_version(&version)?;
+
+    let dir = match options.dir {
+        Some(dir) => dir,
+        None => std::env::var_os("OUT_DIR")
+            .map(|out| PathBuf::from(out).join("msedgedriver"))
+            .ok_or_else(|| anyhow!("EnsureOptions::dir is needed outside a build script"))?,
+    };
+    fs::create_dir_all(&dir).map_err(anyhow::Error::from)?;
+    let driver = dir.join(driver_filename(platform));
+
+    let _lock = lock::acquire(&driver)?;
+    if let Ok(Some(receipt)) = Receipt::read(&driver) {
+        let unchanged =
+            download::sha256_file(&driver).is_ok_and(|sha| sha == receipt.driver_sha256);
+        if unchanged && receipt.version == version && receipt.platform == platform {
+            metrics::set_installed(&receipt.version, &driver);
+            return Ok(driver);
+        }
+    }
+
+    let pin = options.download.sha256.as_deref();
+    let mut cached = cache::lookup(&version, platform, driver_filename(platform), pin);
+    // on a shared cache one agent downloads while the others wait to use its copy
+    let _claim = match cached {
+        None => {
+            let claim = cache::claim(&version, platform, driver_filename(platform))?;
+            if claim.is_some() {
+                cached = cache::lookup(&version, platform, driver_filename(platform), pin);
+            }
+            claim
+        }
+        Some(_) => None,
+    };
+    let receipt = match cached {
+        Some((cached, receipt)) => {
+            metrics::record_cache_hit();
+            let temp = dir.join(format!(".{}.tmp", driver_filename(platform)));
+            fs::copy(&cached, &temp).map_err(anyhow::Error::from)?;
+            download::set_permissions(&temp, None)?;
+            fs::rename(&temp, &driver).map_err(anyhow::Error::from)?;
+            receipt
+        }
+        None => {
+            let (version, url) = Edge.resolve(&version, platform)?;
+            let hashes = download::download_driver(
+                &url,
+                driver_filename(platform),
+                &driver,
+                &options.download,
+            )?;
+            let receipt = Receipt::new(&version, platform, &url, &hashes);
+            // a cache that can't be written only costs the next caller a download
+            let _ = cache::store(&driver, driver_filename(platform), &receipt);
+            receipt
+        }
+    };
+    receipt.write(&driver)?;
+    metrics::set_installed(&receipt.version, &driver);
+
+    Ok(driver)
+}
+
+/// Like [`ensure_driver`], as a future for async test setup, such as under tokio.
+///
+/// The work runs on a thread of its own, so no executor thread blocks on it, and the
+/// future needs no particular runtime. Dropping it cancels the work at its next
+/// request or downloaded chunk, leaving a partial download to be resumed.
+///
+/// ```no_run
+/// # async fn setup() -> Result<(), msedgedriver_tool::Error> {
+/// let driver = msedgedriver_tool::ensure_driver_async(Default::default()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "async")]
+pub fn ensure_driver_async(options: EnsureOptions) -> EnsureFuture {
+    let shared = Arc::new(Shared::default());
+    let worker = shared.clone();
+    let spawned = std::thread::Builder::new()
+        .name("ensure_driver".into())
+        .spawn(move || {
+            crate::http::cancel_with(worker.cancelled.clone());
+            let result = ensure_driver(options);
+            let mut state = worker.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+    if let Err(e) = spawned {
+        shared
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .result = Some(Err(anyhow!(
+            "unable to start a thread for ensure_driver: {}",
+            e
+        )
+        .into()));
+    }
+    EnsureFuture { shared }
+}
+
+/// The driver [`ensure_driver_async`] is putting in place.
+#[cfg(feature = "async")]
+#[must_use = "the driver isn't ensured unless the future is awaited"]
+pub struct EnsureFuture {
+    shared: Arc<Shared>,
+}
+
+#[cfg(feature = "async")]
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "async")]
+#[derive(Default)]
+struct State {
+    result: Option<Result<PathBuf, Error>>,
+    waker: Option<Waker>,
+}
+
+#[cfg(feature = "async")]
+impl Future for EnsureFuture {
+    type Output = Result<PathBuf, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for EnsureFuture {
+    fn drop(&mut self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{fmt, time::Duration};
+
+/// Why something failed, for callers that need to tell failures apart; anything
+/// that isn't one of the specific classes is [`Error::Other`].
+///
+/// Each class has a stable exit code, see [`Error::exit_code`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// There is no installed browser or runtime, e.g. webview2, to match a driver to.
+    NotInstalled {
+        what: String,
+    },
+    /// Drivers aren't published for the platform asked for.
+    UnsupportedPlatform {
+        platform: String,
+    },
+    /// A server couldn't be reached or answered with an error.
+    Network {
+        source: anyhow::Error,
+    },
+    /// No driver is published for the version.
+    VersionMissing {
+        version: String,
+        platform: String,
+    },
+    /// The downloaded archive couldn't be opened or the driver extracted from it.
+    Extraction {
+        source: anyhow::Error,
+    },
+    /// `--offline` forbade a download that was needed.
+    Offline {
+        what: String,
+    },
+    /// `--max-duration` ran out.
+    Timeout {
+        limit: Duration,
+    },
+    /// The future of an async call was dropped before it finished.
+    Cancelled,
+    Other(anyhow::Error),
+}
+
+/// Unknown commands and malformed command lines.
+pub const EXIT_USAGE: i32 = 2;
+const EXIT_OTHER: i32 = 1;
+const EXIT_OFFLINE: i32 = 3;
+const EXIT_NOT_INSTALLED: i32 = 4;
+const EXIT_UNSUPPORTED_PLATFORM: i32 = 5;
+const EXIT_NETWORK: i32 = 6;
+const EXIT_VERSION_MISSING: i32 = 7;
+const EXIT_EXTRACTION: i32 = 8;
+const EXIT_TIMEOUT: i32 = 9;
+
+impl Error {
+    /// The process exit code for this class of failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Other(_) | Error::Cancelled => EXIT_OTHER,
+            Error::Offline { .. } => EXIT_OFFLINE,
+            Error::NotInstalled { .. } => EXIT_NOT_INSTALLED,
+            Error::UnsupportedPlatform { .. } => EXIT_UNSUPPORTED_PLATFORM,
+            Error::Network { .. } => EXIT_NETWORK,
+            Error::VersionMissing { .. } => EXIT_VERSION_MISSING,
+            Error::Extraction { .. } => EXIT_EXTRACTION,
+            Error::Timeout { .. } => EXIT_TIMEOUT,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotInstalled { what } => write!(f, "unable to find {}", what),
+            Error::UnsupportedPlatform { platform } => write!(
+                f,
+                "unknown platform {}, expected one of {}",
+                platform,
+                crate::ALL_PLATFORMS.join(", ")
+            ),
+            Error::Network { .. } => write!(f, "network request failed"),
+            Error::VersionMissing { version, platform } => {
+                write!(f, "no {} driver published for {}", platform, version)
+            }
+            Error::Extraction { .. } => write!(f, "unable to extract the driver"),
+            Error::Offline { what } => write!(
+                f,
+                "{} isn't available locally and --offline forbids downloading it",
+                what
+            ),
+            Error::Timeout { limit } => write!(
+                f,
+                "gave up after {:.1}s, the limit set by --max-duration",
+                limit.as_secs_f64()
+            ),
+            Error::Cancelled => write!(f, "cancelled before it finished"),
+            Error::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network { source } | Error::Extraction { source } => Some(source.as_ref()),
+            Error::Other(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    /// Recovers the class of an error raised inside the tool, looking through any
+    /// context added along the way.
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<Error>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        if e.chain()
+            .any(|cause| cause.is::<ureq::Error>() || cause.is::<crate::http::Status>())
+        {
+            Error::Network { source: e }
+        } else if e.chain().any(|cause| cause.is::<zip::result::ZipError>()) {
+            Error::Extraction { source: e }
+        } else {
+            Error::Other(e)
+        }
+    }
+}
+
+/// The exit code for `e`, by the class of its innermost classified cause.
+pub fn exit_code(e: &anyhow::Error) -> i32 {
+    // context attached as an `Error` isn't visible in the chain below
+    if let Some(error) = e.downcast_ref::<Error>() {
+        return error.exit_code();
+    }
+    for cause in e.chain() {
+        if let Some(error) = cause.downcast_ref::<Error>() {
+            return error.exit_code();
+        }
+        if cause.is::<ureq::Error>() || cause.is::<crate::http::Status>() {
+            return EXIT_NETWORK;
+        }
+        if cause.is::<zip::result::ZipError>() {
+            return EXIT_EXTRACTION;
+        }
+    }
+    EXIT_OTHER
+}
@@ -0,0 +1,57 @@
+//! Progress as events, for front-ends embedding the library that draw their own
+//! progress rather than read the tool's output: nothing is sent unless a library
+//! caller sets a [`ProgressHandler`].
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Something that happened while putting a driver in place, as sent to a
+/// [`ProgressHandler`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    /// A download of `url` began, of `size` bytes if the server said.
+    DownloadStarted { url: &'a str, size: Option<u64> },
+    /// `done` bytes of the current download have arrived, of `total` if known.
+    BytesProgressed { done: u64, total: Option<u64> },
+    /// A request to `url` failed and is tried again after `delay`, as retry
+    /// `attempt` of `retries`.
+    RetryAttempted {
+        url: &'a str,
+        attempt: u32,
+        retries: u32,
+        delay: Duration,
+    },
+    /// `filename` is being extracted from the downloaded archive.
+    ExtractionStarted { filename: &'a str },
+    /// The driver `version` is in place at `path`, downloaded or not.
+    Completed { version: &'a str, path: &'a Path },
+    /// Something went less well than asked without stopping the work, as `code`,
+    /// e.g. `substituted-build`, with a `message` for people.
+    Warning { code: &'a str, message: &'a str },
+}
+
+/// Receives each [`Event`] as it happens, e.g. to drive a progress bar.
+pub trait ProgressHandler: Send + Sync {
+    /// Called on the thread doing the work, so it should return quickly; a slow
+    /// handler slows the download.
+    fn on_event(&self, event: &Event);
+}
+
+static HANDLER: Mutex<Option<Arc<dyn ProgressHandler>>> = Mutex::new(None);
+
+/// Sends every later [`Event`] to `handler`, replacing any set before.
+pub fn set_progress_handler(handler: impl ProgressHandler + 'static) {
+    *HANDLER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(handler));
+}
+
+/// Sends `event` to the handler, if one is set.
+pub fn emit(event: Event) {
+    let handler = HANDLER.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(handler) = handler {
+        handler.on_event(&event);
+    }
+}
@@ -0,0 +1,17 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns on `--explain` tracing for the rest of the process.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Records one decision made while resolving which driver to install.
+///
+/// Traces go to stderr so they never mix with output meant for scripts.
+pub fn step(message: impl AsRef<str>) {
+    if ENABLED.load(Ordering::Relaxed) {
+        eprintln!("explain: {}", message.as_ref());
+    }
+}
@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error, Result};
+
+use crate::{
+    channel::{self, Channel},
+    explain, find_blob, flag_value, major, newest_for_prefix, output, Blob,
+};
+
+/// What to install when no driver is published for the exact browser build.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fallback {
+    /// Fail, so a mismatch never goes unnoticed.
+    #[default]
+    Exact,
+    /// The latest release Microsoft names for the same major version, or failing
+    /// that the newest one in the listing.
+    SameMajor,
+    /// The newest driver of the same `MAJOR.MINOR.BUILD`, differing only in its patch.
+    SameBuild,
+    /// The newest driver of the detected edge's channel, stable for webview2,
+    /// whatever its major.
+    Latest,
+}
+
+impl FromStr for Fallback {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(Fallback::Exact),
+            "same-major" | "major" => Ok(Fallback::SameMajor),
+            "same-build" | "minor" => Ok(Fallback::SameBuild),
+            "latest" => Ok(Fallback::Latest),
+            _ => bail!(
+                "unknown fallback {}, expected exact, same-major, same-build or latest",
+                s
+            ),
+        }
+    }
+}
+
+impl Fallback {
+    /// Reads `--fallback`, or `--match exact|major|minor` in terms of what must
+    /// match the browser, defaulting to `exact`.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let policy = match (flag_value(args, "--fallback"), flag_value(args, "--match")) {
+            (Some(_), Some(_)) => {
+                bail!("--fallback and --match both choose a substitute, pass only one")
+            }
+            (Some(fallback), None) => Some(fallback),
+            (None, Some(policy)) => match policy.as_str() {
+                "exact" | "major" | "minor" => Some(policy),
+                _ => bail!("unknown --match {}, expected exact, major or minor", policy),
+            },
+            (None, None) => None,
+        };
+        policy
+            .map(|s| s.parse())
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    /// How a substitute for the browser's build was chosen, as reported in results.
+    fn describe(self) -> &'static str {
+        match self {
+            Fallback::Exact => "exact",
+            Fallback::SameMajor => "major",
+            Fallback::SameBuild => "minor",
+            Fallback::Latest => "latest",
+        }
+    }
+
+    /// Finds the driver to install for `version` of a browser on `channel`,
+    /// substituting another build as allowed when that exact one isn't published;
+    /// returns the version chosen.
+    pub fn resolve(
+        self,
+        manifest: &str,
+        version: &str,
+        platform: &str,
+        channel: Channel,
+    ) -> Result<(String, Blob)> {
+        let version = version.trim();
+        if let Some(blob) = find_blob(manifest, version, platform)? {
+            output::parameter("msedgedriver.match", Fallback::Exact.describe());
+            return Ok((version.to_string(), blob));
+        }
+
+        let substitute = match self {
+            Fallback::Exact => {
+                eprintln!(
+                    "note: pass --match major or --fallback latest to substitute another build"
+                );
+                return Err(crate::Error::VersionMissing {
+                    version: version.into(),
+                    platform: platform.into(),
+                }
+                .into());
+            }
+            Fallback::SameMajor => match channel::latest_release(major(version), platform) {
+                Ok(latest) if major(&latest) == major(version) => latest,
+                Ok(latest) => bail!(
+                    "release marker for major {} names {}, a different major",
+                    major(version),
+                    latest
+                ),
+                Err(e) => {
+                    explain::step(format!(
+                        "release marker unavailable ({:#}), searching the listing instead",
+                        e
+                    ));
+                    newest_for_prefix(manifest, major(version), platform)?
+                }
+            },
+            Fallback::SameBuild => {
+                let build = version.rsplit_once('.').map_or(version, |(build, _)| build);
+                newest_for_prefix(manifest, build, platform)?
+            }
+            Fallback::Latest => channel.latest_version()?,
+        };
+        let message = format!(
+            "no driver published for {}, substituting {} ({} match)",
+            version,
+            substitute,
+            self.describe()
+        );
+        output::warn("substituted-build", &message)?;
+        output::parameter("msedgedriver.match", self.describe());
+
+        let blob = find_blob(manifest, &substitute, platform)?.ok_or_else(|| {
+            crate::Error::VersionMissing {
+                version: substitute.clone(),
+                platform: platform.into(),
+            }
+        })?;
+        Ok((substitute, blob))
+    }
+}
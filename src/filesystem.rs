@@ -0,0 +1,273 @@
+//! What extraction writes through, so an archive's handling of paths, links and
+//! limits can be exercised against memory rather than a disk.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The few file operations extracting a driver needs.
+pub trait Fs: Send + Sync {
+    /// Creates or truncates the file at `path` for writing.
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write>>;
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Moves `from` to `to`, replacing whatever is there.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// `path` with every symbolic link along it resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    fn is_symlink(&self, path: &Path) -> bool;
+    /// Sets the unix permission bits of `path`, where there are any.
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()>;
+    /// The unix permission bits of `path`, or `None` where there are none.
+    fn unix_mode(&self, path: &Path) -> io::Result<Option<u32>>;
+    /// Gives on-access scanners the moment they take to act on a new file.
+    fn settle(&self) {}
+}
+
+/// The real filesystem.
+pub struct Disk;
+
+impl Fs for Disk {
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write>> {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn unix_mode(&self, path: &Path) -> io::Result<Option<u32>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        Ok(Some(fs::metadata(path)?.permissions().mode()))
+    }
+
+    #[cfg(not(unix))]
+    fn unix_mode(&self, path: &Path) -> io::Result<Option<u32>> {
+        fs::metadata(path).map(|_| None)
+    }
+
+    fn settle(&self) {
+        std::thread::sleep(Duration::from_millis(200));
+    }
+}
+
+static FS: Mutex<Option<Arc<dyn Fs>>> = Mutex::new(None);
+
+/// Extracts every later driver through `fs` rather than onto the [`Disk`].
+pub fn set_fs(fs: impl Fs + 'static) {
+    *FS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(fs));
+}
+
+/// The filesystem [`set_fs`] set, or the [`Disk`].
+pub fn installed() -> Arc<dyn Fs> {
+    FS.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(Disk))
+}
+
+#[cfg(test)]
+pub use memory::MemoryFs;
+
+#[cfg(test)]
+mod memory {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        io::{self, Cursor, Read, Write},
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use super::Fs;
+
+    #[derive(Default)]
+    struct Files {
+        contents: BTreeMap<PathBuf, Vec<u8>>,
+        modes: BTreeMap<PathBuf, u32>,
+        dirs: BTreeSet<PathBuf>,
+        symlinks: BTreeSet<PathBuf>,
+        quarantined: BTreeSet<PathBuf>,
+    }
+
+    /// Files held in memory, with no links but those added with [`MemoryFs::symlink`].
+    #[derive(Clone, Default)]
+    pub struct MemoryFs {
+        files: Arc<Mutex<Files>>,
+    }
+
+    impl MemoryFs {
+        fn files(&self) -> std::sync::MutexGuard<'_, Files> {
+            self.files.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        /// The contents of the file at `path`.
+        pub fn read(&self, path: &Path) -> Option<Vec<u8>> {
+            self.files().contents.get(path).cloned()
+        }
+
+        /// The permission bits last set on `path`.
+        pub fn mode(&self, path: &Path) -> Option<u32> {
+            self.files().modes.get(path).copied()
+        }
+
+        /// Every file, in order.
+        pub fn paths(&self) -> Vec<PathBuf> {
+            self.files().contents.keys().cloned().collect()
+        }
+
+        /// Marks `path` as a symbolic link, as one already on disk would be.
+        pub fn symlink(&self, path: &Path) {
+            self.files().symlinks.insert(path.to_path_buf());
+        }
+
+        /// Makes whatever is moved to `path` vanish, as antivirus quarantining it would.
+        pub fn quarantine(&self, path: &Path) {
+            self.files().quarantined.insert(path.to_path_buf());
+        }
+    }
+
+    struct MemoryFile {
+        path: PathBuf,
+        files: Arc<Mutex<Files>>,
+    }
+
+    impl Write for MemoryFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+            files
+                .contents
+                .entry(self.path.clone())
+                .or_default()
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Fs for MemoryFs {
+        fn create(&self, path: &Path) -> io::Result<Box<dyn Write>> {
+            self.files().contents.insert(path.to_path_buf(), Vec::new());
+            Ok(Box::new(MemoryFile {
+                path: path.to_path_buf(),
+                files: self.files.clone(),
+            }))
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+            match self.files().contents.get(path) {
+                Some(contents) => Ok(Box::new(Cursor::new(contents.clone()))),
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            let mut files = self.files();
+            for dir in path.ancestors() {
+                files.dirs.insert(dir.to_path_buf());
+            }
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            let mut files = self.files();
+            let contents = files
+                .contents
+                .remove(from)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            let mode = files.modes.remove(from);
+            if files.quarantined.contains(to) {
+                return Ok(());
+            }
+            files.contents.insert(to.to_path_buf(), contents);
+            if let Some(mode) = mode {
+                files.modes.insert(to.to_path_buf(), mode);
+            }
+            Ok(())
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            let mut files = self.files();
+            files.modes.remove(path);
+            files
+                .contents
+                .remove(path)
+                .map(drop)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            let files = self.files();
+            if files.symlinks.iter().any(|link| path.starts_with(link)) {
+                // a link is taken to lead out of wherever it is
+                return Ok(PathBuf::from("/elsewhere"));
+            }
+            // a file's directories exist as surely as it does
+            let exists = files.dirs.contains(path)
+                || files.contents.keys().any(|file| file.starts_with(path));
+            if exists {
+                Ok(path.to_path_buf())
+            } else {
+                Err(io::ErrorKind::NotFound.into())
+            }
+        }
+
+        fn is_symlink(&self, path: &Path) -> bool {
+            self.files().symlinks.contains(path)
+        }
+
+        fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+            self.files().modes.insert(path.to_path_buf(), mode);
+            Ok(())
+        }
+
+        fn unix_mode(&self, path: &Path) -> io::Result<Option<u32>> {
+            let files = self.files();
+            if !files.contents.contains_key(path) {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            Ok(files.modes.get(path).copied())
+        }
+    }
+}
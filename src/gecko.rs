@@ -0,0 +1,205 @@
+//! geckodriver, matched to an installed Firefox and fetched from Mozilla's GitHub
+//! releases.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::{browser, driver::Driver, explain, http, registry, version_key, Error};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/mozilla/geckodriver/releases?per_page=50";
+
+/// The oldest Firefox major version each geckodriver release supports, newest
+/// first, from geckodriver's supported platforms documentation. Releases newer than
+/// the table are taken to need what its newest entry does.
+const MIN_FIREFOX: &[(&str, u32)] = &[
+    ("0.36.0", 128),
+    ("0.35.0", 115),
+    ("0.34.0", 115),
+    ("0.33.0", 102),
+    ("0.32.0", 102),
+    ("0.31.0", 91),
+    ("0.30.0", 78),
+    ("0.29.0", 60),
+    ("0.26.0", 60),
+];
+
+const REGISTRY_KEYS: &[&str] = &[
+    "HKLM\\SOFTWARE\\Mozilla\\Mozilla Firefox",
+    "HKLM\\SOFTWARE\\WOW6432Node\\Mozilla\\Mozilla Firefox",
+    "HKCU\\SOFTWARE\\Mozilla\\Mozilla Firefox",
+];
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// geckodriver, following Firefox.
+pub struct Gecko;
+
+impl Driver for Gecko {
+    fn name(&self) -> &'static str {
+        "geckodriver"
+    }
+
+    fn browser(&self) -> &'static str {
+        "firefox"
+    }
+
+    fn filename(&self, platform: &str) -> String {
+        if platform.starts_with("win") || platform == "arm64" {
+            "geckodriver.exe".into()
+        } else {
+            "geckodriver".into()
+        }
+    }
+
+    fn check_version(&self, version: &str) -> Result<()> {
+        let parts: Vec<&str> = version.split('.').collect();
+        if !(2..=3).contains(&parts.len())
+            || parts
+                .iter()
+                .any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()))
+        {
+            bail!(
+                "invalid firefox version {:?}, expected MAJOR.MINOR such as 121.0",
+                version
+            );
+        }
+        Ok(())
+    }
+
+    fn browser_version(&self) -> Result<String> {
+        Ok(installed_version()?.ok_or_else(|| Error::NotInstalled {
+            what: "an installed firefox".into(),
+        })?)
+    }
+
+    fn resolve(&self, version: &str, platform: &str) -> Result<(String, String)> {
+        let suffix = asset_platform(platform).ok_or_else(|| Error::UnsupportedPlatform {
+            platform: platform.into(),
+        })?;
+        let firefox = version_key(version).first().copied().unwrap_or(0);
+        explain::step(format!("querying geckodriver releases at {}", RELEASES_URL));
+        let releases: Vec<Release> = http::call(http::get(RELEASES_URL))?.into_json()?;
+
+        // e.g. geckodriver-v0.34.0-linux64.tar.gz
+        let mut compatible: Vec<(String, String)> = releases
+            .into_iter()
+            .filter_map(|release| {
+                let driver = release.tag_name.trim_start_matches('v').to_string();
+                let names = [
+                    format!("geckodriver-{}-{}.zip", release.tag_name, suffix),
+                    format!("geckodriver-{}-{}.tar.gz", release.tag_name, suffix),
+                ];
+                let asset = release
+                    .assets
+                    .into_iter()
+                    .find(|a| names.contains(&a.name))?;
+                Some((driver, asset.browser_download_url))
+            })
+            .filter(|(driver, _)| min_firefox(driver) <= firefox)
+            .collect();
+        compatible.sort_by_key(|(driver, _)| version_key(driver));
+
+        let (driver, url) = compatible.pop().ok_or_else(|| Error::VersionMissing {
+            version: version.trim().into(),
+            platform: platform.into(),
+        })?;
+        explain::step(format!(
+            "geckodriver {} is the newest supporting firefox {}",
+            driver, firefox
+        ));
+        Ok((driver, url))
+    }
+}
+
+/// The oldest Firefox major version geckodriver `version` supports.
+fn min_firefox(version: &str) -> u32 {
+    let key = version_key(version);
+    MIN_FIREFOX
+        .iter()
+        .find(|(release, _)| key >= version_key(release))
+        .or(MIN_FIREFOX.first())
+        .map_or(0, |&(_, min)| min)
+}
+
+/// The suffix of geckodriver archives for `platform`.
+fn asset_platform(platform: &str) -> Option<&'static str> {
+    match platform {
+        "win64" => Some("win64"),
+        "win32" => Some("win32"),
+        "arm64" => Some("win-aarch64"),
+        "mac64" => Some("macos"),
+        "mac64_m1" => Some("macos-aarch64"),
+        "linux64" => Some("linux64"),
+        "linux_arm64" => Some("linux-aarch64"),
+        _ => None,
+    }
+}
+
+/// The version of the installed Firefox, from the registry or its files.
+fn installed_version() -> Result<Option<String>> {
+    for key in REGISTRY_KEYS {
+        // e.g. `121.0 (x64 en-US)`
+        match registry::read_string(key, "CurrentVersion") {
+            Ok(Some(value)) => {
+                if let Some(version) = value.split_whitespace().next() {
+                    explain::step(format!(
+                        "firefox registry key {} answered with {}",
+                        key, version
+                    ));
+                    return Ok(Some(version.into()));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => explain::step(format!("firefox registry key {} unreadable: {:#}", key, e)),
+        }
+    }
+
+    for exe in executables().iter().filter(|exe| exe.is_file()) {
+        if let Some(version) = browser::file_version(exe)? {
+            explain::step(format!("firefox at {} reports {}", exe.display(), version));
+            return Ok(Some(version));
+        }
+    }
+
+    explain::step("no installed firefox found");
+    Ok(None)
+}
+
+fn executables() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        ["ProgramFiles", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(|root| {
+                PathBuf::from(root)
+                    .join("Mozilla Firefox")
+                    .join("firefox.exe")
+            })
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        vec![PathBuf::from(
+            "/Applications/Firefox.app/Contents/MacOS/firefox",
+        )]
+    } else {
+        [
+            "/usr/bin/firefox",
+            "/usr/lib/firefox/firefox",
+            "/snap/bin/firefox",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    }
+}
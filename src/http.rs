@@ -0,0 +1,1023 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error as _,
+    fmt,
+    hash::{BuildHasher, RandomState},
+    io::{self, Read},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{
+    events::{self, Event},
+    explain, flag_value, flag_values, has_flag, output, timestamp, tls, Error, USER_AGENT,
+};
+
+/// Retries after the first failure, unless `--retries` says otherwise.
+const DEFAULT_RETRIES: u32 = 3;
+// the first retry waits about this long, each later one about twice the last
+const BASE_BACKOFF_MS: u64 = 1000;
+const MAX_BACKOFF_MS: u64 = 30_000;
+// a server asking us to wait longer than this, unless --max-retry-after says
+// otherwise, is treated as down
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+// enough idle connections per host for every parallel download to go back to
+const IDLE_PER_HOST: usize = 16;
+
+/// A class of failure that may be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Status(u16),
+    /// Every status from `n * 100` to `n * 100 + 99`.
+    StatusClass(u16),
+    Timeout,
+    /// The connection was reset or closed mid-response.
+    Reset,
+    /// The connection couldn't be established at all.
+    Connect,
+}
+
+const DEFAULT_RETRY_ON: &[Condition] = &[
+    Condition::Status(429),
+    Condition::StatusClass(5),
+    Condition::Timeout,
+    Condition::Reset,
+];
+
+static RETRY_ON: OnceLock<Vec<Condition>> = OnceLock::new();
+static RETRIES: OnceLock<u32> = OnceLock::new();
+static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+/// Hosts `NO_PROXY` exempts, reached through [`DIRECT`] rather than the proxy.
+static NO_PROXY: OnceLock<Vec<String>> = OnceLock::new();
+static DIRECT: OnceLock<ureq::Agent> = OnceLock::new();
+/// The proxy [`AGENT`] goes through, with any password hidden.
+static PROXY: OnceLock<String> = OnceLock::new();
+static CONNECT_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+static READ_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+static MAX_RETRY_AFTER_FLAG: OnceLock<Duration> = OnceLock::new();
+/// When `--max-duration` runs out, and how long it was.
+static DEADLINE: OnceLock<(Instant, Duration)> = OnceLock::new();
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Set on a thread working for a future that may be dropped, see [`cancel_with`].
+    static CANCEL: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Makes requests and downloads on this thread fail once `flag` is set.
+#[cfg(feature = "async")]
+pub fn cancel_with(flag: Arc<AtomicBool>) {
+    CANCEL.with(|cancel| *cancel.borrow_mut() = Some(flag));
+}
+
+/// Fails with [`Error::Cancelled`] once the work on this thread was cancelled.
+pub fn check_cancelled() -> Result<()> {
+    let cancelled = CANCEL.with(|cancel| {
+        cancel
+            .borrow()
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    });
+    if cancelled {
+        return Err(Error::Cancelled.into());
+    }
+    Ok(())
+}
+
+/// Whether `--offline` or `MSEDGEDRIVER_TOOL_OFFLINE` forbids network access.
+pub fn offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Whether `--dry-run` limits the network to HEAD requests, and the run to
+/// changing nothing.
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Reads `--retry-on`, `--retries`, `--max-retry-after`, `--resolve`, `--proxy`,
+/// `--connect-timeout` and `--read-timeout`, which apply to every request the tool
+/// makes, and starts the clock on `--max-duration`.
+///
+/// Without `--proxy`, `HTTPS_PROXY` or `HTTP_PROXY` is used, except for the hosts
+/// listed in `NO_PROXY`.
+pub fn configure(args: &[String]) -> Result<()> {
+    tls::configure(args)?;
+    if let Some(timeout) = duration_flag(args, "--connect-timeout")? {
+        let _ = CONNECT_TIMEOUT.set(timeout);
+    }
+    if let Some(timeout) = duration_flag(args, "--read-timeout")? {
+        let _ = READ_TIMEOUT.set(timeout);
+    }
+    if let Some(limit) = duration_flag(args, "--max-duration")? {
+        let _ = DEADLINE.set((Instant::now() + limit, limit));
+        watch_deadline(limit);
+    }
+
+    let offline = has_flag(args, "--offline")
+        || std::env::var("MSEDGEDRIVER_TOOL_OFFLINE")
+            .is_ok_and(|v| matches!(v.as_str(), "1" | "true"));
+    if offline {
+        OFFLINE.store(true, Ordering::Relaxed);
+        explain::step("--offline given, only the cache and installed drivers can be used");
+    }
+    if has_flag(args, "--dry-run") {
+        DRY_RUN.store(true, Ordering::Relaxed);
+        explain::step("--dry-run given, only planning and sending HEAD requests");
+    }
+    if let Some(list) = flag_value(args, "--retry-on") {
+        let _ = RETRY_ON.set(parse_retry_on(&list)?);
+    }
+    if let Some(retries) = flag_value(args, "--retries") {
+        let retries = retries
+            .parse()
+            .map_err(|_| anyhow!("invalid --retries {:?}, expected a number", retries))?;
+        let _ = RETRIES.set(retries);
+    }
+    if let Some(limit) = duration_flag(args, "--max-retry-after")? {
+        let _ = MAX_RETRY_AFTER_FLAG.set(limit);
+    }
+
+    let overrides = flag_values(args, "--resolve")
+        .iter()
+        .map(|entry| parse_resolve(entry))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let builder = || {
+        let mut builder = pooled();
+        if !overrides.is_empty() {
+            let overrides = overrides.clone();
+            builder = builder.resolver(move |netloc: &str| -> io::Result<Vec<SocketAddr>> {
+                match overrides.get(&netloc.to_ascii_lowercase()) {
+                    Some(addr) => Ok(vec![*addr]),
+                    None => netloc.to_socket_addrs().map(Iterator::collect),
+                }
+            });
+        }
+        builder
+    };
+
+    let proxy = match flag_value(args, "--proxy") {
+        Some(proxy) => Some(proxy),
+        None => proxy_from_env(),
+    };
+    match proxy {
+        Some(proxy) => {
+            let parsed = ureq::Proxy::new(&proxy)
+                .map_err(|e| anyhow!("invalid proxy {}: {}", redact(&proxy), e))?;
+            explain::step(format!("sending requests through proxy {}", redact(&proxy)));
+            let _ = AGENT.set(builder().proxy(parsed).build());
+            let _ = PROXY.set(redact(&proxy));
+            let no_proxy = env_var("NO_PROXY")
+                .map(|list| {
+                    list.split(',')
+                        .map(|host| host.trim().trim_start_matches('.').to_ascii_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            if !no_proxy.is_empty() {
+                let _ = DIRECT.set(builder().build());
+                let _ = NO_PROXY.set(no_proxy);
+            }
+        }
+        None => {
+            let _ = AGENT.set(builder().build());
+        }
+    }
+
+    Ok(())
+}
+
+fn proxy_from_env() -> Option<String> {
+    env_var("HTTPS_PROXY").or_else(|| env_var("HTTP_PROXY"))
+}
+
+/// An environment variable by its conventional upper or lower case name.
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_ascii_lowercase()))
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// `proxy` with any password hidden, for messages.
+fn redact(proxy: &str) -> String {
+    match proxy.split_once('@') {
+        Some((credentials, host)) => match credentials.rsplit_once(':') {
+            Some((user, _)) if !user.ends_with('/') => format!("{}:***@{}", user, host),
+            _ => proxy.to_string(),
+        },
+        None => proxy.to_string(),
+    }
+}
+
+/// The proxy requests to `url` go through, with any password hidden, or `None`
+/// if they go directly.
+pub fn proxy_for(url: &str) -> Option<&'static str> {
+    PROXY
+        .get()
+        .map(String::as_str)
+        .filter(|_| !bypasses_proxy(url))
+}
+
+/// Whether `NO_PROXY` exempts the host of `url` from the proxy.
+fn bypasses_proxy(url: &str) -> bool {
+    let Some(no_proxy) = NO_PROXY.get() else {
+        return false;
+    };
+    let host = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    }
+    .trim_start_matches('[')
+    .trim_end_matches(']')
+    .to_ascii_lowercase();
+
+    no_proxy
+        .iter()
+        .any(|entry| entry == "*" || host == *entry || host.ends_with(&format!(".{}", entry)))
+}
+
+/// Parses a curl style `host:port:address` override into the `host:port` it
+/// applies to and where to connect instead.
+fn parse_resolve(entry: &str) -> Result<(String, SocketAddr)> {
+    let invalid = || anyhow!("invalid --resolve {}, expected host:port:address", entry);
+    let mut parts = entry.splitn(3, ':');
+    let (Some(host), Some(port), Some(address)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    let address: IpAddr = address
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| invalid())?;
+    if host.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok((
+        format!("{}:{}", host.to_ascii_lowercase(), port),
+        SocketAddr::new(address, port),
+    ))
+}
+
+/// Parses a comma separated list of statuses like `429`, classes like `5xx`, and
+/// `timeout`, `reset` or `connect`; `none` disables retrying.
+///
+/// Without `--retry-on` the list is `429,5xx,timeout,reset`.
+fn parse_retry_on(list: &str) -> Result<Vec<Condition>> {
+    let mut conditions = Vec::new();
+    for item in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        conditions.push(match item.to_ascii_lowercase().as_str() {
+            "none" => continue,
+            "timeout" => Condition::Timeout,
+            "reset" => Condition::Reset,
+            "connect" => Condition::Connect,
+            class if class.len() == 3 && class.ends_with("xx") => {
+                match class[..1].parse::<u16>() {
+                    Ok(n) if (1..=5).contains(&n) => Condition::StatusClass(n),
+                    _ => bail!("invalid --retry-on status class {}", item),
+                }
+            }
+            status => match status.parse::<u16>() {
+                Ok(code) if (100..=599).contains(&code) => Condition::Status(code),
+                _ => bail!(
+                    "invalid --retry-on condition {}, expected a status, a class like 5xx, timeout, reset or connect",
+                    item
+                ),
+            },
+        });
+    }
+
+    Ok(conditions)
+}
+
+/// The agent a request for `url` goes through, honouring `--resolve` and the proxy.
+///
+/// Sharing one agent keeps connections alive between the marker lookups, listing
+/// and archive downloads of a run. ureq only speaks HTTP/1.1, so concurrent
+/// downloads each hold their own pooled connection rather than sharing one.
+fn agent(url: &str) -> &'static ureq::Agent {
+    if bypasses_proxy(url) {
+        if let Some(direct) = DIRECT.get() {
+            return direct;
+        }
+    }
+    AGENT.get_or_init(|| pooled().build())
+}
+
+fn pooled() -> ureq::AgentBuilder {
+    let mut builder = ureq::AgentBuilder::new()
+        .tls_config(tls::client_config())
+        .max_idle_connections_per_host(IDLE_PER_HOST)
+        .max_idle_connections(IDLE_PER_HOST * 4);
+    if let Some(timeout) = CONNECT_TIMEOUT.get() {
+        builder = builder.timeout_connect(*timeout);
+    }
+    if let Some(timeout) = READ_TIMEOUT.get() {
+        builder = builder.timeout_read(*timeout);
+    }
+    builder
+}
+
+/// Parses a duration flag such as `--read-timeout 30s`, in `ms`, `s`, `m` or `h`
+/// with seconds as the default unit.
+pub fn duration_flag(args: &[String], flag: &str) -> Result<Option<Duration>> {
+    let Some(value) = flag_value(args, flag) else {
+        return Ok(None);
+    };
+    let invalid = || {
+        anyhow!(
+            "invalid {} {:?}, expected e.g. 30s, 500ms or 10m",
+            flag,
+            value
+        )
+    };
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (digits, unit) = trimmed.split_at(split);
+    let n: u64 = digits.parse().map_err(|_| invalid())?;
+    let duration = match unit.trim() {
+        "ms" => Duration::from_millis(n),
+        "" | "s" => Duration::from_secs(n),
+        "m" => Duration::from_secs(n.saturating_mul(60)),
+        "h" => Duration::from_secs(n.saturating_mul(60 * 60)),
+        _ => return Err(invalid()),
+    };
+    if duration.is_zero() {
+        return Err(invalid());
+    }
+    Ok(Some(duration))
+}
+
+/// Ends the process once `--max-duration` is up, whatever it is waiting on, since
+/// not every wait is a request that can be given a timeout.
+fn watch_deadline(limit: Duration) {
+    thread::spawn(move || {
+        thread::sleep(limit);
+        eprintln!("Error: {}", deadline_error());
+        std::process::exit(Error::Timeout { limit }.exit_code());
+    });
+}
+
+fn deadline_error() -> Error {
+    Error::Timeout {
+        limit: DEADLINE
+            .get()
+            .map_or_else(Duration::default, |(_, limit)| *limit),
+    }
+}
+
+/// Time left before `--max-duration` runs out, if it was given.
+fn remaining() -> Option<Duration> {
+    DEADLINE
+        .get()
+        .map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// A request about to be sent, built the way ureq's are but sent through the
+/// installed [`HttpClient`].
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: &'static str,
+    url: String,
+    headers: Vec<(String, String)>,
+    timeout: Option<Duration>,
+    body: Option<Vec<u8>>,
+}
+
+impl Request {
+    fn new(method: &'static str, url: &str) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: vec![("User-Agent".into(), USER_AGENT.into())],
+            timeout: None,
+            body: None,
+        }
+    }
+
+    pub fn method(&self) -> &str {
+        self.method
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The value of header `name`, matched without regard to case, if it is set.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every header set, in the order they were.
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// How long the whole request may take, if limited.
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    /// Sets header `name` to `value`, replacing any value it had.
+    pub fn set(mut self, name: &str, value: &str) -> Self {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends `value` as the JSON body, once.
+    pub fn send_json(mut self, value: serde_json::Value) -> Result<Response> {
+        self.body = Some(serde_json::to_vec(&value)?);
+        self.set("Content-Type", "application/json").call()
+    }
+
+    /// Sends the request once, without retrying, failing with a [`Status`] if the
+    /// server answered with an error.
+    pub fn call(self) -> Result<Response> {
+        check_cancelled()?;
+        if offline() {
+            return Err(Error::Offline {
+                what: redact(&self.url),
+            }
+            .into());
+        }
+        if dry_run() && self.method != "HEAD" {
+            bail!(
+                "--dry-run only sends HEAD requests, not {} {}",
+                self.method,
+                redact(&self.url)
+            );
+        }
+        log::debug!("{} {}", self.method, self.url);
+        let response = client().send(&self)?;
+        log::debug!(
+            "{} answered {} {}",
+            self.url,
+            response.status(),
+            response.status_text()
+        );
+        if response.status() >= 400 {
+            return Err(Status {
+                url: redact(&self.url),
+                code: response.status(),
+                retry_after: retry_after(&response),
+            }
+            .into());
+        }
+        Ok(response)
+    }
+}
+
+/// A server's answer to a [`Request`], with its body still to be read.
+pub struct Response {
+    status: u16,
+    status_text: String,
+    headers: Vec<(String, String)>,
+    body: Box<dyn Read + Send + Sync>,
+}
+
+impl Response {
+    /// A response with `status` whose body is read from `body`, for an
+    /// [`HttpClient`] to answer with.
+    pub fn new(status: u16, status_text: &str, body: impl Read + Send + Sync + 'static) -> Self {
+        Self {
+            status,
+            status_text: status_text.into(),
+            headers: Vec::new(),
+            body: Box::new(body),
+        }
+    }
+
+    /// Adds header `name` with `value`.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn status_text(&self) -> &str {
+        &self.status_text
+    }
+
+    /// The first value of header `name`, matched without regard to case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn into_reader(self) -> Box<dyn Read + Send + Sync> {
+        self.body
+    }
+
+    pub fn into_string(self) -> Result<String> {
+        let mut body = String::new();
+        self.into_reader().read_to_string(&mut body)?;
+        Ok(body)
+    }
+
+    pub fn into_json<T: DeserializeOwned>(self) -> Result<T> {
+        Ok(serde_json::from_reader(self.into_reader())?)
+    }
+}
+
+/// An error status a server answered a request with.
+#[derive(Debug)]
+pub struct Status {
+    pub code: u16,
+    url: String,
+    /// How long the server asked to be left before the next request.
+    retry_after: Option<Duration>,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: status code {}", self.url, self.code)
+    }
+}
+
+impl std::error::Error for Status {}
+
+/// What every request is sent through, so a library caller or a test can answer
+/// them from a [`MemoryHttp`] instead of the network.
+pub trait HttpClient: Send + Sync {
+    /// Sends `request` once, answering with whatever status the server did; only
+    /// a request that got no answer at all fails.
+    fn send(&self, request: &Request) -> Result<Response>;
+}
+
+/// The network, through the proxies and timeouts configured. Every request goes
+/// through it unless [`set_http_client`] replaced it.
+pub struct Ureq;
+
+impl HttpClient for Ureq {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let mut sending = agent(&request.url).request(request.method, &request.url);
+        for (name, value) in &request.headers {
+            sending = sending.set(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            sending = sending.timeout(timeout);
+        }
+        let result = match &request.body {
+            Some(body) => sending.send_bytes(body),
+            None => sending.call(),
+        };
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(e.into()),
+        };
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_string();
+                Some((name, value))
+            })
+            .collect();
+        Ok(Response {
+            status: response.status(),
+            status_text: response.status_text().into(),
+            headers,
+            body: response.into_reader(),
+        })
+    }
+}
+
+/// Serves only the bodies set on it, by exact url, answering anything else with
+/// a 404, and remembers what was asked for. Clones share their bodies and
+/// requests.
+#[derive(Clone, Default)]
+pub struct MemoryHttp {
+    bodies: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    requested: Arc<Mutex<Vec<String>>>,
+}
+
+impl MemoryHttp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers requests for `url` with `body` from now on.
+    pub fn serve(&self, url: &str, body: impl Into<Vec<u8>>) -> &Self {
+        self.bodies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(url.into(), body.into());
+        self
+    }
+
+    /// Every url asked for so far, in order.
+    pub fn requested(&self) -> Vec<String> {
+        self.requested
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl HttpClient for MemoryHttp {
+    fn send(&self, request: &Request) -> Result<Response> {
+        self.requested
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(request.url.clone());
+        let body = self
+            .bodies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&request.url)
+            .cloned();
+        Ok(match body {
+            Some(body) => {
+                let len = body.len().to_string();
+                let body = if request.method == "HEAD" {
+                    Vec::new()
+                } else {
+                    body
+                };
+                Response::new(200, "OK", io::Cursor::new(body)).with_header("Content-Length", &len)
+            }
+            None => Response::new(404, "Not Found", io::empty()),
+        })
+    }
+}
+
+static CLIENT: Mutex<Option<Arc<dyn HttpClient>>> = Mutex::new(None);
+
+/// Sends every later request through `client` rather than [`Ureq`].
+pub fn set_http_client(client: impl HttpClient + 'static) {
+    *CLIENT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(client));
+}
+
+/// The client [`set_http_client`] set, or [`Ureq`].
+fn client() -> Arc<dyn HttpClient> {
+    CLIENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(Ureq))
+}
+
+/// Serves every request from `client` until the guard is dropped, keeping tests
+/// that share the process-wide client from running at once, and downloads out of
+/// the user's cache.
+#[cfg(test)]
+pub fn serving(client: &MemoryHttp) -> std::sync::MutexGuard<'static, ()> {
+    static SERVING: Mutex<()> = Mutex::new(());
+    let guard = SERVING.lock().unwrap_or_else(|e| e.into_inner());
+    crate::paths::set_cache_dir(
+        std::env::temp_dir().join(format!("msedgedriver-tool-test-{}", std::process::id())),
+    );
+    set_http_client(client.clone());
+    guard
+}
+
+/// A GET request for `url` identifying the tool.
+pub fn get(url: &str) -> Request {
+    Request::new("GET", url)
+}
+
+/// A POST request for `url` identifying the tool.
+pub fn post(url: &str) -> Request {
+    Request::new("POST", url)
+}
+
+/// A HEAD request for `url` identifying the tool.
+pub fn head(url: &str) -> Request {
+    Request::new("HEAD", url)
+}
+
+/// How many times a failed request is retried, from `--retries`.
+pub fn retries() -> u32 {
+    RETRIES.get().copied().unwrap_or(DEFAULT_RETRIES)
+}
+
+/// Sends `request`, retrying with backoff while it fails in a way `--retry-on` allows.
+pub fn call(request: Request) -> Result<Response> {
+    let retries = retries();
+    let mut attempt = 0;
+    loop {
+        let error = match request.clone().call() {
+            Ok(response) => return Ok(response),
+            Err(e) => e,
+        };
+        if remaining().is_some_and(|left| left.is_zero()) {
+            return Err(error.context(deadline_error()));
+        }
+        if attempt >= retries || !retryable(&error) {
+            return Err(error);
+        }
+
+        let status = error.downcast_ref::<Status>();
+        let retry_after = status.and_then(|status| status.retry_after);
+        let limit = *MAX_RETRY_AFTER_FLAG.get().unwrap_or(&MAX_RETRY_AFTER);
+        let delay = match retry_after {
+            Some(wait) if wait > limit => {
+                let source = anyhow!(
+                    "{} asked to be retried in {}s, longer than --max-retry-after allows ({}s)",
+                    redact(request.url()),
+                    wait.as_secs(),
+                    limit.as_secs()
+                );
+                return Err(Error::Network {
+                    source: error.context(source),
+                }
+                .into());
+            }
+            Some(wait) => wait,
+            None => backoff(attempt),
+        };
+        attempt += 1;
+        // a retry that can't finish in time only postpones the failure
+        if remaining().is_some_and(|left| left < delay) {
+            return Err(error.context(deadline_error()));
+        }
+
+        let reason = match (status, retry_after) {
+            (Some(status), Some(_)) => {
+                format!("answered {}, waiting as its Retry-After asks", status.code)
+            }
+            (Some(status), None) => format!("answered {}", status.code),
+            (None, _) => match error.downcast_ref::<ureq::Error>() {
+                Some(ureq::Error::Transport(transport)) => transport.kind().to_string(),
+                _ => error.to_string(),
+            },
+        };
+        explain::step(format!(
+            "{} failed ({}), retry {} of {} in {:.1}s",
+            redact(request.url()),
+            error,
+            attempt,
+            retries,
+            delay.as_secs_f64()
+        ));
+        // a long pause is otherwise indistinguishable from a hang in CI logs
+        output::progress(&format!(
+            "{} {}, retry {} of {} in {:.1}s",
+            redact(request.url()),
+            reason,
+            attempt,
+            retries,
+            delay.as_secs_f64()
+        ));
+        output::push(
+            "retries",
+            json!({
+                "url": redact(request.url()),
+                "attempt": attempt,
+                "reason": reason,
+                "retry_after": retry_after.is_some(),
+                "delay_ms": delay.as_millis() as u64,
+            }),
+        );
+        events::emit(Event::RetryAttempted {
+            url: request.url(),
+            attempt,
+            retries,
+            delay,
+        });
+        thread::sleep(delay);
+    }
+}
+
+/// How long `response` asks to be left before the next request, as seconds or an
+/// HTTP date, which may already have passed.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.header("Retry-After")?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = timestamp::parse_http_date(value)?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// How long to wait before retry `attempt + 1`: exponential, with jitter so that
+/// many runners failing together don't all come back at the same moment.
+fn backoff(attempt: u32) -> Duration {
+    let ceiling = BASE_BACKOFF_MS
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_BACKOFF_MS);
+    // a randomly seeded hasher is the only randomness std offers
+    let random = RandomState::new().hash_one(attempt);
+    Duration::from_millis(ceiling / 2 + random % (ceiling / 2 + 1))
+}
+
+fn retryable(error: &anyhow::Error) -> bool {
+    let conditions = RETRY_ON
+        .get()
+        .map(Vec::as_slice)
+        .unwrap_or(DEFAULT_RETRY_ON);
+
+    if let Some(status) = error.downcast_ref::<Status>() {
+        return conditions.iter().any(|c| match *c {
+            Condition::Status(code) => code == status.code,
+            Condition::StatusClass(class) => status.code / 100 == class,
+            _ => false,
+        });
+    }
+    let failure = match error.downcast_ref::<ureq::Error>() {
+        Some(ureq::Error::Transport(transport)) => match transport.kind() {
+            ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Dns => Condition::Connect,
+            ureq::ErrorKind::Io => match transport
+                .source()
+                .and_then(|e| e.downcast_ref::<io::Error>())
+                .map(io::Error::kind)
+            {
+                Some(io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => Condition::Timeout,
+                Some(
+                    io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof,
+                ) => Condition::Reset,
+                _ => return false,
+            },
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    conditions.contains(&failure)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Answers everything with a 429 asking to be left for `retry_after`, counting
+    /// the requests it gets.
+    #[derive(Clone)]
+    struct Throttled {
+        retry_after: &'static str,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl HttpClient for Throttled {
+        fn send(&self, _request: &Request) -> Result<Response> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Ok(Response::new(429, "Too Many Requests", io::empty())
+                .with_header("Retry-After", self.retry_after))
+        }
+    }
+
+    fn throttled(retry_after: &'static str) -> (Result<Response>, usize) {
+        let _serving = serving(&MemoryHttp::new());
+        let client = Throttled {
+            retry_after,
+            requests: Arc::default(),
+        };
+        set_http_client(client.clone());
+        let result = call(get("https://msedgedriver.azureedge.net/"));
+        (result, client.requests.load(Ordering::Relaxed))
+    }
+
+    fn asking(retry_after: &str) -> Response {
+        Response::new(429, "Too Many Requests", io::empty()).with_header("Retry-After", retry_after)
+    }
+
+    #[test]
+    fn reads_retry_after_as_seconds_or_a_date() {
+        assert_eq!(retry_after(&asking("30")), Some(Duration::from_secs(30)));
+        assert_eq!(retry_after(&asking(" 0 ")), Some(Duration::ZERO));
+        // a date already past asks for no wait at all
+        assert_eq!(
+            retry_after(&asking("Sun, 06 Nov 1994 08:49:37 GMT")),
+            Some(Duration::ZERO)
+        );
+        let soon = retry_after(&asking("Fri, 31 Dec 9999 23:59:59 GMT")).unwrap();
+        assert!(soon > Duration::from_secs(60 * 60 * 24 * 365));
+    }
+
+    #[test]
+    fn ignores_a_malformed_retry_after() {
+        for value in ["soon", "-5", "1.5", "", "Sun, 06 Nov 1994"] {
+            assert_eq!(retry_after(&asking(value)), None, "{}", value);
+        }
+        let without = Response::new(429, "Too Many Requests", io::empty());
+        assert_eq!(retry_after(&without), None);
+    }
+
+    #[test]
+    fn fails_at_once_when_asked_to_wait_too_long() {
+        let (result, requests) = throttled("3600");
+        let e = result.err().unwrap();
+        assert_eq!(requests, 1);
+        assert_eq!(crate::error::exit_code(&e), 6);
+        assert!(
+            format!("{:#}", e).contains("longer than --max-retry-after allows (60s)"),
+            "{:#}",
+            e
+        );
+    }
+
+    #[test]
+    fn retries_at_once_when_retry_after_has_passed() {
+        let (result, requests) = throttled("Sun, 06 Nov 1994 08:49:37 GMT");
+        let e = result.err().unwrap();
+        assert_eq!(requests, 1 + DEFAULT_RETRIES as usize);
+        assert_eq!(e.downcast_ref::<Status>().map(|s| s.code), Some(429));
+    }
+
+    #[test]
+    fn parses_retry_on_lists() {
+        assert_eq!(
+            parse_retry_on("429, 5xx,Timeout,reset,connect,").unwrap(),
+            vec![
+                Condition::Status(429),
+                Condition::StatusClass(5),
+                Condition::Timeout,
+                Condition::Reset,
+                Condition::Connect,
+            ]
+        );
+        assert_eq!(parse_retry_on("none").unwrap(), vec![]);
+        for list in ["6xx", "0xx", "99", "600", "later", "429,sometimes"] {
+            assert!(parse_retry_on(list).is_err(), "{}", list);
+        }
+    }
+
+    #[test]
+    fn parses_resolve_overrides() {
+        assert_eq!(
+            parse_resolve("Example.com:443:127.0.0.1").unwrap(),
+            ("example.com:443".into(), "127.0.0.1:443".parse().unwrap())
+        );
+        assert_eq!(
+            parse_resolve("example.com:8080:[::1]").unwrap(),
+            ("example.com:8080".into(), "[::1]:8080".parse().unwrap())
+        );
+        for entry in [
+            "example.com:443",
+            ":443:127.0.0.1",
+            "example.com:https:127.0.0.1",
+            "example.com:443:localhost",
+        ] {
+            assert!(parse_resolve(entry).is_err(), "{}", entry);
+        }
+    }
+
+    #[test]
+    fn parses_durations_in_each_unit() {
+        let parse =
+            |value: &str| duration_flag(&args(&["--read-timeout", value]), "--read-timeout");
+        assert_eq!(parse("30").unwrap(), Some(Duration::from_secs(30)));
+        assert_eq!(parse("30s").unwrap(), Some(Duration::from_secs(30)));
+        assert_eq!(parse("500ms").unwrap(), Some(Duration::from_millis(500)));
+        assert_eq!(parse("10m").unwrap(), Some(Duration::from_secs(600)));
+        assert_eq!(parse("2h").unwrap(), Some(Duration::from_secs(7200)));
+        assert_eq!(
+            duration_flag(&args(&["--read-timeout=5s"]), "--read-timeout").unwrap(),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(duration_flag(&args(&[]), "--read-timeout").unwrap(), None);
+        for value in ["0", "0s", "s", "-5s", "1.5s", "10d", "ten"] {
+            assert!(parse(value).is_err(), "{}", value);
+        }
+    }
+}
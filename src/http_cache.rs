@@ -0,0 +1,153 @@
+use std::{
+    fs,
+    io::Read,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{explain, http, metrics, paths};
+
+/// Validators and freshness of one cached response, stored beside its body.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Unix time until which the body may be reused without asking the server.
+    fresh_until: u64,
+}
+
+/// Fetches a small document, reusing and revalidating an on-disk copy according
+/// to the server's `Cache-Control`, `ETag` and `Last-Modified` headers.
+///
+/// Any problem with the cache itself falls back to a plain request.
+pub fn get(url: &str) -> Result<Vec<u8>> {
+    let Some((meta_path, body_path)) = entry_paths(url) else {
+        return fetch(url, None).map(|(body, _)| body);
+    };
+
+    let cached = fs::read(&meta_path)
+        .ok()
+        .and_then(|meta| serde_json::from_slice::<Entry>(&meta).ok())
+        .filter(|entry| entry.url == url)
+        .and_then(|entry| fs::read(&body_path).ok().map(|body| (entry, body)));
+
+    if let Some((entry, body)) = &cached {
+        if now() < entry.fresh_until {
+            explain::step(format!("reusing cached response for {}", url));
+            metrics::record_cache_hit();
+            return Ok(body.clone());
+        }
+    }
+
+    let (body, entry) = match cached {
+        Some((entry, body)) => match fetch(url, Some(&entry))? {
+            (_, Fetched::NotModified(fresh_until)) => {
+                explain::step(format!("{} not modified since last fetch", url));
+                let entry = Entry {
+                    fresh_until,
+                    ..entry
+                };
+                (body, Some(entry))
+            }
+            (body, Fetched::Body(entry)) => (body, entry),
+        },
+        None => match fetch(url, None)? {
+            (body, Fetched::Body(entry)) => (body, entry),
+            (body, Fetched::NotModified(_)) => (body, None),
+        },
+    };
+
+    match entry {
+        Some(entry) => {
+            if let Some(dir) = meta_path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            if fs::write(&body_path, &body).is_ok() {
+                let _ = fs::write(&meta_path, serde_json::to_vec(&entry)?);
+            }
+        }
+        None => {
+            let _ = fs::remove_file(&meta_path);
+            let _ = fs::remove_file(&body_path);
+        }
+    }
+
+    Ok(body)
+}
+
+enum Fetched {
+    /// A new body, with the entry to store for it unless the server forbids caching.
+    Body(Option<Entry>),
+    /// The cached body is still current, and fresh until the given time.
+    NotModified(u64),
+}
+
+fn fetch(url: &str, cached: Option<&Entry>) -> Result<(Vec<u8>, Fetched)> {
+    let mut request = http::get(url);
+    if let Some(entry) = cached {
+        if let Some(etag) = &entry.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+    }
+
+    let response = http::call(request)?;
+    let cache_control = response
+        .header("Cache-Control")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let directives: Vec<&str> = cache_control.split(',').map(str::trim).collect();
+    let max_age = directives
+        .iter()
+        .find_map(|d| d.strip_prefix("max-age="))
+        .and_then(|age| age.trim_matches('"').parse::<u64>().ok())
+        .unwrap_or(0);
+    let fresh_until = if directives.contains(&"no-cache") {
+        0
+    } else {
+        now().saturating_add(max_age)
+    };
+
+    if response.status() == 304 {
+        return Ok((Vec::new(), Fetched::NotModified(fresh_until)));
+    }
+
+    let etag = response.header("ETag").map(str::to_string);
+    let last_modified = response.header("Last-Modified").map(str::to_string);
+    let mut body = Vec::new();
+    response.into_reader().read_to_end(&mut body)?;
+
+    let storable = !directives.contains(&"no-store")
+        && (max_age > 0 || etag.is_some() || last_modified.is_some());
+    let entry = storable.then(|| Entry {
+        url: url.to_string(),
+        etag,
+        last_modified,
+        fresh_until,
+    });
+
+    Ok((body, Fetched::Body(entry)))
+}
+
+fn entry_paths(url: &str) -> Option<(PathBuf, PathBuf)> {
+    let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let dir = paths::cache_dir()?.join("http");
+    Some((
+        dir.join(format!("{}.json", key)),
+        dir.join(format!("{}.body", key)),
+    ))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
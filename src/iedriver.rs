@@ -0,0 +1,100 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use crate::{
+    audit, checksums,
+    download::{self, download_driver, DownloadOptions, ExtractLimits},
+    flag_value, http, output, policy,
+};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/SeleniumHQ/selenium/releases?per_page=50";
+const FILENAME: &str = "IEDriverServer.exe";
+
+#[derive(Deserialize)]
+struct Release {
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Installs IEDriverServer, which Selenium needs to drive Edge in Internet Explorer mode.
+pub fn install(args: &[String]) -> Result<()> {
+    let arch = match flag_value(args, "--ie-arch").as_deref() {
+        None | Some("x64") => "x64",
+        Some("win32" | "Win32" | "x86") => "Win32",
+        Some(other) => bail!("unknown --ie-arch {}, expected x64 or win32", other),
+    };
+    let pinned = flag_value(args, "--ie-version");
+
+    let options = DownloadOptions {
+        sha256: download::sha256_pin(args)?,
+        limits: ExtractLimits::from_args(args)?,
+        include: download::include_patterns(args)?,
+        size_hint: None,
+    };
+    if policy::get().require_sha256 && options.sha256.is_none() && !checksums::configured() {
+        bail!("policy requires the archive to be pinned with --sha256 or a checksum source");
+    }
+
+    output::progress("searching selenium releases for IEDriverServer");
+    let (version, url) = resolve(arch, pinned.as_deref())?;
+    output::progress(&format!("IEDriverServer version: {} ({})", version, arch));
+
+    let hashes = download_driver(&url, FILENAME, Path::new(FILENAME), &options)?;
+
+    let path = std::env::current_dir()?.join(FILENAME);
+    output::parameter("iedriver.path", &path.to_string_lossy());
+    output::parameter("iedriver.version", &version);
+    output::parameter("iedriver.sha256", &hashes.driver);
+
+    if let Some(log) = audit::log_path(args) {
+        audit::record(
+            &log,
+            &audit::Event {
+                version: Some(&version),
+                platform: Some(arch),
+                archive_sha256: Some(&hashes.archive),
+                driver_sha256: Some(&hashes.driver),
+                source: Some(&url),
+                ..audit::Event::new("install", &path)
+            },
+        )?;
+    }
+
+    output::progress(
+        "to drive Edge in IE mode, request the internet explorer browser with the \
+         capabilities \"ie.edgechromium\": true and \"ie.edgepath\" set to the path of msedge.exe",
+    );
+
+    Ok(())
+}
+
+/// Finds the download url of the `arch` IEDriverServer, the newest unless `pinned`.
+fn resolve(arch: &str, pinned: Option<&str>) -> Result<(String, String)> {
+    let releases: Vec<Release> = http::call(http::get(RELEASES_URL))?.into_json()?;
+
+    // e.g. IEDriverServer_x64_4.14.0.zip
+    let prefix = format!("IEDriverServer_{}_", arch);
+    releases
+        .into_iter()
+        .flat_map(|r| r.assets)
+        .filter_map(|asset| {
+            let version = asset
+                .name
+                .strip_prefix(&prefix)?
+                .strip_suffix(".zip")?
+                .to_string();
+            Some((version, asset.browser_download_url))
+        })
+        .find(|(version, _)| pinned.is_none_or(|p| p == version))
+        .ok_or_else(|| match pinned {
+            Some(p) => anyhow!("no {} IEDriverServer {} release found", arch, p),
+            None => anyhow!("no {} IEDriverServer release found", arch),
+        })
+}
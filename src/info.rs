@@ -0,0 +1,158 @@
+//! `info [version]`: everything known about one driver version, for dashboards and
+//! working out why an install chose what it did.
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser::{self, Browser},
+    cache, check_version, checksums, driver_filename, fetch_manifest, flag_value, http,
+    lockfile::Lockfile,
+    mapping, output, parse_blobs, resume, target_platform, ALL_PLATFORMS, BASE_URL,
+};
+
+/// `version`, or else the driver the preferred installed browser needs, checked to
+/// be a full version; `example` shows how to pass one when there is no browser.
+pub fn version_or_detected(
+    args: &[String],
+    version: Option<String>,
+    example: &str,
+) -> Result<String> {
+    let version = match version {
+        Some(version) => version.trim().to_string(),
+        None => {
+            let found = browser::all(args)?;
+            let installation =
+                browser::preferred(&found, Browser::from_args(args)?).ok_or_else(|| {
+                    anyhow!(
+                        "no browser found to match, pass a version such as {}",
+                        example
+                    )
+                })?;
+            output::progress(&format!("matching {}", installation.describe()));
+            mapping::driver_for(&installation.version)
+        }
+    };
+    check_version(&version)?;
+    Ok(version)
+}
+
+/// Prints what the listing, the lockfile, the checksum source and the cache know
+/// about `version`, or the driver the installed browser needs, for `--platform`.
+pub fn info(args: &[String]) -> Result<()> {
+    let version = args
+        .first()
+        .filter(|arg| !arg.starts_with('-'))
+        .cloned()
+        .or_else(|| flag_value(args, "--version"));
+    let version = version_or_detected(args, version, "`info 120.0.2210.91`")?;
+    let platform = target_platform(args)?;
+
+    output::line(&format!("msedgedriver {}", version));
+    output::parameter("msedgedriver.version", &version);
+    output::parameter("msedgedriver.platform", platform);
+    if mapping::pulled(&version) {
+        output::line("  pulled: yes, the version map says it can't be downloaded");
+        output::flag("msedgedriver.pulled", true);
+    }
+
+    let blobs = if http::offline() {
+        output::line("  listing: not consulted offline");
+        Vec::new()
+    } else {
+        parse_blobs(&fetch_manifest()?)?
+    };
+    let platforms: Vec<&str> = ALL_PLATFORMS
+        .iter()
+        .copied()
+        .filter(|p| {
+            let names = crate::blob_names(&version, p);
+            blobs.iter().any(|blob| names.contains(&blob.name))
+        })
+        .collect();
+    output::line(&format!(
+        "  platforms: {}",
+        if platforms.is_empty() {
+            "none in the listing".to_string()
+        } else {
+            platforms.join(", ")
+        }
+    ));
+    for p in &platforms {
+        output::push("platforms", (*p).into());
+    }
+
+    let names = crate::blob_names(&version, platform);
+    let url = match blobs.into_iter().find(|blob| names.contains(&blob.name)) {
+        Some(blob) => {
+            if let Some(published) = &blob.properties.last_modified {
+                output::line(&format!("  published: {}", published));
+                output::parameter("msedgedriver.published", published);
+            }
+            if let Some(size) = blob.properties.content_length {
+                output::line(&format!("  archive size: {} bytes", size));
+                output::parameter("msedgedriver.archive_size", &size.to_string());
+            }
+            // the mirrored listing still names Microsoft's host for each archive
+            match BASE_URL.get().and_then(Option::as_deref) {
+                Some(base) => format!("{}/{}", base, blob.name),
+                None => blob.url,
+            }
+        }
+        None if http::offline() => String::new(),
+        None => {
+            output::line(&format!("  {}: not in the listing", platform));
+            String::new()
+        }
+    };
+    if !url.is_empty() {
+        output::line(&format!("  url: {}", url));
+        output::parameter("msedgedriver.url", &url);
+    }
+
+    let locked = Lockfile::from_args(args)?.and_then(|lockfile| {
+        lockfile
+            .locked("msedgedriver", platform)
+            .ok()
+            .flatten()
+            .filter(|locked| locked.version == version)
+            .map(|locked| locked.archive_sha256.clone())
+    });
+    let checksum = match (&locked, url.is_empty() || !checksums::configured()) {
+        (None, false) => checksums::expected(&url)?,
+        _ => None,
+    };
+    let cached = cache::lookup(&version, platform, driver_filename(platform), None);
+    let sha256 = locked
+        .map(|sha256| (sha256, "lockfile"))
+        .or(checksum.map(|sha256| (sha256, "checksum source")))
+        .or(cached
+            .as_ref()
+            .map(|(_, receipt)| (receipt.archive_sha256.clone(), "cached receipt")));
+    if let Some((sha256, source)) = &sha256 {
+        output::line(&format!(
+            "  archive sha256: {} (from the {})",
+            sha256, source
+        ));
+        output::parameter("msedgedriver.archive_sha256", sha256);
+        output::parameter("msedgedriver.sha256_source", source);
+    }
+
+    match &cached {
+        Some((path, _)) => {
+            output::line(&format!("  cached: {}", path.display()));
+            output::parameter("msedgedriver.cached_path", &path.to_string_lossy());
+        }
+        None => output::line("  cached: no"),
+    }
+    output::flag("msedgedriver.cached", cached.is_some());
+    let source = match &cached {
+        Some((_, receipt)) if url.is_empty() => receipt.source.as_str(),
+        _ => url.as_str(),
+    };
+    let archive_cached = !source.is_empty() && resume::has_kept(source);
+    if archive_cached {
+        output::line("  cached archive: yes, --reextract can rebuild the driver offline");
+    }
+    output::flag("msedgedriver.archive_cached", archive_cached);
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{output, receipt, retry, running};
+
+/// How far an install got before it was interrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Stage {
+    /// The previous driver, if any, was copied aside; nothing else changed yet.
+    BackupTaken,
+    /// The new driver is complete at the temporary path, and may have been moved
+    /// into place, but hasn't yet been seen to run.
+    TempWritten,
+    /// The new driver replaced the previous one and runs.
+    Renamed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    stage: Stage,
+    backup: Option<PathBuf>,
+    temp: PathBuf,
+}
+
+/// An install in progress, journaled beside the driver so an interrupted one is
+/// rolled back by the next run instead of leaving a broken or missing driver.
+pub struct Journal {
+    path: PathBuf,
+    entry: Entry,
+}
+
+impl Journal {
+    /// Starts replacing `driver`, backing up the current one.
+    pub fn begin(driver: &Path) -> Result<Self> {
+        recover(driver)?;
+
+        let backup = if driver.is_file() {
+            let backup = sibling(driver, "bak");
+            fs::copy(driver, &backup)
+                .with_context(|| format!("unable to back up {}", driver.display()))?;
+            // and the receipt describing it, so a rollback restores both
+            let _ = fs::copy(receipt::path_for(driver), receipt::path_for(&backup));
+            Some(backup)
+        } else {
+            None
+        };
+
+        let journal = Journal {
+            path: sibling(driver, "journal"),
+            entry: Entry {
+                stage: Stage::BackupTaken,
+                backup,
+                temp: sibling(driver, "new"),
+            },
+        };
+        journal.save()?;
+        Ok(journal)
+    }
+
+    /// Where the new driver is written before it replaces the old one.
+    pub fn temp(&self) -> &Path {
+        &self.entry.temp
+    }
+
+    /// Records that the new driver is fully written and verified.
+    pub fn written(&mut self) -> Result<()> {
+        self.entry.stage = Stage::TempWritten;
+        self.save()
+    }
+
+    /// Moves the new driver into place and returns where it is.
+    ///
+    /// A running driver that windows won't let us replace is stopped with
+    /// `stop_running`; otherwise the new driver is put beside it, to be swapped in by
+    /// [`swap_pending`] on a later run, and that path is returned.
+    pub fn replace(&mut self, driver: &Path, stop_running: bool) -> Result<PathBuf> {
+        let what = driver.display().to_string();
+        let rename = || retry::when_locked(&what, || fs::rename(&self.entry.temp, driver));
+        let e = match rename() {
+            Ok(()) => return Ok(driver.to_path_buf()),
+            Err(e) => e,
+        };
+        let pids = if retry::is_transient_lock(&e) {
+            running::processes(driver)
+        } else {
+            Vec::new()
+        };
+        if pids.is_empty() {
+            return Err(e).with_context(|| format!("unable to replace {}", what));
+        }
+
+        if stop_running {
+            output::progress(&format!(
+                "stopping pid {} running {} to replace it",
+                running::list(&pids),
+                what
+            ));
+            running::stop(&pids)?;
+            rename().with_context(|| format!("unable to replace {}", what))?;
+            return Ok(driver.to_path_buf());
+        }
+
+        output::forbid(&format!(
+            "{} is running as pid {} and can't be replaced without --stop-running",
+            what,
+            running::list(&pids)
+        ))?;
+        let pending = sibling(driver, "pending");
+        fs::rename(&self.entry.temp, &pending)
+            .with_context(|| format!("unable to write {}", pending.display()))?;
+        // the old driver stays where it is, so there is nothing to restore
+        if let Some(backup) = self.entry.backup.take() {
+            let _ = fs::remove_file(&backup);
+            let _ = fs::remove_file(receipt::path_for(&backup));
+        }
+        self.entry.temp = pending.clone();
+        self.entry.stage = Stage::BackupTaken;
+        self.save()?;
+        output::note_warning(
+            "driver-pending",
+            &format!(
+                "{} is running as pid {}, so the new driver was put beside it as {}; \
+                 the next run swaps it in, or pass --stop-running to replace it now",
+                what,
+                running::list(&pids),
+                pending.display()
+            ),
+        );
+        Ok(pending)
+    }
+
+    /// Finishes the install once the new driver is known to work, dropping the backup.
+    pub fn commit(mut self) -> Result<()> {
+        self.entry.stage = Stage::Renamed;
+        self.save()?;
+        if let Some(backup) = &self.entry.backup {
+            let _ = fs::remove_file(backup);
+            let _ = fs::remove_file(receipt::path_for(backup));
+        }
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    /// Abandons the install after a failure, putting the previous driver back.
+    pub fn rollback(self, driver: &Path) -> Result<()> {
+        restore(driver, &self.entry)?;
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::write(&self.path, serde_json::to_vec_pretty(&self.entry)?)
+            .with_context(|| format!("unable to write install journal {}", self.path.display()))
+    }
+}
+
+/// Finishes or rolls back an install of `driver` that a previous run left incomplete.
+pub fn recover(driver: &Path) -> Result<()> {
+    let path = sibling(driver, "journal");
+    let entry: Entry = match fs::read(&path) {
+        Ok(contents) => match serde_json::from_slice(&contents) {
+            Ok(entry) => entry,
+            Err(_) => {
+                fs::remove_file(&path)?;
+                return Ok(());
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if entry.stage == Stage::Renamed {
+        // the new driver was seen to run, only the cleanup was missed
+        output::progress(&format!(
+            "completing interrupted install of {}",
+            driver.display()
+        ));
+        if let Some(backup) = &entry.backup {
+            let _ = fs::remove_file(backup);
+            let _ = fs::remove_file(receipt::path_for(backup));
+        }
+    } else {
+        output::progress(&format!(
+            "rolling back interrupted install of {}",
+            driver.display()
+        ));
+        restore(driver, &entry)?;
+    }
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Moves a driver that [`Journal::replace`] put beside a running `driver` into its
+/// place, once nothing runs it any more.
+pub fn swap_pending(driver: &Path) -> Result<()> {
+    let pending = sibling(driver, "pending");
+    if !pending.is_file() {
+        return Ok(());
+    }
+    match fs::rename(&pending, driver) {
+        Ok(()) => {
+            let _ = fs::rename(receipt::path_for(&pending), receipt::path_for(driver));
+            output::progress(&format!(
+                "swapped in the driver installed while {} was running",
+                driver.display()
+            ));
+            Ok(())
+        }
+        Err(e) if retry::is_transient_lock(&e) => {
+            let pids = running::processes(driver);
+            output::progress(&format!(
+                "{} is still running{}, leaving the new driver at {}",
+                driver.display(),
+                if pids.is_empty() {
+                    String::new()
+                } else {
+                    format!(" as pid {}", running::list(&pids))
+                },
+                pending.display()
+            ));
+            Ok(())
+        }
+        Err(e) => Err(e).with_context(|| format!("unable to swap in {}", pending.display())),
+    }
+}
+
+fn restore(driver: &Path, entry: &Entry) -> Result<()> {
+    let _ = fs::remove_file(&entry.temp);
+    match &entry.backup {
+        Some(backup) if backup.is_file() => {
+            fs::rename(backup, driver)
+                .with_context(|| format!("unable to restore {}", driver.display()))?;
+            let _ = fs::rename(receipt::path_for(backup), receipt::path_for(driver));
+        }
+        Some(_) => {}
+        // there was no driver before, and whatever was moved into place never ran
+        None if entry.stage == Stage::TempWritten => {
+            let _ = fs::remove_file(driver);
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
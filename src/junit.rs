@@ -0,0 +1,83 @@
+use std::{fmt::Write as _, path::Path, time::Duration};
+
+use anyhow::Result;
+
+/// The result of a single check, as recorded in a JUnit report.
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+#[derive(Debug)]
+pub struct TestCase {
+    pub name: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+/// Writes `cases` as a single JUnit `<testsuite>` to `path`.
+pub fn write_report(path: &Path, suite: &str, cases: &[TestCase]) -> Result<()> {
+    let failures = cases
+        .iter()
+        .filter(|c| matches!(c.outcome, Outcome::Failed(_)))
+        .count();
+    let skipped = cases
+        .iter()
+        .filter(|c| matches!(c.outcome, Outcome::Skipped(_)))
+        .count();
+    let time: f64 = cases.iter().map(|c| c.duration.as_secs_f64()).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">",
+        escape(suite),
+        cases.len(),
+        failures,
+        skipped,
+        time
+    )?;
+    for case in cases {
+        write!(
+            xml,
+            "  <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            escape(suite),
+            escape(&case.name),
+            case.duration.as_secs_f64()
+        )?;
+        match &case.outcome {
+            Outcome::Passed => xml.push_str("/>\n"),
+            Outcome::Failed(message) => writeln!(
+                xml,
+                ">\n    <failure message=\"{}\"/>\n  </testcase>",
+                escape(message)
+            )?,
+            Outcome::Skipped(message) => writeln!(
+                xml,
+                ">\n    <skipped message=\"{}\"/>\n  </testcase>",
+                escape(message)
+            )?,
+        }
+    }
+    xml.push_str("</testsuite>\n");
+
+    std::fs::write(path, xml)?;
+    Ok(())
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,2060 @@
+//! Finds, downloads and verifies the msedgedriver matching the installed WebView2
+//! runtime, as the `msedgedriver-tool` binary does.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{exit, Command},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, bail, Result};
+use quick_xml::de::from_str;
+use serde::Deserialize;
+
+use crate::{
+    attest::Provenance, browser::Browser, compat::SkewPolicy, download::download_driver_to_stdout,
+    fallback::Fallback, journal::Journal, receipt::Receipt, scope::Scope, shell::Shell,
+};
+
+mod arch;
+mod archive;
+mod attest;
+mod audit;
+mod browser;
+mod cache;
+mod channel;
+mod check;
+mod checksums;
+mod chrome;
+mod chunked;
+mod cli;
+mod compat;
+mod completions;
+mod config;
+mod deploy;
+mod diagnose;
+mod doctor;
+mod download;
+mod driver;
+mod elevate;
+mod emit;
+mod ensure;
+mod error;
+mod events;
+mod explain;
+mod fallback;
+mod filesystem;
+mod gecko;
+mod http;
+mod http_cache;
+mod iedriver;
+mod info;
+mod journal;
+mod junit;
+mod list_remote;
+mod lock;
+mod lockfile;
+mod logfile;
+mod mapping;
+mod metrics;
+mod mirror;
+mod output;
+mod panic;
+mod parallel;
+mod paths;
+mod policy;
+mod pool;
+mod prefetch;
+mod project;
+mod protected;
+mod quarantine;
+mod receipt;
+mod registry;
+mod report;
+mod resolve;
+mod resume;
+mod retry;
+mod run;
+mod running;
+mod runtime;
+mod sandbox;
+mod scope;
+mod script;
+mod self_update;
+mod shell;
+mod signature;
+mod smoke;
+mod source;
+mod status;
+mod sync;
+mod tar;
+mod timestamp;
+mod tls;
+mod trace;
+mod uninstall;
+mod update_check;
+mod watch;
+mod wine;
+
+const MANIFEST_URL: &str = "https://msedgedriver.azureedge.net";
+
+#[derive(Debug, Default, Deserialize)]
+struct EnumerationResults {
+    #[serde(rename = "Blobs", default)]
+    blobs: Blobs,
+}
+
+#[derive(Debug, Default, Deserialize)]
+
+struct Blobs {
+    #[serde(rename = "Blob", default)]
+    blobs: Vec<Blob>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+
+struct Blob {
+    #[serde(rename = "Name", default)]
+    name: String,
+    #[serde(rename = "Url", default)]
+    url: String,
+    #[serde(rename = "Properties", default)]
+    properties: BlobProperties,
+}
+
+/// What the listing says about an archive besides where it is.
+#[derive(Debug, Default, Deserialize)]
+
+struct BlobProperties {
+    /// When it was published, e.g. `Tue, 09 Jan 2024 22:05:35 GMT`.
+    #[serde(rename = "Last-Modified")]
+    last_modified: Option<String>,
+    #[serde(rename = "Content-Length")]
+    content_length: Option<u64>,
+}
+
+/// Where drivers were published before the current listing, newest layout first.
+const LEGACY_LAYOUTS: &[&str] = &[
+    "https://msedgewebdriverstorage.blob.core.windows.net/edgewebdriver/{version}/edgedriver_{platform}.zip",
+    "https://msedgecdn.azureedge.net/webdriver/{version}/edgedriver_{platform}.zip",
+];
+
+/// The platform whose drivers this build runs natively.
+const PLATFORM: &str = if cfg!(all(target_os = "linux", target_arch = "aarch64")) {
+    "linux_arm64"
+} else if cfg!(target_os = "linux") {
+    "linux64"
+} else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+    "mac64_m1"
+} else if cfg!(target_os = "macos") {
+    "mac64"
+} else {
+    "win64"
+};
+
+/// Every platform the driver is published for.
+const ALL_PLATFORMS: &[&str] = &[
+    "win64",
+    "win32",
+    "arm64",
+    "mac64",
+    "mac64_m1",
+    "linux64",
+    "linux_arm64",
+];
+
+const DRIVER_FILENAME: &str = "msedgedriver.exe";
+
+const MANIFEST_FILENAME: &str = "msedgedriver-manifest.xml";
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+pub use browser::Installation;
+pub use channel::Channel;
+pub use download::{DownloadOptions, ExtractLimits, Hashes};
+pub use ensure::{ensure_driver, EnsureOptions};
+#[cfg(feature = "async")]
+pub use ensure::{ensure_driver_async, EnsureFuture};
+pub use error::Error;
+pub use events::{set_progress_handler, Event, ProgressHandler};
+pub use filesystem::{set_fs, Disk, Fs};
+pub use http::{set_http_client, HttpClient, MemoryHttp, Request, Response, Ureq};
+pub use registry::{set_registry, MemoryRegistry, RegistryReader, SystemRegistry};
+pub use report::{set_reporter, Report, Reporter};
+pub use source::{
+    Chain, FileMetadata, Found, InstallDirs, LatestStable, Pinned, Registry, VersionSource,
+};
+
+/// The platform this build of the tool targets, e.g. `win64`; see [`host_platform`]
+/// for the one drivers are installed for when none is given.
+pub const HOST_PLATFORM: &str = PLATFORM;
+
+/// The platform drivers are installed for when none is given: the machine's native
+/// one, so an x64 build emulated on windows on arm installs the arm64 driver.
+pub fn host_platform() -> &'static str {
+    static NATIVE: OnceLock<&'static str> = OnceLock::new();
+    NATIVE.get_or_init(|| {
+        let native = arch::native_platform();
+        if native != PLATFORM {
+            explain::step(format!(
+                "this {} build runs on a native {} machine, preferring {} drivers",
+                PLATFORM, native, native
+            ));
+        }
+        native
+    })
+}
+
+/// The version of the WebView2 runtime installed on this machine, if any.
+pub fn detect_webview2_version() -> Result<Option<String>, Error> {
+    Ok(webview2_version()?)
+}
+
+/// The version of the installed Edge browser `channel`, or of the first installed
+/// of stable, beta, dev and canary.
+pub fn detect_edge_version(channel: Option<Channel>) -> Result<Option<String>, Error> {
+    Ok(browser::edge_version(channel)?.map(|(version, _)| version))
+}
+
+/// Every installation of the WebView2 runtime and of each Edge channel, machine-wide
+/// and per user, in the order the tool prefers them: the runtime before Edge, then
+/// stable, beta, dev and canary, machine-wide before per user. Callers with their
+/// own preference can pick from the list instead.
+pub fn detect_all() -> Result<Vec<Installation>, Error> {
+    Ok(browser::installations(None)?)
+}
+
+/// The fixed version WebView2 runtime an app ships in `dir`, which no registry
+/// key knows of.
+pub fn detect_fixed_runtime(dir: &Path) -> Result<Installation, Error> {
+    let (version, dir) = browser::fixed_runtime_in(dir.to_path_buf())?;
+    Ok(Installation::fixed_runtime(version, &dir))
+}
+
+/// The archive URL of the `platform` driver for `version`, if one is published.
+pub fn driver_url(version: &str, platform: &str) -> Result<Option<String>, Error> {
+    check_platform(platform)?;
+    Ok(find_blob(&fetch_manifest()?, version, platform)?.map(|blob| blob.url))
+}
+
+/// Downloads the `platform` driver for exactly `version`, e.g. `120.0.2210.91`, and
+/// extracts it to `dest`; nothing is detected.
+pub fn download_driver(version: &str, platform: &str, dest: &Path) -> Result<Hashes, Error> {
+    download_driver_with(version, platform, dest, &DownloadOptions::default())
+}
+
+/// Like [`download_driver`], with a sha256 pin or extraction limits.
+pub fn download_driver_with(
+    version: &str,
+    platform: &str,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> Result<Hashes, Error> {
+    check_version(version)?;
+    let url = driver_url(version, platform)?.ok_or_else(|| Error::VersionMissing {
+        version: version.into(),
+        platform: platform.into(),
+    })?;
+    Ok(download::download_driver(
+        &url,
+        driver_filename(platform),
+        dest,
+        options,
+    )?)
+}
+
+/// The version an existing driver at `path` reports.
+pub fn installed_driver_version(path: &Path) -> Result<String, Error> {
+    Ok(driver_version(path)?)
+}
+
+fn check_platform(platform: &str) -> Result<(), Error> {
+    if !ALL_PLATFORMS.contains(&platform) {
+        return Err(Error::UnsupportedPlatform {
+            platform: platform.into(),
+        });
+    }
+    Ok(())
+}
+
+/// Runs the command line tool with the process arguments.
+#[doc(hidden)]
+pub fn run() -> Result<()> {
+    panic::install_hook();
+    let started = std::time::Instant::now();
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // only what was typed is held to the command's flags, config applies to them all
+    let given = args.clone();
+    paths::set_portable(take_flag(&mut args, "--portable"));
+    let config = config::apply(&mut args)?;
+    paths::set_shared_cache(take_flag(&mut args, "--shared-cache"));
+    if let Some(dir) = flag_value(&args, "--cache-dir") {
+        paths::set_cache_dir(PathBuf::from(dir));
+    } else if let Ok(Some(scope)) = Scope::from_args(&args) {
+        // a machine install shares its cache with every user, a portable one keeps its own
+        if paths::portable_root().is_none() {
+            if let Some(dir) = scope.cache_dir() {
+                paths::set_cache_dir(dir);
+            }
+        }
+    }
+    status::configure(&args)?;
+    let verbosity = if take_flag(&mut args, "-vv") {
+        output::Verbosity::Trace
+    } else if take_flag(&mut args, "-v") | take_flag(&mut args, "--verbose") {
+        output::Verbosity::Verbose
+    } else if take_flag(&mut args, "-q") | take_flag(&mut args, "--quiet") {
+        output::Verbosity::Quiet
+    } else {
+        output::Verbosity::Normal
+    };
+    output::set_verbosity(verbosity);
+    if take_flag(&mut args, "--explain") || verbosity >= output::Verbosity::Verbose {
+        explain::enable();
+    }
+    for setting in config {
+        explain::step(format!("using {}", setting));
+    }
+    if take_flag(&mut args, "--registry-powershell") {
+        registry::use_powershell();
+    }
+    let ci = take_flag(&mut args, "--ci");
+    let allow_warnings = take_flag(&mut args, "--allow-warnings");
+    if take_flag(&mut args, "--deny-warnings") {
+        if allow_warnings {
+            bail!("--deny-warnings and --allow-warnings contradict each other, pass one");
+        }
+        output::set_deny_warnings();
+    }
+    if take_flag(&mut args, "--teamcity") {
+        output::set_mode(output::Mode::TeamCity);
+    }
+    // --ci keeps stdout to one JSON result unless TeamCity is reading it
+    if take_flag(&mut args, "--json") || ci {
+        output::set_mode(output::Mode::Json);
+    }
+    if ci {
+        output::set_ci(allow_warnings);
+        if has_flag(&args, "--interactive") {
+            bail!("--interactive asks on a terminal, which --ci forbids; pick with --install-index or --channel instead");
+        }
+        if !has_flag(&args, "--frozen") && lockfile::Lockfile::exists(&args) {
+            explain::step("--ci installs exactly what the lockfile names, as with --frozen");
+            args.push("--frozen".into());
+        }
+    } else if allow_warnings {
+        bail!("--allow-warnings only applies with --ci");
+    }
+    if has_flag(&args, "--stdout") {
+        output::use_stderr();
+    }
+
+    // flags alone, e.g. `msedgedriver-tool --version 120.0.2210.91`, mean install
+    let command = if args.first().is_none_or(|arg| arg.starts_with('-')) {
+        None
+    } else {
+        Some(args.remove(0))
+    };
+    if cli::wants_help(&args) {
+        println!("{}", cli::help(command.as_deref()));
+        return Ok(());
+    }
+    if cli::wants_version(&args) {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    if let Some(other) = command.as_deref().filter(|c| !cli::is_command(c)) {
+        eprintln!("unknown command: {}\n\n{}", other, cli::help(None));
+        exit(error::EXIT_USAGE);
+    }
+    if let Some(flag) = cli::unknown_flag(command.as_deref(), &given) {
+        let name = command.as_deref().unwrap_or("install");
+        eprintln!(
+            "unknown flag for {}: {}\n\n{}",
+            name,
+            flag,
+            cli::help(Some(name))
+        );
+        exit(error::EXIT_USAGE);
+    }
+
+    if let Some(file) = flag_value(&args, "--version-from") {
+        if flag_value(&args, "--version").is_some() {
+            bail!("--version-from and --version both pin the driver, pass only one");
+        }
+        let version = project::version_from(Path::new(&file))?;
+        explain::step(format!("{} pins driver {}", file, version));
+        args.extend(["--version".into(), version]);
+    }
+
+    if take_flag(&mut args, "--no-detect") {
+        let name = command.as_deref().unwrap_or("install");
+        if !sandbox::COMMANDS.contains(&name) {
+            bail!(
+                "--no-detect only applies to {}",
+                sandbox::COMMANDS.join(", ")
+            );
+        }
+        if flag_value(&args, "--version").is_none() {
+            bail!("--no-detect can't ask the browser for its version, pass --version or --version-from");
+        }
+        sandbox::enable();
+    }
+
+    policy::load()?;
+    checksums::configure(flag_value(&args, "--checksums"));
+    configure_base_url(&args)?;
+    http::configure(&args)?;
+    resume::configure(&args)?;
+    source::configure(&args)?;
+    mapping::configure(&args)?;
+    mirror::configure(&args);
+    trace::configure(&args);
+    if let Some(url) = flag_value(&args, "--report-endpoint") {
+        report::set_reporter(report::Endpoint(url));
+    }
+    if http::dry_run() && !matches!(command.as_deref(), None | Some("install")) {
+        bail!("--dry-run only applies to install");
+    }
+    // detect and --reextract promise not to touch the network
+    if command.as_deref() != Some("detect")
+        && !has_flag(&args, "--reextract")
+        && !ci
+        && !http::offline()
+        && !http::dry_run()
+    {
+        update_check::notify();
+    }
+
+    let metrics_textfile = flag_value(&args, "--metrics-textfile");
+    panic::set_phase("running command");
+    let mut span = trace::span("command");
+    span.attr("command", command.as_deref().unwrap_or("install"));
+    let result = match command.as_deref() {
+        None | Some("install") => install_command(&args),
+        Some("detect") => browser::detect(&args),
+        Some("run") => run::run(&args),
+        Some("cache") => cache::command(&args),
+        Some("clean") => cache::clean(&args),
+        Some("list-remote") => list_remote::list_remote(&args),
+        Some("cache-key") => cache_key(),
+        Some("env") => env(&args),
+        Some("check") => check::check(&args),
+        Some("selftest") => check::selftest(&args),
+        Some("deploy") => deploy::deploy(&args),
+        Some("sync") => sync::sync(&args),
+        Some("self-update") => self_update::self_update(&args),
+        Some("diagnose") => diagnose::diagnose(&args),
+        Some("doctor") => doctor::doctor(&args),
+        Some("compat") => compat::sdk_compat(&args),
+        Some("prefetch") => prefetch::prefetch(&args),
+        Some("verify") => check::verify(&args),
+        Some("verify-path") => check::verify_path(&args),
+        Some("uninstall") => uninstall::uninstall(&args),
+        Some("watch") => watch::watch(&args),
+        Some("register-task") => watch::register_task(&args),
+        Some("info") => info::info(&args),
+        Some("status") => status::status(&args),
+        Some("resolve") => resolve::resolve(&args),
+        Some("generate-script") => script::generate_script(&args),
+        Some("completions") => completions::completions(&args),
+        Some("manpage") => completions::manpage(&args),
+        Some(other) => unreachable!("{} is not in the command table", other),
+    };
+
+    let result = span.record(result.and_then(|()| output::deny_warnings()));
+    drop(span);
+    trace::export();
+
+    if let Err(e) = &result {
+        output::problem(&e.to_string());
+        if !http::dry_run() {
+            diagnose::save_last_error(e);
+        }
+    }
+    output::finish(result.is_ok());
+    if let Some(path) = metrics_textfile.or_else(|| std::env::var("MSEDGEDRIVER_TOOL_METRICS").ok())
+    {
+        if let Err(e) = metrics::write_textfile(Path::new(&path), result.is_ok()) {
+            eprintln!("warning: {:#}", e);
+        }
+    }
+
+    report::emit(
+        command.as_deref().unwrap_or("install"),
+        started.elapsed(),
+        result.as_ref().err().map(error::exit_code),
+    );
+
+    // each class of failure exits with its own code, for scripts to tell apart
+    if let Err(e) = &result {
+        eprintln!("Error: {:?}", e);
+        exit(error::exit_code(e));
+    }
+    result
+}
+
+/// The directory a `scope` installs into, or the current directory without one.
+fn install_dir(scope: Option<Scope>) -> Result<PathBuf> {
+    match scope {
+        Some(scope) => scope.dir(),
+        None => Ok(std::env::current_dir()?),
+    }
+}
+
+/// Where the driver is installed: `--output`, a file or a directory, or else the
+/// `--scope` directory or the current one, named `--filename` if given.
+fn driver_location(args: &[String]) -> Result<PathBuf> {
+    driver_location_named(args, driver_filename(target_platform(args)?))
+}
+
+/// Like [`driver_location`], for a driver named `default_name` unless `--filename`
+/// says otherwise.
+fn driver_location_named(args: &[String], default_name: &str) -> Result<PathBuf> {
+    let scope = Scope::from_args(args)?;
+    let filename = flag_value(args, "--filename");
+    if let Some(name) = &filename {
+        if Path::new(name).file_name() != Some(std::ffi::OsStr::new(name)) {
+            bail!(
+                "--filename {} must be a file name, use --output for a directory",
+                name
+            );
+        }
+    }
+    let name = match &filename {
+        Some(name) => name.clone(),
+        None => default_name.into(),
+    };
+
+    match flag_value(args, "--output") {
+        Some(_) if scope.is_some() => {
+            bail!("--output and --scope both choose where the driver goes, pass only one")
+        }
+        Some(output) => {
+            let path = PathBuf::from(&output);
+            // with --filename, or naming a directory, the output is where the driver goes
+            if filename.is_some() || output.ends_with(['/', '\\']) || path.is_dir() {
+                Ok(path.join(name))
+            } else {
+                Ok(path)
+            }
+        }
+        None => Ok(install_dir(scope)?.join(name)),
+    }
+}
+
+/// Prints a key suitable for CI cache actions, changing whenever the driver would.
+fn cache_key() -> Result<()> {
+    let webview2_version = detected_version()?;
+    println!(
+        "msedgedriver-{}-{}",
+        host_platform(),
+        webview2_version.trim()
+    );
+    Ok(())
+}
+
+/// Prints statements that wire the driver into a shell session when `eval`ed.
+fn env(args: &[String]) -> Result<()> {
+    let shell = match flag_value(args, "--shell") {
+        Some(shell) => shell.parse()?,
+        None => Shell::default_for_host(),
+    };
+
+    let skew = SkewPolicy::from_args(args)?;
+
+    let webview2_version = detected_version()?;
+    let driver = std::path::absolute(driver_location(args)?)?;
+    if let Some(installed) = installed_version(&driver, host_platform()) {
+        skew.enforce(&installed, &webview2_version)?;
+    }
+
+    println!(
+        "{}",
+        shell.export("MSEDGEDRIVER_PATH", &driver.to_string_lossy())
+    );
+    println!(
+        "{}",
+        shell.export("MSEDGEDRIVER_VERSION", webview2_version.trim())
+    );
+    println!(
+        "{}",
+        shell.export("WEBVIEW2_VERSION", webview2_version.trim())
+    );
+    let dir = driver.parent().unwrap_or(&driver);
+    println!("{}", shell.prepend_path(&dir.to_string_lossy()));
+
+    Ok(())
+}
+
+/// Removes a boolean flag from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != name);
+    args.len() != len
+}
+
+/// Whether a boolean flag is present in `args`.
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+/// Finds the values of every `--name value` or `--name=value` occurrence.
+fn flag_values(args: &[String], name: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == name {
+            values.extend(iter.next().cloned());
+        } else if let Some(value) = arg
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            values.push(value.to_string());
+        }
+    }
+
+    values
+}
+
+/// `args` without any `--name value` or `--name=value` occurrence.
+fn without_flag(args: &[String], name: &str) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == name {
+            iter.next();
+        } else if !arg
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with('='))
+        {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Parses a byte size such as `1048576`, `512KiB`, `256MiB` or `2GiB`.
+fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => bail!("unknown size unit in {:?}", s),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid size {:?}", s))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("size {:?} is too large", s))
+}
+
+/// Finds the value of a `--name value` or `--name=value` flag.
+fn flag_value(args: &[String], name: &str) -> Option<String> {
+    flag_values(args, name).into_iter().next()
+}
+
+/// The platform to install for: `--platform`, else the host's.
+fn target_platform(args: &[String]) -> Result<&'static str> {
+    match (flag_value(args, "--platform"), flag_value(args, "--arch")) {
+        (Some(_), Some(_)) => {
+            bail!("--platform and --arch both choose the driver's platform, pass only one")
+        }
+        (None, Some(arch)) => arch::platform_for(&arch),
+        (Some(platform), None) => ALL_PLATFORMS
+            .iter()
+            .find(|p| p.eq_ignore_ascii_case(platform.trim()))
+            .copied()
+            .ok_or_else(|| Error::UnsupportedPlatform { platform }.into()),
+        (None, None) => Ok(host_platform()),
+    }
+}
+
+/// Whether `platform` is this machine's OS, so an installed browser can still be
+/// detected for it, if perhaps of another architecture.
+fn same_os(platform: &str) -> bool {
+    let os = |platform: &str| match platform {
+        "mac64" | "mac64_m1" => "mac",
+        "linux64" | "linux_arm64" => "linux",
+        _ => "windows",
+    };
+    os(platform) == os(host_platform())
+}
+
+/// Rebuilds the driver at its location from the archive the cache kept when it was
+/// installed, as its receipt names, for `--reextract`: nothing is downloaded, and
+/// the driver must hash as the receipt says.
+fn reextract(
+    args: &[String],
+    platform: &str,
+    options: DownloadOptions,
+    zone: quarantine::Zone,
+) -> Result<PathBuf> {
+    if flag_value(args, "--version").is_some()
+        || has_flag(args, "--stdout")
+        || flag_value(args, "--driver-pool").is_some()
+    {
+        bail!("--reextract rebuilds the driver its receipt describes, so it can't be combined with --version, --stdout or --driver-pool");
+    }
+    let driver = driver_location(args)?;
+    let receipt = Receipt::read(&driver)?.ok_or_else(|| {
+        anyhow!(
+            "--reextract needs the receipt {} that installing leaves beside the driver",
+            receipt::path_for(&driver).display()
+        )
+    })?;
+    if receipt.platform != platform {
+        bail!(
+            "{} describes a {} driver, not {}",
+            receipt::path_for(&driver).display(),
+            receipt.platform,
+            platform
+        );
+    }
+    output::progress(&format!(
+        "rebuilding {} from msedgedriver {}",
+        driver.display(),
+        receipt.version
+    ));
+    let options = DownloadOptions {
+        sha256: Some(receipt.archive_sha256.clone()),
+        ..options
+    };
+
+    let mut journal = Journal::begin(&driver)?;
+    let replaced = (|| {
+        let hashes = download::extract_kept(
+            &receipt.source,
+            driver_filename(platform),
+            journal.temp(),
+            &options,
+        )?;
+        if !hashes.driver.eq_ignore_ascii_case(&receipt.driver_sha256) {
+            bail!(
+                "the re-extracted driver hashes to {}, but its receipt says {}",
+                hashes.driver,
+                receipt.driver_sha256
+            );
+        }
+        quarantine::apply_zone(journal.temp(), zone, &receipt.source)?;
+        journal.written()?;
+        let installed = journal.replace(&driver, has_flag(args, "--stop-running"))?;
+        Receipt::new(&receipt.version, platform, &receipt.source, &hashes)
+            .copied_from(&receipt)
+            .write(&installed)?;
+        Ok::<_, anyhow::Error>(installed)
+    })();
+    let installed = match replaced {
+        Ok(installed) => {
+            journal.commit()?;
+            installed
+        }
+        Err(e) => {
+            if let Err(rollback) = journal.rollback(&driver) {
+                eprintln!(
+                    "warning: unable to restore the previous driver: {}",
+                    rollback
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    let path = std::path::absolute(&installed)?;
+    metrics::set_installed(&receipt.version, &path);
+    output::parameter("msedgedriver.path", &path.to_string_lossy());
+    output::parameter("msedgedriver.version", &receipt.version);
+    output::parameter("msedgedriver.platform", platform);
+    output::parameter("msedgedriver.sha256", &receipt.driver_sha256);
+    if output::quiet() {
+        println!("{}", path.display());
+    }
+    Ok(path)
+}
+
+/// The newest driver the `--channel` edge channel published, for when no edge of
+/// that channel is installed, e.g. on a lane testing dev or canary headless;
+/// otherwise `missing`, the error choosing an installation failed with.
+fn channel_marker(args: &[String], missing: anyhow::Error) -> Result<(String, Channel)> {
+    let not_installed = matches!(
+        missing.downcast_ref::<Error>(),
+        Some(Error::NotInstalled { .. })
+    );
+    let channel = flag_value(args, "--channel")
+        .filter(|_| not_installed && !http::offline())
+        .and_then(|c| c.parse::<Channel>().ok());
+    let Some(channel) = channel else {
+        return Err(missing);
+    };
+    let version = channel.latest_version()?;
+    output::progress(&format!(
+        "edge {} isn't installed, matching its newest driver {}",
+        channel.as_str(),
+        version
+    ));
+    output::parameter("edge.channel", channel.as_str());
+    Ok((version, channel))
+}
+
+/// Warns when `--arch` chose a driver of another architecture than the detected
+/// browser's, which is allowed but rarely meant.
+fn check_arch(args: &[String], platform: &str) -> Result<()> {
+    let Some(requested) = flag_value(args, "--arch") else {
+        return Ok(());
+    };
+    let found = browser::all(args)?;
+    let Some(installation) = browser::preferred(&found, Browser::from_args(args)?) else {
+        return Ok(());
+    };
+    let driver = arch::of_platform(platform);
+    match installation.arch {
+        Some(detected) if !matches!(detected, "unknown" | "universal") && detected != driver => {
+            output::warn(
+                "arch-mismatch",
+                &format!(
+                    "--arch {} installs the {} driver, but the detected {} is {} ({})",
+                    requested,
+                    platform,
+                    installation.browser,
+                    installation.bitness(),
+                    detected
+                ),
+            )?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The `install` command: installs, then runs the smoke test and writes the files
+/// asked for, all checked before anything is downloaded.
+fn install_command(args: &[String]) -> Result<()> {
+    let smoke = smoke::requested(args)?;
+    let emits = emit::requested(args)?;
+    let prune = cache::Retention::auto(args)?;
+    let installed = install(args)?;
+    if let Some(retention) = prune {
+        retention.prune_after_install();
+    }
+    let Some(driver) = installed else {
+        return Ok(());
+    };
+    status::record(&driver);
+    if let Some(capabilities) = smoke {
+        smoke::test(&driver, capabilities)?;
+    }
+    emit::write(&emits, &driver)
+}
+
+/// Installs the driver, returning where unless it was only streamed to stdout.
+fn install(args: &[String]) -> Result<Option<PathBuf>> {
+    let dry_run = http::dry_run();
+    if dry_run {
+        let drivers = flag_values(args, "--driver");
+        if drivers.len() > 1
+            || flag_values(args, "--platform").len() > 1
+            || drivers
+                .iter()
+                .any(|d| !matches!(d.as_str(), "edge" | "msedgedriver"))
+        {
+            bail!("--dry-run only plans a single msedgedriver install");
+        }
+        if has_flag(args, "--install-runtime") {
+            bail!("--dry-run can't install the webview2 runtime to match, pass --version instead");
+        }
+    }
+    if flag_values(args, "--driver").len() > 1 || flag_values(args, "--platform").len() > 1 {
+        return driver::install_many(args).map(|()| None);
+    }
+    match flag_value(args, "--driver").as_deref() {
+        None | Some("edge" | "msedgedriver") => {}
+        Some("chrome" | "chromedriver") => return driver::install(&chrome::Chrome, args),
+        Some("gecko" | "geckodriver") => return driver::install(&gecko::Gecko, args),
+        Some("iedriver") => return iedriver::install(args).map(|()| None),
+        Some(other) => bail!(
+            "unknown driver {}, expected edge, chrome, gecko or iedriver",
+            other
+        ),
+    }
+
+    let options = DownloadOptions {
+        sha256: download::sha256_pin(args)?,
+        limits: ExtractLimits::from_args(args)?,
+        include: download::include_patterns(args)?,
+        size_hint: None,
+    };
+    let policy = policy::get();
+    if policy.require_sha256 && options.sha256.is_none() && !checksums::configured() {
+        bail!("policy requires the archive to be pinned with --sha256 or a checksum source");
+    }
+    let stop_running = has_flag(args, "--stop-running");
+    let keep_quarantine = has_flag(args, "--keep-quarantine");
+    policy.check_insecure("--keep-quarantine", keep_quarantine)?;
+    let zone = quarantine::Zone::from_args(args)?;
+    let signature_check = signature::Check::from_args(args)?;
+    let require_signature = signature_check == signature::Check::Require;
+    let to_stdout = has_flag(args, "--stdout");
+    let platform = target_platform(args)?;
+    if platform != host_platform() {
+        if !same_os(platform) && flag_value(args, "--version").is_none() {
+            bail!(
+                "--platform {} can't match a browser on this machine, pass --version as well",
+                platform
+            );
+        }
+        if require_signature && driver_filename(platform) != DRIVER_FILENAME {
+            bail!(
+                "only windows drivers are signed, so a signature can't be required for {}",
+                platform
+            );
+        }
+    }
+    if to_stdout && require_signature {
+        bail!("--stdout can't be combined with a required signature check, which needs the driver on disk");
+    }
+    if has_flag(args, "--reextract") {
+        return reextract(args, platform, options, zone).map(Some);
+    }
+    let mut lockfile = lockfile::Lockfile::from_args(args)?;
+    let (options, frozen_version) = match &lockfile {
+        Some(lockfile) => (
+            lockfile.pin("msedgedriver", platform, options)?,
+            lockfile.frozen_version("msedgedriver", platform)?,
+        ),
+        None => (options, None),
+    };
+    let scope = Scope::from_args(args)?;
+    let pool = pool::from_args(args)?;
+    // in a pool, the driver's directory is only known once its version is
+    let mut driver = driver_location(args)?;
+    let dir = match (&pool, driver.parent()) {
+        (Some(pool), _) => pool.clone(),
+        (None, Some(dir)) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        (None, _) => PathBuf::from("."),
+    };
+    let elevate = (has_flag(args, "--elevate") || scope.is_some_and(Scope::elevates_by_default))
+        && !has_flag(args, "--no-elevate");
+    if !to_stdout && !dry_run {
+        elevate::ensure_writable(&dir, elevate)?;
+        if scope == Some(Scope::Machine) {
+            secure_machine_dirs(&dir)?;
+        }
+    }
+    let fallback = Fallback::from_args(args)?;
+    let min_version = flag_value(args, "--min-driver-version");
+    let attest = flag_value(args, "--attest");
+    let started_on = timestamp::now();
+    // the channel of the edge matched, whose driver `--fallback latest` falls back to
+    let mut channel = Channel::Stable;
+    let wanted = match flag_value(args, "--version") {
+        Some(pinned) => {
+            if has_flag(args, "--install-runtime") {
+                bail!("--version skips webview2 detection, so it can't be combined with --install-runtime");
+            }
+            if has_flag(args, "--interactive")
+                || flag_value(args, "--install-index").is_some()
+                || flag_value(args, "--channel").is_some()
+            {
+                bail!("--version skips detection, so there is no installation to choose");
+            }
+            check_version(&pinned)?;
+            explain::step(format!(
+                "--version {} given, skipping webview2 detection",
+                pinned
+            ));
+            output::progress(&format!("requested driver version: {}", pinned));
+            pinned
+        }
+        None => match browser::choose(args) {
+            Err(e) => {
+                let (version, marker) = channel_marker(args, e)?;
+                channel = marker;
+                version
+            }
+            Ok(Some(chosen)) => {
+                if has_flag(args, "--install-runtime") {
+                    bail!("--install-runtime installs webview2 itself, so it can't be combined with choosing an installation");
+                }
+                output::progress(&format!("matching {}", chosen.describe()));
+                output::parameter(&format!("{}.version", chosen.browser), &chosen.version);
+                if let Some(chosen) = chosen.channel {
+                    output::parameter("edge.channel", chosen.as_str());
+                    channel = chosen;
+                }
+                chosen.version
+            }
+            Ok(None) => match Browser::from_args(args)? {
+                Browser::Edge(wanted) => {
+                    if has_flag(args, "--install-runtime") {
+                        bail!("--install-runtime installs webview2, so it can't be combined with --browser edge");
+                    }
+                    panic::set_phase("detecting edge");
+                    let (detected, found) =
+                        browser::edge_version(wanted)?.ok_or_else(|| Error::NotInstalled {
+                            what: match wanted {
+                                Some(wanted) => format!("edge {}", wanted.as_str()),
+                                None => "an installed edge browser".into(),
+                            },
+                        })?;
+                    output::progress(&format!("edge {} version: {}", found.as_str(), detected));
+                    output::parameter("edge.version", &detected);
+                    output::parameter("edge.channel", found.as_str());
+                    channel = found;
+                    detected
+                }
+                Browser::WebView2 => {
+                    let fixed = browser::fixed_runtime(args)?;
+                    if fixed.is_some() && has_flag(args, "--install-runtime") {
+                        bail!("--install-runtime installs the evergreen webview2, so it can't be combined with a fixed version runtime");
+                    }
+                    let detected = if let Some((version, dir)) = fixed {
+                        output::progress(&format!(
+                            "using fixed version webview2 runtime in {}",
+                            dir.display()
+                        ));
+                        version
+                    } else if has_flag(args, "--install-runtime") {
+                        ensure_runtime(elevate)?
+                    } else {
+                        detected_version()?
+                    };
+                    output::progress(&format!("webview2 version: {}", detected));
+                    output::parameter("webview2.version", detected.trim());
+                    detected
+                }
+            },
+        },
+    };
+
+    if flag_value(args, "--version").is_none() {
+        check_arch(args, platform)?;
+    }
+    explain::step(if platform == host_platform() {
+        format!("using platform {}, this host's", platform)
+    } else if has_flag(args, "--arch") {
+        format!("using platform {} from --arch", platform)
+    } else {
+        format!("using platform {} from --platform", platform)
+    });
+
+    let pinned = flag_value(args, "--version").is_some();
+    // a pinned version is the driver's own, only a browser's may need mapping
+    let wanted = match mapping::driver_for(&wanted) {
+        mapped if pinned || mapped == wanted.trim() => wanted,
+        mapped => {
+            output::progress(&format!(
+                "browser {} is matched by driver {}, from the version map",
+                wanted.trim(),
+                mapped
+            ));
+            mapped
+        }
+    };
+    if let (Some(lockfile), true) = (&lockfile, pinned) {
+        lockfile.check("msedgedriver", platform, &wanted)?;
+    }
+    if let Some(pool) = &pool {
+        driver = pool::driver_path(pool, &wanted, platform);
+    }
+    // concurrent installs of the same driver wait here, then find it up to date
+    let _lock = match (&pool, to_stdout || dry_run) {
+        (_, true) => None,
+        (Some(pool), false) => Some(lock::acquire(&pool.join(pool::CURRENT_LINK))?),
+        (None, false) => Some(lock::acquire(&driver)?),
+    };
+    if !to_stdout && !dry_run {
+        journal::recover(&driver)?;
+        journal::swap_pending(&driver)?;
+    }
+    if !to_stdout && !has_flag(args, "--force") {
+        if let Some(present) = installed_version(&driver, platform) {
+            let compatible = if let Some(locked) = &frozen_version {
+                present == *locked
+            } else if pinned {
+                present == wanted.trim()
+            } else {
+                SkewPolicy::from_args(args)?
+                    .mismatch(&present, &wanted)
+                    .is_none()
+            };
+            let above_floor = min_version
+                .as_deref()
+                .is_none_or(|floor| version_key(&present) >= version_key(floor));
+            if compatible && above_floor {
+                output::progress(&format!(
+                    "{} already reports {}, compatible with {}; pass --force to reinstall",
+                    driver.display(),
+                    present,
+                    wanted.trim()
+                ));
+                let path = std::path::absolute(&driver)?;
+                metrics::set_installed(&present, &path);
+                output::parameter("msedgedriver.path", &path.to_string_lossy());
+                output::parameter("msedgedriver.version", &present);
+                output::parameter("msedgedriver.platform", platform);
+                output::flag("msedgedriver.up_to_date", true);
+                if output::quiet() {
+                    println!("{}", path.display());
+                }
+                if let Some(pool) = pool.as_ref().filter(|_| !dry_run) {
+                    pool::set_current(pool, &present, platform)?;
+                }
+                if let (Some(lockfile), false) = (&mut lockfile, dry_run) {
+                    // a driver installed before the lockfile has no receipt to lock
+                    if let Some(receipt) = Receipt::read(&driver)? {
+                        lockfile
+                            .record(lockfile::Locked::from_receipt("msedgedriver", &receipt))?;
+                    }
+                }
+                return Ok((!dry_run).then_some(path));
+            }
+        }
+    }
+
+    let clear_cache = has_flag(args, "--clear-cache");
+    if clear_cache && !dry_run {
+        cache::clear()?;
+    }
+    let use_cache = !has_flag(args, "--no-cache") && !to_stdout;
+    // the cache keeps only drivers, so other files mean extracting the archive again,
+    // and a dry run plans as if the cache had been cleared
+    let lookup = use_cache && options.include.is_empty() && !(dry_run && clear_cache);
+    let mut cached = if lookup {
+        cache::lookup(
+            &wanted,
+            platform,
+            driver_filename(platform),
+            options.sha256.as_deref(),
+        )
+    } else {
+        None
+    };
+    // on a shared cache one agent downloads while the others wait to use its copy
+    let _claim = match cached {
+        None if lookup && !dry_run && !http::offline() => {
+            let claim = cache::claim(&wanted, platform, driver_filename(platform))?;
+            if claim.is_some() {
+                cached = cache::lookup(
+                    &wanted,
+                    platform,
+                    driver_filename(platform),
+                    options.sha256.as_deref(),
+                );
+            }
+            claim
+        }
+        _ => None,
+    };
+
+    let (version, url) = match &cached {
+        Some((path, receipt)) => {
+            output::progress(&format!("using cached driver {}", path.display()));
+            metrics::record_cache_hit();
+            resume::discard_kept(&receipt.source);
+            (receipt.version.clone(), receipt.source.clone())
+        }
+        None if http::offline() => {
+            return Err(Error::Offline {
+                what: format!("msedgedriver {} for {}", wanted.trim(), platform),
+            }
+            .into())
+        }
+        // the listing is fetched with a GET, but the archive's own url answers a HEAD
+        None if dry_run => {
+            let blob = legacy_blob(&wanted, platform)?.ok_or_else(|| {
+                if fallback != Fallback::Exact {
+                    eprintln!(
+                        "note: --dry-run can't read the listing to pick a substitute for {}",
+                        wanted.trim()
+                    );
+                }
+                Error::VersionMissing {
+                    version: wanted.trim().into(),
+                    platform: platform.into(),
+                }
+            })?;
+            let version = wanted.trim().to_string();
+            let url = mirror::select(&blob.url, &blob.name);
+            (version, url)
+        }
+        None => {
+            let manifest = fetch_manifest()?;
+
+            let manifest_path = dir.join(MANIFEST_FILENAME);
+            if !to_stdout {
+                output::progress(&format!(
+                    "writing manifest file to {}",
+                    manifest_path.display()
+                ));
+                std::fs::write(&manifest_path, manifest.as_bytes())?;
+            }
+
+            output::progress(&format!(
+                "searching manifest for {}",
+                blob_name(&wanted, platform)
+            ));
+            panic::set_phase("searching driver listing");
+            let (version, blob) = fallback.resolve(&manifest, &wanted, platform, channel)?;
+            let url = mirror::select(&blob.url, &blob.name);
+            (version, url)
+        }
+    };
+    enforce_floor(&version, min_version.as_deref())?;
+    if let Some(lockfile) = &lockfile {
+        lockfile.check("msedgedriver", platform, &version)?;
+    }
+    // a pool keeps every version side by side, so nothing is overwritten there
+    if !to_stdout && pool.is_none() && !has_flag(args, "--allow-downgrade") {
+        // a driver this machine can't run still has the receipt it was installed with
+        let present = installed_version(&driver, platform).or_else(|| {
+            Receipt::read(&driver)
+                .ok()
+                .flatten()
+                .filter(|receipt| receipt.platform == platform)
+                .map(|receipt| receipt.version)
+        });
+        refuse_downgrade(present.as_deref(), &driver, &version)?;
+    }
+
+    if dry_run {
+        let destination = match &pool {
+            Some(pool) => pool::driver_path(pool, &version, platform),
+            None => driver,
+        };
+        plan(
+            &version,
+            platform,
+            &url,
+            cached.as_ref().map(|(path, _)| path.as_path()),
+            (!to_stdout).then_some(destination.as_path()),
+            &options,
+        )?;
+        return Ok(None);
+    }
+    // a missing archive fails here, before anything is replaced
+    let options = match &cached {
+        Some(_) => options,
+        None => DownloadOptions {
+            size_hint: confirm_published(&url, &version, platform)?,
+            ..options
+        },
+    };
+
+    if to_stdout {
+        download_driver_to_stdout(&url, driver_filename(platform), &options)?;
+        return Ok(None);
+    }
+
+    if let Some(pool) = &pool {
+        driver = pool::driver_path(pool, &version, platform);
+        std::fs::create_dir_all(driver.parent().unwrap_or(pool))?;
+    }
+    let previous_version = installed_version(&driver, platform);
+    let mut journal = Journal::begin(&driver)?;
+    let replaced = (|| {
+        let hashes = match &cached {
+            Some((path, receipt)) => {
+                std::fs::copy(path, journal.temp())?;
+                download::set_permissions(journal.temp(), None)?;
+                Hashes {
+                    archive: receipt.archive_sha256.clone(),
+                    driver: receipt.driver_sha256.clone(),
+                }
+            }
+            None => download::download_driver(
+                &url,
+                driver_filename(platform),
+                journal.temp(),
+                &options,
+            )?,
+        };
+        panic::set_phase("verifying installed driver");
+        // only windows drivers carry authenticode signatures
+        if driver_filename(platform) == DRIVER_FILENAME {
+            verify_signature(journal.temp(), signature_check)?;
+        }
+        quarantine::clear(journal.temp(), keep_quarantine)?;
+        quarantine::apply_zone(journal.temp(), zone, &url)?;
+        journal.written()?;
+        let installed = journal.replace(&driver, stop_running)?;
+        verify_runs(&installed, platform, &version)?;
+        let mut receipt = Receipt::new(&version, platform, &url, &hashes);
+        if let Some((_, cached)) = &cached {
+            receipt = receipt.copied_from(cached);
+        }
+        receipt.write(&installed)?;
+        Ok::<_, anyhow::Error>((hashes, receipt, installed))
+    })();
+    let hashes = match replaced {
+        Ok((hashes, receipt, installed)) => {
+            driver = installed;
+            journal.commit()?;
+            if let Some(pool) = &pool {
+                pool::set_current(pool, &version, platform)?;
+            }
+            if use_cache && cached.is_none() {
+                if let Err(e) = cache::store(&driver, driver_filename(platform), &receipt) {
+                    output::note_warning(
+                        "cache-write-failed",
+                        &format!("unable to cache the driver: {:#}", e),
+                    );
+                }
+            }
+            hashes
+        }
+        Err(e) => {
+            if let Err(rollback) = journal.rollback(&driver) {
+                eprintln!(
+                    "warning: unable to restore the previous driver: {}",
+                    rollback
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(lockfile) = &mut lockfile {
+        lockfile.record(lockfile::Locked::new(
+            "msedgedriver",
+            platform,
+            &version,
+            &url,
+            &hashes,
+        ))?;
+    }
+    let path = std::path::absolute(&driver)?;
+    metrics::set_installed(&version, &path);
+    if let Some(scope) = scope {
+        scope.add_to_path(&dir)?;
+    }
+    output::parameter("msedgedriver.path", &path.to_string_lossy());
+    output::parameter("msedgedriver.version", &version);
+    output::parameter("msedgedriver.archive_sha256", &hashes.archive);
+    output::parameter("msedgedriver.sha256", &hashes.driver);
+    output::parameter("msedgedriver.platform", platform);
+    output::parameter("msedgedriver.source", &url);
+    output::flag("msedgedriver.cached", cached.is_some());
+    output::flag("msedgedriver.up_to_date", false);
+    if output::quiet() {
+        println!("{}", path.display());
+    }
+
+    if let Some(log) = audit::log_path(args) {
+        audit::record(
+            &log,
+            &audit::Event {
+                action: if previous_version.is_some() {
+                    "update"
+                } else {
+                    "install"
+                },
+                path: &path,
+                version: Some(&version),
+                previous_version: previous_version.as_deref(),
+                platform: Some(platform),
+                archive_sha256: Some(&hashes.archive),
+                driver_sha256: Some(&hashes.driver),
+                source: Some(&url),
+            },
+        )?;
+    }
+
+    if let Some(attest) = attest {
+        let builder_id =
+            flag_value(args, "--builder-id").unwrap_or_else(attest::default_builder_id);
+        let provenance = Provenance {
+            driver_name: &driver.file_name().unwrap_or_default().to_string_lossy(),
+            source_url: &url,
+            webview2_version: wanted.trim(),
+            platform,
+            hashes: &hashes,
+            started_on: &started_on,
+            finished_on: &timestamp::now(),
+        };
+        attest::write_statement(Path::new(&attest), &builder_id, &provenance)?;
+        output::progress(&format!("wrote provenance statement to {}", attest));
+    }
+
+    Ok(Some(path))
+}
+
+/// Asks with a HEAD request whether the archive at `url` exists before it is
+/// streamed, returning its size if the server says. A missing archive fails with
+/// the newest driver of the same major suggested instead.
+fn confirm_published(url: &str, version: &str, platform: &str) -> Result<Option<u64>> {
+    let response = http::head(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .call();
+    match response {
+        Ok(response) => {
+            let size = response
+                .header("Content-Length")
+                .and_then(|len| len.parse().ok());
+            explain::step(format!(
+                "{} exists, {} bytes",
+                url,
+                size.map_or_else(|| "unknown".into(), |size: u64| size.to_string())
+            ));
+            Ok(size)
+        }
+        Err(e)
+            if e.downcast_ref::<http::Status>()
+                .is_some_and(|status| matches!(status.code, 404 | 410)) =>
+        {
+            explain::step(format!("{} doesn't exist", url));
+            let major = major(version);
+            match channel::latest_release(major, platform) {
+                Ok(latest) if latest != version.trim() => eprintln!(
+                    "note: the newest {} driver of major {} is {}, pass --version {} or --match major",
+                    platform, major, latest, latest
+                ),
+                Ok(_) => {}
+                Err(e) => explain::step(format!(
+                    "no release marker to suggest another version: {:#}",
+                    e
+                )),
+            }
+            Err(Error::VersionMissing {
+                version: version.trim().into(),
+                platform: platform.into(),
+            }
+            .into())
+        }
+        // some mirrors refuse HEAD, so leave it to the download to tell
+        Err(e) => {
+            explain::step(format!("HEAD {} failed ({}), downloading anyway", url, e));
+            Ok(None)
+        }
+    }
+}
+
+/// Restricts the machine-wide driver directory `dir`, and the machine cache when
+/// it is the one in use, to administrators.
+fn secure_machine_dirs(dir: &Path) -> Result<()> {
+    // a shared bin directory such as /usr/local/bin isn't the tool's to change
+    if Scope::Machine.owns_dir() && Some(dir) == Scope::Machine.dir().ok().as_deref() {
+        scope::secure(dir)?;
+    }
+    if let Some(cache) =
+        paths::cache_root().filter(|cache| Some(cache) == Scope::Machine.cache_dir().as_ref())
+    {
+        std::fs::create_dir_all(&cache)?;
+        scope::secure(&cache)?;
+    }
+    Ok(())
+}
+
+/// Reports what `--dry-run` would have done: the download or cached driver used,
+/// what is extracted from it and the file it is written to.
+fn plan(
+    version: &str,
+    platform: &str,
+    url: &str,
+    cached: Option<&Path>,
+    destination: Option<&Path>,
+    options: &DownloadOptions,
+) -> Result<()> {
+    output::parameter("msedgedriver.version", version);
+    output::parameter("msedgedriver.platform", platform);
+    output::parameter("msedgedriver.source", url);
+    output::flag("msedgedriver.cached", cached.is_some());
+
+    let filename = driver_filename(platform);
+    match cached {
+        Some(path) => {
+            output::line(&format!("would copy the cached driver {}", path.display()));
+            output::push(
+                "plan",
+                serde_json::json!({ "action": "copy", "from": path }),
+            );
+        }
+        None => {
+            policy::get().check_url(url)?;
+            let bytes = confirm_published(url, version, platform)?;
+            output::line(&match bytes {
+                Some(bytes) => format!("would download {} ({} bytes)", url, bytes),
+                None => format!("would download {}", url),
+            });
+            output::push(
+                "plan",
+                serde_json::json!({ "action": "download", "url": url, "bytes": bytes }),
+            );
+            output::line(&match options.include.as_slice() {
+                [] => format!("would extract {}", filename),
+                include => format!(
+                    "would extract {} and files matching {}",
+                    filename,
+                    include.join(", ")
+                ),
+            });
+            output::push(
+                "plan",
+                serde_json::json!({
+                    "action": "extract",
+                    "entry": filename,
+                    "include": options.include,
+                }),
+            );
+        }
+    }
+
+    match destination {
+        Some(path) => {
+            let replaces = installed_version(path, platform).or_else(|| {
+                Receipt::read(path)
+                    .ok()
+                    .flatten()
+                    .map(|receipt| receipt.version)
+            });
+            output::line(&match (&replaces, path.exists()) {
+                (Some(present), _) => format!("would replace {} ({} now)", path.display(), present),
+                (None, true) => format!("would overwrite {}", path.display()),
+                (None, false) => format!("would write {}", path.display()),
+            });
+            output::push(
+                "plan",
+                serde_json::json!({ "action": "write", "path": path, "replaces": replaces }),
+            );
+        }
+        None => output::line("would write the driver to stdout"),
+    }
+    output::progress("dry run, nothing was downloaded or written");
+    Ok(())
+}
+
+/// Checks the Authenticode signature of an extracted driver, warning on failure
+/// unless `check` requires it, in which case the driver is removed and an error
+/// returned.
+fn verify_signature(path: &Path, check: signature::Check) -> Result<()> {
+    let require = check == signature::Check::Require;
+    // only windows can verify, so don't warn about it elsewhere unless asked to
+    if check == signature::Check::Off || (!cfg!(windows) && !require) {
+        if check == signature::Check::Off {
+            explain::step("signature verification turned off by --verify-signature=off");
+        }
+        return Ok(());
+    }
+
+    match signature::verify(path) {
+        Ok(signer) => {
+            output::progress(&format!("signature verified, signed by {}", signer));
+            Ok(())
+        }
+        Err(e) if require => {
+            let _ = std::fs::remove_file(path);
+            Err(e)
+        }
+        Err(e) => output::warn("signature-unverified", &e.to_string()),
+    }
+}
+
+/// Runs a freshly extracted driver to make sure it executes and reports `expected`,
+/// removing it otherwise. Drivers for platforms the host can't execute are skipped.
+fn verify_runs(path: &Path, platform: &str, expected: &str) -> Result<()> {
+    if !host_can_run(platform) {
+        return Ok(());
+    }
+
+    let result = match driver_version(path) {
+        Ok(version) if version == expected.trim() => {
+            output::progress(&format!("driver runs and reports version {}", version));
+            return Ok(());
+        }
+        Ok(version) => Err(anyhow::anyhow!(
+            "installed driver reports version {}, expected {}",
+            version,
+            expected.trim()
+        )),
+        Err(e) => Err(e.context("installed driver failed to run")),
+    };
+
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+/// The version of an existing driver at `path`, if there is one the host can run.
+fn installed_version(path: &Path, platform: &str) -> Option<String> {
+    if path.is_file() && host_can_run(platform) {
+        driver_version(path).ok()
+    } else {
+        None
+    }
+}
+
+/// Whether drivers built for `platform` can be executed on this machine, which
+/// under `--no-detect` none may be: the driver's hashes have to vouch for it.
+fn host_can_run(platform: &str) -> bool {
+    if sandbox::active() {
+        false
+    } else if cfg!(windows) {
+        driver_filename(platform) == DRIVER_FILENAME
+    } else {
+        // apple silicon runs intel drivers through rosetta
+        platform == host_platform() || (platform == "mac64" && host_platform() == "mac64_m1")
+    }
+}
+
+/// The name of the driver executable inside archives for `platform`.
+fn driver_filename(platform: &str) -> &'static str {
+    if platform.starts_with("win") || platform == "arm64" {
+        DRIVER_FILENAME
+    } else {
+        "msedgedriver"
+    }
+}
+
+static BASE_URL: OnceLock<Option<String>> = OnceLock::new();
+
+/// Reads `--base-url` or `MSEDGEDRIVER_MIRROR`, an internal copy of Microsoft's
+/// driver host that every listing, marker and archive is fetched from instead.
+fn configure_base_url(args: &[String]) -> Result<()> {
+    let base = flag_value(args, "--base-url")
+        .or_else(|| std::env::var("MSEDGEDRIVER_MIRROR").ok())
+        .map(|base| base.trim().trim_end_matches('/').to_string())
+        .filter(|base| !base.is_empty());
+    if let Some(base) = &base {
+        policy::get().check_url(base)?;
+        explain::step(format!("using {} instead of Microsoft's driver host", base));
+    }
+    let _ = BASE_URL.set(base);
+    Ok(())
+}
+
+/// The `--base-url` override, or else the policy mandated mirror.
+fn base_url() -> Option<&'static str> {
+    BASE_URL
+        .get()
+        .and_then(Option::as_deref)
+        .or(policy::get().mirror.as_deref())
+}
+
+/// The listing every driver is found in, served by the base URL if one is set.
+fn manifest_url() -> &'static str {
+    base_url().unwrap_or(MANIFEST_URL)
+}
+
+fn fetch_manifest() -> Result<String> {
+    panic::set_phase("fetching driver listing");
+    let mut span = trace::span("fetch listing");
+    span.attr("url", manifest_url());
+    explain::step(format!("querying driver listing at {}", manifest_url()));
+    let manifest = http::call(http::get(manifest_url())).and_then(http::Response::into_string);
+    span.record(manifest)
+}
+
+/// The name of the manifest blob holding the `platform` driver archive for `version`.
+fn blob_name(version: &str, platform: &str) -> String {
+    format!("{}/edgedriver_{}.zip", version.trim(), platform)
+}
+
+/// The names `platform` archives have been published under, as newer releases
+/// renamed some: `mac64_m1` became `mac_arm64`. Which one a release uses is read
+/// from the listing rather than assumed from its version.
+fn artifact_names(platform: &str) -> Vec<&str> {
+    match platform {
+        "mac64_m1" => vec!["mac_arm64", "mac64_m1"],
+        "linux_arm64" => vec!["linux_arm64", "linuxarm64"],
+        other => vec![other],
+    }
+}
+
+/// Every name the listing may file the `platform` archive for `version` under,
+/// per [`artifact_names`].
+fn blob_names(version: &str, platform: &str) -> Vec<String> {
+    artifact_names(platform)
+        .into_iter()
+        .map(|name| blob_name(version, name))
+        .collect()
+}
+
+/// The version whose `platform` archive `blob` is, under any of its names.
+fn blob_version<'a>(blob: &'a Blob, platform: &str) -> Option<&'a str> {
+    artifact_names(platform).into_iter().find_map(|name| {
+        blob.name
+            .strip_suffix(".zip")?
+            .strip_suffix(name)?
+            .strip_suffix("/edgedriver_")
+    })
+}
+
+fn parse_blobs(manifest: &str) -> Result<Vec<Blob>> {
+    let results: EnumerationResults = from_str(manifest)?;
+    Ok(results.blobs.blobs)
+}
+
+fn find_blob(manifest: &str, version: &str, platform: &str) -> Result<Option<Blob>> {
+    let mut span = trace::span("resolve driver");
+    span.attr("version", version.trim());
+    span.attr("platform", platform);
+    let name_to_find = blob_name(version, platform);
+    if mapping::pulled(version) {
+        explain::step(format!("version map says {} was pulled", name_to_find));
+        return Ok(None);
+    }
+    let mut blobs = parse_blobs(manifest)?;
+    let blob = blob_names(version, platform)
+        .iter()
+        .find_map(|name| blobs.iter().position(|b| &b.name == name))
+        .map(|index| blobs.swap_remove(index));
+    match blob {
+        Some(mut blob) => {
+            // the mirrored listing still names Microsoft's host for each archive
+            if let Some(base) = BASE_URL.get().and_then(Option::as_deref) {
+                blob.url = format!("{}/{}", base, blob.name);
+            }
+            explain::step(format!("listing has {} at {}", blob.name, blob.url));
+            Ok(Some(blob))
+        }
+        None => {
+            explain::step(format!("listing has no {}", name_to_find));
+            legacy_blob(version, platform)
+        }
+    }
+}
+
+/// Finds an archive missing from the listing under one of the older URL layouts,
+/// trying each in order, for drivers of releases old enough to predate the listing.
+/// With a base url only its own layout is tried.
+///
+/// Only a 404 or 410 from the driver host means the archive isn't there, anything
+/// else fails with that error. The older hosts are retired, so whatever goes wrong
+/// reaching them only means they don't have it.
+fn legacy_blob(version: &str, platform: &str) -> Result<Option<Blob>> {
+    if mapping::pulled(version) {
+        return Ok(None);
+    }
+    let direct = format!("{}/{{version}}/edgedriver_{{platform}}.zip", manifest_url());
+    let names = artifact_names(platform);
+    // a base url stands in for Microsoft's hosts, the older ones included
+    let legacy = if base_url().is_some() {
+        &[][..]
+    } else {
+        LEGACY_LAYOUTS
+    };
+    let layouts = std::iter::once((direct.as_str(), true))
+        .chain(legacy.iter().map(|layout| (*layout, false)));
+    for (layout, primary) in layouts {
+        for name in &names {
+            let url = layout
+                .replace("{version}", version.trim())
+                .replace("{platform}", name);
+            if policy::get().check_url(&url).is_err() {
+                continue;
+            }
+            let found = match http::head(&url)
+                .timeout(std::time::Duration::from_secs(10))
+                .call()
+            {
+                Ok(_) => true,
+                Err(e)
+                    if !primary
+                        || e.downcast_ref::<http::Status>()
+                            .is_some_and(|status| matches!(status.code, 404 | 410)) =>
+                {
+                    false
+                }
+                Err(e) => return Err(e),
+            };
+            explain::step(format!(
+                "legacy location {} {}",
+                url,
+                if found { "exists" } else { "doesn't exist" }
+            ));
+            if found {
+                return Ok(Some(Blob {
+                    name: blob_name(version, name),
+                    url,
+                    properties: BlobProperties::default(),
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The newest published `platform` driver whose version starts with `prefix`, such as
+/// a major version.
+fn newest_for_prefix(manifest: &str, prefix: &str, platform: &str) -> Result<String> {
+    let dotted = format!("{}.", prefix);
+    parse_blobs(manifest)?
+        .iter()
+        .filter_map(|b| blob_version(b, platform).map(str::to_string))
+        .filter(|v| v.starts_with(&dotted) && !mapping::pulled(v))
+        .max_by_key(|v| version_key(v))
+        .inspect(|v| {
+            explain::step(format!(
+                "newest published {} driver for {}.x is {}",
+                platform, prefix, v
+            ))
+        })
+        .ok_or_else(|| anyhow!("no {} driver published for {}.x", platform, prefix))
+}
+
+/// Fails unless `version` is a full `MAJOR.MINOR.BUILD.PATCH` driver version.
+fn check_version(version: &str) -> Result<()> {
+    let parts: Vec<&str> = version.split('.').collect();
+    if parts.len() != 4
+        || parts
+            .iter()
+            .any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()))
+    {
+        bail!(
+            "invalid version {:?}, expected MAJOR.MINOR.BUILD.PATCH such as 120.0.2210.91",
+            version
+        );
+    }
+    Ok(())
+}
+
+/// Refuses a driver older than `floor`, from `--min-driver-version`.
+fn enforce_floor(version: &str, floor: Option<&str>) -> Result<()> {
+    match floor {
+        Some(floor) if version_key(version) < version_key(floor) => bail!(
+            "refusing to install driver {}, older than the minimum {} set by --min-driver-version",
+            version.trim(),
+            floor.trim()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Refuses to replace the `present` driver at `path` with an older `version`, such
+/// as after an old version was pinned by mistake in a shared tools directory.
+fn refuse_downgrade(present: Option<&str>, path: &Path, version: &str) -> Result<()> {
+    match present {
+        Some(present) if version_key(present) > version_key(version) => bail!(
+            "refusing to replace driver {} at {} with the older {}; pass --allow-downgrade to install it anyway",
+            present.trim(),
+            path.display(),
+            version.trim()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Splits a dotted version into its numeric components for ordering.
+fn version_key(version: &str) -> Vec<u32> {
+    version
+        .trim()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Runs the driver at `path` with `--version` and returns the version it reports.
+fn driver_version(path: &Path) -> Result<String> {
+    // a scanner may still hold a driver that was only just extracted
+    let output = retry::when_locked(&path.display().to_string(), || {
+        Command::new(path).arg("--version").output()
+    })?;
+    if !output.status.success() {
+        bail!("{} --version exited with {}", path.display(), output.status);
+    }
+
+    // e.g. "Microsoft Edge WebDriver 120.0.2210.91 (ba7b2ed0b2b44f7f1d1e91c5b5a0e3c1e4f6a3b8)"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout
+        .split_whitespace()
+        .find(|word| word.contains('.') && word.chars().all(|c| c.is_ascii_digit() || c == '.'))
+    {
+        Some(version) => Ok(version.to_string()),
+        None => bail!("unable to parse driver version from {:?}", stdout.trim()),
+    }
+}
+
+/// The major component of a dotted version string.
+fn major(version: &str) -> &str {
+    version.trim().split('.').next().unwrap_or_default()
+}
+
+/// Detects the installed webview2 version, failing with [`Error::NotInstalled`] if
+/// none is found.
+fn detected_version() -> Result<String> {
+    panic::set_phase("detecting webview2");
+    match webview2_version() {
+        Ok(Some(w2v)) => Ok(w2v),
+        Ok(None) => {
+            if cfg!(windows) {
+                eprintln!(
+                    "note: pass --install-runtime to install the evergreen webview2 runtime first"
+                );
+            }
+            Err(Error::NotInstalled {
+                what: "webview2_version".into(),
+            }
+            .into())
+        }
+        Err(e) => Err(e.context("unable to find webview2_version due to underlying error")),
+    }
+}
+
+/// Detects the installed webview2 version, installing the runtime first if it is missing.
+fn ensure_runtime(elevate: bool) -> Result<String> {
+    panic::set_phase("detecting webview2");
+    if let Some(version) = webview2_version()? {
+        return Ok(version);
+    }
+
+    output::progress("no webview2 runtime found, installing it");
+    panic::set_phase("installing webview2 runtime");
+    runtime::install(elevate)?;
+
+    // the bootstrapper can exit before edge update has registered the runtime
+    let started = std::time::Instant::now();
+    loop {
+        if let Some(version) = webview2_version()? {
+            output::progress(&format!("installed webview2 runtime {}", version));
+            return Ok(version);
+        }
+        if started.elapsed() >= runtime::SETTLE {
+            bail!(
+                "webview2 runtime still not found {}s after installing it",
+                runtime::SETTLE.as_secs()
+            );
+        }
+        http::check_cancelled()?;
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    }
+}
+
+fn webview2_version() -> Result<Option<String>> {
+    Ok(webview2_detection()?.map(|(version, _)| version))
+}
+
+const WEBVIEW2_KEY_64BIT_MACHINE: &str =
+    "HKLM\\SOFTWARE\\WOW6432Node\\Microsoft\\EdgeUpdate\\Clients\\{F3017226-FE2A-4295-8BDF-00C3A9A7E4C5}";
+const WEBVIEW2_KEY_32BIT_MACHINE: &str =
+    "HKLM\\SOFTWARE\\Microsoft\\EdgeUpdate\\Clients\\{F3017226-FE2A-4295-8BDF-00C3A9A7E4C5}";
+const WEBVIEW2_KEY_USER: &str =
+    "HKCU\\SOFTWARE\\Microsoft\\EdgeUpdate\\Clients\\{F3017226-FE2A-4295-8BDF-00C3A9A7E4C5}";
+
+/// The installed webview2 version and the registry key or directory it was found in,
+/// from the `--version-sources` chain.
+fn webview2_detection() -> Result<Option<(String, String)>> {
+    let _span = trace::span("detect webview2");
+    Ok(source::configured()
+        .detect()?
+        .map(|found| (found.version, found.location)))
+}
+
+/// Where the evergreen runtime keeps its version-named directories.
+fn webview2_dirs() -> Vec<PathBuf> {
+    ["ProgramFiles(x86)", "ProgramFiles", "LOCALAPPDATA"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|root| {
+            PathBuf::from(root)
+                .join("Microsoft")
+                .join("EdgeWebView")
+                .join("Application")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A listing of `names`, each archive at its path under Microsoft's host.
+    fn listing(names: &[&str]) -> String {
+        let blobs: String = names
+            .iter()
+            .map(|name| {
+                format!(
+                    "<Blob><Name>{}</Name><Url>{}/{}</Url></Blob>",
+                    name, MANIFEST_URL, name
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?><EnumerationResults><Blobs>{}</Blobs></EnumerationResults>",
+            blobs
+        )
+    }
+
+    #[test]
+    fn picks_each_releases_archive_name_from_the_listing() {
+        let http = MemoryHttp::new();
+        http.serve(
+            MANIFEST_URL,
+            listing(&[
+                "120.0.2210.91/edgedriver_mac64_m1.zip",
+                "125.0.2535.51/edgedriver_mac_arm64.zip",
+                "125.0.2535.51/edgedriver_linux64.zip",
+            ]),
+        );
+        let _serving = http::serving(&http);
+        let manifest = fetch_manifest().unwrap();
+
+        let old = find_blob(&manifest, "120.0.2210.91", "mac64_m1").unwrap();
+        assert_eq!(old.unwrap().name, "120.0.2210.91/edgedriver_mac64_m1.zip");
+        let new = find_blob(&manifest, "125.0.2535.51", "mac64_m1").unwrap();
+        assert_eq!(
+            new.unwrap().url,
+            format!("{}/125.0.2535.51/edgedriver_mac_arm64.zip", MANIFEST_URL)
+        );
+        assert_eq!(
+            newest_for_prefix(&manifest, "125", "mac64_m1").unwrap(),
+            "125.0.2535.51"
+        );
+        assert_eq!(http.requested(), vec![MANIFEST_URL.to_string()]);
+    }
+
+    #[test]
+    fn finds_archives_missing_from_the_listing_at_legacy_locations() {
+        let legacy = format!("{}/99.0.1150.30/edgedriver_win64.zip", MANIFEST_URL);
+        let http = MemoryHttp::new();
+        http.serve(MANIFEST_URL, listing(&[]));
+        http.serve(&legacy, b"PK".to_vec());
+        let _serving = http::serving(&http);
+        let manifest = fetch_manifest().unwrap();
+
+        let blob = find_blob(&manifest, "99.0.1150.30", "win64")
+            .unwrap()
+            .unwrap();
+        assert_eq!(blob.url, legacy);
+        assert!(find_blob(&manifest, "99.0.1150.31", "win64")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn accepts_only_full_driver_versions() {
+        assert!(check_version("120.0.2210.91").is_ok());
+        for version in [
+            "120",
+            "120.0.2210",
+            "120.0.2210.91.1",
+            "120.0.x.91",
+            "120..2210.91",
+        ] {
+            assert!(check_version(version).is_err(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn orders_versions_numerically() {
+        let mut versions = vec!["120.0.2210.91", "120.0.999.0", "99.0.1150.30"];
+        versions.sort_by_key(|v| version_key(v));
+        assert_eq!(versions, ["99.0.1150.30", "120.0.999.0", "120.0.2210.91"]);
+    }
+
+    #[test]
+    fn parses_sizes_in_binary_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512b").unwrap(), 512);
+        assert_eq!(parse_size("64k").unwrap(), 64 << 10);
+        assert_eq!(parse_size(" 500 MB ").unwrap(), 500 << 20);
+        assert_eq!(parse_size("2GiB").unwrap(), 2 << 30);
+        for size in ["", "MB", "1.5G", "-1", "10T", "ten"] {
+            assert!(parse_size(size).is_err(), "{}", size);
+        }
+        assert!(parse_size("18446744073709551615g").is_err());
+    }
+}
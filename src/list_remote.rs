@@ -0,0 +1,56 @@
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    blob_version, fetch_manifest, flag_value, output, parse_blobs, target_platform, version_key,
+};
+
+/// Prints the driver versions published for `--platform`, newest first, narrowed
+/// by `--major` and `--latest N`, to help pick a pin or see why one is missing.
+pub fn list_remote(args: &[String]) -> Result<()> {
+    let platform = target_platform(args)?;
+    let major = flag_value(args, "--major");
+    if let Some(major) = &major {
+        if major.is_empty() || !major.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("invalid --major {}, expected a number such as 124", major);
+        }
+    }
+    let latest = flag_value(args, "--latest")
+        .map(|n| {
+            n.parse::<usize>()
+                .map_err(|_| anyhow!("invalid --latest {}, expected a count", n))
+        })
+        .transpose()?;
+
+    let manifest = fetch_manifest()?;
+    let mut versions: Vec<String> = parse_blobs(&manifest)?
+        .iter()
+        .filter_map(|blob| blob_version(blob, platform).map(str::to_string))
+        .filter(|version| {
+            major
+                .as_deref()
+                .is_none_or(|major| version.split('.').next() == Some(major))
+        })
+        .collect();
+    versions.sort_by_key(|version| std::cmp::Reverse(version_key(version)));
+    versions.dedup();
+    if let Some(latest) = latest {
+        versions.truncate(latest);
+    }
+
+    if versions.is_empty() {
+        match &major {
+            Some(major) => bail!(
+                "no {} driver published for major version {}",
+                platform,
+                major
+            ),
+            None => bail!("no {} driver published", platform),
+        }
+    }
+    for version in &versions {
+        output::line(version);
+        output::push("versions", version.as_str().into());
+    }
+    output::parameter("msedgedriver.platform", platform);
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{http, output};
+
+/// How often the holder of a lease rewrites it to show it is still working.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// How long a lease may go unchanged before a waiter takes it over, as from an
+/// agent that lost power mid-download.
+const STALE_AFTER: Duration = Duration::from_secs(120);
+
+/// How often a waiter looks at a lease held by someone else.
+const POLL: Duration = Duration::from_millis(500);
+
+/// An exclusive lock on a file or directory, released when dropped, so processes
+/// take turns rather than trample each other.
+pub struct Lock {
+    _file: Option<File>,
+    _lease: Option<Lease>,
+}
+
+/// Waits until this process holds the lock for `path`, through a `.lock` file
+/// beside it.
+pub fn acquire(path: &Path) -> Result<Lock> {
+    let lock_path = lock_path(path);
+    if let Some(dir) = lock_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("unable to open lock {}", lock_path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            output::progress(&format!(
+                "waiting for another process using {}",
+                path.display()
+            ));
+            file.lock()
+                .with_context(|| format!("unable to lock {}", lock_path.display()))?;
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("unable to lock {}", lock_path.display()))
+        }
+    }
+    Ok(Lock {
+        _file: Some(file),
+        _lease: None,
+    })
+}
+
+/// Waits until this process holds the lock for `path` on a file server, such as a
+/// cache shared by a fleet of agents over SMB or NFS.
+///
+/// Advisory locks aren't honoured across machines by every client, so the lock is a
+/// `.lease` file created exclusively beside `path` and kept fresh while it is held.
+/// One left behind by a machine that died is taken over once it stops changing,
+/// judged by the waiter's clock alone so skewed clocks can't steal a live lease.
+pub fn acquire_shared(path: &Path) -> Result<Lock> {
+    let lease_path = {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lease");
+        path.with_file_name(name)
+    };
+    if let Some(dir) = lease_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let holder = holder();
+    let mut waiting = None;
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lease_path)
+        {
+            Ok(mut file) => {
+                file.write_all(format!("{} 0\n", holder).as_bytes())
+                    .with_context(|| format!("unable to write lease {}", lease_path.display()))?;
+                return Ok(Lock {
+                    _file: None,
+                    _lease: Some(Lease::hold(lease_path, holder)),
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("unable to create lease {}", lease_path.display()))
+            }
+        }
+
+        // a lease removed in the meantime reads as empty, and is retried at once
+        let seen = fs::read_to_string(&lease_path).unwrap_or_default();
+        let (since, last) = match waiting.take() {
+            Some((since, last)) if last == seen => (since, last),
+            previous => {
+                if previous.is_none() {
+                    output::progress(&format!(
+                        "waiting for {} using {}",
+                        seen.split_whitespace().next().unwrap_or("another agent"),
+                        path.display()
+                    ));
+                }
+                (Instant::now(), seen)
+            }
+        };
+        if since.elapsed() >= STALE_AFTER {
+            output::progress(&format!(
+                "taking over {}, unchanged for {}s",
+                lease_path.display(),
+                STALE_AFTER.as_secs()
+            ));
+            take_over(&lease_path, &last, &holder);
+            continue;
+        }
+        waiting = Some((since, last));
+        http::check_cancelled()?;
+        thread::sleep(POLL);
+    }
+}
+
+/// Removes the stale lease at `path` if it still reads `seen`. It is moved aside
+/// before being checked, so a lease another waiter created there in the meantime
+/// is put back rather than removed, and two waiters can't both take it over.
+fn take_over(path: &Path, seen: &str, holder: &str) {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.stale", holder));
+    let aside = path.with_file_name(name);
+    // gone already, taken over by another waiter or released
+    if fs::rename(path, &aside).is_err() {
+        return;
+    }
+    if fs::read_to_string(&aside).unwrap_or_default() != seen {
+        // linking fails rather than replace a lease created since
+        let _ = fs::hard_link(&aside, path);
+    }
+    let _ = fs::remove_file(&aside);
+}
+
+/// Who holds a lease, unique across the machines sharing it.
+pub fn holder() -> String {
+    let host = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| {
+            fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+        })
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "unknown".into());
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    format!(
+        "{}-{}-{:x}",
+        host.replace(char::is_whitespace, "_"),
+        std::process::id(),
+        nanos
+    )
+}
+
+/// A held `.lease` file and the thread keeping it fresh.
+struct Lease {
+    path: PathBuf,
+    holder: String,
+    stop: Arc<AtomicBool>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+impl Lease {
+    fn hold(path: PathBuf, holder: String) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let heartbeat = {
+            let (path, holder, stop) = (path.clone(), holder.clone(), stop.clone());
+            thread::spawn(move || {
+                let mut beat = 0u64;
+                let mut last = Instant::now();
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(200));
+                    if last.elapsed() < HEARTBEAT {
+                        continue;
+                    }
+                    beat += 1;
+                    last = Instant::now();
+                    // never recreates a lease that was taken over and removed
+                    if let Ok(mut file) = OpenOptions::new().write(true).truncate(true).open(&path)
+                    {
+                        let _ = file.write_all(format!("{} {}\n", holder, beat).as_bytes());
+                    }
+                }
+            })
+        };
+        Lease {
+            path,
+            holder,
+            stop,
+            heartbeat: Some(heartbeat),
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+        // a lease taken over from this process now belongs to someone else
+        let ours = fs::read_to_string(&self.path)
+            .is_ok_and(|seen| seen.split_whitespace().next() == Some(self.holder.as_str()));
+        if ours {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The file whose lock stands for `path`'s.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
@@ -0,0 +1,246 @@
+//! `--lockfile`: the driver each platform resolved to, with its archive's and its
+//! own sha256, recorded so a team installs the same drivers, and `--frozen` to
+//! refuse installing anything the lockfile doesn't name.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    download::Hashes, explain, flag_value, has_flag, lock, output, receipt::Receipt,
+    DownloadOptions,
+};
+
+/// Where `--frozen` looks without `--lockfile`.
+const DEFAULT_PATH: &str = "msedgedriver.lock";
+
+const HEADER: &str =
+    "# written by msedgedriver-tool, install with --frozen to get exactly these drivers\n";
+
+/// One driver as installed for one platform.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Locked {
+    pub driver: String,
+    pub platform: String,
+    pub version: String,
+    /// Where it was downloaded from; mirrors may serve it from elsewhere, so only
+    /// the version and hashes have to match.
+    pub url: String,
+    pub archive_sha256: String,
+    pub sha256: String,
+}
+
+impl Locked {
+    pub fn new(driver: &str, platform: &str, version: &str, url: &str, hashes: &Hashes) -> Self {
+        Locked {
+            driver: driver.into(),
+            platform: platform.into(),
+            version: version.trim().into(),
+            url: url.into(),
+            archive_sha256: hashes.archive.clone(),
+            sha256: hashes.driver.clone(),
+        }
+    }
+
+    /// The entry for the driver `receipt` describes, for one already installed.
+    pub fn from_receipt(driver: &str, receipt: &Receipt) -> Self {
+        Locked {
+            driver: driver.into(),
+            platform: receipt.platform.clone(),
+            version: receipt.version.clone(),
+            url: receipt.source.clone(),
+            archive_sha256: receipt.archive_sha256.clone(),
+            sha256: receipt.driver_sha256.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Contents {
+    #[serde(default, rename = "driver")]
+    drivers: Vec<Locked>,
+}
+
+/// The lockfile `--lockfile` or `--frozen` names.
+#[derive(Debug)]
+pub struct Lockfile {
+    path: PathBuf,
+    frozen: bool,
+    drivers: Vec<Locked>,
+}
+
+impl Lockfile {
+    /// Reads `--lockfile <file>`, or `msedgedriver.lock` with `--frozen`, which
+    /// must then exist; `None` without either flag.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>> {
+        let frozen = has_flag(args, "--frozen");
+        let path = match flag_value(args, "--lockfile") {
+            Some(path) => PathBuf::from(path),
+            None if frozen => PathBuf::from(DEFAULT_PATH),
+            None => return Ok(None),
+        };
+        if frozen && !path.is_file() {
+            bail!(
+                "--frozen needs the lockfile {}, run without --frozen to write it",
+                path.display()
+            );
+        }
+        let drivers = read(&path)?;
+        Ok(Some(Lockfile {
+            path,
+            frozen,
+            drivers,
+        }))
+    }
+
+    /// Whether the lockfile `--lockfile` names, or else `msedgedriver.lock`, exists,
+    /// which `--ci` then installs from as if `--frozen`.
+    ///
+    /// An empty file doesn't count: on linux it is the advisory lock an install into
+    /// the current directory leaves beside `msedgedriver`.
+    pub fn exists(args: &[String]) -> bool {
+        let path = flag_value(args, "--lockfile")
+            .map_or_else(|| PathBuf::from(DEFAULT_PATH), PathBuf::from);
+        fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() > 0)
+    }
+
+    /// The locked `driver` for `platform`, failing with `--frozen` if there is none.
+    pub fn locked(&self, driver: &str, platform: &str) -> Result<Option<&Locked>> {
+        let locked = self
+            .drivers
+            .iter()
+            .find(|l| l.driver == driver && l.platform == platform);
+        if locked.is_none() && self.frozen {
+            bail!(
+                "{} has no {} for {} and --frozen forbids adding one",
+                self.path.display(),
+                driver,
+                platform
+            );
+        }
+        Ok(locked)
+    }
+
+    /// The locked version of `driver` for `platform` with `--frozen`, which a driver
+    /// already installed must be to be left alone.
+    pub fn frozen_version(&self, driver: &str, platform: &str) -> Result<Option<String>> {
+        if !self.frozen {
+            return Ok(None);
+        }
+        Ok(self
+            .locked(driver, platform)?
+            .map(|locked| locked.version.clone()))
+    }
+
+    /// `options` with `--frozen` pinning the archive to the locked sha256.
+    pub fn pin(
+        &self,
+        driver: &str,
+        platform: &str,
+        options: DownloadOptions,
+    ) -> Result<DownloadOptions> {
+        if !self.frozen {
+            return Ok(options);
+        }
+        let Some(locked) = self.locked(driver, platform)? else {
+            return Ok(options);
+        };
+        match &options.sha256 {
+            Some(pin) if !pin.eq_ignore_ascii_case(&locked.archive_sha256) => bail!(
+                "--sha256 {} differs from the archive sha256 {} locks",
+                pin,
+                self.path.display()
+            ),
+            _ => Ok(DownloadOptions {
+                sha256: Some(locked.archive_sha256.clone()),
+                ..options
+            }),
+        }
+    }
+
+    /// Fails with `--frozen` unless `version` is the locked `driver`'s for `platform`.
+    pub fn check(&self, driver: &str, platform: &str, version: &str) -> Result<()> {
+        if !self.frozen {
+            return Ok(());
+        }
+        match self.locked(driver, platform)? {
+            Some(locked) if locked.version == version.trim() => Ok(()),
+            Some(locked) => bail!(
+                "{} {} resolved for {}, but {} locks {} and --frozen forbids updating it",
+                driver,
+                version.trim(),
+                platform,
+                self.path.display(),
+                locked.version
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Records `entry`, or with `--frozen` fails unless it is exactly what's locked.
+    pub fn record(&mut self, entry: Locked) -> Result<()> {
+        if self.frozen {
+            return match self.locked(&entry.driver, &entry.platform)? {
+                Some(locked)
+                    if locked.version == entry.version
+                        && locked.archive_sha256 == entry.archive_sha256
+                        && locked.sha256 == entry.sha256 =>
+                {
+                    Ok(())
+                }
+                _ => bail!(
+                    "{} {} for {} doesn't match {}, which --frozen requires",
+                    entry.driver,
+                    entry.version,
+                    entry.platform,
+                    self.path.display()
+                ),
+            };
+        }
+
+        // parallel installs record into the same file, each keeping the others' entries
+        let _lock = lock::acquire(&self.path)?;
+        let mut drivers = read(&self.path)?;
+        if drivers.contains(&entry) {
+            self.drivers = drivers;
+            return Ok(());
+        }
+        drivers.retain(|l| !(l.driver == entry.driver && l.platform == entry.platform));
+        explain::step(format!(
+            "locking {} {} for {} in {}",
+            entry.driver,
+            entry.version,
+            entry.platform,
+            self.path.display()
+        ));
+        drivers.push(entry);
+        drivers.sort_by(|a, b| (&a.driver, &a.platform).cmp(&(&b.driver, &b.platform)));
+
+        let contents = toml::to_string(&Contents {
+            drivers: drivers.clone(),
+        })?;
+        let temp = self.path.with_extension("lock.tmp");
+        fs::write(&temp, format!("{}\n{}", HEADER, contents))
+            .and_then(|()| fs::rename(&temp, &self.path))
+            .with_context(|| format!("unable to write lockfile {}", self.path.display()))?;
+        output::progress(&format!("updated {}", self.path.display()));
+        self.drivers = drivers;
+        Ok(())
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Locked>> {
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("unable to read lockfile {}", path.display()))?;
+    let contents: Contents = toml::from_str(&contents)
+        .with_context(|| format!("invalid lockfile {}", path.display()))?;
+    Ok(contents.drivers)
+}
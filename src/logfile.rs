@@ -0,0 +1,102 @@
+//! `run --log-file`: the driver's stdout and stderr kept in a file that is rotated
+//! once it grows past `--log-max-size`, across restarts and runs.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::{Context, Result};
+
+/// How large the file may grow before it is rotated, without `--log-max-size`.
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How many rotated files are kept beside it, as `<file>.1` (the newest) onwards.
+const KEEP: u32 = 3;
+
+/// A log file shared by the threads copying each stream into it.
+#[derive(Clone)]
+pub struct Log(Arc<Mutex<Inner>>);
+
+struct Inner {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl Log {
+    /// Appends to `path`, rotating it past `max_size` bytes.
+    pub fn open(path: &Path, max_size: u64) -> Result<Self> {
+        let file = append(path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Log(Arc::new(Mutex::new(Inner {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+        }))))
+    }
+
+    /// Copies `stream` into the log a line at a time until it ends, on its own thread.
+    pub fn capture(&self, stream: impl Read + Send + 'static) {
+        let log = self.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stream);
+            let mut line = Vec::new();
+            while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                log.write(&line);
+                line.clear();
+            }
+        });
+    }
+
+    /// Writes a line of the tool's own, e.g. that the driver was restarted.
+    pub fn note(&self, message: &str) {
+        self.write(format!("msedgedriver-tool: {}\n", message).as_bytes());
+    }
+
+    fn write(&self, line: &[u8]) {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.size > 0 && inner.size + line.len() as u64 > inner.max_size {
+            if let Err(e) = inner.rotate() {
+                crate::explain::step(format!("unable to rotate the driver log: {:#}", e));
+            }
+        }
+        // losing a line of the driver's output is better than stopping the driver
+        if inner.file.write_all(line).is_ok() {
+            inner.size += line.len() as u64;
+        }
+    }
+}
+
+impl Inner {
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = |n: u32| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        let _ = fs::remove_file(rotated(KEEP));
+        for n in (1..KEEP).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        self.file.flush()?;
+        fs::rename(&self.path, rotated(1))
+            .with_context(|| format!("unable to rotate {}", self.path.display()))?;
+        self.file = append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("unable to open log file {}", path.display()))
+}
@@ -0,0 +1,127 @@
+//! Known mismatches between browser builds and their drivers: builds whose driver
+//! was published under another version, and drivers pulled from the CDN after
+//! they were listed, so resolution goes straight to what can be downloaded.
+//!
+//! A table built into the tool is extended by `version-map.toml` in the config
+//! directory and then by each `--version-map <file>`, later entries winning:
+//!
+//! ```toml
+//! # drivers that must not be installed, e.g. because the CDN no longer has them
+//! pulled = ["119.0.2151.97"]
+//!
+//! # browser build = the driver build to install for it
+//! [drivers]
+//! "120.0.2210.133" = "120.0.2210.121"
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{explain, flag_values, paths};
+
+/// Browser builds whose driver was published as a different build, added as they
+/// are reported.
+const BUILT_IN_DRIVERS: &[(&str, &str)] = &[];
+
+/// Drivers listed once but no longer downloadable, added as they are reported.
+const BUILT_IN_PULLED: &[&str] = &[];
+
+const FILENAME: &str = "version-map.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapFile {
+    #[serde(default)]
+    pulled: Vec<String>,
+    #[serde(default)]
+    drivers: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct Map {
+    drivers: HashMap<String, String>,
+    pulled: Vec<String>,
+}
+
+static MAP: OnceLock<Map> = OnceLock::new();
+
+/// Reads the config directory's `version-map.toml` and every `--version-map`.
+pub fn configure(args: &[String]) -> Result<()> {
+    let mut files: Vec<PathBuf> = paths::config_dir()
+        .map(|dir| dir.join(FILENAME))
+        .filter(|file| file.is_file())
+        .into_iter()
+        .collect();
+    files.extend(
+        flag_values(args, "--version-map")
+            .into_iter()
+            .map(PathBuf::from),
+    );
+
+    let mut map = built_in();
+    for file in &files {
+        let extra =
+            read(file).with_context(|| format!("invalid version map {}", file.display()))?;
+        explain::step(format!(
+            "version map {} adds {} drivers and {} pulled",
+            file.display(),
+            extra.drivers.len(),
+            extra.pulled.len()
+        ));
+        map.drivers.extend(extra.drivers);
+        map.pulled.extend(extra.pulled);
+    }
+    let _ = MAP.set(map);
+    Ok(())
+}
+
+fn read(file: &Path) -> Result<MapFile> {
+    let contents = std::fs::read_to_string(file)?;
+    let mut map: MapFile = toml::from_str(&contents)?;
+    for version in map.drivers.values_mut().chain(&mut map.pulled) {
+        *version = version.trim().to_string();
+        crate::check_version(version)?;
+    }
+    Ok(map)
+}
+
+fn built_in() -> Map {
+    Map {
+        drivers: BUILT_IN_DRIVERS
+            .iter()
+            .map(|&(browser, driver)| (browser.into(), driver.into()))
+            .collect(),
+        pulled: BUILT_IN_PULLED.iter().map(|&v| v.into()).collect(),
+    }
+}
+
+fn get() -> &'static Map {
+    MAP.get_or_init(built_in)
+}
+
+/// The driver to look for to match browser `version`: the one the map names for
+/// it, or the same version.
+pub fn driver_for(version: &str) -> String {
+    let version = version.trim();
+    match get().drivers.get(version) {
+        Some(driver) => {
+            explain::step(format!(
+                "version map names driver {} for browser {}",
+                driver, version
+            ));
+            driver.clone()
+        }
+        None => version.into(),
+    }
+}
+
+/// Whether the map says driver `version` was pulled and can't be downloaded.
+pub fn pulled(version: &str) -> bool {
+    get().pulled.iter().any(|pulled| pulled == version.trim())
+}
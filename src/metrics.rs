@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+
+use crate::events::{self, Event};
+
+static DOWNLOADS_OK: AtomicU64 = AtomicU64::new(0);
+static DOWNLOADS_FAILED: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static INSTALLED: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+// counters carried over from the previous textfile, so they keep counting across runs
+const COUNTERS: &[&str] = &[
+    "msedgedriver_tool_downloads_total{result=\"success\"}",
+    "msedgedriver_tool_downloads_total{result=\"failure\"}",
+    "msedgedriver_tool_cache_hits_total",
+];
+
+pub fn record_download(ok: bool) {
+    let counter = if ok { &DOWNLOADS_OK } else { &DOWNLOADS_FAILED };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts a download avoided because the file was already present or fresh.
+pub fn record_cache_hit() {
+    CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Downloads avoided so far in this run.
+pub fn cache_hits() -> u64 {
+    CACHE_HITS.load(Ordering::Relaxed)
+}
+
+/// The version of the driver this run installed or found up to date, if any.
+pub fn installed_version() -> Option<String> {
+    INSTALLED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|(version, _)| version.clone())
+}
+
+pub fn set_installed(version: &str, path: &Path) {
+    events::emit(Event::Completed { version, path });
+    *INSTALLED.lock().unwrap_or_else(|e| e.into_inner()) =
+        Some((version.trim().into(), path.display().to_string()));
+}
+
+/// Writes this run's metrics in the Prometheus text format for node_exporter's
+/// textfile collector, adding to the counters of the run before.
+pub fn write_textfile(path: &Path, succeeded: bool) -> Result<()> {
+    let previous = fs::read_to_string(path).unwrap_or_default();
+    let previous: HashMap<&str, f64> = previous
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.rsplit_once(' '))
+        .filter_map(|(name, value)| Some((name, value.parse().ok()?)))
+        .collect();
+    let carried = |name: &str| previous.get(name).copied().unwrap_or_default() as u64;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let last_success = if succeeded {
+        now
+    } else {
+        carried("msedgedriver_tool_last_success_timestamp_seconds")
+    };
+    let runs = [&DOWNLOADS_OK, &DOWNLOADS_FAILED, &CACHE_HITS];
+
+    let mut text = String::new();
+    text.push_str("# HELP msedgedriver_tool_last_run_timestamp_seconds When the tool last ran.\n");
+    text.push_str("# TYPE msedgedriver_tool_last_run_timestamp_seconds gauge\n");
+    text.push_str(&format!(
+        "msedgedriver_tool_last_run_timestamp_seconds {}\n",
+        now
+    ));
+    text.push_str(
+        "# HELP msedgedriver_tool_last_success_timestamp_seconds When a run last succeeded.\n",
+    );
+    text.push_str("# TYPE msedgedriver_tool_last_success_timestamp_seconds gauge\n");
+    text.push_str(&format!(
+        "msedgedriver_tool_last_success_timestamp_seconds {}\n",
+        last_success
+    ));
+    text.push_str("# HELP msedgedriver_tool_last_run_success Whether the last run succeeded.\n");
+    text.push_str("# TYPE msedgedriver_tool_last_run_success gauge\n");
+    text.push_str(&format!(
+        "msedgedriver_tool_last_run_success {}\n",
+        u8::from(succeeded)
+    ));
+    text.push_str("# HELP msedgedriver_tool_downloads_total Driver archives downloaded.\n");
+    text.push_str("# TYPE msedgedriver_tool_downloads_total counter\n");
+    text.push_str(
+        "# HELP msedgedriver_tool_cache_hits_total Downloads avoided by an existing copy.\n",
+    );
+    text.push_str("# TYPE msedgedriver_tool_cache_hits_total counter\n");
+    for (name, run) in COUNTERS.iter().zip(runs) {
+        text.push_str(&format!(
+            "{} {}\n",
+            name,
+            carried(name) + run.load(Ordering::Relaxed)
+        ));
+    }
+    if let Some((version, driver)) = &*INSTALLED.lock().unwrap_or_else(|e| e.into_inner()) {
+        text.push_str("# HELP msedgedriver_tool_installed_driver_info The driver installed by the last run.\n");
+        text.push_str("# TYPE msedgedriver_tool_installed_driver_info gauge\n");
+        text.push_str(&format!(
+            "msedgedriver_tool_installed_driver_info{{version=\"{}\",path=\"{}\"}} 1\n",
+            escape(version),
+            escape(driver)
+        ));
+    }
+
+    // the collector may read at any moment, so never let it see a partial file
+    let staged = path.with_extension("prom.tmp");
+    fs::write(&staged, text)
+        .and_then(|()| fs::rename(&staged, path))
+        .with_context(|| format!("unable to write metrics to {}", path.display()))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{explain, flag_values, has_flag, http, output, paths, policy};
+
+/// How long a probe result is trusted before the mirror is measured again.
+const PROBE_TTL: Duration = Duration::from_secs(10 * 60);
+/// Bytes fetched from each candidate to measure it.
+const PROBE_BYTES: u64 = 256 << 10;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Stands in for the url the listing gives, which has no mirror base.
+const ORIGIN: &str = "origin";
+
+struct Config {
+    mirrors: Vec<String>,
+    probe: bool,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+static RESULTS: Mutex<Option<HashMap<String, Probe>>> = Mutex::new(None);
+
+/// What one probe of a candidate measured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Probe {
+    /// Time to the first response byte, in milliseconds; absent if the probe failed.
+    latency_ms: Option<u64>,
+    bytes_per_sec: u64,
+    /// Seconds since the unix epoch.
+    probed_at: u64,
+}
+
+/// Reads `--mirror` (repeatable, or a comma separated `MSEDGEDRIVER_TOOL_MIRRORS`)
+/// and `--probe-mirrors`.
+///
+/// A mirror is a base URL serving archives as `{base}/{version}/edgedriver_{platform}.zip`.
+pub fn configure(args: &[String]) {
+    let mut mirrors = flag_values(args, "--mirror");
+    if mirrors.is_empty() {
+        if let Ok(list) = std::env::var("MSEDGEDRIVER_TOOL_MIRRORS") {
+            mirrors = list
+                .split(',')
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+    let mirrors = mirrors
+        .into_iter()
+        .map(|m| m.trim_end_matches('/').to_string())
+        .collect();
+    let _ = CONFIG.set(Config {
+        mirrors,
+        probe: has_flag(args, "--probe-mirrors"),
+    });
+}
+
+/// Where to download the archive the listing names `name` from, given the `url` the
+/// listing has for it.
+///
+/// Without mirrors this is `url`. With mirrors it's the first one, or with
+/// `--probe-mirrors` whichever of the mirrors and `url` answered a small ranged
+/// request fastest.
+pub fn select(url: &str, name: &str) -> String {
+    let Some(config) = CONFIG.get().filter(|c| !c.mirrors.is_empty()) else {
+        return url.to_string();
+    };
+
+    let mut candidates: Vec<(&str, String)> = config
+        .mirrors
+        .iter()
+        .map(|base| (base.as_str(), format!("{}/{}", base, name)))
+        .filter(|(_, url)| policy::get().check_url(url).is_ok())
+        .collect();
+    if !config.probe {
+        return match candidates.into_iter().next() {
+            Some((_, url)) => {
+                explain::step(format!("downloading from the first mirror, {}", url));
+                url
+            }
+            None => url.to_string(),
+        };
+    }
+
+    if policy::get().check_url(url).is_ok() {
+        candidates.push((ORIGIN, url.to_string()));
+    }
+    let fastest = candidates
+        .iter()
+        .filter_map(|(key, url)| {
+            let probe = probe(key, url);
+            let latency = probe.latency_ms?;
+            explain::step(format!(
+                "{} answered in {}ms at {} KiB/s",
+                url,
+                latency,
+                probe.bytes_per_sec >> 10
+            ));
+            Some((probe.bytes_per_sec, url))
+        })
+        .max_by_key(|(rate, _)| *rate)
+        .map(|(_, url)| url.clone());
+
+    match fastest {
+        Some(fastest) => {
+            output::progress(&format!("fastest source is {}", fastest));
+            fastest
+        }
+        None => {
+            output::progress("no mirror answered the probe, using the listing url");
+            url.to_string()
+        }
+    }
+}
+
+/// Measures `url`, or reuses a recent measurement of the mirror it's under.
+fn probe(key: &str, url: &str) -> Probe {
+    let now = unix_now();
+    let mut results = RESULTS.lock().unwrap_or_else(|e| e.into_inner());
+    let results = results.get_or_insert_with(load);
+    if let Some(probe) = results.get(key) {
+        if now.saturating_sub(probe.probed_at) < PROBE_TTL.as_secs() {
+            return probe.clone();
+        }
+    }
+
+    let probe = measure(url).unwrap_or(Probe {
+        latency_ms: None,
+        bytes_per_sec: 0,
+        probed_at: now,
+    });
+    results.insert(key.to_string(), probe.clone());
+    if let Some(path) = cache_path() {
+        if let Ok(contents) = serde_json::to_vec_pretty(&*results) {
+            let _ = std::fs::write(path, contents);
+        }
+    }
+    probe
+}
+
+fn measure(url: &str) -> Option<Probe> {
+    let start = Instant::now();
+    let response = http::get(url)
+        .timeout(PROBE_TIMEOUT)
+        .set("Range", &format!("bytes=0-{}", PROBE_BYTES - 1))
+        .call()
+        .ok()?;
+    let latency = start.elapsed();
+    let mut body = Vec::new();
+    // a server ignoring the range would send the whole archive
+    response
+        .into_reader()
+        .take(PROBE_BYTES)
+        .read_to_end(&mut body)
+        .ok()?;
+    let elapsed = start.elapsed().as_secs_f64().max(0.001);
+
+    Some(Probe {
+        latency_ms: Some(latency.as_millis() as u64),
+        bytes_per_sec: (body.len() as f64 / elapsed) as u64,
+        probed_at: unix_now(),
+    })
+}
+
+fn load() -> HashMap<String, Probe> {
+    cache_path()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|contents| serde_json::from_slice(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let dir = paths::cache_dir()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir.join("mirrors.json"))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
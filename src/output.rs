@@ -0,0 +1,448 @@
+use std::{
+    cell::RefCell,
+    io::{IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+
+/// How progress and results are presented to whoever is reading stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Plain,
+    /// `##teamcity[...]` service messages, understood natively by TeamCity agents.
+    TeamCity,
+    /// One JSON object of results on stdout once the command finishes, with
+    /// progress on stderr.
+    Json,
+}
+
+/// How much the tool says while it works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only results, such as the installed driver's path, and warnings.
+    Quiet,
+    Normal,
+    /// Adds `--explain` decisions and the HTTP requests made.
+    Verbose,
+    /// Adds the HTTP client's own connection and header logging.
+    Trace,
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+static QUIET: AtomicBool = AtomicBool::new(false);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+static INTERACTIVE: OnceLock<bool> = OnceLock::new();
+/// Whether `--ci` without `--allow-warnings` makes warnings fail the run.
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// Whether `--deny-warnings` fails a run that finished with any warning.
+static DENY_WARNINGS: AtomicBool = AtomicBool::new(false);
+/// Every warning so far, listed again once the command finishes.
+static WARNINGS: Mutex<Vec<Warning>> = Mutex::new(Vec::new());
+/// What [`Mode::Json`] prints when the command finishes.
+static RESULT: Mutex<Option<Map<String, Value>>> = Mutex::new(None);
+
+/// Sets the output mode for the rest of the process; only the first call has an effect.
+pub fn set_mode(mode: Mode) {
+    let _ = MODE.set(mode);
+}
+
+/// Sets how much is reported, from `-q`/`--quiet`, `-v`/`--verbose` or `-vv`, and
+/// routes `log` records from the tool and its HTTP client to stderr accordingly.
+pub fn set_verbosity(verbosity: Verbosity) {
+    QUIET.store(verbosity == Verbosity::Quiet, Ordering::Relaxed);
+    let level = match verbosity {
+        Verbosity::Quiet | Verbosity::Normal => log::LevelFilter::Warn,
+        Verbosity::Verbose => log::LevelFilter::Debug,
+        Verbosity::Trace => log::LevelFilter::Trace,
+    };
+    if log::set_logger(&Logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Whether only results are printed.
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{}: {}: {}",
+                record.level().as_str().to_ascii_lowercase(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sends all reporting to stderr, keeping stdout for data such as a piped driver.
+pub fn use_stderr() {
+    TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+/// Prints one line of reporting to stdout, or stderr after [`use_stderr`].
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if TO_STDERR.load(Ordering::Relaxed) || mode() == Mode::Json {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+fn mode() -> Mode {
+    *MODE.get().unwrap_or(&Mode::Plain)
+}
+
+/// Whether results go out as [`Mode::Json`]'s one object, so a command printing
+/// bare data to stdout must leave it to [`parameter`] instead.
+pub fn json() -> bool {
+    mode() == Mode::Json
+}
+
+/// Runs as `--ci` does: never prompting or redrawing progress, and failing on
+/// warnings unless `allow_warnings`.
+pub fn set_ci(allow_warnings: bool) {
+    let _ = INTERACTIVE.set(false);
+    STRICT.store(!allow_warnings, Ordering::Relaxed);
+}
+
+/// Something that went less well than asked but didn't stop the command, such as a
+/// substituted driver build.
+struct Warning {
+    /// A stable name for the kind of warning, e.g. `substituted-build`.
+    code: &'static str,
+    message: String,
+}
+
+/// Makes a run that finished with any warning fail, for `--deny-warnings`.
+pub fn set_deny_warnings() {
+    DENY_WARNINGS.store(true, Ordering::Relaxed);
+}
+
+/// Prints `message` as a warning of kind `code`, or fails with it under `--ci`
+/// unless `--allow-warnings` was given.
+pub fn warn(code: &'static str, message: &str) -> Result<()> {
+    forbid(message)?;
+    note_warning(code, message);
+    Ok(())
+}
+
+/// Prints and collects `message` as a warning of kind `code` that even `--ci`
+/// lets pass, for trouble that leaves the result as asked, such as failing to
+/// cache the driver; `--deny-warnings` still fails on it.
+pub fn note_warning(code: &'static str, message: &str) {
+    eprintln!("warning: {}", message);
+    crate::events::emit(crate::events::Event::Warning { code, message });
+    push(
+        "warnings",
+        serde_json::json!({ "code": code, "message": message }),
+    );
+    WARNINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Warning {
+            code,
+            message: message.into(),
+        });
+}
+
+/// Fails if `--deny-warnings` was given and the command warned.
+pub fn deny_warnings() -> Result<()> {
+    let count = WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).len();
+    if DENY_WARNINGS.load(Ordering::Relaxed) && count > 0 {
+        bail!(
+            "finished with {} warning{}, which --deny-warnings turns into failure",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+/// Fails with `message` under `--ci` unless `--allow-warnings`, for something the
+/// tool otherwise does quietly but a strict run must not let pass.
+pub fn forbid(message: &str) -> Result<()> {
+    if STRICT.load(Ordering::Relaxed) {
+        bail!("{}, which --ci forbids unless --allow-warnings", message);
+    }
+    Ok(())
+}
+
+/// Whether a person is watching, so redrawn progress lines are worth drawing.
+///
+/// False with `--ci` or under CI (`CI` set to anything but `false`/`0`), when
+/// stdout or stderr aren't terminals, on dumb terminals and in TeamCity mode.
+pub fn interactive() -> bool {
+    *INTERACTIVE.get_or_init(|| {
+        let ci = std::env::var("CI").is_ok_and(|v| !matches!(v.trim(), "" | "0" | "false"));
+        let dumb = std::env::var("TERM").is_ok_and(|t| t == "dumb");
+        mode() == Mode::Plain
+            && !ci
+            && !dumb
+            && std::io::stdout().is_terminal()
+            && std::io::stderr().is_terminal()
+    })
+}
+
+// how often a transfer line is redrawn, or logged when nobody is watching
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+const LOG_EVERY: Duration = Duration::from_secs(5);
+
+/// The transfer in progress on this thread, for its rate and ETA.
+struct Transfer {
+    started: Instant,
+    /// Bytes already present when it started, e.g. from a resumed download.
+    initial: u64,
+    shown: Option<Instant>,
+}
+
+thread_local! {
+    static TRANSFER: RefCell<Option<Transfer>> = const { RefCell::new(None) };
+}
+
+/// Shows how a transfer is going, with its throughput and time remaining: a bar
+/// redrawn on stderr when `interactive`, otherwise a progress line every few seconds.
+pub fn transfer(label: &str, done: u64, total: Option<u64>) {
+    crate::events::emit(crate::events::Event::BytesProgressed { done, total });
+    if quiet() {
+        return;
+    }
+
+    let now = Instant::now();
+    let interactive = interactive();
+    let line = TRANSFER.with(|transfer| {
+        let mut transfer = transfer.borrow_mut();
+        let transfer = transfer.get_or_insert(Transfer {
+            started: now,
+            initial: done,
+            shown: None,
+        });
+        let every = if interactive { REDRAW_EVERY } else { LOG_EVERY };
+        let due = match transfer.shown {
+            Some(shown) => now - shown >= every,
+            // a line right away is only useful to someone watching
+            None => interactive || now - transfer.started >= every,
+        };
+        if !due && total != Some(done) {
+            return None;
+        }
+        transfer.shown = Some(now);
+
+        let elapsed = (now - transfer.started).as_secs_f64();
+        let rate = (done - transfer.initial.min(done)) as f64 / elapsed.max(0.001);
+        Some(describe(label, done, total, rate))
+    });
+
+    let Some(line) = line else {
+        return;
+    };
+    if interactive {
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", line);
+        let _ = stderr.flush();
+    } else {
+        progress(&line);
+    }
+}
+
+fn describe(label: &str, done: u64, total: Option<u64>, rate: f64) -> String {
+    let speed = format!("{:.1} MiB/s", rate / (1 << 20) as f64);
+    match total {
+        Some(total) if total > 0 => {
+            let bar_width = 20;
+            let filled = (done.min(total) * bar_width / total) as usize;
+            let eta = if rate > 0.0 {
+                let secs = (total.saturating_sub(done) as f64 / rate) as u64;
+                format!("{}:{:02}", secs / 60, secs % 60)
+            } else {
+                "--:--".into()
+            };
+            format!(
+                "{}: [{}{}] {}% {:.1} / {:.1} MiB, {}, ETA {}",
+                label,
+                "#".repeat(filled),
+                " ".repeat(bar_width as usize - filled),
+                done * 100 / total,
+                done as f64 / (1 << 20) as f64,
+                total as f64 / (1 << 20) as f64,
+                speed,
+                eta
+            )
+        }
+        _ => format!(
+            "{}: {:.1} MiB, {}",
+            label,
+            done as f64 / (1 << 20) as f64,
+            speed
+        ),
+    }
+}
+
+/// Ends a line drawn by `transfer` so later output starts on its own line.
+pub fn transfer_done() {
+    let shown = TRANSFER.with(|transfer| transfer.borrow_mut().take().and_then(|t| t.shown));
+    if shown.is_some() && interactive() && !quiet() {
+        eprintln!();
+    }
+}
+
+/// Prints a line of a command's own report, such as one check's outcome, which
+/// moves to stderr along with progress in JSON mode.
+pub fn line(message: &str) {
+    report!("{}", message);
+}
+
+/// Reports what the tool is currently doing, unless quiet.
+pub fn progress(message: &str) {
+    if quiet() {
+        return;
+    }
+    match mode() {
+        Mode::Plain | Mode::Json => report!("{}", message),
+        Mode::TeamCity => report!("##teamcity[progressMessage '{}']", escape(message)),
+    }
+}
+
+/// Publishes a result value, e.g. the installed driver path, for later build steps.
+pub fn parameter(name: &str, value: &str) {
+    match mode() {
+        Mode::Plain => {}
+        Mode::TeamCity => report!(
+            "##teamcity[setParameter name='{}' value='{}']",
+            escape(name),
+            escape(value)
+        ),
+        Mode::Json => record(name, value.into()),
+    }
+}
+
+/// Like [`parameter`], for a yes or no result such as whether the cache was used.
+pub fn flag(name: &str, value: bool) {
+    match mode() {
+        Mode::Json => record(name, value.into()),
+        _ => parameter(name, if value { "true" } else { "false" }),
+    }
+}
+
+/// Appends `item` to the list under `name`, such as one of several installations
+/// found; only [`Mode::Json`] has a place for lists.
+pub fn push(name: &str, item: Value) {
+    if mode() != Mode::Json {
+        return;
+    }
+    let mut result = RESULT.lock().unwrap_or_else(|e| e.into_inner());
+    let list = result
+        .get_or_insert_with(Map::new)
+        .entry(name)
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Value::Array(items) = list {
+        items.push(item);
+    } else {
+        *list = Value::Array(vec![item]);
+    }
+}
+
+/// Flags a failure of the build; plain output already reports errors on stderr.
+pub fn problem(description: &str) {
+    match mode() {
+        Mode::Plain => {}
+        Mode::TeamCity => report!(
+            "##teamcity[buildProblem description='{}']",
+            escape(description)
+        ),
+        Mode::Json => record("error", description.into()),
+    }
+}
+
+/// Prints the results [`Mode::Json`] collected so far as a line of its own, for a
+/// command such as `run` whose results are needed before it finishes; the full
+/// object still follows once it does.
+pub fn publish() {
+    if mode() != Mode::Json {
+        return;
+    }
+    let result = RESULT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    println!("{}", Value::Object(result.unwrap_or_default()));
+}
+
+/// Prints the results [`Mode::Json`] collected, once the command has finished,
+/// or lists the warnings again where they can't be missed at the end of a log.
+pub fn finish(succeeded: bool) {
+    if mode() != Mode::Json {
+        let warnings = WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+        if !warnings.is_empty() {
+            eprintln!("warnings:");
+            for warning in warnings.iter() {
+                eprintln!("  {} [{}]", warning.message, warning.code);
+            }
+        }
+        return;
+    }
+
+    let mut result = RESULT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .unwrap_or_default();
+    result.insert("ok".into(), succeeded.into());
+    println!("{}", Value::Object(result));
+}
+
+/// Files `value` under `name`, nesting on the dots, so `msedgedriver.path` becomes
+/// `{"msedgedriver": {"path": ...}}`.
+fn record(name: &str, value: Value) {
+    let mut result = RESULT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut object = result.get_or_insert_with(Map::new);
+    let mut parts = name.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            object.insert(part.into(), value);
+            return;
+        }
+        let entry = object
+            .entry(part)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        let Value::Object(inner) = entry else {
+            unreachable!()
+        };
+        object = inner;
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,70 @@
+use std::{panic, sync::Mutex};
+
+static PHASE: Mutex<&'static str> = Mutex::new("starting");
+
+/// What a shell reports for a process SIGPIPE ended, which is all a closed pipe is.
+const EXIT_BROKEN_PIPE: i32 = 141;
+
+/// Records what the tool is doing, so a panic report can say where it happened.
+pub fn set_phase(phase: &'static str) {
+    if let Ok(mut current) = PHASE.lock() {
+        *current = phase;
+    }
+}
+
+/// Whether `message` is `println!` failing because whatever read stdout went away,
+/// as unix and windows word it.
+fn is_broken_pipe(message: &str) -> bool {
+    message.starts_with("failed printing to std")
+        && (message.contains("Broken pipe") || message.contains("(os error 232)"))
+}
+
+/// Replaces the default panic output with a report that is useful in a bug ticket.
+///
+/// The default hook still runs when `RUST_BACKTRACE` is set, so backtraces aren't lost.
+/// Printing to a pipe closed early isn't a bug, so it exits quietly instead.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let phase = PHASE.lock().map(|p| *p).unwrap_or("unknown");
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "unknown location".into());
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".into());
+        // e.g. `--help | head`: the reader has all it wanted, nothing crashed
+        if is_broken_pipe(&message) {
+            std::process::exit(EXIT_BROKEN_PIPE);
+        }
+
+        eprintln!();
+        eprintln!(
+            "{} crashed unexpectedly, this is a bug.",
+            env!("CARGO_PKG_NAME")
+        );
+        eprintln!();
+        eprintln!("  version: {}", env!("CARGO_PKG_VERSION"));
+        eprintln!(
+            "  os/arch: {}/{}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        eprintln!("  phase:   {}", phase);
+        eprintln!("  message: {}", message);
+        eprintln!("  at:      {}", location);
+        eprintln!();
+        eprintln!("Please file an issue at https://github.com/chippers/msedgedriver-tool/issues");
+        eprintln!("including the details above and the command you ran.");
+
+        if std::env::var_os("RUST_BACKTRACE").is_some() {
+            default_hook(info);
+        } else {
+            eprintln!("Rerunning with RUST_BACKTRACE=1 set adds a backtrace to this report.");
+        }
+    }));
+}
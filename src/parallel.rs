@@ -0,0 +1,53 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::flag_value;
+
+const DEFAULT_PARALLELISM: usize = 4;
+
+/// Reads `--parallel`, the most downloads allowed in flight at once.
+pub fn limit(args: &[String]) -> Result<usize> {
+    match flag_value(args, "--parallel") {
+        Some(n) => match n.parse() {
+            Ok(0) | Err(_) => Err(anyhow!("--parallel needs a positive number, got {:?}", n)),
+            Ok(n) => Ok(n),
+        },
+        None => Ok(DEFAULT_PARALLELISM),
+    }
+}
+
+/// Runs `f` over `items` on at most `limit` threads, returning results in input order.
+pub fn map<T, R, F>(items: &[T], limit: usize, f: F) -> Vec<Result<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<R>>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..limit.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(i) else { break };
+                let result = f(item);
+                results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|| Err(anyhow!("download thread panicked"))))
+        .collect()
+}
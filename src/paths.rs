@@ -0,0 +1,141 @@
+use std::{
+    path::{Component, Path, PathBuf, Prefix},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+/// Placed beside the executable, turns on portable mode without `--portable`.
+const PORTABLE_MARKER: &str = "msedgedriver-tool.portable";
+
+static PORTABLE_ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+static PROJECT: OnceLock<String> = OnceLock::new();
+static SHARED_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Decides once whether the tool keeps its state beside its executable, as asked
+/// with `--portable` or by a marker file there.
+pub fn set_portable(flag: bool) {
+    let root = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from))
+        .filter(|dir| flag || dir.join(PORTABLE_MARKER).is_file());
+    let _ = PORTABLE_ROOT.set(root);
+}
+
+/// The executable's directory when running portable.
+pub fn portable_root() -> Option<&'static PathBuf> {
+    PORTABLE_ROOT.get().and_then(Option::as_ref)
+}
+
+/// Uses `dir` as the cache from now on, as given with `--cache-dir`.
+pub fn set_cache_dir(dir: PathBuf) {
+    let _ = CACHE_DIR.set(dir);
+}
+
+/// Keeps the cache, pools and shared install directories apart for `project` from
+/// now on, as asked with `--project`.
+pub fn set_project(project: &str) {
+    let _ = PROJECT.set(project.into());
+}
+
+/// The `--project` installs are namespaced under.
+pub fn project() -> Option<&'static str> {
+    PROJECT.get().map(String::as_str)
+}
+
+/// `dir`, or its subdirectory for the `--project` when there is one.
+pub fn namespaced(dir: PathBuf) -> PathBuf {
+    match project() {
+        Some(project) => dir.join(project),
+        None => dir,
+    }
+}
+
+/// Treats the cache as shared between machines, as asked with `--shared-cache`.
+pub fn set_shared_cache(shared: bool) {
+    SHARED_CACHE.store(shared, Ordering::Relaxed);
+}
+
+/// Whether the cache lives on a file server used by other machines too, from
+/// `--shared-cache` or a UNC `--cache-dir` such as `\\server\share\drivers`.
+pub fn shared_cache() -> bool {
+    SHARED_CACHE.load(Ordering::Relaxed) || cache_dir().is_some_and(|dir| is_unc(&dir))
+}
+
+fn is_unc(path: &Path) -> bool {
+    matches!(
+        path.components().next(),
+        Some(Component::Prefix(prefix))
+            if matches!(prefix.kind(), Prefix::UNC(..) | Prefix::VerbatimUNC(..))
+    )
+}
+
+/// Per-user directory for state the tool can always recreate, such as downloads.
+///
+/// `%LOCALAPPDATA%\msedgedriver-tool` on Windows, `$XDG_CACHE_HOME/msedgedriver-tool`
+/// or `~/.cache/msedgedriver-tool` elsewhere, and `cache` beside the executable
+/// when portable, unless `--cache-dir` or `--scope machine` says otherwise. With
+/// `--project`, that directory's `projects/<name>`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let root = cache_root()?;
+    Some(match project() {
+        Some(project) => root.join("projects").join(project),
+        None => root,
+    })
+}
+
+/// The cache every project's is kept inside, see [`cache_dir`].
+pub fn cache_root() -> Option<PathBuf> {
+    if let Some(dir) = CACHE_DIR.get() {
+        return Some(dir.clone());
+    }
+    if let Some(root) = portable_root() {
+        return Some(root.join("cache"));
+    }
+
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".cache")))
+    };
+
+    base.map(|base| base.join(env!("CARGO_PKG_NAME")))
+}
+
+/// Where the tool's own logs, such as the last error, are kept: the cache, or
+/// `logs` beside the executable when portable.
+pub fn log_dir() -> Option<PathBuf> {
+    match portable_root() {
+        Some(root) => Some(root.join("logs")),
+        None => cache_dir(),
+    }
+}
+
+/// Per-user directory for settings: `%APPDATA%\msedgedriver-tool` on Windows,
+/// `$XDG_CONFIG_HOME/msedgedriver-tool` or `~/.config/msedgedriver-tool` elsewhere,
+/// and the executable's own directory when portable.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(root) = portable_root() {
+        return Some(root.clone());
+    }
+
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".config")))
+    };
+
+    base.map(|base| base.join(env!("CARGO_PKG_NAME")))
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
@@ -0,0 +1,186 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use url::Url;
+
+use crate::registry;
+
+/// Settings an administrator enforces on a managed machine, overriding user flags.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Every download must come from under this base URL.
+    pub mirror: Option<String>,
+    /// Drivers without a valid Microsoft signature are never installed.
+    #[serde(default)]
+    pub require_signature: bool,
+    /// Archives must be pinned with `--sha256`.
+    #[serde(default)]
+    pub require_sha256: bool,
+    /// Disables check-free shortcuts such as `--keep-quarantine`.
+    #[serde(default)]
+    pub forbid_insecure: bool,
+    /// Archives are verified against hashes listed in this file or URL.
+    pub checksums: Option<String>,
+    /// Every change is recorded to this audit log regardless of user flags.
+    pub audit_log: Option<PathBuf>,
+}
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Loads the machine policy, if any; must run before [`get`] is first used.
+pub fn load() -> Result<()> {
+    let policy = match policy_path() {
+        Some(path) if path.is_file() => {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("unable to read policy {}", path.display()))?;
+            toml::from_str(&contents)
+                .with_context(|| format!("invalid policy {}", path.display()))?
+        }
+        _ => Policy::default(),
+    };
+
+    let _ = POLICY.set(policy);
+    Ok(())
+}
+
+pub fn get() -> &'static Policy {
+    POLICY.get_or_init(Policy::default)
+}
+
+impl Policy {
+    /// Fails if `url` is outside the mandated mirror.
+    pub fn check_url(&self, url: &str) -> Result<()> {
+        match &self.mirror {
+            Some(mirror) if !within(mirror, url) => {
+                bail!(
+                    "policy only allows downloads from {}, refusing {}",
+                    mirror,
+                    url
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a flag that weakens verification is used while the policy forbids it.
+    pub fn check_insecure(&self, flag: &str, used: bool) -> Result<()> {
+        if used && self.forbid_insecure {
+            bail!("{} is forbidden by policy", flag);
+        }
+        Ok(())
+    }
+}
+
+/// Whether `url` is on the same scheme, host and port as `mirror` and under its
+/// path, a whole segment at a time, so look-alike hosts and paths don't pass.
+fn within(mirror: &str, url: &str) -> bool {
+    let (Ok(mirror), Ok(url)) = (Url::parse(mirror), Url::parse(url)) else {
+        return false;
+    };
+    if mirror.scheme() != url.scheme()
+        || mirror.host() != url.host()
+        || mirror.port_or_known_default() != url.port_or_known_default()
+    {
+        return false;
+    }
+    let base = mirror.path().trim_end_matches('/');
+    match url.path().strip_prefix(base) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// The registry-pointed policy file if configured, otherwise the fixed machine-wide location.
+fn policy_path() -> Option<PathBuf> {
+    if let Some(path) = registry_policy_path() {
+        return Some(path);
+    }
+
+    if cfg!(windows) {
+        std::env::var_os("ProgramData").map(|dir| {
+            Path::new(&dir)
+                .join("msedgedriver-tool")
+                .join("policy.toml")
+        })
+    } else {
+        Some(PathBuf::from("/etc/msedgedriver-tool/policy.toml"))
+    }
+}
+
+fn registry_policy_path() -> Option<PathBuf> {
+    if !cfg!(windows) {
+        return None;
+    }
+
+    registry::read_string("HKLM\\SOFTWARE\\Policies\\msedgedriver-tool", "PolicyFile")
+        .ok()
+        .flatten()
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| PathBuf::from(path.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirrored(mirror: &str) -> Policy {
+        Policy {
+            mirror: Some(mirror.into()),
+            ..Policy::default()
+        }
+    }
+
+    #[test]
+    fn allows_only_urls_under_the_mirror() {
+        let policy = mirrored("https://mirror.corp/edge/");
+        for url in [
+            "https://mirror.corp/edge",
+            "https://mirror.corp/edge/",
+            "https://mirror.corp/edge/120.0.2210.91/edgedriver_win64.zip",
+            "https://MIRROR.corp:443/edge/LATEST_STABLE",
+        ] {
+            assert!(policy.check_url(url).is_ok(), "{}", url);
+        }
+        for url in [
+            "http://mirror.corp/edge/LATEST_STABLE",
+            "https://mirror.corp:8443/edge/LATEST_STABLE",
+            "https://mirror.corp/other/LATEST_STABLE",
+            "not a url",
+        ] {
+            assert!(policy.check_url(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn rejects_look_alike_hosts() {
+        let policy = mirrored("https://mirror.corp");
+        assert!(policy
+            .check_url("https://mirror.corp/LATEST_STABLE")
+            .is_ok());
+        for url in [
+            "https://mirror.corp.evil.com/LATEST_STABLE",
+            "https://mirror.corporate.io/",
+            "https://mirror.corp@evil.com/LATEST_STABLE",
+            "https://evil.com/https://mirror.corp/",
+        ] {
+            assert!(policy.check_url(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn rejects_look_alike_paths() {
+        let policy = mirrored("https://mirror.corp/edge");
+        for url in [
+            "https://mirror.corp/edgedriver/LATEST_STABLE",
+            "https://mirror.corp/edge-evil/LATEST_STABLE",
+            "https://mirror.corp/",
+        ] {
+            assert!(policy.check_url(url).is_err(), "{}", url);
+        }
+    }
+}
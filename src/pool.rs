@@ -0,0 +1,103 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+use crate::{driver_filename, flag_value, has_flag, output, paths, timestamp};
+
+/// Names the pool's current driver, for tools that can't follow the `current` link.
+const CURRENT_MANIFEST: &str = "current.json";
+pub const CURRENT_LINK: &str = "current";
+
+#[derive(Serialize)]
+struct Current<'a> {
+    version: &'a str,
+    path: &'a Path,
+    updated_at: String,
+}
+
+/// Reads `--driver-pool`, a directory keeping every installed version side by side,
+/// or the `--project`'s subdirectory of it.
+pub fn from_args(args: &[String]) -> Result<Option<PathBuf>> {
+    let Some(pool) = flag_value(args, "--driver-pool") else {
+        return Ok(None);
+    };
+    for conflicting in ["--output", "--filename", "--scope", "--install-to-path"] {
+        if has_flag(args, conflicting) {
+            bail!(
+                "--driver-pool decides where drivers go, so it can't be combined with {}",
+                conflicting
+            );
+        }
+    }
+    Ok(Some(paths::namespaced(PathBuf::from(pool))))
+}
+
+/// Where the `version` driver is kept in `pool`.
+pub fn driver_path(pool: &Path, version: &str, platform: &str) -> PathBuf {
+    pool.join(version.trim()).join(driver_filename(platform))
+}
+
+/// The version `current.json` names as the pool's current driver, if any.
+pub fn current_version(pool: &Path) -> Option<String> {
+    let manifest = fs::read(pool.join(CURRENT_MANIFEST)).ok()?;
+    let current: serde_json::Value = serde_json::from_slice(&manifest).ok()?;
+    current["version"].as_str().map(String::from)
+}
+
+/// Makes `version` the pool's current driver, through both `current.json` and a
+/// `current` link to its directory.
+pub fn set_current(pool: &Path, version: &str, platform: &str) -> Result<()> {
+    let version = version.trim();
+    let dir = std::path::absolute(pool.join(version))?;
+    let manifest = pool.join(CURRENT_MANIFEST);
+    let current = Current {
+        version,
+        path: &dir.join(driver_filename(platform)),
+        updated_at: timestamp::now(),
+    };
+    fs::write(&manifest, serde_json::to_string_pretty(&current)? + "\n")
+        .with_context(|| format!("unable to write {}", manifest.display()))?;
+
+    // the manifest is the record, the link only a convenience
+    if let Err(e) = link(&dir, &pool.join(CURRENT_LINK)) {
+        eprintln!(
+            "warning: unable to point {} at {}: {:#}",
+            CURRENT_LINK, version, e
+        );
+    }
+    output::progress(&format!("pool's current driver is now {}", version));
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link(target: &Path, link: &Path) -> Result<()> {
+    let _ = fs::remove_file(link);
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn link(target: &Path, link: &Path) -> Result<()> {
+    // a junction needs no privileges, unlike a directory symlink
+    crate::sandbox::check("running mklink for the pool's current junction")?;
+    let _ = fs::remove_dir(link);
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(link)
+        .arg(target)
+        .stdout(std::process::Stdio::null())
+        .status()?;
+    if !status.success() {
+        bail!("mklink /J exited with {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn link(_target: &Path, _link: &Path) -> Result<()> {
+    bail!("links aren't supported on this platform")
+}
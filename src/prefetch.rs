@@ -0,0 +1,105 @@
+use std::fs;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    cache, channel, checksums,
+    download::{download_driver, DownloadOptions, ExtractLimits},
+    driver_filename, enforce_floor, fetch_manifest, find_blob, flag_value, has_flag, host_platform,
+    lock, metrics, mirror, output, parallel, policy,
+    receipt::Receipt,
+    ALL_PLATFORMS,
+};
+
+/// Resolves and caches the newest driver of every requested channel, concurrently.
+pub fn prefetch(args: &[String]) -> Result<()> {
+    let channels =
+        channel::parse_list(&flag_value(args, "--channels").unwrap_or_else(|| "stable".into()))?;
+    if channels.is_empty() {
+        bail!("prefetch needs at least one channel");
+    }
+    let host = [host_platform()];
+    let platforms: &[&str] = if has_flag(args, "--all-platforms") {
+        ALL_PLATFORMS
+    } else {
+        &host
+    };
+    if policy::get().require_sha256 && !checksums::configured() {
+        bail!("policy requires a checksum source to verify prefetched archives against");
+    }
+    let limits = ExtractLimits::from_args(args)?;
+    let parallel = parallel::limit(args)?;
+    let min_version = flag_value(args, "--min-driver-version");
+    let manifest = fetch_manifest()?;
+
+    let mut jobs = Vec::new();
+    for &channel in &channels {
+        let version = channel.latest_version()?;
+        enforce_floor(&version, min_version.as_deref())?;
+        for &platform in platforms {
+            jobs.push((channel, version.clone(), platform));
+        }
+    }
+    let results = parallel::map(&jobs, parallel, |(_, version, platform)| {
+        fetch_driver(version, platform, &manifest, limits)
+    });
+
+    let mut failed = 0;
+    for ((channel, version, platform), result) in jobs.iter().zip(results) {
+        match result {
+            Ok(()) => output::progress(&format!(
+                "{} {}: {} cached",
+                channel.as_str(),
+                platform,
+                version
+            )),
+            Err(e) => {
+                failed += 1;
+                output::line(&format!("{} {}: FAILED: {}", channel.as_str(), platform, e));
+                output::problem(&format!(
+                    "prefetch of {} {} failed: {}",
+                    channel.as_str(),
+                    platform,
+                    e
+                ));
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} drivers failed to prefetch", failed, jobs.len());
+    }
+    Ok(())
+}
+
+fn fetch_driver(
+    version: &str,
+    platform: &str,
+    manifest: &str,
+    limits: ExtractLimits,
+) -> Result<()> {
+    let dest = cache::driver_path(version, platform, driver_filename(platform))?;
+    // one process downloads an entry while others wait and then find it there
+    let _lock = lock::acquire(&dest)?;
+    if dest.is_file() {
+        metrics::record_cache_hit();
+        return Ok(());
+    }
+
+    let blob = find_blob(manifest, version, platform)?
+        .ok_or_else(|| anyhow!("no {} driver published for {}", platform, version))?;
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let options = DownloadOptions {
+        sha256: None,
+        limits,
+        include: Vec::new(),
+        size_hint: None,
+    };
+    let url = mirror::select(&blob.url, &blob.name);
+    let hashes = download_driver(&url, driver_filename(platform), &dest, &options)?;
+    Receipt::new(version, platform, &url, &hashes).write(&dest)?;
+
+    Ok(())
+}
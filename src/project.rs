@@ -0,0 +1,56 @@
+//! `--version-from`: the driver version pinned in a project's own files, so the pin
+//! lives beside the code it is tested with rather than in CI configuration.
+
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+/// The keys a project file may pin the version under, in `package.json` or under
+/// `[package.metadata]` or `[workspace.metadata]` in `Cargo.toml`.
+const KEYS: &[&str] = &["edgedriver", "msedgedriver"];
+
+/// The version pinned in `path`: the `edgedriver` field of a `package.json`, the
+/// `edgedriver` metadata of a `Cargo.toml`, or the first line of any other file,
+/// such as `.edge-version`, that isn't blank or a `#` comment.
+///
+/// Either key may also be a table with a `version`, e.g.
+/// `[package.metadata.edgedriver] version = "120.0.2210.91"`.
+pub fn version_from(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("unable to read --version-from {}", path.display()))?;
+    let what = || format!("{} names no edgedriver version", path.display());
+
+    let version = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => {
+            let json: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("invalid JSON in {}", path.display()))?;
+            KEYS.iter()
+                .find_map(|key| match json.get(key)? {
+                    serde_json::Value::String(version) => Some(version.clone()),
+                    other => other.get("version")?.as_str().map(str::to_string),
+                })
+                .ok_or_else(|| anyhow!(what()))?
+        }
+        Some("toml") => {
+            let toml: toml::Value = toml::from_str(&contents)
+                .with_context(|| format!("invalid TOML in {}", path.display()))?;
+            ["package", "workspace"]
+                .iter()
+                .filter_map(|section| toml.get(section)?.get("metadata"))
+                .find_map(|metadata| {
+                    KEYS.iter().find_map(|key| match metadata.get(key)? {
+                        toml::Value::String(version) => Some(version.clone()),
+                        other => other.get("version")?.as_str().map(str::to_string),
+                    })
+                })
+                .ok_or_else(|| anyhow!(what()))?
+        }
+        _ => contents
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!(what()))?,
+    };
+    Ok(version.trim().to_string())
+}
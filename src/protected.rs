@@ -0,0 +1,131 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Error};
+use sha2::{Digest, Sha256};
+
+use crate::{filesystem::Fs, paths};
+
+// folders Controlled Folder Access protects by default, relative to the profile
+const PROTECTED_FOLDERS: &[&str] = &[
+    "Documents",
+    "Desktop",
+    "Pictures",
+    "Videos",
+    "Music",
+    "Favorites",
+];
+
+// ERROR_CLOUD_FILE_PROVIDER_NOT_RUNNING and its siblings, up to ERROR_CLOUD_FILE_REQUEST_TIMEOUT
+const CLOUD_FILE_ERRORS: std::ops::RangeInclusive<i32> = 362..=426;
+
+/// Turns a failure writing `dest` into an error explaining the likely cause when
+/// the directory is guarded by Controlled Folder Access or a sync client.
+pub fn write_error(dest: &Path, e: io::Error) -> Error {
+    let dir = dest
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let absolute = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let alternative = paths::cache_dir()
+        .map(|dir| dir.join("bin").display().to_string())
+        .unwrap_or_else(|| "a directory outside your profile".into());
+
+    match cause(&absolute, &e) {
+        Some(cause) => anyhow!(
+            "unable to write {}: {}\n{}; run from another directory such as {}, \
+             or allow msedgedriver-tool through Controlled Folder Access",
+            dest.display(),
+            e,
+            cause,
+            alternative
+        ),
+        None => anyhow!("unable to write {}: {}", dest.display(), e),
+    }
+}
+
+fn cause(dir: &Path, e: &io::Error) -> Option<String> {
+    if e.raw_os_error()
+        .is_some_and(|code| CLOUD_FILE_ERRORS.contains(&code))
+    {
+        return Some(format!(
+            "{} is managed by a file sync client that refused the write",
+            dir.display()
+        ));
+    }
+    if e.kind() != io::ErrorKind::PermissionDenied {
+        return None;
+    }
+
+    if let Some(root) = sync_root(dir) {
+        return Some(format!(
+            "{} is inside the synced folder {}, whose client may be holding or blocking new executables",
+            dir.display(),
+            root.display()
+        ));
+    }
+    protected_folder(dir).map(|folder| {
+        format!(
+            "{} is inside {}, which Windows Defender Controlled Folder Access blocks unknown programs from writing to",
+            dir.display(),
+            folder.display()
+        )
+    })
+}
+
+fn sync_root(dir: &Path) -> Option<PathBuf> {
+    ["OneDrive", "OneDriveCommercial", "OneDriveConsumer"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .find(|root| dir.starts_with(root))
+}
+
+fn protected_folder(dir: &Path) -> Option<PathBuf> {
+    if !cfg!(windows) {
+        return None;
+    }
+    let profile = PathBuf::from(std::env::var_os("USERPROFILE")?);
+    PROTECTED_FOLDERS
+        .iter()
+        .map(|folder| profile.join(folder))
+        .find(|folder| dir.starts_with(folder))
+}
+
+/// Checks the driver just extracted to `dest` is still there as `len` bytes hashing
+/// to `sha256`, and still executable, since antivirus and EDR software can remove
+/// or lock a new executable moments after it was written without the write failing.
+pub fn check_intact(dest: &Path, fs: &dyn Fs, len: u64, sha256: &str) -> Result<(), Error> {
+    fs.settle();
+    let mut hasher = Sha256::new();
+    let found = fs
+        .open(dest)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher));
+    let problem = match found {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => "disappeared".to_string(),
+        Err(e) => format!("can no longer be read ({})", e),
+        Ok(read) if read != len => format!("shrank from {} to {} bytes", len, read),
+        Ok(_) if format!("{:x}", hasher.finalize()) != sha256 => {
+            "no longer matches what was extracted".to_string()
+        }
+        Ok(_) => match fs.unix_mode(dest) {
+            Ok(Some(mode)) if mode & 0o100 == 0 => "lost its executable permission".to_string(),
+            _ => return Ok(()),
+        },
+    };
+    let dir = dest
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Err(anyhow!(
+        "{} {} right after it was extracted, which usually means antivirus or EDR \
+         software quarantined or locked it; check its quarantine history, then have {} \
+         excluded, running with --verify-signature to confirm the driver is \
+         Microsoft's signed build before allowing it",
+        dest.display(),
+        problem,
+        dir.display()
+    ))
+}
@@ -0,0 +1,133 @@
+use std::path::Path;
+
+#[cfg(target_os = "macos")]
+const ATTRIBUTE: &str = "com.apple.quarantine";
+
+/// Removes the Gatekeeper quarantine attribute from an extracted driver, unless `keep`
+/// is set, so the first headless launch isn't blocked by a prompt nobody can answer.
+#[cfg(target_os = "macos")]
+pub fn clear(path: &Path, keep: bool) -> anyhow::Result<()> {
+    use std::process::Command;
+
+    if crate::sandbox::active() {
+        crate::output::note_warning(
+            "quarantine-kept",
+            &format!(
+                "{} may be quarantined, --no-detect forbids running xattr to clear it",
+                path.display()
+            ),
+        );
+        return Ok(());
+    }
+    let listed = Command::new("xattr").arg(path).output()?;
+    let quarantined = String::from_utf8_lossy(&listed.stdout)
+        .lines()
+        .any(|line| line.trim() == ATTRIBUTE);
+    if !quarantined {
+        return Ok(());
+    }
+
+    if keep {
+        eprintln!(
+            "warning: {} is quarantined, Gatekeeper will prompt before it first runs which blocks headless use",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let status = Command::new("xattr")
+        .args(["-d", ATTRIBUTE])
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        anyhow::bail!(
+            "unable to remove {} from {}, xattr exited with {}",
+            ATTRIBUTE,
+            path.display(),
+            status
+        );
+    }
+    crate::output::progress(&format!("removed {} from {}", ATTRIBUTE, path.display()));
+
+    Ok(())
+}
+
+/// Quarantine only exists on macOS.
+#[cfg(not(target_os = "macos"))]
+pub fn clear(_path: &Path, _keep: bool) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// What to do with the `Zone.Identifier` stream, windows' mark of the web, on an
+/// extracted driver. Some SmartScreen and EDR policies block binaries that carry
+/// it, others block binaries that lack it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// Leave whatever the file system gave the driver; the default.
+    Keep,
+    Strip,
+    /// Mark the driver as downloaded from the internet, naming where from.
+    Set,
+}
+
+impl Zone {
+    /// Reads `--zone-identifier keep|strip|set`.
+    pub fn from_args(args: &[String]) -> anyhow::Result<Self> {
+        match crate::flag_value(args, "--zone-identifier").as_deref() {
+            None | Some("keep") => Ok(Zone::Keep),
+            Some("strip") => Ok(Zone::Strip),
+            Some("set") => Ok(Zone::Set),
+            Some(other) => anyhow::bail!(
+                "invalid --zone-identifier {}, expected keep, strip or set",
+                other
+            ),
+        }
+    }
+}
+
+/// Strips or sets the mark of the web on `path`, downloaded from `source`.
+#[cfg(windows)]
+pub fn apply_zone(path: &Path, zone: Zone, source: &str) -> anyhow::Result<()> {
+    let mut stream = path.as_os_str().to_os_string();
+    stream.push(":Zone.Identifier");
+    match zone {
+        Zone::Keep => {}
+        Zone::Strip => match std::fs::remove_file(&stream) {
+            Ok(()) => crate::output::progress(&format!(
+                "removed the mark of the web from {}",
+                path.display()
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => anyhow::bail!(
+                "unable to remove the mark of the web from {}: {}",
+                path.display(),
+                e
+            ),
+        },
+        Zone::Set => {
+            // zone 3 is the internet zone, as browsers record it for downloads
+            let contents = format!("[ZoneTransfer]\r\nZoneId=3\r\nHostUrl={}\r\n", source);
+            std::fs::write(&stream, contents).map_err(|e| {
+                anyhow::anyhow!(
+                    "unable to mark {} as downloaded from the web: {}",
+                    path.display(),
+                    e
+                )
+            })?;
+            crate::output::progress(&format!(
+                "marked {} as downloaded from the web",
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The mark of the web only exists on windows.
+#[cfg(not(windows))]
+pub fn apply_zone(_path: &Path, zone: Zone, _source: &str) -> anyhow::Result<()> {
+    if zone != Zone::Keep {
+        crate::explain::step("--zone-identifier has no effect outside windows");
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{download::Hashes, timestamp};
+
+/// What was installed at a path and where it came from, kept beside the driver so
+/// `verify` can later tell whether the file is still the one we put there.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Receipt {
+    pub version: String,
+    pub platform: String,
+    pub source: String,
+    pub archive_sha256: String,
+    pub driver_sha256: String,
+    pub installed_at: String,
+    /// When the archive was downloaded, earlier than `installed_at` for a driver
+    /// copied from the cache; empty in receipts older than the field.
+    #[serde(default)]
+    pub downloaded_at: String,
+    /// The version of the tool that installed the driver.
+    #[serde(default)]
+    pub tool_version: String,
+    /// The browser version the driver was chosen for, when that isn't the driver's
+    /// own version, as geckodriver's never is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser_version: Option<String>,
+}
+
+impl Receipt {
+    pub fn new(version: &str, platform: &str, source: &str, hashes: &Hashes) -> Self {
+        let now = timestamp::now();
+        Self {
+            version: version.trim().into(),
+            platform: platform.into(),
+            source: source.into(),
+            archive_sha256: hashes.archive.clone(),
+            driver_sha256: hashes.driver.clone(),
+            installed_at: now.clone(),
+            downloaded_at: now,
+            tool_version: env!("CARGO_PKG_VERSION").into(),
+            browser_version: None,
+        }
+    }
+
+    /// This receipt for a driver chosen to match browser `version`.
+    pub fn for_browser(mut self, version: &str) -> Self {
+        if version.trim() != self.version {
+            self.browser_version = Some(version.trim().into());
+        }
+        self
+    }
+
+    /// Whether this receipt's driver is the one for browser `version`.
+    pub fn matches_browser(&self, version: &str) -> bool {
+        let version = version.trim();
+        self.version == version || self.browser_version.as_deref() == Some(version)
+    }
+
+    /// This receipt for a driver copied out of the cache, downloaded when the
+    /// cached copy's receipt `cached` says.
+    pub fn copied_from(mut self, cached: &Receipt) -> Self {
+        if !cached.downloaded_at.is_empty() {
+            self.downloaded_at = cached.downloaded_at.clone();
+        }
+        self
+    }
+
+    /// Writes the receipt for the driver at `driver`.
+    pub fn write(&self, driver: &Path) -> Result<()> {
+        let path = path_for(driver);
+        fs::write(&path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("unable to write install receipt {}", path.display()))
+    }
+
+    /// Reads the receipt written for the driver at `driver`, if there is one.
+    pub fn read(driver: &Path) -> Result<Option<Self>> {
+        let path = path_for(driver);
+        match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map(Some)
+                .with_context(|| format!("invalid install receipt {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// `msedgedriver.exe` is described by `msedgedriver.exe.receipt.json` next to it.
+pub fn path_for(driver: &Path) -> PathBuf {
+    let mut name = driver.file_name().unwrap_or_default().to_os_string();
+    name.push(".receipt.json");
+    driver.with_file_name(name)
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{bail, Result};
+
+static POWERSHELL: AtomicBool = AtomicBool::new(false);
+static READER: Mutex<Option<Arc<dyn RegistryReader>>> = Mutex::new(None);
+
+/// Where registry values are read from, so detection can be tried against a
+/// [`MemoryRegistry`] on any machine.
+pub trait RegistryReader: Send + Sync {
+    /// The string `value` of `subkey` in `hive`, `HKLM` or `HKCU`, or `None` if the
+    /// key or value doesn't exist.
+    fn read_string(&self, hive: &str, subkey: &str, value: &str) -> Result<Option<String>>;
+}
+
+/// This machine's registry, read directly or through PowerShell with
+/// `--registry-powershell`; nothing exists in it off windows.
+pub struct SystemRegistry;
+
+impl RegistryReader for SystemRegistry {
+    fn read_string(&self, hive: &str, subkey: &str, value: &str) -> Result<Option<String>> {
+        // wine has the registry but seldom powershell
+        if POWERSHELL.load(Ordering::Relaxed) && !crate::wine::detected() {
+            return read_with_powershell(hive, subkey, value);
+        }
+        read_native(hive, subkey, value)
+    }
+}
+
+/// A registry holding only the values set on it, matched without regard to case
+/// as windows does. Clones share their values.
+#[derive(Clone, Default)]
+pub struct MemoryRegistry {
+    values: Arc<Mutex<HashMap<(String, String), String>>>,
+}
+
+impl MemoryRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `value` of `key`, given as e.g. `HKLM\SOFTWARE\Microsoft\...`, to `data`.
+    pub fn set(&self, key: &str, value: &str, data: &str) -> &Self {
+        self.values
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                (key.to_ascii_lowercase(), value.to_ascii_lowercase()),
+                data.into(),
+            );
+        self
+    }
+}
+
+impl RegistryReader for MemoryRegistry {
+    fn read_string(&self, hive: &str, subkey: &str, value: &str) -> Result<Option<String>> {
+        let key = format!("{}\\{}", hive, subkey).to_ascii_lowercase();
+        Ok(self
+            .values
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(key, value.to_ascii_lowercase()))
+            .cloned())
+    }
+}
+
+/// Reads every later registry value through `reader` rather than the system's.
+pub fn set_registry(reader: impl RegistryReader + 'static) {
+    *READER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(reader));
+}
+
+/// The reader [`set_registry`] set, or the [`SystemRegistry`].
+fn reader() -> Arc<dyn RegistryReader> {
+    READER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(SystemRegistry))
+}
+
+/// Reads the registry through PowerShell rather than directly, from `--registry-powershell`.
+pub fn use_powershell() {
+    POWERSHELL.store(true, Ordering::Relaxed);
+}
+
+/// The string `value` of `key`, given as e.g. `HKLM\SOFTWARE\Microsoft\...`, or `None`
+/// if the key or value doesn't exist.
+pub fn read_string(key: &str, value: &str) -> Result<Option<String>> {
+    let Some((hive, subkey)) = key.split_once('\\') else {
+        bail!("invalid registry key {}", key);
+    };
+    if !matches!(hive, "HKLM" | "HKCU") {
+        bail!("unsupported registry hive {}", hive);
+    }
+    crate::sandbox::check("reading the registry")?;
+
+    reader().read_string(hive, subkey, value)
+}
+
+#[cfg(windows)]
+fn read_native(hive: &str, subkey: &str, value: &str) -> Result<Option<String>> {
+    use std::ptr;
+
+    use windows_sys::Win32::{
+        Foundation::{ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA, ERROR_SUCCESS},
+        System::Registry::{RegGetValueW, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ},
+    };
+
+    let root = if hive == "HKLM" {
+        HKEY_LOCAL_MACHINE
+    } else {
+        HKEY_CURRENT_USER
+    };
+    let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let (subkey, value) = (wide(subkey), wide(value));
+
+    let mut buf = vec![0u16; 64];
+    loop {
+        let mut size = (buf.len() * 2) as u32;
+        // SAFETY: the names are nul terminated and `size` is the byte length of `buf`
+        let status = unsafe {
+            RegGetValueW(
+                root,
+                subkey.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ,
+                ptr::null_mut(),
+                buf.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        match status {
+            ERROR_SUCCESS => {
+                // `size` counts the trailing nul
+                let len = (size as usize / 2).saturating_sub(1);
+                return Ok(Some(String::from_utf16_lossy(&buf[..len])));
+            }
+            ERROR_MORE_DATA => buf.resize(size as usize / 2 + 1, 0),
+            ERROR_FILE_NOT_FOUND => return Ok(None),
+            other => bail!(
+                "unable to read {}\\{}: error {}",
+                hive,
+                String::from_utf16_lossy(&subkey[..subkey.len() - 1]),
+                other
+            ),
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn read_native(_hive: &str, _subkey: &str, _value: &str) -> Result<Option<String>> {
+    Ok(None)
+}
+
+fn read_with_powershell(hive: &str, subkey: &str, value: &str) -> Result<Option<String>> {
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command"])
+        .arg(format!(
+            "Get-ItemProperty -Path '{}:\\{}' -ErrorAction Stop | ForEach-Object {{$_.{}}}",
+            hive,
+            subkey.replace('\'', "''"),
+            value
+        ))
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(stdout).filter(|s| output.status.success() && !s.is_empty()))
+}
@@ -0,0 +1,88 @@
+//! Opt-in reporting of how each run went, for teams watching provisioning across
+//! many machines: nothing is reported unless `--report-endpoint` is given or a
+//! library caller sets a [`Reporter`].
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{explain, http, metrics};
+
+/// How long a report may take before it is abandoned, so a slow collector never
+/// holds up a build.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What one run did, as reported to a [`Reporter`].
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct Report {
+    pub tool_version: &'static str,
+    /// The subcommand, e.g. `install`, or `ensure_driver` for the library.
+    pub command: String,
+    /// The driver installed or found up to date, if the run got that far.
+    pub driver_version: Option<String>,
+    /// Whether a download was avoided by a cached or unchanged copy.
+    pub cache_hit: bool,
+    pub duration_ms: u64,
+    /// `success` or `failure`.
+    pub outcome: &'static str,
+    /// The exit code of a failure, see [`Error::exit_code`](crate::Error::exit_code).
+    pub exit_code: Option<i32>,
+}
+
+/// Receives a [`Report`] at the end of each run, e.g. to forward it to monitoring.
+pub trait Reporter: Send + Sync {
+    /// Called once per run; failures should be swallowed, a run never fails for
+    /// its report.
+    fn report(&self, report: &Report);
+}
+
+/// Posts each report as JSON to a URL, for `--report-endpoint`.
+pub struct Endpoint(pub String);
+
+impl Reporter for Endpoint {
+    fn report(&self, report: &Report) {
+        if http::offline() || http::dry_run() {
+            return;
+        }
+        match http::post(&self.0)
+            .timeout(TIMEOUT)
+            .send_json(serde_json::to_value(report).unwrap_or_default())
+        {
+            Ok(_) => explain::step(format!("reported the run to {}", self.0)),
+            Err(e) => explain::step(format!("unable to report the run to {}: {}", self.0, e)),
+        }
+    }
+}
+
+static REPORTER: Mutex<Option<Arc<dyn Reporter>>> = Mutex::new(None);
+
+/// Sends a [`Report`] of every later run to `reporter`, replacing any set before.
+pub fn set_reporter(reporter: impl Reporter + 'static) {
+    *REPORTER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(reporter));
+}
+
+/// Reports a run of `command` that took `duration` and ended with `exit_code`, or
+/// succeeded without one, if a reporter is set.
+pub fn emit(command: &str, duration: Duration, exit_code: Option<i32>) {
+    let reporter = REPORTER.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let Some(reporter) = reporter else {
+        return;
+    };
+    reporter.report(&Report {
+        tool_version: env!("CARGO_PKG_VERSION"),
+        command: command.into(),
+        driver_version: metrics::installed_version(),
+        cache_hit: metrics::cache_hits() > 0,
+        duration_ms: duration.as_millis().try_into().unwrap_or(u64::MAX),
+        outcome: if exit_code.is_none() {
+            "success"
+        } else {
+            "failure"
+        },
+        exit_code,
+    });
+}
@@ -0,0 +1,94 @@
+//! `resolve`: the archive URL an install would download, printed alone for
+//! pipelines that fetch through their own hardened client or an artifact proxy
+//! such as Nexus.
+
+use anyhow::Result;
+
+use crate::{
+    checksums, fallback::Fallback, fetch_manifest, flag_value, has_flag, info, lockfile::Lockfile,
+    mirror, output, resume, target_platform, Channel,
+};
+
+/// The archive a driver version resolved to.
+pub struct Resolved {
+    pub version: String,
+    pub platform: &'static str,
+    pub url: String,
+}
+
+/// Resolves `--version`, or the driver the installed browser needs, on
+/// `--platform` as an install would: substituting per `--fallback` or `--match`
+/// and downloading from `--mirror` if one is set.
+pub fn resolved(args: &[String]) -> Result<Resolved> {
+    let wanted = info::version_or_detected(
+        args,
+        flag_value(args, "--version"),
+        "`--version 120.0.2210.91`",
+    )?;
+    let platform = target_platform(args)?;
+    let channel = flag_value(args, "--channel")
+        .map(|channel| channel.parse::<Channel>())
+        .transpose()?
+        .unwrap_or(Channel::Stable);
+    let (version, blob) =
+        Fallback::from_args(args)?.resolve(&fetch_manifest()?, &wanted, platform, channel)?;
+    Ok(Resolved {
+        version,
+        platform,
+        url: mirror::select(&blob.url, &blob.name),
+    })
+}
+
+/// The sha256 the `platform` archive for `version` at `url` must hash to: as the lockfile pins it, else as the
+/// checksum source publishes it, else as downloading it now finds it.
+pub fn sha256(args: &[String], platform: &str, version: &str, url: &str) -> Result<String> {
+    let locked = Lockfile::from_args(args)?.and_then(|lockfile| {
+        lockfile
+            .locked("msedgedriver", platform)
+            .ok()
+            .flatten()
+            .filter(|locked| locked.version == version)
+            .map(|locked| locked.archive_sha256.clone())
+    });
+    let sha256 = match locked {
+        Some(sha256) => sha256,
+        None => match checksums::expected(url)? {
+            Some(sha256) => sha256,
+            // nothing vouches for the archive yet, so hash it as the fetcher will
+            None => {
+                output::progress(&format!("downloading {} to hash it", url));
+                resume::fetch(url, None)?.sha256.clone()
+            }
+        },
+    };
+    Ok(sha256.trim().to_ascii_lowercase())
+}
+
+/// Prints the URL of the archive to download, followed by its sha256 with
+/// `--with-sha256`; everything else goes to stderr.
+pub fn resolve(args: &[String]) -> Result<()> {
+    output::use_stderr();
+    let resolved = resolved(args)?;
+    output::progress(&format!(
+        "msedgedriver {} for {}",
+        resolved.version, resolved.platform
+    ));
+    output::parameter("msedgedriver.version", &resolved.version);
+    output::parameter("msedgedriver.platform", resolved.platform);
+    output::parameter("msedgedriver.url", &resolved.url);
+
+    let sha256 = if has_flag(args, "--with-sha256") {
+        let sha256 = sha256(args, resolved.platform, &resolved.version, &resolved.url)?;
+        output::parameter("msedgedriver.archive_sha256", &sha256);
+        Some(sha256)
+    } else {
+        None
+    };
+    if !output::json() {
+        match sha256 {
+            Some(sha256) => println!("{} {}", resolved.url, sha256),
+            None => println!("{}", resolved.url),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,576 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    chunked,
+    events::{self, Event},
+    explain, flag_value, has_flag, http, metrics, output, paths,
+};
+
+// how much is downloaded between state file updates
+const SAVE_EVERY: u64 = 1 << 20;
+
+/// Which downloaded archives stay in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keeping {
+    /// Those the server gave validators for, so they can be revalidated.
+    Revalidatable,
+    /// Every one, `--keep-archive`, so `--reextract` can use them.
+    All,
+    /// None, `--discard-archive`, dropping any kept before.
+    None,
+}
+
+static KEEPING: OnceLock<Keeping> = OnceLock::new();
+static CONNECTIONS: OnceLock<usize> = OnceLock::new();
+
+/// Reads `--keep-archive`, `--discard-archive` and `--connections`.
+pub fn configure(args: &[String]) -> Result<()> {
+    if let Some(n) = flag_value(args, "--connections") {
+        let connections = match n.trim().parse::<usize>() {
+            Ok(n) if (1..=chunked::MAX_CONNECTIONS).contains(&n) => n,
+            _ => bail!(
+                "invalid --connections {:?}, expected 1 to {}",
+                n,
+                chunked::MAX_CONNECTIONS
+            ),
+        };
+        let _ = CONNECTIONS.set(connections);
+    }
+    let keeping = match (
+        has_flag(args, "--keep-archive"),
+        has_flag(args, "--discard-archive"),
+    ) {
+        (true, true) => bail!("--keep-archive and --discard-archive contradict each other"),
+        (true, false) => Keeping::All,
+        (false, true) => Keeping::None,
+        (false, false) => Keeping::Revalidatable,
+    };
+    let _ = KEEPING.set(keeping);
+    Ok(())
+}
+
+fn keeping() -> Keeping {
+    *KEEPING.get().unwrap_or(&Keeping::Revalidatable)
+}
+
+/// Progress of an interrupted download, kept beside its partial body so a later
+/// run can pick up where an aborted one stopped.
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    url: String,
+    /// Full size of the body, when the server said.
+    expected_size: Option<u64>,
+    /// Validator the partial body was fetched under, so a changed file restarts.
+    etag: Option<String>,
+    written: u64,
+    /// Hex SHA-256 of the first `written` bytes, catching a corrupted partial body.
+    sha256_so_far: String,
+}
+
+/// The validators of a complete archive kept in the cache, so the next fetch of
+/// the same url can ask the server whether it changed rather than download it.
+#[derive(Debug, Serialize, Deserialize)]
+struct Kept {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    len: u64,
+}
+
+/// A finished download, streamed to a file rather than held in memory, which is
+/// deleted once this is dropped unless it is kept in the cache.
+pub struct Downloaded {
+    file: Option<File>,
+    path: PathBuf,
+    kept: bool,
+    pub len: u64,
+    /// Hex SHA-256 of the whole body, hashed as it arrived.
+    pub sha256: String,
+}
+
+impl Read for Downloaded {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.as_mut().map_or(Ok(0), |file| file.read(buf))
+    }
+}
+
+impl Seek for Downloaded {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file.as_mut().map_or(Ok(0), |file| file.seek(pos))
+    }
+}
+
+impl Drop for Downloaded {
+    fn drop(&mut self) {
+        // windows can't delete a file that is still open
+        drop(self.file.take());
+        if !self.kept {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// A download whose connection dropped part way, saved to be resumed.
+#[derive(Debug)]
+struct Interrupted {
+    url: String,
+    written: u64,
+}
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "download of {} was interrupted after {} bytes, rerun to resume",
+            self.url, self.written
+        )
+    }
+}
+
+/// Downloads `url` in full to disk, resuming a previous partial download of it
+/// when possible, or reusing the copy kept from the last download if the server
+/// says it is unchanged. `size_hint` is shown as the download's size if the
+/// server doesn't send one.
+pub fn fetch(url: &str, size_hint: Option<u64>) -> Result<Downloaded> {
+    let retries = http::retries();
+    let mut attempt = 0;
+    loop {
+        match fetch_once(url, size_hint, true) {
+            Err(e) if attempt < retries && e.downcast_ref::<Interrupted>().is_some() => {
+                attempt += 1;
+                explain::step(format!("{:#}, retry {} of {}", e, attempt, retries));
+                events::emit(Event::RetryAttempted {
+                    url,
+                    attempt,
+                    retries,
+                    delay: Duration::ZERO,
+                });
+                output::progress("connection dropped, resuming the download");
+            }
+            result => return result,
+        }
+    }
+}
+
+/// One attempt at [`fetch`], over several connections if `ranged` and `--connections`
+/// allow.
+fn fetch_once(url: &str, size_hint: Option<u64>, ranged: bool) -> Result<Downloaded> {
+    let (state_path, part_path) = state_paths(url);
+    if let Some(dir) = part_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut hasher = Sha256::new();
+    let previous = resumable(url, &state_path, &part_path, &mut hasher);
+    discard_kept(url);
+    let kept = previous.is_none().then(|| kept(url)).flatten();
+
+    let mut request = http::get(url);
+    if let Some((validators, _)) = &kept {
+        if let Some(etag) = &validators.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+    }
+    if let Some(state) = &previous {
+        explain::step(format!(
+            "resuming {} at byte {} of {}",
+            url,
+            state.written,
+            state
+                .expected_size
+                .map_or_else(|| "unknown".into(), |size| size.to_string())
+        ));
+        request = request.set("Range", &format!("bytes={}-", state.written));
+        if let Some(etag) = &state.etag {
+            request = request.set("If-Range", etag);
+        }
+    }
+    let response = match http::call(request) {
+        Ok(response) => response,
+        // e.g. 416 for a range the server no longer has, so start afresh
+        Err(e) if previous.is_some() => {
+            explain::step(format!("resuming {} failed ({}), restarting", url, e));
+            let _ = fs::remove_file(&state_path);
+            return fetch_once(url, size_hint, ranged);
+        }
+        Err(e) => return Err(e),
+    };
+
+    if let Some((validators, path)) = kept.filter(|_| response.status() == 304) {
+        match reuse(&path, validators.len) {
+            Ok(downloaded) => {
+                explain::step(format!("{} not modified since it was last downloaded", url));
+                output::progress("archive unchanged, reusing the cached copy");
+                metrics::record_cache_hit();
+                return Ok(downloaded);
+            }
+            Err(e) => {
+                explain::step(format!(
+                    "cached copy of {} unusable ({}), refetching",
+                    url, e
+                ));
+                let _ = fs::remove_file(&path);
+                return fetch_once(url, size_hint, ranged);
+            }
+        }
+    }
+
+    let resumed = previous.filter(|_| response.status() == 206);
+    if resumed.is_none() {
+        hasher = Sha256::new();
+    }
+    let mut written = resumed.as_ref().map_or(0, |state| state.written);
+    let expected_size = match &resumed {
+        Some(state) => state.expected_size,
+        None => response
+            .header("Content-Length")
+            .and_then(|len| len.parse().ok()),
+    };
+    let etag = response.header("ETag").map(str::to_string);
+    events::emit(Event::DownloadStarted {
+        url,
+        size: expected_size.or(size_hint),
+    });
+    let last_modified = response.header("Last-Modified").map(str::to_string);
+
+    let connections = CONNECTIONS
+        .get()
+        .map_or(1, |&n| chunked::split(expected_size.unwrap_or_default(), n));
+    let accepts_ranges = response
+        .header("Accept-Ranges")
+        .is_some_and(|units| units.eq_ignore_ascii_case("bytes"));
+    if let (true, None, true, Some(total)) = (
+        ranged,
+        &resumed,
+        connections > 1 && accepts_ranges,
+        expected_size,
+    ) {
+        // the ranges are fetched afresh, so this response's body isn't needed
+        drop(response);
+        let _ = fs::remove_file(&state_path);
+        return match chunked::fetch(url, total, etag.as_deref(), &part_path, connections) {
+            Ok(sha256) => finish(
+                part_path,
+                Kept {
+                    url: url.into(),
+                    etag,
+                    last_modified,
+                    len: total,
+                },
+                sha256,
+            ),
+            Err(e) if http::check_cancelled().is_ok() => {
+                explain::step(format!(
+                    "downloading in ranges failed ({:#}), using one connection",
+                    e
+                ));
+                fetch_once(url, size_hint, false)
+            }
+            Err(e) => Err(e),
+        };
+    }
+
+    let mut part = if resumed.is_some() {
+        OpenOptions::new().append(true).open(&part_path)?
+    } else {
+        File::create(&part_path)?
+    };
+
+    let save = |part: &mut File, written: u64, hasher: &Sha256| -> Result<()> {
+        part.flush()?;
+        let state = State {
+            url: url.into(),
+            expected_size,
+            etag: etag.clone(),
+            written,
+            sha256_so_far: format!("{:x}", hasher.clone().finalize()),
+        };
+        let _ = fs::write(&state_path, serde_json::to_vec(&state)?);
+        Ok(())
+    };
+    let mut reader = response.into_reader();
+    let mut chunk = [0; 64 << 10];
+    let mut unsaved = 0;
+    loop {
+        if let Err(e) = http::check_cancelled() {
+            output::transfer_done();
+            save(&mut part, written, &hasher)?;
+            return Err(e);
+        }
+        let read = match reader.read(&mut chunk) {
+            Ok(read) => read,
+            Err(e) => {
+                // a dropped connection resumes from everything received, not the
+                // last checkpoint
+                output::transfer_done();
+                save(&mut part, written, &hasher)?;
+                return Err(anyhow::Error::from(e).context(Interrupted {
+                    url: url.into(),
+                    written,
+                }));
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        part.write_all(&chunk[..read])?;
+        hasher.update(&chunk[..read]);
+        written += read as u64;
+        unsaved += read as u64;
+        output::transfer("downloading", written, expected_size.or(size_hint));
+
+        if unsaved >= SAVE_EVERY {
+            unsaved = 0;
+            save(&mut part, written, &hasher)?;
+        }
+    }
+    output::transfer_done();
+
+    if expected_size.is_some_and(|size| size != written) {
+        save(&mut part, written, &hasher)?;
+        bail!(
+            "download of {} ended after {} of {} bytes, rerun to resume",
+            url,
+            written,
+            expected_size.unwrap_or_default()
+        );
+    }
+    drop(part);
+
+    let _ = fs::remove_file(&state_path);
+    let validators = Kept {
+        url: url.into(),
+        etag,
+        last_modified,
+        len: written,
+    };
+    finish(part_path, validators, format!("{:x}", hasher.finalize()))
+}
+
+/// The complete download at `part_path`, moved into the cache if it is to be kept
+/// there.
+fn finish(part_path: PathBuf, validators: Kept, sha256: String) -> Result<Downloaded> {
+    let (path, kept) = match keep(&part_path, &validators) {
+        Some(path) => (path, true),
+        None => (part_path, false),
+    };
+    Ok(Downloaded {
+        file: Some(File::open(&path)?),
+        path,
+        kept,
+        len: validators.len,
+        sha256,
+    })
+}
+
+/// The validators and path of the archive kept from the last download of `url`.
+fn kept(url: &str) -> Option<(Kept, PathBuf)> {
+    kept_any(url)
+        .filter(|(validators, _)| validators.etag.is_some() || validators.last_modified.is_some())
+}
+
+/// Like [`kept`], including an archive kept without validators by `--keep-archive`.
+fn kept_any(url: &str) -> Option<(Kept, PathBuf)> {
+    let (meta_path, body_path) = kept_paths(url)?;
+    let validators: Kept = serde_json::from_slice(&fs::read(meta_path).ok()?).ok()?;
+    let usable =
+        validators.url == url && fs::metadata(&body_path).is_ok_and(|m| m.len() == validators.len);
+    usable.then_some((validators, body_path))
+}
+
+/// Whether an archive of `url` is kept, for `--reextract` to use.
+pub fn has_kept(url: &str) -> bool {
+    kept_any(url).is_some()
+}
+
+/// Opens the archive kept from the last download of `url`, without the network.
+pub fn open_kept(url: &str) -> Result<Downloaded> {
+    let (validators, path) = kept_any(url).ok_or_else(|| {
+        anyhow!(
+            "no cached archive of {}, install without --reextract to download it",
+            url
+        )
+    })?;
+    metrics::record_cache_hit();
+    reuse(&path, validators.len)
+        .map_err(|e| anyhow!("cached archive {} is damaged: {}", path.display(), e))
+}
+
+/// Drops the archive kept from the last download of `url` under `--discard-archive`,
+/// e.g. once its driver came from the cache instead.
+pub fn discard_kept(url: &str) {
+    if keeping() != Keeping::None {
+        return;
+    }
+    if let Some((meta_path, body_path)) = kept_paths(url) {
+        if fs::remove_file(&body_path).is_ok() {
+            explain::step(format!("discarding the cached archive of {}", url));
+        }
+        let _ = fs::remove_file(meta_path);
+    }
+}
+
+/// Moves the completed download at `part_path` into the cache, as `--keep-archive`
+/// and `--discard-archive` say or else if the server gave anything to revalidate
+/// it with, and returns where it went.
+fn keep(part_path: &Path, validators: &Kept) -> Option<PathBuf> {
+    let revalidatable = validators.etag.is_some() || validators.last_modified.is_some();
+    match keeping() {
+        Keeping::None => return None,
+        Keeping::Revalidatable if !revalidatable => return None,
+        _ => {}
+    }
+    let (meta_path, body_path) = kept_paths(&validators.url)?;
+    fs::create_dir_all(body_path.parent()?).ok()?;
+    // the old validators go first, so they never describe the new body or vice versa
+    let _ = fs::remove_file(&meta_path);
+    fs::rename(part_path, &body_path).ok()?;
+    let _ = fs::write(&meta_path, serde_json::to_vec(validators).ok()?);
+    Some(body_path)
+}
+
+/// Opens a kept archive again, rehashing it so a damaged copy isn't trusted.
+fn reuse(path: &Path, len: u64) -> Result<Downloaded> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let read = std::io::copy(&mut file, &mut hasher)?;
+    if read != len {
+        bail!("expected {} bytes, found {}", len, read);
+    }
+    file.seek(SeekFrom::Start(0))?;
+    Ok(Downloaded {
+        file: Some(file),
+        path: path.to_path_buf(),
+        kept: true,
+        len,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+/// The saved state of an earlier download of `url`, if its partial body is intact,
+/// with `hasher` fed the bytes already on disk.
+fn resumable(url: &str, state_path: &Path, part_path: &Path, hasher: &mut Sha256) -> Option<State> {
+    let state: State = serde_json::from_slice(&fs::read(state_path).ok()?).ok()?;
+    if state.url != url {
+        return None;
+    }
+
+    let mut part = File::open(part_path).ok()?.take(state.written);
+    let copied = std::io::copy(&mut part, hasher).ok()?;
+    let intact = copied == state.written
+        && format!("{:x}", hasher.clone().finalize()) == state.sha256_so_far;
+    if !intact {
+        explain::step(format!(
+            "partial download of {} is damaged, restarting",
+            url
+        ));
+        return None;
+    }
+
+    // anything written past the last saved state is untrusted
+    OpenOptions::new()
+        .write(true)
+        .open(part_path)
+        .and_then(|f| f.set_len(state.written))
+        .ok()?;
+    Some(state)
+}
+
+/// Downloads `url` without keeping any state on disk.
+pub fn fetch_in_memory(url: &str, size_hint: Option<u64>) -> Result<Vec<u8>> {
+    let response = http::call(http::get(url))?;
+    let total: Option<u64> = response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok())
+        .or(size_hint);
+    events::emit(Event::DownloadStarted { url, size: total });
+
+    let mut reader = response.into_reader();
+    let mut buf = Vec::with_capacity(total.unwrap_or_default().min(64 << 20) as usize);
+    let mut chunk = [0; 64 << 10];
+    loop {
+        http::check_cancelled()?;
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..read]);
+        output::transfer("downloading", buf.len() as u64, total);
+    }
+    output::transfer_done();
+
+    Ok(buf)
+}
+
+fn kept_paths(url: &str) -> Option<(PathBuf, PathBuf)> {
+    let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let dir = paths::cache_dir()?.join("archives");
+    Some((
+        dir.join(format!("{}.json", key)),
+        dir.join(format!("{}.archive", key)),
+    ))
+}
+
+fn state_paths(url: &str) -> (PathBuf, PathBuf) {
+    let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let dir = paths::cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join(env!("CARGO_PKG_NAME")))
+        .join("downloads");
+    (
+        dir.join(format!("{}.json", key)),
+        dir.join(format!("{}.part", key)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryHttp;
+
+    const URL: &str = "https://msedgedriver.azureedge.net/120.0.2210.91/edgedriver_win64.zip";
+
+    #[test]
+    fn hashes_a_download_and_removes_it_once_dropped() {
+        let http = MemoryHttp::new();
+        http.serve(URL, b"archive".to_vec());
+        let _serving = http::serving(&http);
+
+        let mut downloaded = fetch(URL, None).unwrap();
+        let path = downloaded.path.clone();
+        assert_eq!(downloaded.len, 7);
+        assert_eq!(
+            downloaded.sha256,
+            format!("{:x}", Sha256::digest(b"archive"))
+        );
+        let mut body = Vec::new();
+        downloaded.read_to_end(&mut body).unwrap();
+        assert_eq!(body, b"archive");
+
+        drop(downloaded);
+        assert!(!path.exists());
+        assert_eq!(http.requested(), vec![URL.to_string()]);
+    }
+
+    #[test]
+    fn fails_as_a_network_error_where_nothing_is_served() {
+        let http = MemoryHttp::new();
+        let _serving = http::serving(&http);
+
+        let e = fetch(URL, None).err().unwrap();
+        assert_eq!(crate::error::exit_code(&e), 6);
+        assert!(fetch_in_memory(URL, None).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+use std::{io, thread, time::Duration};
+
+use crate::explain;
+
+/// How long to wait before each retry, about three seconds in total.
+const BACKOFF_MS: &[u64] = &[100, 200, 400, 800, 1600];
+
+/// Whether `e` looks like another process, typically a real-time antivirus scan,
+/// briefly holding a file we just wrote.
+pub fn is_transient_lock(e: &io::Error) -> bool {
+    // ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION
+    cfg!(windows) && matches!(e.raw_os_error(), Some(5 | 32 | 33))
+}
+
+/// Runs `f`, retrying with a short backoff while it fails with a transient lock.
+pub fn when_locked<T>(what: &str, mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delays = BACKOFF_MS.iter();
+    loop {
+        match f() {
+            Err(e) if is_transient_lock(&e) => match delays.next() {
+                Some(&ms) => {
+                    explain::step(format!("{} is locked ({}), retrying in {}ms", what, e, ms));
+                    thread::sleep(Duration::from_millis(ms));
+                }
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{flag_value, has_flag, install, logfile::Log, output, parse_size};
+
+/// How long the driver gets to start answering on its port.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait before restarting a driver that crashed, so one that can't
+/// start doesn't spin.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// The running driver, for the signal handlers to pass signals on to.
+static CHILD: AtomicU32 = AtomicU32::new(0);
+
+/// Set once the tool is interrupted or told to stop, after which the driver
+/// exiting is expected and not restarted.
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// Installs the driver as `install` would, then runs it on `--port` or a free one
+/// until it exits or is interrupted. A `--port` already in use is an error, or with
+/// `--any-port-if-busy` is swapped for a free one; `--allowed-ips` and
+/// `--allowed-origins` are passed on to the driver.
+///
+/// A driver that crashes is started again on the same port up to `--max-restarts`
+/// times. `--log-file` keeps its output in a file rotated past `--log-max-size`,
+/// and `--pid-file` holds the pid of the driver currently running, for harnesses
+/// checking that it is alive.
+///
+/// Arguments after `--` are passed to the driver.
+pub fn run(args: &[String]) -> Result<()> {
+    let (args, driver_args) = match args.iter().position(|arg| arg == "--") {
+        Some(split) => (&args[..split], &args[split + 1..]),
+        None => (args, &[][..]),
+    };
+    if has_flag(args, "--stdout") {
+        bail!("run needs the driver on disk, so it can't be combined with --stdout");
+    }
+
+    let mut driver_args = driver_args.to_vec();
+    // the driver listens beyond localhost once other addresses may connect
+    let host = if flag_value(args, "--allowed-ips").is_some() {
+        "0.0.0.0"
+    } else {
+        "127.0.0.1"
+    };
+    for flag in ["--allowed-ips", "--allowed-origins"] {
+        if let Some(list) = flag_value(args, flag) {
+            driver_args.push(format!("{}={}", flag, list));
+        }
+    }
+    // a port that is taken fails before anything is downloaded
+    let port = match flag_value(args, "--port") {
+        Some(port) => {
+            let port = port
+                .parse::<u16>()
+                .map_err(|_| anyhow!("invalid --port {:?}", port))?;
+            match TcpListener::bind((host, port)) {
+                Ok(_) => port,
+                Err(_) if has_flag(args, "--any-port-if-busy") => {
+                    let free = free_port()?;
+                    output::progress(&format!("port {} is in use, using {}", port, free));
+                    free
+                }
+                Err(e) => bail!(
+                    "port {} is already in use ({}), pick another --port or pass --any-port-if-busy",
+                    port,
+                    e
+                ),
+            }
+        }
+        None => free_port()?,
+    };
+
+    let driver =
+        install(args)?.ok_or_else(|| anyhow!("run only supports launching msedgedriver"))?;
+    let max_restarts = match flag_value(args, "--max-restarts") {
+        Some(n) => n
+            .parse::<u32>()
+            .map_err(|_| anyhow!("invalid --max-restarts {:?}", n))?,
+        None => 0,
+    };
+    let log = match flag_value(args, "--log-file") {
+        Some(path) => {
+            let max_size = match flag_value(args, "--log-max-size") {
+                Some(size) => parse_size(&size)?,
+                None => crate::logfile::DEFAULT_MAX_SIZE,
+            };
+            Some(Log::open(Path::new(&path), max_size)?)
+        }
+        None if has_flag(args, "--log-max-size") => {
+            bail!("--log-max-size only applies with --log-file")
+        }
+        None => None,
+    };
+    let pid_file = flag_value(args, "--pid-file").map(PathBuf::from);
+
+    forward_signals();
+    let result = supervise(
+        &driver,
+        port,
+        &driver_args,
+        max_restarts,
+        log.as_ref(),
+        pid_file.as_deref(),
+    );
+    if let Some(pid_file) = &pid_file {
+        let _ = fs::remove_file(pid_file);
+    }
+    result
+}
+
+/// Runs the driver until it exits cleanly or is stopped, restarting it after each
+/// crash up to `max_restarts` times.
+fn supervise(
+    driver: &Path,
+    port: u16,
+    driver_args: &[String],
+    max_restarts: u32,
+    log: Option<&Log>,
+    pid_file: Option<&Path>,
+) -> Result<()> {
+    let mut restarts = 0;
+    loop {
+        let mut command = Command::new(driver);
+        command.arg(format!("--port={}", port)).args(driver_args);
+        if log.is_some() {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("unable to start {}", driver.display()))?;
+        CHILD.store(child.id(), Ordering::SeqCst);
+        if let Some(log) = log {
+            log.note(&format!(
+                "started {} as pid {}",
+                driver.display(),
+                child.id()
+            ));
+            if let Some(stdout) = child.stdout.take() {
+                log.capture(stdout);
+            }
+            if let Some(stderr) = child.stderr.take() {
+                log.capture(stderr);
+            }
+        }
+        if let Some(pid_file) = pid_file {
+            fs::write(pid_file, format!("{}\n", child.id()))
+                .with_context(|| format!("unable to write pid file {}", pid_file.display()))?;
+        }
+
+        wait_until_ready(&mut child, driver, port)?;
+
+        output::parameter("msedgedriver.port", &port.to_string());
+        output::parameter("msedgedriver.url", &format!("http://127.0.0.1:{}", port));
+        output::parameter("msedgedriver.pid", &child.id().to_string());
+        output::publish();
+        if restarts == 0 {
+            output::progress(&format!(
+                "msedgedriver ready at http://127.0.0.1:{} (pid {}), Ctrl+C stops it",
+                port,
+                child.id()
+            ));
+        } else {
+            output::progress(&format!("msedgedriver ready again (pid {})", child.id()));
+        }
+
+        let status = child.wait()?;
+        CHILD.store(0, Ordering::SeqCst);
+        if status.success() || STOPPING.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if restarts == max_restarts {
+            if max_restarts > 0 {
+                bail!(
+                    "{} exited with {}, and was already restarted {} times",
+                    driver.display(),
+                    status,
+                    restarts
+                );
+            }
+            bail!("{} exited with {}", driver.display(), status);
+        }
+        restarts += 1;
+        let message = format!(
+            "{} exited with {}, restarting it ({} of {})",
+            driver.display(),
+            status,
+            restarts,
+            max_restarts
+        );
+        if let Some(log) = log {
+            log.note(&message);
+        }
+        output::note_warning("driver-restarted", &message);
+        thread::sleep(RESTART_DELAY);
+    }
+}
+
+/// Waits for the driver at `driver`, started as `child`, to answer on `port`.
+pub fn wait_until_ready(child: &mut Child, driver: &Path, port: u16) -> Result<()> {
+    let started = Instant::now();
+    let status_url = format!("http://127.0.0.1:{}/status", port);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            bail!(
+                "{} exited with {} before it was ready",
+                driver.display(),
+                status
+            );
+        }
+        // straight to localhost, never through a configured proxy
+        if ureq::get(&status_url)
+            .timeout(Duration::from_secs(1))
+            .call()
+            .is_ok()
+        {
+            return Ok(());
+        }
+        if started.elapsed() > READY_TIMEOUT {
+            let _ = child.kill();
+            bail!(
+                "{} didn't answer on port {} within {}s",
+                driver.display(),
+                port,
+                READY_TIMEOUT.as_secs()
+            );
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// A port nothing is listening on right now.
+pub fn free_port() -> Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Keeps Ctrl+C, which the terminal also delivers to the driver, from killing the
+/// tool before the driver has shut down, and passes termination on to the driver.
+#[cfg(unix)]
+fn forward_signals() {
+    const SIGHUP: i32 = 1;
+    const SIGINT: i32 = 2;
+    const SIGTERM: i32 = 15;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn kill(pid: i32, sig: i32) -> i32;
+    }
+
+    extern "C" fn on_interrupt(_: i32) {
+        STOPPING.store(true, Ordering::SeqCst);
+    }
+
+    extern "C" fn on_terminate(sig: i32) {
+        STOPPING.store(true, Ordering::SeqCst);
+        let pid = CHILD.load(Ordering::SeqCst);
+        if pid != 0 {
+            // SAFETY: kill is async-signal-safe
+            unsafe { kill(pid as i32, sig) };
+        }
+    }
+
+    // SAFETY: the handlers only touch atomics and call kill; a handler, unlike an
+    // ignored signal, is reset for the driver when it is exec'd
+    unsafe {
+        signal(SIGINT, on_interrupt);
+        signal(SIGTERM, on_terminate);
+        signal(SIGHUP, on_terminate);
+    }
+}
+
+/// Keeps Ctrl+C, which the console also delivers to the driver, from killing the
+/// tool before the driver has shut down.
+#[cfg(windows)]
+fn forward_signals() {
+    use windows_sys::Win32::{Foundation::BOOL, System::Console::SetConsoleCtrlHandler};
+
+    unsafe extern "system" fn handled(_: u32) -> BOOL {
+        STOPPING.store(true, Ordering::SeqCst);
+        1
+    }
+
+    // SAFETY: the handler only sets an atomic; registering one rather than ignoring the
+    // events keeps them enabled for the driver
+    unsafe {
+        SetConsoleCtrlHandler(Some(handled), 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn forward_signals() {}
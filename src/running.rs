@@ -0,0 +1,85 @@
+//! Processes running a driver executable, which windows refuses to let a new
+//! driver replace while they last.
+
+use std::{path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+
+use crate::explain;
+
+/// The ids of processes running the executable at `exe`, as far as they can be
+/// found; only windows keeps a running executable from being replaced.
+pub fn processes(exe: &Path) -> Vec<u32> {
+    if !cfg!(windows) {
+        return Vec::new();
+    }
+    if crate::wine::detected() {
+        explain::step("not listing processes under wine, which has no powershell to ask");
+        return Vec::new();
+    }
+    if crate::sandbox::active() {
+        explain::step("not listing processes, --no-detect forbids running powershell");
+        return Vec::new();
+    }
+    let Some(name) = exe.file_name() else {
+        return Vec::new();
+    };
+    let path = std::path::absolute(exe).unwrap_or_else(|_| exe.to_path_buf());
+    let command = format!(
+        "Get-CimInstance Win32_Process -Filter \"Name='{}'\" | Where-Object {{ $_.ExecutablePath -eq {} }} | ForEach-Object {{ $_.ProcessId }}",
+        name.to_string_lossy().replace('\'', "''"),
+        crate::browser::ps_quote(&path.to_string_lossy())
+    );
+    let output = match Command::new("powershell")
+        .args(["-NoProfile", "-Command", &command])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            explain::step(format!(
+                "unable to list processes running {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+            return Vec::new();
+        }
+        Err(e) => {
+            explain::step(format!("unable to run powershell to list processes: {}", e));
+            return Vec::new();
+        }
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}
+
+/// Ends each of `pids`, for `--stop-running`.
+pub fn stop(pids: &[u32]) -> Result<()> {
+    for pid in pids {
+        let pid = pid.to_string();
+        let output = if cfg!(windows) {
+            Command::new("taskkill").args(["/PID", &pid, "/F"]).output()
+        } else {
+            Command::new("kill").arg(&pid).output()
+        }
+        .with_context(|| format!("unable to stop pid {}", pid))?;
+        if !output.status.success() {
+            bail!(
+                "unable to stop pid {}: {}",
+                pid,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        explain::step(format!("stopped pid {}", pid));
+    }
+    Ok(())
+}
+
+/// `pids` as a list for messages, e.g. `12, 34`.
+pub fn list(pids: &[u32]) -> String {
+    pids.iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
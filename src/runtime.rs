@@ -0,0 +1,60 @@
+use std::{io::Read, process::Command, time::Duration};
+
+use anyhow::{bail, Result};
+
+use crate::{http, output, paths};
+
+/// Microsoft's permanent link to the Evergreen WebView2 Runtime bootstrapper.
+const BOOTSTRAPPER_URL: &str = "https://go.microsoft.com/fwlink/p/?LinkId=2124703";
+const BOOTSTRAPPER_FILENAME: &str = "MicrosoftEdgeWebview2Setup.exe";
+
+/// How long after the bootstrapper exits the runtime may take to show up in the
+/// registry, as it can on a fresh windows server image.
+pub const SETTLE: Duration = Duration::from_secs(60);
+
+/// Downloads and silently runs the Evergreen WebView2 Runtime bootstrapper.
+///
+/// With `elevate`, the installer is started through UAC so it can install machine-wide;
+/// otherwise it runs with the current rights and installs for the current user.
+pub fn install(elevate: bool) -> Result<()> {
+    if !cfg!(windows) {
+        bail!("the WebView2 Runtime can only be installed on Windows");
+    }
+
+    let dir = paths::cache_dir().unwrap_or_else(std::env::temp_dir);
+    std::fs::create_dir_all(&dir)?;
+    let installer = dir.join(BOOTSTRAPPER_FILENAME);
+
+    output::progress("downloading the WebView2 Runtime bootstrapper");
+    let mut buf = Vec::new();
+    http::call(http::get(BOOTSTRAPPER_URL))?
+        .into_reader()
+        .read_to_end(&mut buf)?;
+    std::fs::write(&installer, buf)?;
+
+    // verify before running anything we just downloaded
+    crate::signature::verify(&installer)?;
+
+    crate::sandbox::check("running the WebView2 Runtime installer")?;
+    output::progress("installing the WebView2 Runtime, this can take a few minutes");
+    let status = if elevate {
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command"])
+            .arg(format!(
+                "$p = Start-Process -FilePath '{}' -ArgumentList '/silent','/install' -Verb RunAs -Wait -PassThru; exit $p.ExitCode",
+                installer.display().to_string().replace('\'', "''")
+            ))
+            .status()?
+    } else {
+        Command::new(&installer)
+            .args(["/silent", "/install"])
+            .status()?
+    };
+    let _ = std::fs::remove_file(&installer);
+
+    if !status.success() {
+        bail!("the WebView2 Runtime installer exited with {}", status);
+    }
+
+    Ok(())
+}
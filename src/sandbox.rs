@@ -0,0 +1,40 @@
+//! `--no-detect`: nothing but HTTP and file I/O, for build sandboxes such as Bazel
+//! actions or AppContainer that forbid reading the registry and starting processes.
+//! What would need either is skipped where the tool can do without it, and fails
+//! anywhere else rather than being tried.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Result};
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The commands that can do their work with only HTTP and file I/O.
+pub const COMMANDS: &[&str] = &[
+    "install",
+    "resolve",
+    "generate-script",
+    "prefetch",
+    "info",
+    "list-remote",
+    "cache",
+    "clean",
+];
+
+/// Forbids registry reads and child processes for the rest of the process.
+pub fn enable() {
+    ACTIVE.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--no-detect` was given.
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Fails under `--no-detect` instead of doing `what`, e.g. `reading the registry`.
+pub fn check(what: &str) -> Result<()> {
+    if active() {
+        bail!("--no-detect forbids {}", what);
+    }
+    Ok(())
+}
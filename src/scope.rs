@@ -0,0 +1,211 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use crate::{flag_value, has_flag, output, paths};
+
+/// Who an install is for, deciding where the driver goes and whether PATH is touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// A per-user bin directory, never needing elevation.
+    User,
+    /// A system-wide directory on the system PATH, for machines shared by many users.
+    Machine,
+}
+
+impl FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Scope::User),
+            "machine" => Ok(Scope::Machine),
+            _ => bail!("unknown scope {}, expected user or machine", s),
+        }
+    }
+}
+
+impl Scope {
+    /// Reads `--scope`, or `--install-to-path` as short for `--scope user`; without
+    /// either drivers go in the current directory as they always have.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>> {
+        let scope = flag_value(args, "--scope").map(|s| s.parse()).transpose()?;
+        if has_flag(args, "--install-to-path") {
+            if scope.is_some() {
+                bail!("--install-to-path is --scope user, pass only one");
+            }
+            return Ok(Some(Scope::User));
+        }
+        Ok(scope)
+    }
+
+    /// `%LOCALAPPDATA%\msedgedriver-tool\bin` or `~/.local/bin` for users,
+    /// `%ProgramFiles%\msedgedriver-tool` or `/usr/local/bin` for the machine, or the
+    /// `--project`'s subdirectory of it.
+    pub fn dir(self) -> Result<PathBuf> {
+        self.shared_dir().map(paths::namespaced)
+    }
+
+    fn shared_dir(self) -> Result<PathBuf> {
+        match self {
+            Scope::User if cfg!(windows) => paths::cache_root()
+                .map(|dir| dir.join("bin"))
+                .ok_or_else(|| anyhow!("unable to locate %LOCALAPPDATA%")),
+            Scope::User => paths::home_dir()
+                .map(|home| home.join(".local").join("bin"))
+                .ok_or_else(|| anyhow!("unable to locate a home directory")),
+            Scope::Machine if cfg!(windows) => std::env::var_os("ProgramFiles")
+                .map(|dir| PathBuf::from(dir).join(env!("CARGO_PKG_NAME")))
+                .ok_or_else(|| anyhow!("unable to locate %ProgramFiles%")),
+            Scope::Machine => Ok(PathBuf::from("/usr/local/bin")),
+        }
+    }
+
+    /// Where this scope keeps the cache: the usual per-user one for users, and
+    /// `%ProgramData%\msedgedriver-tool` or `/var/cache/msedgedriver-tool` shared by
+    /// everyone on the machine.
+    pub fn cache_dir(self) -> Option<PathBuf> {
+        match self {
+            Scope::User => paths::cache_root(),
+            Scope::Machine if cfg!(windows) => std::env::var_os("ProgramData")
+                .map(|dir| PathBuf::from(dir).join(env!("CARGO_PKG_NAME"))),
+            Scope::Machine => Some(PathBuf::from("/var/cache").join(env!("CARGO_PKG_NAME"))),
+        }
+    }
+
+    /// Machine installs need administrator rights, so they elevate unless told not to.
+    pub fn elevates_by_default(self) -> bool {
+        self == Scope::Machine && cfg!(windows)
+    }
+
+    /// Puts `dir` on this scope's persistent PATH if it isn't already, the `Environment`
+    /// registry key on windows, and reports whether shells already open can't find
+    /// drivers there.
+    pub fn add_to_path(self, dir: &Path) -> Result<()> {
+        let on_path = std::env::var_os("PATH")
+            .is_some_and(|path| std::env::split_paths(&path).any(|p| p == dir));
+        output::flag("path.new_shell_needed", !on_path);
+        if !cfg!(windows) {
+            if !on_path {
+                eprintln!(
+                    "warning: {} isn't on PATH, add it in your shell profile",
+                    dir.display()
+                );
+            }
+            return Ok(());
+        }
+
+        let added = run_powershell(&format!(
+            "$path = [Environment]::GetEnvironmentVariable('Path', '{target}'); \
+             if (($path -split ';') -notcontains {dir}) {{ \
+             [Environment]::SetEnvironmentVariable('Path', (($path.TrimEnd(';'), {dir}) -join ';').TrimStart(';'), '{target}'); 'added' }}",
+            target = self.path_target(),
+            dir = ps_quote(&dir.to_string_lossy())
+        ))?;
+        let target = self.path_target().to_ascii_lowercase();
+        if added.trim() == "added" {
+            output::progress(&format!("added {} to the {} PATH", dir.display(), target));
+        }
+        if on_path {
+            output::progress(&format!("{} is on PATH", dir.display()));
+        } else {
+            output::progress(&format!(
+                "{} is on the {} PATH, open a new shell to pick it up",
+                dir.display(),
+                target
+            ));
+        }
+        Ok(())
+    }
+
+    /// Removes `dir` from this scope's persistent PATH, reporting whether it was there.
+    pub fn remove_from_path(self, dir: &Path) -> Result<bool> {
+        if !cfg!(windows) {
+            return Ok(false);
+        }
+
+        let removed = run_powershell(&format!(
+            "$path = [Environment]::GetEnvironmentVariable('Path', '{target}'); \
+             $all = @($path -split ';' | Where-Object {{ $_ }}); \
+             $kept = @($all | Where-Object {{ $_ -ne {dir} }}); \
+             if ($kept.Count -ne $all.Count) {{ \
+             [Environment]::SetEnvironmentVariable('Path', ($kept -join ';'), '{target}'); 'removed' }}",
+            target = self.path_target(),
+            dir = ps_quote(&dir.to_string_lossy())
+        ))?;
+        Ok(removed.trim() == "removed")
+    }
+
+    /// Whether `dir` belongs to the tool alone, rather than being a shared bin directory.
+    pub fn owns_dir(self) -> bool {
+        cfg!(windows)
+    }
+
+    fn path_target(self) -> &'static str {
+        match self {
+            Scope::User => "User",
+            Scope::Machine => "Machine",
+        }
+    }
+}
+
+/// Lets everyone read and run what is in the machine-wide `dir`, but only
+/// administrators change it, so a test runner without admin rights can't replace
+/// a driver other users run.
+pub fn secure(dir: &Path) -> Result<()> {
+    #[cfg(windows)]
+    {
+        crate::sandbox::check("running icacls to secure the machine-wide directory")?;
+        // well-known SIDs, as group names are translated: SYSTEM, Administrators, Users
+        let output = Command::new("icacls")
+            .arg(dir)
+            .args([
+                "/inheritance:r",
+                "/grant:r",
+                "*S-1-5-18:(OI)(CI)F",
+                "*S-1-5-32-544:(OI)(CI)F",
+                "*S-1-5-32-545:(OI)(CI)RX",
+                "/Q",
+            ])
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "unable to restrict {} to administrators: {}",
+                dir.display(),
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755))?;
+    }
+    crate::explain::step(format!(
+        "{} is writable by administrators only",
+        dir.display()
+    ));
+    Ok(())
+}
+
+fn run_powershell(script: &str) -> Result<String> {
+    crate::sandbox::check("running powershell to update PATH")?;
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command", script])
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "updating PATH failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
@@ -0,0 +1,137 @@
+//! `generate-script powershell|bash`: a standalone script that fetches exactly the
+//! driver this tool resolved, checked against its sha256, for pipelines that may
+//! run scripts but not this tool.
+
+use anyhow::{bail, Result};
+
+use crate::{
+    driver_filename, output, resolve,
+    shell::{posix_quote, pwsh_quote},
+};
+
+const SHELLS: &[&str] = &["powershell", "bash"];
+
+/// Prints the script for the shell named first in `args`, for the archive
+/// [`resolve::resolved`] picks.
+pub fn generate_script(args: &[String]) -> Result<()> {
+    let shell = match args.first().map(String::as_str) {
+        Some(shell @ ("powershell" | "bash")) => shell,
+        Some("pwsh") => "powershell",
+        Some(other) => bail!(
+            "unknown shell {}, expected one of {}",
+            other,
+            SHELLS.join(", ")
+        ),
+        None => bail!(
+            "generate-script needs a shell, one of {}",
+            SHELLS.join(", ")
+        ),
+    };
+    // the script is the result, so everything else goes to stderr
+    output::use_stderr();
+
+    let resolve::Resolved {
+        version,
+        platform,
+        url,
+    } = resolve::resolved(args)?;
+    let sha256 = resolve::sha256(args, platform, &version, &url)?;
+
+    output::progress(&format!(
+        "msedgedriver {} for {}, archive sha256 {}",
+        version, platform, sha256
+    ));
+    let header = format!(
+        "generated by {} {}: msedgedriver {} for {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        version,
+        platform
+    );
+    let filename = driver_filename(platform);
+    print!(
+        "{}",
+        match shell {
+            "powershell" => powershell(&header, &url, &sha256, filename),
+            _ => bash(&header, &url, &sha256, filename),
+        }
+    );
+    Ok(())
+}
+
+fn powershell(header: &str, url: &str, sha256: &str, filename: &str) -> String {
+    format!(
+        r#"# {header}
+# usage: .\get-msedgedriver.ps1 [-Output <dir>]
+param([string]$Output = '.')
+$ErrorActionPreference = 'Stop'
+$url = {url}
+$sha256 = {sha256}
+$filename = {filename}
+
+$archive = Join-Path ([IO.Path]::GetTempPath()) ([IO.Path]::GetRandomFileName() + '.zip')
+try {{
+    Invoke-WebRequest -UseBasicParsing -Uri $url -OutFile $archive
+    $actual = (Get-FileHash -Algorithm SHA256 -LiteralPath $archive).Hash.ToLowerInvariant()
+    if ($actual -ne $sha256) {{
+        throw "sha256 mismatch for ${{url}}: expected $sha256, got $actual"
+    }}
+    New-Item -ItemType Directory -Force -Path $Output | Out-Null
+    Add-Type -AssemblyName System.IO.Compression.FileSystem
+    $zip = [IO.Compression.ZipFile]::OpenRead($archive)
+    try {{
+        $entry = $zip.GetEntry($filename)
+        if (-not $entry) {{
+            throw "$filename is missing from $url"
+        }}
+        $driver = Join-Path $Output $filename
+        [IO.Compression.ZipFileExtensions]::ExtractToFile($entry, $driver, $true)
+    }} finally {{
+        $zip.Dispose()
+    }}
+}} finally {{
+    Remove-Item -LiteralPath $archive -ErrorAction SilentlyContinue
+}}
+Write-Output $driver
+"#,
+        header = header,
+        url = pwsh_quote(url),
+        sha256 = pwsh_quote(sha256),
+        filename = pwsh_quote(filename),
+    )
+}
+
+fn bash(header: &str, url: &str, sha256: &str, filename: &str) -> String {
+    format!(
+        r#"#!/usr/bin/env bash
+# {header}
+# usage: get-msedgedriver.sh [output dir]
+set -euo pipefail
+url={url}
+sha256={sha256}
+filename={filename}
+output="${{1:-.}}"
+
+archive="$(mktemp)"
+trap 'rm -f "$archive"' EXIT
+curl -fsSL --retry 3 -o "$archive" "$url"
+if command -v sha256sum >/dev/null 2>&1; then
+    actual="$(sha256sum "$archive" | cut -d' ' -f1)"
+else
+    actual="$(shasum -a 256 "$archive" | cut -d' ' -f1)"
+fi
+if [ "$actual" != "$sha256" ]; then
+    echo "sha256 mismatch for $url: expected $sha256, got $actual" >&2
+    exit 1
+fi
+mkdir -p "$output"
+unzip -o -q "$archive" "$filename" -d "$output"
+chmod +x "$output/$filename"
+echo "$output/$filename"
+"#,
+        header = header,
+        url = posix_quote(url),
+        sha256 = posix_quote(sha256),
+        filename = posix_quote(filename),
+    )
+}
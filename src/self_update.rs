@@ -0,0 +1,155 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::{has_flag, http, output, retry, update_check};
+
+/// The release asset built for the running platform, e.g. `msedgedriver-tool-x86_64-windows.exe`.
+fn asset_name() -> String {
+    format!(
+        "{}-{}-{}{}",
+        env!("CARGO_PKG_NAME"),
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// Replaces the running executable with the latest release, after verifying the
+/// published `.sha256` checksum of the new binary.
+///
+/// With `--check` it only reports whether a newer release exists, for images that
+/// are rebuilt rather than updated in place.
+pub fn self_update(args: &[String]) -> Result<()> {
+    let current = std::env::current_exe()?;
+    // left behind by the previous update on windows, once nothing is running it
+    let _ = fs::remove_file(sibling(&current, "old"));
+
+    let release = update_check::latest_release()?;
+    output::parameter("tool.version", env!("CARGO_PKG_VERSION"));
+    output::parameter("tool.latest", &release.tag_name);
+    output::flag("tool.update_available", release.is_newer());
+    if has_flag(args, "--check") {
+        output::line(&if release.is_newer() {
+            format!(
+                "{} is available (running {}), see {}",
+                release.tag_name,
+                env!("CARGO_PKG_VERSION"),
+                release.html_url
+            )
+        } else {
+            format!(
+                "already running the latest release ({})",
+                env!("CARGO_PKG_VERSION")
+            )
+        });
+        return Ok(());
+    }
+    if !release.is_newer() && !has_flag(args, "--force") {
+        output::progress(&format!(
+            "already running the latest release ({})",
+            env!("CARGO_PKG_VERSION")
+        ));
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let find = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.browser_download_url.as_str())
+    };
+    let binary_url =
+        find(&name).ok_or_else(|| anyhow!("release {} has no {} asset", release.tag_name, name))?;
+    let checksum_url = find(&format!("{}.sha256", name)).ok_or_else(|| {
+        anyhow!(
+            "release {} has no checksum for {}, refusing to install it",
+            release.tag_name,
+            name
+        )
+    })?;
+
+    output::progress(&format!("downloading {} {}", name, release.tag_name));
+    let binary = fetch(binary_url)?;
+    let checksum = String::from_utf8(fetch(checksum_url)?)?;
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("empty checksum file for {}", name))?;
+    let actual = format!("{:x}", Sha256::digest(&binary));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(
+            "sha256 mismatch for {}: expected {}, got {}",
+            name,
+            expected,
+            actual
+        );
+    }
+
+    replace_executable(&current, &binary)
+        .with_context(|| format!("unable to replace {}", current.display()))?;
+    output::progress(&format!(
+        "updated {} from {} to {}",
+        current.display(),
+        env!("CARGO_PKG_VERSION"),
+        release.tag_name
+    ));
+
+    Ok(())
+}
+
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    http::call(http::get(url))?
+        .into_reader()
+        .read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Atomically swaps `current` for `binary`, staging it next to the original first.
+fn replace_executable(current: &Path, binary: &[u8]) -> Result<()> {
+    let staged = sibling(current, "new");
+    fs::write(&staged, binary)?;
+    let result = swap(current, &staged);
+    if result.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    result
+}
+
+fn swap(current: &Path, staged: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // windows won't let a running executable be overwritten, but it can be renamed
+    if cfg!(windows) {
+        let old = sibling(current, "old");
+        let _ = fs::remove_file(&old);
+        fs::rename(current, &old)?;
+        if let Err(e) = retry::when_locked("the new executable", || fs::rename(staged, current)) {
+            let _ = fs::rename(&old, current);
+            return Err(e.into());
+        }
+    } else {
+        fs::rename(staged, current)?;
+    }
+
+    Ok(())
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
@@ -0,0 +1,72 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Error};
+
+/// A shell whose syntax we know how to emit environment statements for.
+#[derive(Debug, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Pwsh,
+    Cmd,
+    Fish,
+}
+
+impl Shell {
+    /// The shell most likely in use on the host when none is given.
+    pub fn default_for_host() -> Self {
+        if cfg!(windows) {
+            Shell::Pwsh
+        } else {
+            Shell::Bash
+        }
+    }
+
+    /// A statement setting `name` to `value` for the rest of the session.
+    pub fn export(self, name: &str, value: &str) -> String {
+        match self {
+            Shell::Bash => format!("export {}={}", name, posix_quote(value)),
+            Shell::Pwsh => format!("$env:{} = {}", name, pwsh_quote(value)),
+            Shell::Cmd => format!("set \"{}={}\"", name, value),
+            Shell::Fish => format!("set -gx {} {}", name, fish_quote(value)),
+        }
+    }
+
+    /// A statement putting `dir` in front of the existing `PATH`.
+    pub fn prepend_path(self, dir: &str) -> String {
+        match self {
+            Shell::Bash => format!("export PATH={}:\"$PATH\"", posix_quote(dir)),
+            Shell::Pwsh => format!(
+                "$env:PATH = {} + [IO.Path]::PathSeparator + $env:PATH",
+                pwsh_quote(dir)
+            ),
+            Shell::Cmd => format!("set \"PATH={};%PATH%\"", dir),
+            Shell::Fish => format!("set -gx PATH {} $PATH", fish_quote(dir)),
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" | "sh" | "zsh" => Ok(Shell::Bash),
+            "pwsh" | "powershell" => Ok(Shell::Pwsh),
+            "cmd" => Ok(Shell::Cmd),
+            "fish" => Ok(Shell::Fish),
+            _ => bail!("unknown shell {}, expected one of bash, pwsh, cmd, fish", s),
+        }
+    }
+}
+
+pub fn posix_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+pub fn pwsh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+pub fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::{has_flag, policy};
+
+/// What to do about the Authenticode signature of an extracted driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// Refuse to install a driver without a valid Microsoft signature.
+    Require,
+    /// Verify, but only warn about a bad signature; the default.
+    Warn,
+    Off,
+}
+
+impl Check {
+    /// Reads `--verify-signature[=require|warn|off]`, where the bare flag and
+    /// `--require-signature` mean `require`, as does the policy's `require_signature`.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut check = None;
+        let mut iter = args.iter().take_while(|arg| *arg != "--").peekable();
+        while let Some(arg) = iter.next() {
+            let value = if arg == "--verify-signature" {
+                match iter.peek().map(|v| v.as_str()) {
+                    Some(v @ ("require" | "warn" | "off")) => {
+                        iter.next();
+                        v
+                    }
+                    _ => "require",
+                }
+            } else if let Some(value) = arg.strip_prefix("--verify-signature=") {
+                value
+            } else {
+                continue;
+            };
+            check = Some(match value {
+                "require" => Check::Require,
+                "warn" => Check::Warn,
+                "off" => Check::Off,
+                other => bail!(
+                    "invalid --verify-signature {}, expected require, warn or off",
+                    other
+                ),
+            });
+        }
+
+        if has_flag(args, "--require-signature") {
+            if check.is_some_and(|check| check != Check::Require) {
+                bail!("--require-signature contradicts --verify-signature");
+            }
+            check = Some(Check::Require);
+        }
+        if policy::get().require_signature {
+            if check.is_some_and(|check| check != Check::Require) {
+                bail!("policy requires signature verification, so it can't be relaxed");
+            }
+            check = Some(Check::Require);
+        }
+        policy::get().check_insecure("--verify-signature=off", check == Some(Check::Off))?;
+        Ok(check.unwrap_or(Check::Warn))
+    }
+}
+
+/// The Authenticode signer every genuine msedgedriver build is signed by.
+#[cfg(windows)]
+const EXPECTED_SIGNER: &str = "Microsoft Corporation";
+
+/// Verifies the Authenticode signature of `path` and returns the signer's display name.
+#[cfg(windows)]
+pub fn verify(path: &Path) -> Result<String> {
+    use std::{ffi::c_void, os::windows::ffi::OsStrExt, ptr};
+
+    use anyhow::bail;
+    use windows_sys::Win32::Security::{
+        Cryptography::{CertGetNameStringW, CERT_NAME_SIMPLE_DISPLAY_TYPE},
+        WinTrust::{
+            WTHelperGetProvCertFromChain, WTHelperGetProvSignerFromChain,
+            WTHelperProvDataFromStateData, WinVerifyTrust, WINTRUST_ACTION_GENERIC_VERIFY_V2,
+            WINTRUST_DATA, WINTRUST_DATA_0, WINTRUST_FILE_INFO, WTD_CHOICE_FILE, WTD_REVOKE_NONE,
+            WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+        },
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut file = WINTRUST_FILE_INFO {
+        cbStruct: std::mem::size_of::<WINTRUST_FILE_INFO>() as u32,
+        pcwszFilePath: wide.as_ptr(),
+        hFile: 0,
+        pgKnownSubject: ptr::null_mut(),
+    };
+    let mut data = WINTRUST_DATA {
+        cbStruct: std::mem::size_of::<WINTRUST_DATA>() as u32,
+        pPolicyCallbackData: ptr::null_mut(),
+        pSIPClientData: ptr::null_mut(),
+        dwUIChoice: WTD_UI_NONE,
+        fdwRevocationChecks: WTD_REVOKE_NONE,
+        dwUnionChoice: WTD_CHOICE_FILE,
+        Anonymous: WINTRUST_DATA_0 { pFile: &mut file },
+        dwStateAction: WTD_STATEACTION_VERIFY,
+        hWVTStateData: 0,
+        pwszURLReference: ptr::null_mut(),
+        dwProvFlags: 0,
+        dwUIContext: 0,
+        pSignatureSettings: ptr::null_mut(),
+    };
+    let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+
+    // SAFETY: every pointer handed to wintrust outlives the calls, and the state
+    // data opened by the verify action is always released by the close action
+    unsafe {
+        let status = WinVerifyTrust(0, &mut action, &mut data as *mut _ as *mut c_void);
+
+        let signer = if status == 0 {
+            let provider = WTHelperProvDataFromStateData(data.hWVTStateData);
+            let sgnr = WTHelperGetProvSignerFromChain(provider, 0, 0, 0);
+            let cert = if sgnr.is_null() {
+                ptr::null_mut()
+            } else {
+                WTHelperGetProvCertFromChain(sgnr, 0)
+            };
+            if cert.is_null() {
+                None
+            } else {
+                let mut name = [0u16; 256];
+                let len = CertGetNameStringW(
+                    (*cert).pCert,
+                    CERT_NAME_SIMPLE_DISPLAY_TYPE,
+                    0,
+                    ptr::null(),
+                    name.as_mut_ptr(),
+                    name.len() as u32,
+                );
+                Some(String::from_utf16_lossy(
+                    &name[..(len as usize).saturating_sub(1)],
+                ))
+            }
+        } else {
+            None
+        };
+
+        data.dwStateAction = WTD_STATEACTION_CLOSE;
+        WinVerifyTrust(0, &mut action, &mut data as *mut _ as *mut c_void);
+
+        if status != 0 {
+            bail!(
+                "{} has no valid Authenticode signature (WinVerifyTrust returned {:#010x})",
+                path.display(),
+                status
+            );
+        }
+
+        match signer {
+            Some(signer) if signer == EXPECTED_SIGNER => Ok(signer),
+            Some(signer) => bail!(
+                "{} is signed by {:?}, expected {:?}",
+                path.display(),
+                signer,
+                EXPECTED_SIGNER
+            ),
+            None => bail!("unable to read the signer of {}", path.display()),
+        }
+    }
+}
+
+/// Verifies the Authenticode signature of `path` and returns the signer's display name.
+#[cfg(not(windows))]
+pub fn verify(path: &Path) -> Result<String> {
+    bail!(
+        "unable to verify the signature of {}: Authenticode verification is only available on Windows",
+        path.display()
+    )
+}
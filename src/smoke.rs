@@ -0,0 +1,121 @@
+//! `--smoke-test`: proving an installed driver can drive its browser before any
+//! test depends on it.
+
+use std::{
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::{flag_value, has_flag, output, run};
+
+/// How long the browser gets to start for a new session.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The capabilities `--smoke-test` opens a session with, checked before anything is
+/// installed, or `None` without `--smoke-test`.
+pub fn requested(args: &[String]) -> Result<Option<Value>> {
+    if !has_flag(args, "--smoke-test") {
+        return Ok(None);
+    }
+    if has_flag(args, "--stdout") {
+        bail!("--smoke-test needs the driver on disk, so it can't be combined with --stdout");
+    }
+    let capabilities = match (
+        has_flag(args, "--headless"),
+        flag_value(args, "--smoke-app").or_else(|| flag_value(args, "--webview2-app")),
+    ) {
+        (true, Some(_)) => bail!("--headless tests edge, so it can't be combined with an app"),
+        (true, None) => json!({
+            "browserName": "MicrosoftEdge",
+            "ms:edgeOptions": { "args": ["--headless=new"] },
+        }),
+        (false, Some(app)) => json!({
+            "browserName": "webview2",
+            "ms:edgeOptions": { "binary": app },
+        }),
+        (false, None) => bail!(
+            "--smoke-test drives webview2 in the app given by --smoke-app, or pass --headless for edge"
+        ),
+    };
+    Ok(Some(capabilities))
+}
+
+/// Kills the driver however the test ends.
+struct Running(Child);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Starts `driver`, opens a session with `capabilities`, navigates to
+/// `about:blank` and closes the session again.
+pub fn test(driver: &Path, capabilities: Value) -> Result<()> {
+    crate::sandbox::check("starting the driver to smoke test it")?;
+    output::progress(&format!("smoke testing {}", driver.display()));
+    let port = run::free_port()?;
+    let child = Command::new(driver)
+        .arg(format!("--port={}", port))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("unable to start {}", driver.display()))?;
+    let mut running = Running(child);
+    run::wait_until_ready(&mut running.0, driver, port)?;
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let session = command(
+        "POST",
+        &format!("{}/session", base),
+        json!({ "capabilities": { "alwaysMatch": capabilities } }),
+    )
+    .context("the driver couldn't start a browser session")?;
+    let id = session["sessionId"]
+        .as_str()
+        .ok_or_else(|| anyhow!("the driver's new session has no id"))?
+        .to_string();
+    let session_url = format!("{}/session/{}", base, id);
+
+    let navigated = command(
+        "POST",
+        &format!("{}/url", session_url),
+        json!({ "url": "about:blank" }),
+    )
+    .context("the browser session couldn't navigate to about:blank");
+    let ended = command("DELETE", &session_url, Value::Null)
+        .context("the driver couldn't end the browser session");
+    navigated?;
+    ended?;
+
+    output::progress("smoke test passed: a session started, navigated and ended");
+    output::flag("msedgedriver.smoke_test", true);
+    Ok(())
+}
+
+/// Sends a WebDriver command, returning its `value` or the error the driver gave.
+fn command(method: &str, url: &str, body: Value) -> Result<Value> {
+    // straight to localhost, never through a configured proxy
+    let request = ureq::request(method, url).timeout(SESSION_TIMEOUT);
+    let response = match body {
+        Value::Null => request.call(),
+        body => request.send_json(body),
+    };
+    let reply: Value = match response {
+        Ok(response) => response.into_json()?,
+        Err(ureq::Error::Status(code, response)) => {
+            let reply: Value = response.into_json().unwrap_or_default();
+            match reply["value"]["message"].as_str() {
+                Some(message) => bail!("{} {} failed with {}: {}", method, url, code, message),
+                None => bail!("{} {} failed with {}", method, url, code),
+            }
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(reply["value"].clone())
+}
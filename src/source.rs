@@ -0,0 +1,329 @@
+//! Where the version a driver is matched to comes from: sources tried in order
+//! until one knows, so an unusual machine is covered by adding a source rather
+//! than another branch in detection.
+
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::bail;
+
+use crate::{
+    browser, explain, flag_value, http, output, registry, Channel, Error,
+    WEBVIEW2_KEY_32BIT_MACHINE, WEBVIEW2_KEY_64BIT_MACHINE, WEBVIEW2_KEY_USER,
+};
+
+/// A version a source found, and where, e.g. the registry key that held it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    pub version: String,
+    pub location: String,
+}
+
+/// One way of finding the version to match a driver to.
+pub trait VersionSource: Send + Sync {
+    /// The source's name in `--explain` and `--version-sources`, e.g. `registry`.
+    fn name(&self) -> &str;
+
+    /// The version this source knows of, or `None` to leave it to the next source.
+    /// An error also moves on to the next source, and is only returned if none
+    /// finds a version.
+    fn find(&self) -> Result<Option<Found>, Error>;
+}
+
+/// An explicit version, for callers that already know it.
+pub struct Pinned(pub String);
+
+impl VersionSource for Pinned {
+    fn name(&self) -> &str {
+        "pin"
+    }
+
+    fn find(&self) -> Result<Option<Found>, Error> {
+        Ok(Some(Found {
+            version: self.0.trim().into(),
+            location: "pinned".into(),
+        }))
+    }
+}
+
+/// The webview2 runtime's EdgeUpdate registry keys, machine-wide then per user.
+pub struct Registry;
+
+impl VersionSource for Registry {
+    fn name(&self) -> &str {
+        "registry"
+    }
+
+    fn find(&self) -> Result<Option<Found>, Error> {
+        let mut error = None;
+        for (scope, key) in [
+            ("64bit machine-wide", WEBVIEW2_KEY_64BIT_MACHINE),
+            ("32bit machine-wide", WEBVIEW2_KEY_32BIT_MACHINE),
+            ("user-wide", WEBVIEW2_KEY_USER),
+        ] {
+            // locked-down machines can refuse or redirect the query, the files still tell
+            let version = match registry::read_string(key, "pv") {
+                Ok(version) => version.filter(|v| !v.trim().is_empty()),
+                Err(e) => {
+                    explain::step(format!(
+                        "{} webview2 registry key unreadable: {:#}",
+                        scope, e
+                    ));
+                    error.get_or_insert(e);
+                    continue;
+                }
+            };
+            explain_registry(scope, version.as_deref());
+            if let Some(version) = version {
+                return Ok(Some(Found {
+                    version: version.trim().into(),
+                    location: key.into(),
+                }));
+            }
+        }
+        match error {
+            Some(e) => Err(e.into()),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The evergreen runtime's version-named installation directories.
+pub struct InstallDirs;
+
+impl VersionSource for InstallDirs {
+    fn name(&self) -> &str {
+        "dirs"
+    }
+
+    fn find(&self) -> Result<Option<Found>, Error> {
+        Ok(
+            browser::scan_installations(&crate::webview2_dirs()).map(|(version, dir)| Found {
+                version,
+                location: dir.display().to_string(),
+            }),
+        )
+    }
+}
+
+/// The version resource of the first of some executables that exists.
+pub struct FileMetadata(pub Vec<PathBuf>);
+
+impl FileMetadata {
+    /// Stable edge's `msedge.exe`, whose version usually matches the runtime's.
+    pub fn msedge() -> Self {
+        FileMetadata(browser::executables(Channel::Stable))
+    }
+}
+
+impl VersionSource for FileMetadata {
+    fn name(&self) -> &str {
+        "msedge"
+    }
+
+    fn find(&self) -> Result<Option<Found>, Error> {
+        for exe in self.0.iter().filter(|exe| exe.is_file()) {
+            if let Some(version) = browser::file_version(exe)? {
+                return Ok(Some(Found {
+                    version,
+                    location: exe.display().to_string(),
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The newest stable driver Microsoft published, for machines where nothing is
+/// installed to match, such as containers that only run headless tests.
+pub struct LatestStable;
+
+impl VersionSource for LatestStable {
+    fn name(&self) -> &str {
+        "latest-stable"
+    }
+
+    fn find(&self) -> Result<Option<Found>, Error> {
+        if http::offline() {
+            explain::step("--offline, not asking for the latest stable version");
+            return Ok(None);
+        }
+        Ok(Some(Found {
+            version: Channel::Stable.latest_version()?,
+            location: "LATEST_STABLE".into(),
+        }))
+    }
+}
+
+/// Sources tried in order until one finds a version.
+#[derive(Clone, Default)]
+pub struct Chain {
+    sources: Vec<Arc<dyn VersionSource>>,
+}
+
+impl Chain {
+    /// The chain detection always used: the registry, then installation directories.
+    pub fn webview2() -> Self {
+        Chain::default().with(Registry).with(InstallDirs)
+    }
+
+    /// Adds `source` after the ones already in the chain.
+    pub fn with(mut self, source: impl VersionSource + 'static) -> Self {
+        self.sources.push(Arc::new(source));
+        self
+    }
+
+    /// Puts `source` at `index`, e.g. 0 to ask it before anything else.
+    pub fn insert(&mut self, index: usize, source: impl VersionSource + 'static) {
+        self.sources.insert(index, Arc::new(source));
+    }
+
+    /// The first version a source finds, failing with the first error only if no
+    /// source finds one.
+    pub fn detect(&self) -> Result<Option<Found>, Error> {
+        let mut error: Option<(&str, Error)> = None;
+        for source in &self.sources {
+            match source.find() {
+                Ok(Some(found)) => {
+                    explain::step(format!(
+                        "version source {} found {} at {}",
+                        source.name(),
+                        found.version,
+                        found.location
+                    ));
+                    if let Some((failed, e)) = &error {
+                        output::note_warning(
+                            "detection-fallback",
+                            &format!(
+                                "version source {} failed ({}), fell back to {}",
+                                failed,
+                                e,
+                                source.name()
+                            ),
+                        );
+                    }
+                    return Ok(Some(found));
+                }
+                Ok(None) => {
+                    explain::step(format!("version source {} found nothing", source.name()))
+                }
+                Err(e) => {
+                    explain::step(format!("version source {} failed: {}", source.name(), e));
+                    error.get_or_insert((source.name(), e));
+                }
+            }
+        }
+        match error {
+            Some((_, e)) => Err(e),
+            None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.sources.iter().map(|source| source.name()))
+            .finish()
+    }
+}
+
+static CONFIGURED: OnceLock<Chain> = OnceLock::new();
+
+/// Reads `--version-sources`, a comma-separated order of `registry`, `dirs`,
+/// `msedge` and `latest-stable`.
+pub fn configure(args: &[String]) -> anyhow::Result<()> {
+    let Some(names) = flag_value(args, "--version-sources") else {
+        return Ok(());
+    };
+    let mut chain = Chain::default();
+    for name in names.split(',').map(str::trim) {
+        chain = match name {
+            "registry" => chain.with(Registry),
+            "dirs" => chain.with(InstallDirs),
+            "msedge" => chain.with(FileMetadata::msedge()),
+            "latest-stable" => chain.with(LatestStable),
+            other => bail!(
+                "unknown version source {}, expected registry, dirs, msedge or latest-stable",
+                other
+            ),
+        };
+    }
+    let _ = CONFIGURED.set(chain);
+    Ok(())
+}
+
+/// The chain `--version-sources` configured, or [`Chain::webview2`].
+pub fn configured() -> &'static Chain {
+    CONFIGURED.get_or_init(Chain::webview2)
+}
+
+fn explain_registry(scope: &str, version: Option<&str>) {
+    match version {
+        Some(version) => explain::step(format!(
+            "{} webview2 registry key answered with {}",
+            scope,
+            version.trim()
+        )),
+        None => explain::step(format!("{} webview2 registry key not found", scope)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{set_registry, MemoryRegistry};
+
+    /// A source that fails, as an unreadable registry does.
+    struct Failing;
+
+    impl VersionSource for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn find(&self) -> Result<Option<Found>, Error> {
+            Err(Error::Other(anyhow::anyhow!("access denied")))
+        }
+    }
+
+    // the only test reading the registry, which is set for the whole process
+    #[test]
+    fn reads_the_webview2_registry_keys_in_order() {
+        let registry = MemoryRegistry::new();
+        set_registry(registry.clone());
+
+        assert_eq!(Registry.find().unwrap(), None);
+
+        registry.set(WEBVIEW2_KEY_USER, "pv", "118.0.2088.46");
+        let found = Registry.find().unwrap().unwrap();
+        assert_eq!(found.version, "118.0.2088.46");
+        assert_eq!(found.location, WEBVIEW2_KEY_USER);
+
+        // machine-wide wins over the user's, and key case doesn't matter
+        registry.set(
+            &WEBVIEW2_KEY_32BIT_MACHINE.to_uppercase(),
+            "pv",
+            " 120.0.2210.91 ",
+        );
+        let found = Registry.find().unwrap().unwrap();
+        assert_eq!(found.version, "120.0.2210.91");
+        assert_eq!(found.location, WEBVIEW2_KEY_32BIT_MACHINE);
+    }
+
+    #[test]
+    fn falls_back_past_failing_sources() {
+        let found = Chain::default()
+            .with(Failing)
+            .with(Pinned("120.0.2210.91".into()))
+            .detect()
+            .unwrap();
+        assert_eq!(found.unwrap().location, "pinned");
+
+        let error = Chain::default().with(Failing).detect().unwrap_err();
+        assert_eq!(error.to_string(), "access denied");
+    }
+}
@@ -0,0 +1,165 @@
+//! `--project` and `status`: installs namespaced per project on build machines
+//! shared by several, and a record of which project put which driver where.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{flag_value, lock, output, paths, receipt::Receipt, timestamp};
+
+/// Kept in the cache every project's lives inside, so `status` sees them all.
+const RECORD: &str = "projects.json";
+
+/// One driver a project installed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Install {
+    project: String,
+    version: String,
+    platform: String,
+    path: PathBuf,
+    installed_at: String,
+}
+
+/// Reads `--project`, which must be usable as a directory name everywhere.
+pub fn configure(args: &[String]) -> Result<()> {
+    let Some(project) = flag_value(args, "--project") else {
+        return Ok(());
+    };
+    let valid = !project.is_empty()
+        && project.len() <= 64
+        && !project.starts_with('.')
+        && project
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"._-".contains(&b));
+    if !valid {
+        bail!(
+            "invalid --project {:?}, expected up to 64 letters, digits, '.', '_' or '-', not starting with '.'",
+            project
+        );
+    }
+    paths::set_project(&project);
+    Ok(())
+}
+
+fn record_path() -> Result<PathBuf> {
+    paths::cache_root()
+        .map(|dir| dir.join(RECORD))
+        .ok_or_else(|| anyhow!("unable to locate a cache directory"))
+}
+
+fn read(path: &Path) -> Result<Vec<Install>> {
+    match fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .with_context(|| format!("invalid project record {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("unable to read {}", path.display())),
+    }
+}
+
+/// Records that the `--project` installed the driver at `driver`, replacing what
+/// it last installed there. Installs without a project aren't recorded.
+pub fn record(driver: &Path) {
+    let Some(project) = paths::project() else {
+        return;
+    };
+    if let Err(e) = try_record(project, driver) {
+        output::note_warning(
+            "project-record-failed",
+            &format!("unable to record {}'s install: {:#}", project, e),
+        );
+    }
+}
+
+fn try_record(project: &str, driver: &Path) -> Result<()> {
+    let receipt = Receipt::read(driver)?
+        .ok_or_else(|| anyhow!("{} has no install receipt", driver.display()))?;
+    let path = record_path()?;
+    let _lock = lock::acquire(&path)?;
+    let driver = std::path::absolute(driver)?;
+    let mut installs = read(&path)?;
+    installs.retain(|install| !(install.project == project && install.path == driver));
+    installs.push(Install {
+        project: project.into(),
+        version: receipt.version,
+        platform: receipt.platform,
+        path: driver,
+        installed_at: timestamp::now(),
+    });
+    installs.sort_by(|a, b| (&a.project, &a.path).cmp(&(&b.project, &b.path)));
+    fs::write(&path, serde_json::to_string_pretty(&installs)? + "\n")
+        .with_context(|| format!("unable to write {}", path.display()))
+}
+
+/// The `status` command: every recorded install, or only `--project`'s, and
+/// whether the driver there is still the one that project installed.
+pub fn status(args: &[String]) -> Result<()> {
+    let path = record_path()?;
+    let project = flag_value(args, "--project");
+    let installs: Vec<Install> = read(&path)?
+        .into_iter()
+        .filter(|install| project.as_deref().is_none_or(|p| p == install.project))
+        .collect();
+    if installs.is_empty() {
+        match &project {
+            Some(project) => output::line(&format!("{} has installed nothing", project)),
+            None => {
+                output::line("no project has installed a driver, install with --project <name>")
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    for install in &installs {
+        // another project pointed at the same path may have replaced it since
+        let state = match Receipt::read(&install.path) {
+            _ if !install.path.exists() => "missing".to_string(),
+            Ok(Some(receipt)) if receipt.version != install.version => {
+                format!("replaced by {}", receipt.version)
+            }
+            Ok(Some(_)) => "installed".to_string(),
+            Ok(None) | Err(_) => "unrecorded".to_string(),
+        };
+        output::push(
+            "installs",
+            json!({
+                "project": install.project,
+                "version": install.version,
+                "platform": install.platform,
+                "path": install.path,
+                "installed_at": install.installed_at,
+                "state": state,
+            }),
+        );
+        rows.push((install, state));
+    }
+    let project_width = rows
+        .iter()
+        .map(|(i, _)| i.project.len())
+        .max()
+        .unwrap_or_default();
+    let version_width = rows
+        .iter()
+        .map(|(i, _)| i.version.len())
+        .max()
+        .unwrap_or_default();
+    for (install, state) in &rows {
+        let state = match state.as_str() {
+            "installed" => String::new(),
+            other => format!(" ({})", other),
+        };
+        output::line(&format!(
+            "{:<project_width$}  {:<version_width$}  {:<8}  {}{}",
+            install.project,
+            install.version,
+            install.platform,
+            install.path.display(),
+            state
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    audit, checksums, detected_version,
+    download::{download_driver, DownloadOptions, ExtractLimits},
+    driver_filename, enforce_floor, explain, fetch_manifest, find_blob, flag_value, has_flag,
+    host_platform, lock, metrics, mirror, newest_for_prefix, output, parallel, policy, quarantine,
+    receipt::{self, Receipt},
+    signature, verify_runs, verify_signature,
+};
+
+const DEFAULT_MANIFEST: &str = "msedgedriver-lab.toml";
+const DEFAULT_LAYOUT: &str = "{version}/{platform}";
+
+/// The desired state of a machine or cache, as committed by whoever runs the lab.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabManifest {
+    /// Root directory every driver is placed under.
+    output: PathBuf,
+    /// Directory of each driver below `output`, with `{version}` and `{platform}` placeholders.
+    #[serde(default = "default_layout")]
+    layout: String,
+    #[serde(default, rename = "driver")]
+    drivers: Vec<DriverSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DriverSpec {
+    /// `detected`, a major version like `120` for its newest build, or an exact version.
+    version: String,
+    #[serde(default = "default_platforms")]
+    platforms: Vec<String>,
+    /// Archive pins keyed by platform.
+    #[serde(default)]
+    sha256: HashMap<String, String>,
+}
+
+fn default_layout() -> String {
+    DEFAULT_LAYOUT.into()
+}
+
+fn default_platforms() -> Vec<String> {
+    vec![host_platform().into()]
+}
+
+/// Makes the output directory match the lab manifest, downloading missing drivers
+/// and pruning any driver the manifest no longer lists.
+pub fn sync(args: &[String]) -> Result<()> {
+    let manifest_path = flag_value(args, "--manifest").unwrap_or_else(|| DEFAULT_MANIFEST.into());
+    let contents = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("unable to read lab manifest {}", manifest_path))?;
+    let lab: LabManifest = toml::from_str(&contents)
+        .with_context(|| format!("invalid lab manifest {}", manifest_path))?;
+    let prune = !has_flag(args, "--no-prune");
+    let keep_quarantine = has_flag(args, "--keep-quarantine");
+    policy::get().check_insecure("--keep-quarantine", keep_quarantine)?;
+    let zone = quarantine::Zone::from_args(args)?;
+    let limits = ExtractLimits::from_args(args)?;
+    let signature_check = signature::Check::from_args(args)?;
+    let audit_log = audit::log_path(args);
+    let parallel = parallel::limit(args)?;
+    let min_version = flag_value(args, "--min-driver-version");
+
+    if Path::new(&lab.layout).is_absolute() {
+        bail!("layout must be relative to output");
+    }
+
+    // only fetched once something actually needs resolving or downloading
+    let mut remote = None;
+
+    let mut wanted = HashSet::new();
+    let mut missing = Vec::new();
+    for spec in &lab.drivers {
+        for platform in &spec.platforms {
+            let version = match spec.version.as_str() {
+                "detected" => {
+                    explain::step("lab manifest asks for the detected webview2 version");
+                    detected_version()?.trim().to_string()
+                }
+                v if !v.contains('.') => {
+                    newest_for_prefix(remote_manifest(&mut remote)?, v, platform)?
+                }
+                v => v.to_string(),
+            };
+
+            enforce_floor(&version, min_version.as_deref())?;
+
+            let dir = lab.output.join(
+                lab.layout
+                    .replace("{version}", &version)
+                    .replace("{platform}", platform),
+            );
+            let dest = dir.join(driver_filename(platform));
+            wanted.insert(dest.clone());
+
+            if dest.is_file() {
+                metrics::record_cache_hit();
+                output::progress(&format!("{} {} up to date", version, platform));
+                continue;
+            }
+
+            let options = DownloadOptions {
+                sha256: spec.sha256.get(platform).cloned(),
+                limits,
+                include: Vec::new(),
+                size_hint: None,
+            };
+            if policy::get().require_sha256 && options.sha256.is_none() && !checksums::configured()
+            {
+                bail!("policy requires a sha256 pin for {} {}", version, platform);
+            }
+            let blob = find_blob(remote_manifest(&mut remote)?, &version, platform)?
+                .ok_or_else(|| anyhow!("no {} driver published for {}", platform, version))?;
+            let url = mirror::select(&blob.url, &blob.name);
+            missing.push(Missing {
+                version,
+                platform,
+                dest,
+                url,
+                options,
+            });
+        }
+    }
+
+    let results = parallel::map(&missing, parallel, |driver| {
+        install_missing(
+            driver,
+            keep_quarantine,
+            zone,
+            signature_check,
+            audit_log.as_deref(),
+        )
+    });
+    let mut failed = 0;
+    for (driver, result) in missing.iter().zip(results) {
+        if let Err(e) = result {
+            failed += 1;
+            output::line(&format!(
+                "{} {}: FAILED: {}",
+                driver.version, driver.platform, e
+            ));
+            output::problem(&format!(
+                "sync of {} {} failed: {}",
+                driver.version, driver.platform, e
+            ));
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} drivers failed to sync", failed, missing.len());
+    }
+
+    if prune && lab.output.is_dir() {
+        prune_unlisted(&lab.output, &lab.output, &wanted, audit_log.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// A listed driver not yet present in the output directory.
+struct Missing<'a> {
+    version: String,
+    platform: &'a str,
+    dest: PathBuf,
+    url: String,
+    options: DownloadOptions,
+}
+
+fn install_missing(
+    driver: &Missing,
+    keep_quarantine: bool,
+    zone: quarantine::Zone,
+    signature_check: signature::Check,
+    audit_log: Option<&Path>,
+) -> Result<()> {
+    let dest = &driver.dest;
+    output::progress(&format!(
+        "syncing {} {} into {}",
+        driver.version,
+        driver.platform,
+        dest.parent().unwrap_or(dest).display()
+    ));
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let _lock = lock::acquire(dest)?;
+    let hashes = download_driver(
+        &driver.url,
+        driver_filename(driver.platform),
+        dest,
+        &driver.options,
+    )?;
+    verify_signature(dest, signature_check)?;
+    quarantine::clear(dest, keep_quarantine)?;
+    quarantine::apply_zone(dest, zone, &driver.url)?;
+    verify_runs(dest, driver.platform, &driver.version)?;
+    Receipt::new(&driver.version, driver.platform, &driver.url, &hashes).write(dest)?;
+
+    if let Some(log) = audit_log {
+        audit::record(
+            log,
+            &audit::Event {
+                version: Some(&driver.version),
+                platform: Some(driver.platform),
+                archive_sha256: Some(&hashes.archive),
+                driver_sha256: Some(&hashes.driver),
+                source: Some(&driver.url),
+                ..audit::Event::new("install", dest)
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Removes drivers under `dir` that aren't `wanted`, then any directories left empty.
+fn prune_unlisted(
+    root: &Path,
+    dir: &Path,
+    wanted: &HashSet<PathBuf>,
+    audit_log: Option<&Path>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            prune_unlisted(root, &path, wanted, audit_log)?;
+            if path != root && fs::read_dir(&path)?.next().is_none() {
+                fs::remove_dir(&path)?;
+            }
+        } else if is_driver(&path) && !wanted.contains(&path) {
+            output::progress(&format!("pruning {}", path.display()));
+            fs::remove_file(&path)?;
+            let _ = fs::remove_file(receipt::path_for(&path));
+            if let Some(log) = audit_log {
+                audit::record(log, &audit::Event::new("remove", &path))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_driver(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|n| n.to_str()),
+        Some("msedgedriver" | "msedgedriver.exe")
+    )
+}
+
+fn remote_manifest(cached: &mut Option<String>) -> Result<&str> {
+    if cached.is_none() {
+        *cached = Some(fetch_manifest()?);
+    }
+    Ok(cached.as_deref().unwrap_or_default())
+}
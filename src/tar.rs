@@ -0,0 +1,73 @@
+//! Just enough of the tar format to find one file in a `.tar.gz` driver archive.
+
+use std::io::{self, Read};
+
+use anyhow::{bail, Result};
+
+const BLOCK: u64 = 512;
+
+/// A file's header fields that matter for extracting it.
+pub struct Header {
+    pub size: u64,
+    pub mode: u32,
+}
+
+/// Reads `archive` up to the contents of the regular file `name`, returning its
+/// header so the caller can read exactly `size` bytes, or `None` if the archive has
+/// no such file.
+///
+/// Entries may be stored with a leading `./` or a ustar prefix.
+pub fn seek_entry(archive: &mut impl Read, name: &str) -> Result<Option<Header>> {
+    let mut header = [0u8; BLOCK as usize];
+    loop {
+        if !read_block(archive, &mut header)? || header.iter().all(|&b| b == 0) {
+            return Ok(None);
+        }
+
+        let size = octal(&header[124..136])?;
+        let prefix = field(&header[345..500]);
+        let entry = match field(&header[..100]) {
+            entry if prefix.is_empty() => entry,
+            entry => format!("{}/{}", prefix, entry),
+        };
+        // `0` and NUL are regular files, anything else is skipped over
+        let regular = matches!(header[156], b'0' | 0);
+        if regular && entry.trim_start_matches("./") == name {
+            let mode = octal(&header[100..108])? as u32;
+            return Ok(Some(Header { size, mode }));
+        }
+
+        let padded = size.div_ceil(BLOCK) * BLOCK;
+        io::copy(&mut archive.by_ref().take(padded), &mut io::sink())?;
+    }
+}
+
+/// Fills `block`, or returns false at a clean end of the archive.
+fn read_block(archive: &mut impl Read, block: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < block.len() {
+        match archive.read(&mut block[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => bail!("tar archive ends inside a header"),
+            n => filled += n,
+        }
+    }
+    Ok(true)
+}
+
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn octal(bytes: &[u8]) -> Result<u64> {
+    let digits = field(bytes);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    match u64::from_str_radix(digits, 8) {
+        Ok(n) => Ok(n),
+        Err(_) => bail!("invalid tar entry size {:?}", digits),
+    }
+}
@@ -0,0 +1,94 @@
+//! The certificate authorities https connections trust: the operating system's, so
+//! a corporate root that intercepting proxies sign with works once it is installed
+//! there, and any given with `--ca-bundle`.
+
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer},
+    ClientConfig, RootCertStore,
+};
+
+use crate::{explain, flag_values};
+
+/// Certificates from every `--ca-bundle`, trusted on top of the system's.
+static EXTRA: OnceLock<Vec<CertificateDer<'static>>> = OnceLock::new();
+
+/// Reads every `--ca-bundle <pem>`, failing now on a bundle that can't be used
+/// rather than on the first connection.
+pub fn configure(args: &[String]) -> Result<()> {
+    let mut extra = Vec::new();
+    for bundle in flag_values(args, "--ca-bundle") {
+        let certs = read_bundle(Path::new(&bundle))
+            .with_context(|| format!("unable to use --ca-bundle {}", bundle))?;
+        explain::step(format!(
+            "trusting {} certificates from {}",
+            certs.len(),
+            bundle
+        ));
+        extra.extend(certs);
+    }
+    let _ = EXTRA.set(extra);
+    Ok(())
+}
+
+fn read_bundle(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| anyhow!("{}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("{}", e))?;
+    if certs.is_empty() {
+        bail!("it holds no PEM certificates");
+    }
+    Ok(certs)
+}
+
+/// The TLS settings every agent connects with, built once on first use.
+pub fn client_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let config = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .expect("ring supports the default protocol versions")
+                .with_root_certificates(roots())
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+fn roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for e in &native.errors {
+        explain::step(format!(
+            "skipping part of the system certificate store: {}",
+            e
+        ));
+    }
+    let (added, _) = roots.add_parsable_certificates(native.certs);
+    if added == 0 {
+        // minimal containers often have no store at all
+        explain::step("the system has no usable certificates, trusting the bundled roots");
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    } else {
+        explain::step(format!(
+            "trusting {} certificates from the system store",
+            added
+        ));
+    }
+    let (_, rejected) = roots.add_parsable_certificates(EXTRA.get().cloned().unwrap_or_default());
+    if rejected > 0 {
+        eprintln!(
+            "warning: ignoring {} certificates from --ca-bundle that aren't valid CAs",
+            rejected
+        );
+    }
+    roots
+}
@@ -0,0 +1,184 @@
+use std::{
+    cell::Cell,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{flag_value, http};
+
+static ENDPOINT: OnceLock<String> = OnceLock::new();
+static TRACE_ID: OnceLock<String> = OnceLock::new();
+static ROOT: OnceLock<u64> = OnceLock::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static FINISHED: Mutex<Vec<Value>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Enables span export to an OTLP/HTTP collector given by `--otlp-endpoint` or
+/// `OTEL_EXPORTER_OTLP_ENDPOINT`; without either, spans cost nothing.
+pub fn configure(args: &[String]) {
+    let endpoint = flag_value(args, "--otlp-endpoint")
+        .or_else(|| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok())
+        .filter(|e| !e.trim().is_empty());
+    if let Some(endpoint) = endpoint {
+        let _ = ENDPOINT.set(endpoint.trim_end_matches('/').to_string());
+    }
+}
+
+fn enabled() -> bool {
+    ENDPOINT.get().is_some()
+}
+
+/// A unit of work, reported when dropped; nested spans on the same thread become
+/// its children, and spans on worker threads hang off the outermost span.
+pub struct Span {
+    id: u64,
+    parent: Option<u64>,
+    /// The thread's current span before this one, restored on drop.
+    previous: Option<u64>,
+    name: &'static str,
+    start: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+    error: Option<String>,
+}
+
+pub fn span(name: &'static str) -> Span {
+    let id = if enabled() {
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    } else {
+        0
+    };
+    let previous = CURRENT.with(|current| current.get());
+    let parent = previous.or(ROOT.get().copied());
+    if enabled() {
+        let _ = ROOT.set(id);
+        CURRENT.with(|current| current.set(Some(id)));
+    }
+
+    Span {
+        id,
+        parent,
+        previous,
+        name,
+        start: SystemTime::now(),
+        attributes: Vec::new(),
+        error: None,
+    }
+}
+
+impl Span {
+    pub fn attr(&mut self, key: &'static str, value: impl Display) {
+        if enabled() {
+            self.attributes.push((key, value.to_string()));
+        }
+    }
+
+    /// Marks the span failed when `result` is an error, passing the result through.
+    pub fn record<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            self.error = Some(format!("{:#}", e));
+        }
+        result
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !enabled() {
+            return;
+        }
+        CURRENT.with(|current| current.set(self.previous));
+
+        let mut span = json!({
+            "traceId": trace_id(),
+            "spanId": format!("{:016x}", self.id),
+            "name": self.name,
+            "kind": 1,
+            "startTimeUnixNano": nanos(self.start),
+            "endTimeUnixNano": nanos(SystemTime::now()),
+            "attributes": self.attributes.iter().map(|(key, value)| json!({
+                "key": key,
+                "value": { "stringValue": value },
+            })).collect::<Vec<_>>(),
+            "status": match &self.error {
+                Some(message) => json!({ "code": 2, "message": message }),
+                None => json!({ "code": 1 }),
+            },
+        });
+        if let Some(parent) = self.parent {
+            span["parentSpanId"] = json!(format!("{:016x}", parent));
+        }
+        FINISHED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(span);
+    }
+}
+
+/// Sends every finished span to the collector; a collector that is down only warns.
+pub fn export() {
+    let Some(endpoint) = ENDPOINT.get() else {
+        return;
+    };
+    let spans = std::mem::take(&mut *FINISHED.lock().unwrap_or_else(|e| e.into_inner()));
+    if spans.is_empty() {
+        return;
+    }
+
+    let body = json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [
+                { "key": "service.name", "value": { "stringValue": env!("CARGO_PKG_NAME") } },
+                { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                { "key": "host.name", "value": { "stringValue": host_name() } },
+            ]},
+            "scopeSpans": [{
+                "scope": { "name": env!("CARGO_PKG_NAME") },
+                "spans": spans,
+            }],
+        }],
+    });
+
+    let url = format!("{}/v1/traces", endpoint);
+    if let Err(e) = http::post(&url)
+        .timeout(Duration::from_secs(5))
+        .send_json(body)
+    {
+        eprintln!("warning: unable to export traces to {}: {}", url, e);
+    }
+}
+
+fn trace_id() -> &'static str {
+    TRACE_ID.get_or_init(|| {
+        let seed = format!(
+            "{}-{}-{}",
+            std::process::id(),
+            nanos(SystemTime::now()),
+            host_name()
+        );
+        format!("{:x}", Sha256::digest(seed.as_bytes()))[..32].to_string()
+    })
+}
+
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
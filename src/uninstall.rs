@@ -0,0 +1,116 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    audit, cache,
+    chrome::Chrome,
+    driver::{Driver, Edge},
+    flag_value,
+    gecko::Gecko,
+    has_flag, host_platform, lock, output, paths, receipt,
+    scope::Scope,
+    MANIFEST_FILENAME,
+};
+
+/// Removes everything the tool installed: drivers it has receipts for, the files
+/// beside them, the PATH entries scopes added and, with `--purge-cache`, the cache,
+/// or just what `--older-than` and `--keep-latest` prune.
+///
+/// Without `--scope` both scopes and the current directory are cleaned.
+pub fn uninstall(args: &[String]) -> Result<()> {
+    let chosen = Scope::from_args(args)?;
+    let scopes = match chosen {
+        Some(scope) => vec![scope],
+        None => vec![Scope::User, Scope::Machine],
+    };
+    let audit_log = audit::log_path(args);
+    let mut removed = 0;
+
+    if chosen.is_none() {
+        removed += remove_drivers(&std::env::current_dir()?, audit_log.as_deref())?;
+    }
+
+    for scope in scopes {
+        let dir = match scope.dir() {
+            Ok(dir) => dir,
+            Err(_) => continue,
+        };
+        removed += remove_drivers(&dir, audit_log.as_deref())?;
+
+        if scope.owns_dir() && dir.is_dir() && fs::read_dir(&dir)?.next().is_none() {
+            fs::remove_dir(&dir)?;
+        }
+        if scope.remove_from_path(&dir)? {
+            output::progress(&format!("removed {} from PATH", dir.display()));
+        }
+    }
+
+    let pruning = ["--older-than", "--keep-latest"]
+        .iter()
+        .any(|flag| flag_value(args, flag).is_some());
+    if has_flag(args, "--purge-cache") && pruning {
+        cache::clean(args)?;
+    } else if has_flag(args, "--purge-cache") {
+        let cache = paths::cache_dir().ok_or_else(|| anyhow!("unable to locate the cache"))?;
+        if cache.is_dir() {
+            output::progress(&format!("removing cache {}", cache.display()));
+            fs::remove_dir_all(&cache)?;
+        }
+    }
+
+    output::progress(&format!("removed {} installed drivers", removed));
+    Ok(())
+}
+
+/// Removes the drivers in `dir` and the files installed with them, but only those
+/// whose receipts show this tool put them there.
+fn remove_drivers(dir: &Path, audit_log: Option<&Path>) -> Result<usize> {
+    let platform = host_platform();
+    let mut removed = 0;
+    if remove_driver(&dir.join(Edge.filename(platform)), audit_log)? {
+        // the manifest only describes msedgedriver
+        remove_file(&dir.join(MANIFEST_FILENAME))?;
+        removed += 1;
+    }
+    let others: [&dyn Driver; 2] = [&Chrome, &Gecko];
+    for driver in others {
+        if remove_driver(&dir.join(driver.filename(platform)), audit_log)? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn remove_driver(driver: &Path, audit_log: Option<&Path>) -> Result<bool> {
+    let receipt = receipt::path_for(driver);
+    if !receipt.is_file() {
+        if driver.is_file() {
+            eprintln!(
+                "warning: leaving {}, it wasn't installed by this tool",
+                driver.display()
+            );
+        }
+        return Ok(false);
+    }
+
+    output::progress(&format!("removing {}", driver.display()));
+    // an install in progress finishes first, and its `.lock` stays for whoever
+    // waits on it next
+    let _lock = lock::acquire(driver)?;
+    for path in [driver, &receipt] {
+        remove_file(path)?;
+    }
+    if let Some(log) = audit_log {
+        audit::record(log, &audit::Event::new("uninstall", driver))?;
+    }
+
+    Ok(true)
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::{http, paths, version_key};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/chippers/msedgedriver-tool/releases/latest";
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const STAMP_FILE: &str = "last-update-check";
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Whether this release is newer than the running tool.
+    pub fn is_newer(&self) -> bool {
+        version_key(self.tag_name.trim_start_matches('v')) > version_key(env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// The latest published release of this tool.
+pub fn latest_release() -> Result<Release> {
+    http::get(LATEST_RELEASE_URL)
+        .timeout(Duration::from_secs(10))
+        .call()?
+        .into_json()
+}
+
+/// Prints a one-line notice when a newer release of this tool exists.
+///
+/// Opt-in through `MSEDGEDRIVER_TOOL_UPDATE_CHECK=1`, checked at most once a day, and
+/// silent on any failure since it must never get in the way of the actual work.
+pub fn notify() {
+    if !matches!(
+        std::env::var("MSEDGEDRIVER_TOOL_UPDATE_CHECK").as_deref(),
+        Ok("1" | "true")
+    ) {
+        return;
+    }
+
+    if let Ok(Some(release)) = newer_release() {
+        eprintln!(
+            "note: {} {} is available (running {}), see {}",
+            env!("CARGO_PKG_NAME"),
+            release.tag_name,
+            env!("CARGO_PKG_VERSION"),
+            release.html_url
+        );
+    }
+}
+
+fn newer_release() -> Result<Option<Release>> {
+    let stamp = match paths::cache_dir() {
+        Some(dir) => dir.join(STAMP_FILE),
+        None => return Ok(None),
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let last: u64 = fs::read_to_string(&stamp)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0);
+    if now.saturating_sub(last) < CHECK_INTERVAL.as_secs() {
+        return Ok(None);
+    }
+
+    if let Some(dir) = stamp.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&stamp, now.to_string())?;
+
+    let release = latest_release()?;
+    Ok(release.is_newer().then_some(release))
+}
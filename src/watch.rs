@@ -0,0 +1,178 @@
+//! `watch`: keeping a driver matched to a browser that updates itself, by checking
+//! every `--interval` and installing again once the browser's major version moves
+//! on, and `register-task` to have windows' task scheduler do the checking.
+
+use std::{path::PathBuf, process::Command, thread, time::Duration};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    browser::{self, Browser},
+    detected_version, driver_location, explain, flag_value, has_flag, http, major, output, pool,
+    receipt::Receipt,
+    without_flag,
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_TASK_NAME: &str = "msedgedriver-tool watch";
+
+/// Checks now and then every `--interval`, or only once with `--once`, installing
+/// with the rest of `args` whenever the browser's major version differs from the
+/// installed driver's.
+pub fn watch(args: &[String]) -> Result<()> {
+    let interval = http::duration_flag(args, "--interval")?.unwrap_or(DEFAULT_INTERVAL);
+    let once = has_flag(args, "--once");
+    let install_args: Vec<String> = without_flag(args, "--interval")
+        .into_iter()
+        .filter(|arg| arg != "--once")
+        .collect();
+
+    loop {
+        match check(&install_args) {
+            Ok(()) => {}
+            Err(e) if once => return Err(e),
+            // a missed check is retried at the next, the loop must survive it
+            Err(e) => eprintln!("warning: {:#}", e),
+        }
+        if once {
+            return Ok(());
+        }
+        explain::step(format!("checking again in {:?}", interval));
+        thread::sleep(interval);
+    }
+}
+
+fn check(args: &[String]) -> Result<()> {
+    let browser = match Browser::from_args(args)? {
+        Browser::Edge(channel) => browser::edge_version(channel)?.map(|(version, _)| version),
+        Browser::WebView2 => Some(detected_version()?),
+    };
+    let Some(browser) = browser else {
+        bail!("no edge browser is installed to keep a driver matched to");
+    };
+    let browser = browser.trim();
+
+    match installed_version(args)? {
+        Some(installed) if major(&installed) == major(browser) => {
+            output::progress(&format!(
+                "driver {} still matches browser {}",
+                installed, browser
+            ));
+            output::flag("watch.refreshed", false);
+            return Ok(());
+        }
+        Some(installed) => output::progress(&format!(
+            "browser moved to {} from the major of driver {}, installing its driver",
+            browser, installed
+        )),
+        None => output::progress(&format!(
+            "no driver installed yet, installing one for {}",
+            browser
+        )),
+    }
+    crate::install(args)?;
+    output::flag("watch.refreshed", true);
+    Ok(())
+}
+
+/// The installed driver's version from its receipt, or the pool's current one.
+fn installed_version(args: &[String]) -> Result<Option<String>> {
+    if let Some(pool) = pool::from_args(args)? {
+        return Ok(pool::current_version(&pool));
+    }
+    let driver = driver_location(args)?;
+    Ok(Receipt::read(&driver)?.map(|receipt| receipt.version))
+}
+
+/// Registers `watch --once` with the rest of `args` as a scheduled task running
+/// every `--interval`, or removes it again with `--remove`.
+pub fn register_task(args: &[String]) -> Result<()> {
+    let name = flag_value(args, "--task-name").unwrap_or_else(|| DEFAULT_TASK_NAME.into());
+    if !cfg!(windows) {
+        bail!("register-task needs the windows task scheduler; run `watch` from cron or a systemd timer instead");
+    }
+
+    if has_flag(args, "--remove") {
+        schtasks(&["/Delete", "/F", "/TN", &name])?;
+        output::progress(&format!("removed scheduled task {}", name));
+        return Ok(());
+    }
+
+    let interval = http::duration_flag(args, "--interval")?.unwrap_or(DEFAULT_INTERVAL);
+    let minutes = (interval.as_secs() / 60).max(1);
+    let (schedule, every) = if minutes % (24 * 60) == 0 {
+        ("DAILY", minutes / (24 * 60))
+    } else if minutes % 60 == 0 && minutes < 24 * 60 {
+        ("HOURLY", minutes / 60)
+    } else if minutes < 24 * 60 {
+        ("MINUTE", minutes)
+    } else {
+        bail!("--interval for a scheduled task must be under a day or whole days");
+    };
+
+    let exe: PathBuf = std::env::current_exe()?;
+    let forwarded = ["--interval", "--task-name"]
+        .iter()
+        .fold(args.to_vec(), |args, flag| without_flag(&args, flag));
+    let mut command = format!("{} watch --once", quote(&exe.to_string_lossy()));
+    for arg in &forwarded {
+        command.push(' ');
+        command.push_str(&quote(arg));
+    }
+    // the scheduler refuses longer commands
+    if command.len() > 261 {
+        bail!("the task's command line is over the scheduler's 261 characters, move flags into msedgedriver-tool.toml");
+    }
+    explain::step(format!(
+        "scheduling {} {} every {}",
+        command, schedule, every
+    ));
+
+    schtasks(&[
+        "/Create",
+        "/F",
+        "/TN",
+        &name,
+        "/SC",
+        schedule,
+        "/MO",
+        &every.to_string(),
+        "/TR",
+        &command,
+    ])?;
+    output::progress(&format!(
+        "registered scheduled task {}, checking every {} {}",
+        name,
+        every,
+        match schedule {
+            "DAILY" => "days",
+            "HOURLY" => "hours",
+            _ => "minutes",
+        }
+    ));
+    output::parameter("watch.task", &name);
+    Ok(())
+}
+
+fn schtasks(args: &[&str]) -> Result<()> {
+    let output = Command::new("schtasks")
+        .args(args)
+        .output()
+        .context("unable to run schtasks")?;
+    if !output.status.success() {
+        bail!(
+            "schtasks {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn quote(arg: &str) -> String {
+    if arg.contains([' ', '\t']) {
+        format!("\"{}\"", arg)
+    } else {
+        arg.into()
+    }
+}
@@ -0,0 +1,55 @@
+//! Running as a windows program under Wine or CrossOver, as some windows CI does on
+//! linux and macos: the registry answers, but PowerShell usually isn't there and the
+//! machine's architecture is reported as whatever the host emulates.
+
+use std::sync::OnceLock;
+
+/// The Wine version this process runs under, e.g. `9.0`, or `None` on real windows
+/// and everywhere else.
+pub fn version() -> Option<&'static str> {
+    static VERSION: OnceLock<Option<String>> = OnceLock::new();
+    VERSION.get_or_init(detect).as_deref()
+}
+
+/// Whether this process runs under Wine.
+pub fn detected() -> bool {
+    version().is_some()
+}
+
+/// Wine's ntdll exports `wine_get_version`, which no windows ntdll does.
+#[cfg(windows)]
+fn detect() -> Option<String> {
+    use std::ffi::{c_char, CStr};
+
+    use windows_sys::{
+        core::PCSTR,
+        Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress},
+    };
+
+    type WineGetVersion = unsafe extern "C" fn() -> *const c_char;
+
+    let ntdll: Vec<u16> = "ntdll.dll".encode_utf16().chain(Some(0)).collect();
+    // SAFETY: both names are nul terminated
+    let proc = unsafe {
+        GetProcAddress(
+            GetModuleHandleW(ntdll.as_ptr()),
+            c"wine_get_version".as_ptr() as PCSTR,
+        )
+    }?;
+    // SAFETY: wine_get_version has this signature and returns a static string
+    let version = unsafe {
+        let wine_get_version: WineGetVersion = std::mem::transmute(proc);
+        let version = wine_get_version();
+        if version.is_null() {
+            return Some("unknown".into());
+        }
+        CStr::from_ptr(version).to_string_lossy().into_owned()
+    };
+    crate::explain::step(format!("running under wine {}", version));
+    Some(version)
+}
+
+#[cfg(not(windows))]
+fn detect() -> Option<String> {
+    None
+}
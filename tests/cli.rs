@@ -0,0 +1,391 @@
+//! The command line against a local stand-in for Microsoft's driver host.
+
+mod common;
+
+use std::fs;
+
+use common::{cache_driver, driver_zip, foreign_filename, foreign_platform, run, Server, TempDir};
+use sha2::{Digest, Sha256};
+
+const VERSION: &str = "120.0.2210.91";
+
+/// A server listing one archive of [`VERSION`] for [`foreign_platform`].
+fn host() -> (Server, Vec<u8>) {
+    let server = Server::start();
+    let name = format!("{}/edgedriver_{}.zip", VERSION, foreign_platform());
+    let archive = driver_zip(foreign_filename(), b"driver");
+    server.serve_listing(&[&name]);
+    server.serve(&format!("/{}", name), archive.clone());
+    (server, archive)
+}
+
+fn archive_gets(server: &Server) -> usize {
+    server
+        .requests()
+        .iter()
+        .filter(|request| request.starts_with("GET ") && request.ends_with(".zip"))
+        .count()
+}
+
+fn install(server: &Server, dir: &TempDir, extra: &[&str]) -> std::process::Output {
+    let output = dir.path().join("out");
+    fs::create_dir_all(&output).unwrap();
+    let mut args = vec![
+        "install",
+        "--version",
+        VERSION,
+        "--platform",
+        foreign_platform(),
+        "--output",
+        output.to_str().unwrap(),
+    ];
+    args.extend(extra);
+    run(server, dir, &args)
+}
+
+#[test]
+fn installs_the_driver_from_the_archive() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let output = install(&server, &dir, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let driver = fs::read(dir.path().join("out").join(foreign_filename())).unwrap();
+    assert_eq!(driver, b"driver");
+    assert_eq!(archive_gets(&server), 1);
+}
+
+#[test]
+fn installs_again_from_the_cache() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    assert!(install(&server, &dir, &[]).status.success());
+    fs::remove_file(dir.path().join("out").join(foreign_filename())).unwrap();
+    let output = install(&server, &dir, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.path().join("out").join(foreign_filename()).exists());
+    assert_eq!(archive_gets(&server), 1);
+}
+
+#[test]
+fn checks_the_archive_against_its_pin() {
+    let (server, archive) = host();
+    let dir = TempDir::new();
+
+    let pin = format!("{:x}", Sha256::digest(&archive));
+    assert!(install(&server, &dir, &["--sha256", &pin]).status.success());
+
+    let dir = TempDir::new();
+    let wrong = "0".repeat(64);
+    let output = install(&server, &dir, &["--sha256", &wrong]);
+    assert!(!output.status.success());
+    assert!(!dir.path().join("out").join(foreign_filename()).exists());
+}
+
+#[test]
+fn exits_7_for_a_version_never_published() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let out = dir.path().join("out");
+    let output = run(
+        &server,
+        &dir,
+        &[
+            "install",
+            "--version",
+            "99.0.1150.30",
+            "--platform",
+            foreign_platform(),
+            "--output",
+            out.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{:?}", output);
+}
+
+#[test]
+fn exits_3_offline_with_nothing_cached() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let output = install(&server, &dir, &["--offline"]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn resolves_the_archive_url() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let output = run(
+        &server,
+        &dir,
+        &[
+            "resolve",
+            "--version",
+            VERSION,
+            "--platform",
+            foreign_platform(),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        format!(
+            "{}/{}/edgedriver_{}.zip",
+            server.url,
+            VERSION,
+            foreign_platform()
+        )
+    );
+}
+
+#[test]
+fn exits_2_for_a_flag_the_command_does_not_take() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let output = install(&server, &dir, &["--sha265", &"0".repeat(64)]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--sha265"));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn lists_each_commands_own_flags_in_its_help() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let help = |command: &str| {
+        let output = run(&server, &dir, &[command, "--help"]);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert!(help("install").contains("--sha256 <hex>"));
+    assert!(!help("detect").contains("--sha256"));
+    assert!(help("detect").contains("--offline"));
+}
+
+#[test]
+fn dry_run_exits_7_for_a_version_never_published() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let output = run(
+        &server,
+        &dir,
+        &[
+            "install",
+            "--dry-run",
+            "--version",
+            "99.0.1150.30",
+            "--platform",
+            foreign_platform(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{:?}", output);
+    assert!(server.requests().iter().all(|r| r.starts_with("HEAD ")));
+}
+
+#[test]
+fn dry_run_exits_6_when_the_host_is_unreachable() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    // the first --base-url wins over the one every run is given
+    let output = run(
+        &server,
+        &dir,
+        &[
+            "install",
+            "--dry-run",
+            "--base-url",
+            "http://127.0.0.1:9",
+            "--version",
+            VERSION,
+            "--platform",
+            foreign_platform(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+}
+
+#[test]
+fn looks_for_older_layouts_only_on_the_base_url() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let out = dir.path().join("out");
+    let output = run(
+        &server,
+        &dir,
+        &[
+            "install",
+            "--explain",
+            "--version",
+            "99.0.1150.30",
+            "--platform",
+            foreign_platform(),
+            "--output",
+            out.to_str().unwrap(),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(7), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("legacy location {}/", server.url)));
+    assert!(!stderr.contains("blob.core.windows.net") && !stderr.contains("azureedge"));
+}
+
+#[cfg(unix)]
+#[test]
+fn stops_quietly_once_stdout_is_closed() {
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_msedgedriver-tool"))
+        .arg("--help")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // as `| head` does once it has read enough, here before anything is printed
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("crashed"), "{}", stderr);
+    assert_eq!(output.status.code(), Some(141), "{}", stderr);
+}
+
+#[test]
+fn installs_offline_from_the_cache() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    assert!(install(&server, &dir, &[]).status.success());
+    let requests = server.requests().len();
+    fs::remove_file(dir.path().join("out").join(foreign_filename())).unwrap();
+    let output = install(&server, &dir, &["--offline"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.path().join("out").join(foreign_filename()).exists());
+    assert_eq!(server.requests().len(), requests);
+}
+
+#[test]
+fn dry_run_plans_a_published_version_with_head_requests_only() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let output = install(&server, &dir, &["--dry-run"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(!dir.path().join("out").join(foreign_filename()).exists());
+    assert!(!server.requests().is_empty());
+    assert!(server.requests().iter().all(|r| r.starts_with("HEAD ")));
+}
+
+/// Installs `driver` for browser `browser` offline, from a cache holding
+/// `version` of it chosen for that browser.
+fn install_cached(driver: &str, version: &str, browser: &str, filename: &str) {
+    let (server, _) = host();
+    let dir = TempDir::new();
+    cache_driver(
+        &dir,
+        version,
+        browser,
+        foreign_platform(),
+        filename,
+        b"cached",
+    );
+
+    let out = dir.path().join("out");
+    fs::create_dir_all(&out).unwrap();
+    let output = run(
+        &server,
+        &dir,
+        &[
+            "install",
+            "--driver",
+            driver,
+            "--version",
+            browser,
+            "--platform",
+            foreign_platform(),
+            "--output",
+            out.to_str().unwrap(),
+            "--offline",
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read(out.join(filename)).unwrap(), b"cached");
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn installs_geckodriver_for_firefox_from_the_cache() {
+    let filename = if foreign_platform().starts_with("win") {
+        "geckodriver.exe"
+    } else {
+        "geckodriver"
+    };
+    install_cached("gecko", "0.36.0", "128.0", filename);
+}
+
+#[test]
+fn installs_chromedriver_for_chrome_from_the_cache() {
+    let filename = if foreign_platform().starts_with("win") {
+        "chromedriver.exe"
+    } else {
+        "chromedriver"
+    };
+    install_cached("chrome", "120.0.6099.71", "120.0.6099.109", filename);
+}
+
+/// The policy a windows machine reads from `%ProgramData%`, here `dir`'s.
+#[cfg(windows)]
+fn with_policy(
+    server: &Server,
+    dir: &TempDir,
+    policy: &str,
+    args: &[&str],
+) -> std::process::Output {
+    let data = dir.path().join("programdata");
+    fs::create_dir_all(data.join("msedgedriver-tool")).unwrap();
+    fs::write(data.join("msedgedriver-tool").join("policy.toml"), policy).unwrap();
+    common::command(server, dir, args)
+        .env("ProgramData", &data)
+        .output()
+        .unwrap()
+}
+
+#[cfg(windows)]
+#[test]
+fn refuses_a_base_url_outside_the_policy_mirror() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let policy = format!("mirror = \"{}/mirror\"\n", server.url);
+    let output = with_policy(&server, &dir, &policy, &["install", "--version", VERSION]);
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("policy only allows"));
+    assert!(server.requests().is_empty());
+}
+
+#[cfg(windows)]
+#[test]
+fn refuses_flags_the_policy_forbids() {
+    let (server, _) = host();
+    let dir = TempDir::new();
+
+    let args = [
+        "install",
+        "--version",
+        VERSION,
+        "--platform",
+        foreign_platform(),
+    ];
+    let output = with_policy(&server, &dir, "require_sha256 = true\n", &args);
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("policy requires"));
+    assert!(!dir.path().join(foreign_filename()).exists());
+}
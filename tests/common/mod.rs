@@ -0,0 +1,234 @@
+//! What the command line tests share: a local server standing in for Microsoft's
+//! driver host, driver archives to serve from it, and a directory for everything
+//! the tool writes.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use sha2::{Digest, Sha256};
+use zip::{write::FileOptions, ZipWriter};
+
+/// Serves fixed bodies by path, answering anything else with a 404, and logs
+/// every request as e.g. `GET /120.0.2210.91/edgedriver_win64.zip`.
+pub struct Server {
+    pub url: String,
+    bodies: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let bodies = Arc::<Mutex<HashMap<String, Vec<u8>>>>::default();
+        let requests = Arc::<Mutex<Vec<String>>>::default();
+        let (served, logged) = (bodies.clone(), requests.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (served, logged) = (served.clone(), logged.clone());
+                thread::spawn(move || respond(stream, &served, &logged));
+            }
+        });
+        Server {
+            url,
+            bodies,
+            requests,
+        }
+    }
+
+    /// Serves `body` at `path`, e.g. `/` for the listing.
+    pub fn serve(&self, path: &str, body: impl Into<Vec<u8>>) {
+        self.bodies.lock().unwrap().insert(path.into(), body.into());
+    }
+
+    /// Serves a listing of `names`, each archive at its own path on this server.
+    pub fn serve_listing(&self, names: &[&str]) {
+        let blobs: String = names
+            .iter()
+            .map(|name| {
+                format!(
+                    "<Blob><Name>{0}</Name><Url>{1}/{0}</Url></Blob>",
+                    name, self.url
+                )
+            })
+            .collect();
+        self.serve(
+            "/",
+            format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?><EnumerationResults><Blobs>{}</Blobs></EnumerationResults>",
+                blobs
+            ),
+        );
+    }
+
+    /// The requests so far, in the order they arrived.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn respond(
+    stream: TcpStream,
+    bodies: &Mutex<HashMap<String, Vec<u8>>>,
+    requests: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    if reader.read_line(&mut request).is_err() {
+        return;
+    }
+    // the headers say nothing these tests care about
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+        header.clear();
+    }
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or(target);
+    requests
+        .lock()
+        .unwrap()
+        .push(format!("{} {}", method, path));
+
+    let body = bodies.lock().unwrap().get(path).cloned();
+    let status = if body.is_some() {
+        "200 OK"
+    } else {
+        "404 Not Found"
+    };
+    let body = body.unwrap_or_default();
+    let mut stream = reader.into_inner();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    if method != "HEAD" {
+        let _ = stream.write_all(&body);
+    }
+}
+
+/// A driver archive holding `filename`, whose contents are `driver`.
+pub fn driver_zip(filename: &str, driver: &[u8]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file(filename, FileOptions::default().unix_permissions(0o755))
+        .unwrap();
+    zip.write_all(driver).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+/// A directory removed again once dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = env::temp_dir().join(format!(
+            "msedgedriver-tool-cli-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A platform whose driver can't run here, so installs don't try to start it.
+pub fn foreign_platform() -> &'static str {
+    if cfg!(windows) {
+        "linux64"
+    } else {
+        "win64"
+    }
+}
+
+/// The driver's file name on [`foreign_platform`].
+pub fn foreign_filename() -> &'static str {
+    if cfg!(windows) {
+        "msedgedriver"
+    } else {
+        "msedgedriver.exe"
+    }
+}
+
+/// Runs the tool with `args` against `server` from inside `dir`, keeping its cache,
+/// configuration and anything it writes to the current directory there.
+pub fn run(server: &Server, dir: &TempDir, args: &[&str]) -> Output {
+    command(server, dir, args).output().unwrap()
+}
+
+/// The command [`run`] runs, for a test to change before running it.
+pub fn command(server: &Server, dir: &TempDir, args: &[&str]) -> Command {
+    let home = dir.path().join("home");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_msedgedriver-tool"));
+    command
+        .current_dir(dir.path())
+        .args(args)
+        .args(["--base-url", &server.url])
+        .arg("--cache-dir")
+        .arg(dir.path().join("cache"))
+        .env_remove("MSEDGEDRIVER_MIRROR")
+        .env("HOME", &home)
+        .env("XDG_CACHE_HOME", &home)
+        .env("XDG_CONFIG_HOME", &home)
+        .env("LOCALAPPDATA", &home)
+        .env("APPDATA", &home);
+    command
+}
+
+/// Puts `contents` in `dir`'s cache as the `filename` driver `version` for
+/// `platform`, chosen for browser `browser`, the way an install leaves it.
+pub fn cache_driver(
+    dir: &TempDir,
+    version: &str,
+    browser: &str,
+    platform: &str,
+    filename: &str,
+    contents: &[u8],
+) {
+    let cached = dir
+        .path()
+        .join("cache")
+        .join("drivers")
+        .join(version)
+        .join(platform);
+    fs::create_dir_all(&cached).unwrap();
+    fs::write(cached.join(filename), contents).unwrap();
+    let receipt = serde_json::json!({
+        "version": version,
+        "platform": platform,
+        "source": format!("https://example.com/{}/{}", version, filename),
+        "archive_sha256": "0".repeat(64),
+        "driver_sha256": format!("{:x}", Sha256::digest(contents)),
+        "installed_at": "2024-01-01T00:00:00Z",
+        "browser_version": browser,
+    });
+    fs::write(
+        cached.join(format!("{}.receipt.json", filename)),
+        receipt.to_string(),
+    )
+    .unwrap();
+}